@@ -15,6 +15,14 @@ struct StatsMessage {
     count: usize,
 }
 
+/// Current time as Unix seconds, for message timestamps.
+fn unix_now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
 async fn chat_handler(msg: Message, State(manager): State<ConnectionManager>) -> Result<()> {
     if let Ok(chat_msg) = msg.json::<ChatMessage>() {
         println!(
@@ -27,15 +35,11 @@ async fn chat_handler(msg: Message, State(manager): State<ConnectionManager>) ->
         let broadcast_msg = ChatMessage {
             username: chat_msg.username,
             message: chat_msg.message,
-            timestamp: std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .unwrap()
-                .as_secs(),
+            timestamp: unix_now(),
             msg_type: "user".to_string(),
         };
 
-        let json = serde_json::to_string(&broadcast_msg).unwrap();
-        manager.broadcast(Message::text(json));
+        manager.broadcast_json(&broadcast_msg)?;
     }
 
     Ok(())
@@ -57,25 +61,18 @@ async fn main() -> Result<()> {
             let join_msg = ChatMessage {
                 username: "System".to_string(),
                 message: format!("User {} joined", conn_id),
-                timestamp: std::time::SystemTime::now()
-                    .duration_since(std::time::UNIX_EPOCH)
-                    .unwrap()
-                    .as_secs(),
+                timestamp: unix_now(),
                 msg_type: "join".to_string(),
             };
 
-            if let Ok(json) = serde_json::to_string(&join_msg) {
-                manager.broadcast(Message::text(json));
-            }
+            let _ = manager.broadcast_json(&join_msg);
 
             let stats = StatsMessage {
                 r#type: "stats".to_string(),
                 count,
             };
 
-            if let Ok(json) = serde_json::to_string(&stats) {
-                manager.broadcast(Message::text(json));
-            }
+            let _ = manager.broadcast_json(&stats);
         })
         .on_disconnect(|manager, conn_id| {
             let count = manager.count();
@@ -84,25 +81,18 @@ async fn main() -> Result<()> {
             let leave_msg = ChatMessage {
                 username: "System".to_string(),
                 message: format!("User {} left", conn_id),
-                timestamp: std::time::SystemTime::now()
-                    .duration_since(std::time::UNIX_EPOCH)
-                    .unwrap()
-                    .as_secs(),
+                timestamp: unix_now(),
                 msg_type: "leave".to_string(),
             };
 
-            if let Ok(json) = serde_json::to_string(&leave_msg) {
-                manager.broadcast(Message::text(json));
-            }
+            let _ = manager.broadcast_json(&leave_msg);
 
             let stats = StatsMessage {
                 r#type: "stats".to_string(),
                 count,
             };
 
-            if let Ok(json) = serde_json::to_string(&stats) {
-                manager.broadcast(Message::text(json));
-            }
+            let _ = manager.broadcast_json(&stats);
         });
 
     println!("🚀 Chat server: http://127.0.0.1:8080");
@@ -14,24 +14,24 @@ struct ChatRoom {
     _name: String,
 }
 
-async fn chat_handler(
-    Json(msg): Json<ChatMessage>,
-    conn: Connection,
-    State(manager): State<Arc<ConnectionManager>>,
-) -> Result<()> {
+/// Current time as Unix seconds, for message timestamps.
+fn unix_now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+async fn chat_handler(Json(msg): Json<ChatMessage>, bc: Broadcaster) -> Result<()> {
     println!("💬 {} says: {}", msg.username, msg.message);
 
     let broadcast_msg = ChatMessage {
         username: msg.username.clone(),
         message: msg.message.clone(),
-        timestamp: std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_secs(),
+        timestamp: unix_now(),
     };
 
-    let json = serde_json::to_string(&broadcast_msg).unwrap();
-    manager.broadcast_except(conn.id(), Message::text(json));
+    bc.broadcast_json_except(&broadcast_msg)?;
 
     Ok(())
 }
@@ -40,8 +40,7 @@ async fn broadcast_handler(
     Json(msg): Json<ChatMessage>,
     State(manager): State<Arc<ConnectionManager>>,
 ) -> Result<String> {
-    let json = serde_json::to_string(&msg).unwrap();
-    manager.broadcast(Message::text(json));
+    manager.broadcast_json(&msg)?;
     Ok("Broadcast sent".to_string())
 }
 
@@ -74,14 +73,9 @@ async fn main() -> Result<()> {
             let welcome = ChatMessage {
                 username: "System".to_string(),
                 message: format!("User {} joined the chat", conn_id),
-                timestamp: std::time::SystemTime::now()
-                    .duration_since(std::time::UNIX_EPOCH)
-                    .unwrap()
-                    .as_secs(),
+                timestamp: unix_now(),
             };
-            if let Ok(json) = serde_json::to_string(&welcome) {
-                manager.broadcast(Message::text(json));
-            }
+            let _ = manager.broadcast_json(&welcome);
         })
         .on_disconnect(|_manager, conn_id| {
             println!("❌ User left the chat: {}", conn_id);
@@ -41,8 +41,7 @@ async fn game_handler(
                 "z": position.z,
             });
 
-            let json = serde_json::to_string(&update).unwrap();
-            manager.broadcast_except(conn.id(), Message::text(json));
+            manager.broadcast_json_except(conn.id(), &update)?;
         }
         GameMessage::Shoot { target_id } => {
             println!("💥 {} shot at {}", conn.id(), target_id);
@@ -53,8 +52,7 @@ async fn game_handler(
                 "target_id": target_id,
             });
 
-            let json = serde_json::to_string(&event).unwrap();
-            manager.broadcast(Message::text(json));
+            manager.broadcast_json(&event)?;
         }
         GameMessage::Chat { message } => {
             println!("💬 {}: {}", conn.id(), message);
@@ -65,8 +63,7 @@ async fn game_handler(
                 "message": message,
             });
 
-            let json = serde_json::to_string(&chat).unwrap();
-            manager.broadcast(Message::text(json));
+            manager.broadcast_json(&chat)?;
         }
     }
 
@@ -88,9 +85,7 @@ async fn main() -> Result<()> {
                 "type": "player_joined",
                 "player_id": conn_id,
             });
-            if let Ok(json) = serde_json::to_string(&spawn) {
-                manager.broadcast(Message::text(json));
-            }
+            let _ = manager.broadcast_json(&spawn);
         })
         .on_disconnect(|_manager, conn_id| {
             println!("👋 Player left: {}", conn_id);
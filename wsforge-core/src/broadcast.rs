@@ -0,0 +1,254 @@
+//! Broadcasting across several server instances.
+//!
+//! A [`ConnectionManager`](crate::connection::ConnectionManager) only knows
+//! the connections of its own process, so with several instances behind a
+//! load balancer,
+//! [`broadcast()`](crate::connection::ConnectionManager::broadcast) would
+//! reach only the clients connected to the instance that sent it. A
+//! [`BroadcastBackend`] forwards broadcasts to the other instances over a
+//! pub/sub system such as Redis or NATS.
+//!
+//! # Overview
+//!
+//! - [`broadcast()`](crate::connection::ConnectionManager::broadcast)
+//!   delivers locally, then publishes a [`BroadcastEnvelope`] through the
+//!   backend
+//! - Every instance subscribed to the backend delivers envelopes published by
+//!   other instances to its own connections
+//! - [`broadcast_local()`](crate::connection::ConnectionManager::broadcast_local)
+//!   skips the backend
+//!
+//! Only whole-server broadcasts are forwarded. Rooms, tags and
+//! [`broadcast_to()`](crate::connection::ConnectionManager::broadcast_to)
+//! work on local connections, since their membership isn't shared between
+//! instances.
+//!
+//! [`InProcessBackend`] connects managers within one process, which is useful
+//! for tests and for serving several routers from one binary. Network
+//! backends are implemented by the application.
+//!
+//! # Examples
+//!
+//! ## Redis Backend
+//!
+//! ```
+//! use wsforge::prelude::*;
+//! use wsforge::broadcast::{BroadcastBackend, BroadcastEnvelope};
+//! use futures_util::stream::BoxStream;
+//! use tokio::sync::mpsc;
+//!
+//! struct RedisBackend {
+//!     // Envelopes waiting to be published by a background task
+//!     outgoing: mpsc::UnboundedSender<Vec<u8>>,
+//! }
+//!
+//! impl BroadcastBackend for RedisBackend {
+//!     fn publish(&self, envelope: BroadcastEnvelope) {
+//!         // PUBLISH is async, so hand the bytes to the publishing task
+//!         let _ = self.outgoing.send(envelope.encode());
+//!     }
+//!
+//!     fn subscribe(&self) -> BoxStream<'static, BroadcastEnvelope> {
+//!         // SUBSCRIBE to the channel and decode each payload with
+//!         // BroadcastEnvelope::decode()
+//!         # unimplemented!()
+//!     }
+//! }
+//! ```
+
+use crate::error::{Error, Result};
+use crate::message::{Message, MessageType};
+use futures_util::stream::{self, BoxStream};
+use tokio::sync::broadcast;
+use tracing::warn;
+
+/// Forwards broadcasts between server instances.
+///
+/// Register a backend with
+/// [`Router::broadcast_backend()`](crate::router::Router::broadcast_backend)
+/// or
+/// [`ConnectionManager::set_broadcast_backend()`](crate::connection::ConnectionManager::set_broadcast_backend).
+///
+/// An instance receives its own envelopes back from most pub/sub systems.
+/// That's fine: the manager skips envelopes whose
+/// [`origin`](BroadcastEnvelope::origin) is its own
+/// [`node_id()`](crate::connection::ConnectionManager::node_id).
+pub trait BroadcastBackend: Send + Sync + 'static {
+    /// Publishes a broadcast to the other instances.
+    ///
+    /// Called from
+    /// [`ConnectionManager::broadcast()`](crate::connection::ConnectionManager::broadcast),
+    /// which is synchronous, so this must not block. Network backends should queue the envelope
+    /// for a background task and return.
+    fn publish(&self, envelope: BroadcastEnvelope);
+
+    /// Returns the stream of broadcasts published by any instance.
+    ///
+    /// Called once when the backend is registered. Each envelope is
+    /// delivered to the local connections until the stream ends.
+    fn subscribe(&self) -> BoxStream<'static, BroadcastEnvelope>;
+}
+
+/// A broadcast travelling between instances.
+#[derive(Debug, Clone)]
+pub struct BroadcastEnvelope {
+    /// [`node_id()`](crate::connection::ConnectionManager::node_id) of the
+    /// publishing instance
+    pub origin: String,
+    /// The broadcast message
+    pub message: Message,
+}
+
+impl BroadcastEnvelope {
+    /// Encodes the envelope into bytes for a network backend.
+    ///
+    /// The format is the origin's length as a byte, the origin, the message
+    /// type as a byte, then the message data.
+    pub fn encode(&self) -> Vec<u8> {
+        let origin = self.origin.as_bytes();
+        let origin = &origin[..origin.len().min(u8::MAX as usize)];
+        let kind = match self.message.msg_type {
+            MessageType::Text => 0,
+            MessageType::Binary => 1,
+            MessageType::Ping => 2,
+            MessageType::Pong => 3,
+            MessageType::Close => 4,
+        };
+
+        let mut bytes = Vec::with_capacity(2 + origin.len() + self.message.data.len());
+        bytes.push(origin.len() as u8);
+        bytes.extend_from_slice(origin);
+        bytes.push(kind);
+        bytes.extend_from_slice(&self.message.data);
+        bytes
+    }
+
+    /// Decodes an envelope produced by [`encode()`](Self::encode).
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidMessage`] if `bytes` isn't a valid envelope.
+    pub fn decode(bytes: &[u8]) -> Result<Self> {
+        let (&len, rest) = bytes.split_first().ok_or(Error::InvalidMessage)?;
+        let len = len as usize;
+        if rest.len() <= len {
+            return Err(Error::InvalidMessage);
+        }
+        let origin = std::str::from_utf8(&rest[..len])
+            .map_err(|_| Error::InvalidMessage)?
+            .to_string();
+        let msg_type = match rest[len] {
+            0 => MessageType::Text,
+            1 => MessageType::Binary,
+            2 => MessageType::Ping,
+            3 => MessageType::Pong,
+            4 => MessageType::Close,
+            _ => return Err(Error::InvalidMessage),
+        };
+
+        Ok(Self {
+            origin,
+            message: Message {
+                data: bytes::Bytes::copy_from_slice(&rest[len + 1..]),
+                msg_type,
+            },
+        })
+    }
+}
+
+/// [`BroadcastBackend`] connecting the managers of one process.
+///
+/// Clones share the same channel, so give a clone to each router.
+///
+/// # Examples
+///
+/// ```
+/// use wsforge::prelude::*;
+/// use wsforge::broadcast::InProcessBackend;
+/// use std::sync::Arc;
+///
+/// # fn example() {
+/// let backend = Arc::new(InProcessBackend::new());
+///
+/// // Broadcasts on either router reach the clients of both
+/// let public = Router::new().broadcast_backend(backend.clone());
+/// let internal = Router::new().broadcast_backend(backend);
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct InProcessBackend {
+    sender: broadcast::Sender<BroadcastEnvelope>,
+}
+
+impl InProcessBackend {
+    /// Creates a backend buffering up to 1024 broadcasts per subscriber.
+    pub fn new() -> Self {
+        Self::with_capacity(1024)
+    }
+
+    /// Creates a backend buffering up to `capacity` broadcasts per
+    /// subscriber.
+    ///
+    /// A subscriber that falls further behind skips the oldest broadcasts.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is 0.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            sender: broadcast::channel(capacity).0,
+        }
+    }
+}
+
+impl Default for InProcessBackend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BroadcastBackend for InProcessBackend {
+    fn publish(&self, envelope: BroadcastEnvelope) {
+        // Fails only when nobody is subscribed
+        let _ = self.sender.send(envelope);
+    }
+
+    fn subscribe(&self) -> BoxStream<'static, BroadcastEnvelope> {
+        let receiver = self.sender.subscribe();
+        Box::pin(stream::unfold(receiver, |mut receiver| async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(envelope) => return Some((envelope, receiver)),
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        warn!(
+                            "⚠️ Broadcast subscriber lagged, skipped {} messages",
+                            skipped
+                        );
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return None,
+                }
+            }
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_envelope_round_trip() {
+        let envelope = BroadcastEnvelope {
+            origin: "node-a".to_string(),
+            message: Message::binary(vec![0, 1, 2]),
+        };
+
+        let decoded = BroadcastEnvelope::decode(&envelope.encode()).unwrap();
+        assert_eq!(decoded.origin, "node-a");
+        assert!(decoded.message.is_binary());
+        assert_eq!(decoded.message.as_bytes(), &[0, 1, 2]);
+
+        assert!(BroadcastEnvelope::decode(&[]).is_err());
+        assert!(BroadcastEnvelope::decode(&[6, b'n']).is_err());
+    }
+}
@@ -55,22 +55,34 @@
 //! manager.broadcast_to(&target_ids, Message::text("Private message"));
 //! ```
 
+use crate::broadcast::{BroadcastBackend, BroadcastEnvelope};
 use crate::error::{Error, Result};
+use crate::extractor::Extensions;
 use crate::message::Message;
-use dashmap::DashMap;
+use crate::metrics::Metrics;
+use crate::middleware::OutboundMiddleware;
+use crate::middleware::outbound::run_outbound;
+use dashmap::{DashMap, DashSet};
+use futures_util::future::BoxFuture;
 use futures_util::{SinkExt, StreamExt};
 use serde::{Deserialize, Serialize};
-use std::net::SocketAddr;
-use std::sync::Arc;
-use tokio::net::TcpStream;
-use tokio::sync::mpsc;
+use std::cell::RefCell;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::sync::{Notify, mpsc, oneshot};
 use tokio_tungstenite::WebSocketStream;
+use tokio_tungstenite::tungstenite::Error as WsError;
+use tokio_tungstenite::tungstenite::Message as TungsteniteMessage;
 use tracing::{debug, error, info, warn};
 
 /// A unique identifier for a WebSocket connection.
 ///
 /// Connection IDs are automatically generated and guaranteed to be unique
-/// within the lifetime of the application.
+/// within the lifetime of the application. A custom scheme can be set with
+/// [`Router::connection_id_generator()`](crate::router::Router::connection_id_generator).
 pub type ConnectionId = String;
 
 /// Metadata about a WebSocket connection.
@@ -80,29 +92,76 @@ pub type ConnectionId = String;
 ///
 /// # Examples
 ///
+/// New fields may be added in minor releases, so the struct can't be built
+/// with a literal outside this crate; use [`ConnectionInfo::new()`] and set
+/// the public fields instead.
+///
 /// ```
 /// use wsforge::connection::ConnectionInfo;
 /// use std::net::SocketAddr;
 ///
-/// let info = ConnectionInfo {
-///     id: "conn_0".to_string(),
-///     addr: "127.0.0.1:8080".parse().unwrap(),
-///     connected_at: 1634567890,
-///     protocol: Some("websocket".to_string()),
-/// };
+/// let mut info = ConnectionInfo::new("conn_0".to_string(), "127.0.0.1:8080".parse().unwrap());
+/// info.protocol = Some("websocket".to_string());
 ///
-/// println!("Connection {} from {}", info.id, info.addr);
+/// println!("Connection {} from {}", info.id, info.client_ip());
 /// ```
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
 pub struct ConnectionInfo {
     /// Unique identifier for this connection
     pub id: ConnectionId,
     /// Socket address of the connected client
+    ///
+    /// Clients connected over a Unix domain socket have no IP address; for
+    /// them this is the unspecified address `0.0.0.0:0`.
     pub addr: SocketAddr,
     /// Unix timestamp when the connection was established
+    ///
+    /// Wall-clock time, for wire formats and logs. Use
+    /// [`Connection::uptime()`] to measure how long a connection has been
+    /// open.
     pub connected_at: u64,
+    /// Unix timestamp of the last frame received from the client
+    ///
+    /// Refreshed for every frame, including pongs, so it can be used to
+    /// spot idle connections. See [`Router::keepalive()`](crate::router::Router::keepalive).
+    #[serde(default)]
+    pub last_seen: u64,
     /// Optional protocol information (e.g., "websocket", "wss")
     pub protocol: Option<String>,
+    /// Client address reported by a trusted reverse proxy
+    ///
+    /// Behind a load balancer, [`addr`](Self::addr) is the proxy's address.
+    /// Set from the `Forwarded` or `X-Forwarded-For` header when
+    /// [`Router::trust_proxy_headers()`](crate::router::Router::trust_proxy_headers)
+    /// is enabled and the peer is trusted, `None` otherwise.
+    #[serde(default)]
+    pub real_ip: Option<IpAddr>,
+}
+
+impl ConnectionInfo {
+    /// Creates connection info for a client that connected just now.
+    ///
+    /// [`connected_at`](Self::connected_at) and
+    /// [`last_seen`](Self::last_seen) are set to the current time, and the
+    /// optional fields are `None`.
+    pub fn new(id: ConnectionId, addr: SocketAddr) -> Self {
+        let now = unix_timestamp();
+        Self {
+            id,
+            addr,
+            connected_at: now,
+            last_seen: now,
+            protocol: None,
+            real_ip: None,
+        }
+    }
+
+    /// Returns the client's IP address: [`real_ip`](Self::real_ip) if a
+    /// trusted proxy reported one, otherwise the peer's address.
+    pub fn client_ip(&self) -> IpAddr {
+        self.real_ip.unwrap_or_else(|| self.addr.ip())
+    }
 }
 
 /// Represents an active WebSocket connection.
@@ -160,6 +219,22 @@ pub struct Connection {
     pub info: ConnectionInfo,
     /// Channel sender for outgoing messages
     sender: mpsc::UnboundedSender<Message>,
+    /// Values attached during the handshake, seeded into every message's extensions
+    pub(crate) extensions: Extensions,
+    /// Durable per-connection metadata, shared by all clones
+    meta: Extensions,
+    /// Signalled to tear down the connection's tasks
+    shutdown: Arc<Notify>,
+    /// Set once a close frame has been queued, shared by all clones
+    closing: Arc<AtomicBool>,
+    /// Path of the handshake URL, if the connection came through the router
+    pub(crate) path: Option<String>,
+    /// Write progress, if the connection is driven by [`serve_websocket`]
+    delivery: Option<Arc<Delivery>>,
+    /// When the connection was established, on the monotonic clock
+    connected: Instant,
+    /// Message and byte counters, shared by all clones
+    traffic: Arc<Traffic>,
 }
 
 impl Connection {
@@ -188,17 +263,21 @@ impl Connection {
     /// assert_eq!(conn.id(), "conn_0");
     /// ```
     pub fn new(id: ConnectionId, addr: SocketAddr, sender: mpsc::UnboundedSender<Message>) -> Self {
-        let info = ConnectionInfo {
-            id: id.clone(),
-            addr,
-            connected_at: std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .unwrap()
-                .as_secs(),
-            protocol: None,
-        };
+        let info = ConnectionInfo::new(id.clone(), addr);
 
-        Self { id, info, sender }
+        Self {
+            id,
+            info,
+            sender,
+            extensions: Extensions::new(),
+            meta: Extensions::new(),
+            shutdown: Arc::new(Notify::new()),
+            closing: Arc::new(AtomicBool::new(false)),
+            path: None,
+            delivery: None,
+            connected: Instant::now(),
+            traffic: Arc::new(Traffic::default()),
+        }
     }
 
     /// Sends a message to the connected client.
@@ -208,8 +287,9 @@ impl Connection {
     ///
     /// # Errors
     ///
-    /// Returns an error if the connection has been closed and the channel
-    /// receiver has been dropped.
+    /// Returns an error if the connection has been closed with
+    /// [`close()`](Self::close), or if it has ended and the channel receiver
+    /// has been dropped.
     ///
     /// # Examples
     ///
@@ -223,11 +303,219 @@ impl Connection {
     /// # }
     /// ```
     pub fn send(&self, message: Message) -> Result<()> {
+        if self.closing.load(Ordering::SeqCst) {
+            return Err(Error::custom(
+                "Failed to send message: connection is closing",
+            ));
+        }
+        self.enqueue(message)
+    }
+
+    /// Queues a message for the write task, even if the connection is closing.
+    fn enqueue(&self, message: Message) -> Result<()> {
+        if let Some(delivery) = &self.delivery
+            && let Some(limit) = &delivery.send_limit
+            && !message.is_close()
+            && !message.is_ping()
+            && delivery.pending() >= limit.max_pending as u64
+        {
+            debug!(
+                "🗑️ [{}] Send queue full ({} messages), dropping message",
+                self.id, limit.max_pending
+            );
+            if let Some(on_drop) = &limit.on_drop {
+                let on_drop = on_drop.clone();
+                let id = self.id.clone();
+                run_callback(Box::new(move || on_drop(id, message)));
+            }
+            return Ok(());
+        }
+
+        // Counted before queueing, so `send_flush` never undercounts the
+        // messages ahead of its own
+        if let Some(delivery) = &self.delivery {
+            delivery.queued.fetch_add(1, Ordering::SeqCst);
+            delivery.check_slow(self);
+        }
+
         self.sender
             .send(message)
             .map_err(|e| Error::custom(format!("Failed to send message: {}", e)))
     }
 
+    /// Returns the number of messages queued for this connection but not yet
+    /// written to the socket.
+    ///
+    /// Since [`send()`](Self::send) never blocks, a client that reads slower
+    /// than the server sends makes this number grow without bound. Check it
+    /// before sending bulk updates, use
+    /// [`Router::on_slow_client()`](crate::router::Router::on_slow_client) to
+    /// be notified when it crosses a threshold, or cap it with
+    /// [`Router::max_pending_per_connection()`](crate::router::Router::max_pending_per_connection).
+    ///
+    /// Connections created with [`Connection::new()`] outside the router have
+    /// no write task, so this is always 0 for them.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    ///
+    /// async fn tick(conn: Connection) -> Result<()> {
+    ///     // Skip non-essential updates for clients that are falling behind
+    ///     if conn.pending_count() < 100 {
+    ///         conn.send_text("position update")?;
+    ///     }
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn pending_count(&self) -> usize {
+        self.delivery
+            .as_ref()
+            .map_or(0, |delivery| delivery.pending() as usize)
+    }
+
+    /// Returns `true` if messages sent to this connection can still reach
+    /// the client.
+    ///
+    /// Returns `false` once the connection is closing, through
+    /// [`close()`](Self::close) on any clone, or once it has ended and its
+    /// write task is gone. A `true` result can go stale right away, so
+    /// [`send()`](Self::send) may still fail; this lets long-lived tasks
+    /// holding old clones skip closed connections up front.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    ///
+    /// # fn example(subscribers: &mut Vec<Connection>) {
+    /// // Drop subscribers whose clients have gone away
+    /// subscribers.retain(|conn| conn.is_open());
+    ///
+    /// for conn in subscribers.iter() {
+    ///     let _ = conn.send_text("price update");
+    /// }
+    /// # }
+    /// ```
+    pub fn is_open(&self) -> bool {
+        !self.closing.load(Ordering::SeqCst) && !self.sender.is_closed()
+    }
+
+    /// Closes the connection from the server side.
+    ///
+    /// Queues a close frame, carrying the given close code and reason if
+    /// provided, after any messages already queued, then shuts down the
+    /// connection's read and write tasks. If the client doesn't accept the
+    /// close frame within a second, the socket is dropped regardless. The
+    /// connection is removed from the
+    /// [`ConnectionManager`] once its tasks have ended, and the router's
+    /// `on_disconnect` callback runs as usual.
+    ///
+    /// Every later [`send()`](Self::send) on this connection or any of its
+    /// clones fails.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the connection is already closing, or if it has
+    /// already ended.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    ///
+    /// async fn handler(msg: Message, conn: Connection) -> Result<()> {
+    ///     if msg.as_text() == Some("spam") {
+    ///         // 1008 = policy violation
+    ///         conn.close(Some((1008, "policy violation".into())))?;
+    ///     }
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn close(&self, close: Option<(u16, String)>) -> Result<()> {
+        if self.closing.swap(true, Ordering::SeqCst) {
+            return Err(Error::custom("Connection is already closing"));
+        }
+
+        let message = match close {
+            Some((code, reason)) => Message::close_with(code, reason),
+            None => Message::close(),
+        };
+
+        let queued = self.enqueue(message);
+        self.shutdown.notify_one();
+        queued
+    }
+
+    /// Sends a message and waits until it has been written to the socket.
+    ///
+    /// Unlike [`send()`](Self::send), this resolves only after the connection's
+    /// write task has handed the message to the WebSocket, including every
+    /// message queued before it. Use it when the message must not be lost to
+    /// a disconnect that follows, such as a final error before closing.
+    ///
+    /// Prefer [`send()`](Self::send) for broadcasts and other hot paths, since
+    /// each call waits for a round trip through the write task.
+    ///
+    /// Connections created with [`Connection::new()`] outside the router have
+    /// no write task to wait for, so the message is only queued.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the connection closes before the message is
+    /// written.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    /// use std::sync::Arc;
+    ///
+    /// async fn kick(conn: Connection, State(manager): State<Arc<ConnectionManager>>) -> Result<()> {
+    ///     conn.send_flush(Message::text("You have been removed")).await?;
+    ///     manager.disconnect(conn.id(), Some((1008, "Removed".to_string())))?;
+    ///     Ok(())
+    /// }
+    /// ```
+    pub async fn send_flush(&self, message: Message) -> Result<()> {
+        let Some(delivery) = &self.delivery else {
+            return self.send(message);
+        };
+        if self.closing.load(Ordering::SeqCst) {
+            return Err(Error::custom(
+                "Failed to send message: connection is closing",
+            ));
+        }
+
+        // Queued before counting, so every message ahead of this one is
+        // already included in `position`
+        self.sender
+            .send(message)
+            .map_err(|e| Error::custom(format!("Failed to send message: {}", e)))?;
+        let position = delivery.queued.fetch_add(1, Ordering::SeqCst) + 1;
+        delivery.check_slow(self);
+
+        let ack = {
+            let mut progress = delivery.progress.lock().unwrap();
+            if progress.written >= position {
+                return Ok(());
+            }
+            if progress.closed {
+                return Err(Error::custom(
+                    "Connection closed before the message was sent",
+                ));
+            }
+
+            let (tx, rx) = oneshot::channel();
+            progress.pending.push((position, tx));
+            rx
+        };
+
+        ack.await
+            .map_err(|_| Error::custom("Connection closed before the message was sent"))
+    }
+
     /// Sends a text message to the connected client.
     ///
     /// This is a convenience method that creates a text [`Message`] and sends it.
@@ -307,6 +595,96 @@ impl Connection {
         self.send_text(json)
     }
 
+    /// Serializes data to JSON and sends it as a binary message.
+    ///
+    /// Like [`send_json()`](Self::send_json), but for clients that expect
+    /// JSON in binary frames, e.g. because of the negotiated subprotocol.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    ///
+    /// # async fn example(conn: Connection) -> Result<()> {
+    /// let state = serde_json::json!({ "score": 1000 });
+    /// if conn.info().protocol.as_deref() == Some("game.binary") {
+    ///     conn.send_json_binary(&state)?;
+    /// } else {
+    ///     conn.send_json(&state)?;
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn send_json_binary<T: Serialize>(&self, data: &T) -> Result<()> {
+        self.send(Message::json_binary(data)?)
+    }
+
+    /// Sends several messages to the connected client, in order.
+    ///
+    /// The messages are queued one after another on the connection's
+    /// channel, so the client receives them in iteration order. Stops at the
+    /// first message that can't be queued and returns its error; messages
+    /// queued before it are still sent.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the connection has been closed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    ///
+    /// # async fn example(conn: Connection) -> Result<()> {
+    /// conn.send_all([
+    ///     Message::text("ack"),
+    ///     Message::text("snapshot"),
+    ///     Message::text("welcome"),
+    /// ])?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn send_all(&self, messages: impl IntoIterator<Item = Message>) -> Result<()> {
+        for message in messages {
+            self.send(message)?;
+        }
+        Ok(())
+    }
+
+    /// Serializes each item to JSON and sends them as text messages, in
+    /// order.
+    ///
+    /// All items are serialized before the first one is sent, so a
+    /// serialization failure sends nothing. Otherwise behaves like
+    /// [`send_all()`](Self::send_all).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if:
+    /// - Serialization of any item fails
+    /// - The connection has been closed
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    ///
+    /// # async fn example(conn: Connection) -> Result<()> {
+    /// conn.send_all_json([
+    ///     serde_json::json!({ "type": "ack" }),
+    ///     serde_json::json!({ "type": "snapshot", "players": [] }),
+    /// ])?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn send_all_json<T: Serialize>(&self, items: impl IntoIterator<Item = T>) -> Result<()> {
+        let mut messages = Vec::new();
+        for item in items {
+            messages.push(Message::text(serde_json::to_string(&item)?));
+        }
+        self.send_all(messages)
+    }
+
     /// Returns the unique identifier for this connection.
     ///
     /// # Examples
@@ -338,235 +716,1504 @@ impl Connection {
     pub fn info(&self) -> &ConnectionInfo {
         &self.info
     }
-}
 
-/// Manages a collection of active WebSocket connections.
-///
-/// `ConnectionManager` provides thread-safe operations for managing connections,
-/// including adding, removing, and broadcasting messages. It uses [`DashMap`]
-/// internally for lock-free concurrent access.
-///
-/// # Thread Safety
-///
-/// All operations are thread-safe and can be called from multiple threads
-/// concurrently without additional synchronization.
-///
-/// # Examples
-///
-/// ## Basic Usage
-///
-/// ```
-/// use wsforge::prelude::*;
-/// use std::sync::Arc;
-///
-/// let manager = Arc::new(ConnectionManager::new());
-///
-/// // Get connection count
-/// let count = manager.count();
-/// println!("Active connections: {}", count);
-///
-/// // Get all connection IDs
-/// let ids = manager.all_ids();
-/// for id in ids {
-///     println!("Connection: {}", id);
-/// }
-/// ```
-///
-/// ## Broadcasting
-///
-/// ```
-/// use wsforge::prelude::*;
-/// use std::sync::Arc;
-///
-/// # let manager = Arc::new(ConnectionManager::new());
-/// // Broadcast system announcement
-/// manager.broadcast(Message::text("Server maintenance in 5 minutes"));
-///
-/// // Notify everyone except the sender
-/// let sender_id = "conn_42";
-/// manager.broadcast_except(&sender_id.to_string(),
-///     Message::text("New user joined the chat"));
-/// ```
-pub struct ConnectionManager {
-    /// Thread-safe map of active connections
-    connections: Arc<DashMap<ConnectionId, Connection>>,
-}
+    /// Waits until the connection has ended and its channel receiver has
+    /// been dropped.
+    pub(crate) async fn closed(&self) {
+        self.sender.closed().await
+    }
 
-impl ConnectionManager {
-    /// Creates a new empty `ConnectionManager`.
+    /// Returns how long the connection has been open.
+    ///
+    /// Measured on a monotonic clock, so unlike
+    /// [`ConnectionInfo::connected_at`] it isn't affected by changes to the
+    /// system clock.
     ///
     /// # Examples
     ///
     /// ```
     /// use wsforge::prelude::*;
-    /// use std::sync::Arc;
+    /// use std::time::Duration;
     ///
-    /// let manager = Arc::new(ConnectionManager::new());
-    /// assert_eq!(manager.count(), 0);
+    /// # fn example(conn: Connection) -> Result<()> {
+    /// if conn.uptime() > Duration::from_secs(3600) {
+    ///     conn.send_text("You've been connected for over an hour")?;
+    /// }
+    /// # Ok(())
+    /// # }
     /// ```
-    pub fn new() -> Self {
-        Self {
-            connections: Arc::new(DashMap::new()),
-        }
+    pub fn uptime(&self) -> Duration {
+        self.connected.elapsed()
     }
 
-    /// Adds a connection to the manager.
+    /// Returns how many messages and bytes the connection has sent and
+    /// received so far.
     ///
-    /// Returns the total number of connections after adding.
+    /// Only text and binary messages are counted, once they have been
+    /// written to or read from the socket. Bytes are payload bytes, without
+    /// WebSocket framing.
     ///
     /// # Examples
     ///
     /// ```
     /// use wsforge::prelude::*;
-    /// use tokio::sync::mpsc;
-    /// use std::net::SocketAddr;
-    ///
-    /// # fn example() {
-    /// let manager = ConnectionManager::new();
-    /// let (tx, rx) = mpsc::unbounded_channel();
-    /// let addr: SocketAddr = "127.0.0.1:8080".parse().unwrap();
-    /// let conn = Connection::new("conn_0".to_string(), addr, tx);
     ///
-    /// let count = manager.add(conn);
-    /// assert_eq!(count, 1);
+    /// # fn example(conn: Connection) {
+    /// let stats = conn.stats();
+    /// println!(
+    ///     "{}: {} in / {} out ({} / {} bytes)",
+    ///     conn.id(),
+    ///     stats.messages_received,
+    ///     stats.messages_sent,
+    ///     stats.bytes_received,
+    ///     stats.bytes_sent,
+    /// );
     /// # }
     /// ```
-    pub fn add(&self, conn: Connection) -> usize {
-        let id = conn.id.clone();
-        self.connections.insert(id.clone(), conn);
-        let count = self.connections.len();
-        info!("Added connection: {} (Total: {})", id, count);
-        count
+    pub fn stats(&self) -> ConnStats {
+        self.traffic.snapshot()
     }
 
-    /// Removes a connection from the manager.
+    /// Returns the path of the URL the client connected to.
     ///
-    /// Returns the removed connection if it existed, or `None` if not found.
+    /// For a client connecting to `ws://host/game?room=42` this is `/game`.
+    /// Returns `None` for connections that were not established through the
+    /// router's handshake, such as those created with [`Connection::new()`].
     ///
     /// # Examples
     ///
     /// ```
     /// use wsforge::prelude::*;
     ///
-    /// # fn example(manager: &ConnectionManager) {
-    /// let conn_id = "conn_42".to_string();
-    /// if let Some(conn) = manager.remove(&conn_id) {
-    ///     println!("Removed connection: {}", conn.id());
+    /// async fn handler(conn: Connection) -> Result<String> {
+    ///     Ok(format!("Connected to {}", conn.path().unwrap_or("/")))
     /// }
-    /// # }
     /// ```
-    pub fn remove(&self, id: &ConnectionId) -> Option<Connection> {
-        let result = self.connections.remove(id).map(|(_, conn)| conn);
-        let count = self.connections.len();
-        info!("Removed connection: {} (Total: {})", id, count);
-        result
+    pub fn path(&self) -> Option<&str> {
+        self.path.as_deref()
     }
 
-    /// Retrieves a connection by its ID.
+    /// Stores a value that lives as long as the connection.
     ///
-    /// Returns a clone of the connection if found, or `None` if not found.
-    ///
-    /// # Performance
-    ///
-    /// This operation is O(1) and does not block other concurrent operations.
+    /// Unlike [`Extensions`], which are created fresh for every message,
+    /// metadata persists across messages and is shared by every clone of this
+    /// `Connection`, including those returned by [`ConnectionManager::get()`].
+    /// Storing a value under an existing key replaces it.
     ///
     /// # Examples
     ///
     /// ```
     /// use wsforge::prelude::*;
     ///
-    /// # async fn example(manager: &ConnectionManager) -> Result<()> {
-    /// let conn_id = "conn_0".to_string();
-    /// if let Some(conn) = manager.get(&conn_id) {
+    /// async fn login(msg: Message, conn: Connection) -> Result<String> {
+    ///     let user_id: u64 = msg.as_text().unwrap_or("0").parse().unwrap_or(0);
+    ///     conn.set_meta("user_id", user_id);
+    ///     Ok("logged in".to_string())
+    /// }
+    ///
+    /// async fn whoami(conn: Connection) -> Result<String> {
+    ///     match conn.get_meta::<u64>("user_id") {
+    ///         Some(id) => Ok(format!("user {}", id)),
+    ///         None => Ok("anonymous".to_string()),
+    ///     }
+    /// }
+    /// ```
+    pub fn set_meta<T: Send + Sync + 'static>(&self, key: impl Into<String>, value: T) {
+        self.meta.insert(key, value);
+    }
+
+    /// Returns a value stored with [`set_meta()`](Self::set_meta).
+    ///
+    /// Returns `None` if the key doesn't exist or holds a different type.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    ///
+    /// # fn example(conn: Connection) {
+    /// conn.set_meta("role", "admin".to_string());
+    ///
+    /// let role = conn.get_meta::<String>("role");
+    /// assert_eq!(role.as_deref().map(String::as_str), Some("admin"));
+    /// # }
+    /// ```
+    pub fn get_meta<T: Send + Sync + 'static>(&self, key: &str) -> Option<Arc<T>> {
+        self.meta.get(key)
+    }
+
+    /// Removes a value stored with [`set_meta()`](Self::set_meta).
+    ///
+    /// Returns `true` if a value was stored under `key`.
+    pub fn remove_meta(&self, key: &str) -> bool {
+        self.meta.remove(key)
+    }
+}
+
+/// Outcome of a broadcast.
+///
+/// Returned by [`ConnectionManager::broadcast()`] and related methods. Only
+/// connections that existed when the broadcast ran are counted. A send fails
+/// when the connection's write task is already gone, so `failed_ids` lists
+/// connections that are closing and can be pruned.
+///
+/// Broadcasts to every connection skip connections whose write task is gone
+/// and remove them from the manager instead, so they aren't counted.
+///
+/// Ignoring the report is fine when delivery counts don't matter.
+///
+/// # Examples
+///
+/// ```
+/// use wsforge::prelude::*;
+///
+/// # fn example(manager: &ConnectionManager) {
+/// let report = manager.broadcast(Message::text("tick"));
+///
+/// for id in &report.failed_ids {
+///     manager.remove(id);
+/// }
+/// println!("Delivered to {}/{}", report.succeeded, report.attempted);
+/// # }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BroadcastReport {
+    /// Connections the message was sent to
+    pub attempted: usize,
+    /// Connections the message was queued for
+    pub succeeded: usize,
+    /// Connections whose send failed
+    pub failed: usize,
+    /// IDs of the connections whose send failed
+    pub failed_ids: Vec<ConnectionId>,
+}
+
+impl BroadcastReport {
+    /// Returns `true` if the message was queued for every attempted connection.
+    pub fn is_complete(&self) -> bool {
+        self.failed == 0
+    }
+
+    /// Sends `message` to `conn` and records the outcome.
+    fn send(&mut self, conn: &Connection, message: &Message) {
+        self.attempted += 1;
+        match conn.send(message.clone()) {
+            Ok(()) => {
+                self.succeeded += 1;
+                debug!("✅ Broadcast sent to {}", conn.id());
+            }
+            Err(e) => {
+                self.failed += 1;
+                self.failed_ids.push(conn.id().clone());
+                error!("❌ Failed to broadcast to {}: {}", conn.id(), e);
+            }
+        }
+    }
+}
+
+/// What ended a connection.
+///
+/// See [`DisconnectReason`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DisconnectSource {
+    /// The client sent a close frame
+    ClientClose,
+    /// Reading from the socket failed, or it ended without a close frame
+    ReadError,
+    /// Writing to the socket failed
+    WriteError,
+    /// The server closed the connection, e.g. with
+    /// [`Connection::close()`] or
+    /// [`ConnectionManager::disconnect()`]
+    ServerClose,
+    /// The client stopped answering keepalive pings
+    Timeout,
+}
+
+/// Why a connection ended, passed to
+/// [`Router::on_disconnect_with_reason()`](crate::router::Router::on_disconnect_with_reason).
+///
+/// # Examples
+///
+/// ```
+/// use wsforge::prelude::*;
+/// use wsforge::DisconnectSource;
+///
+/// # fn example() {
+/// let router = Router::new()
+///     .on_disconnect_with_reason(|_manager, conn_id, reason| {
+///         if reason.is_clean() {
+///             println!("{} left", conn_id);
+///         } else if reason.source == DisconnectSource::Timeout {
+///             println!("{} timed out", conn_id);
+///         } else {
+///             println!("{} dropped: {:?}", conn_id, reason);
+///         }
+///     });
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DisconnectReason {
+    /// Code of the close frame sent or received, if it carried one
+    pub code: Option<u16>,
+    /// Reason of the close frame, or a description of the error
+    pub reason: Option<String>,
+    /// What ended the connection
+    pub source: DisconnectSource,
+}
+
+impl DisconnectReason {
+    /// Creates a reason from a close frame's code and reason, if any.
+    fn closed(source: DisconnectSource, close: Option<(u16, String)>) -> Self {
+        let (code, reason) = match close {
+            Some((code, reason)) if reason.is_empty() => (Some(code), None),
+            Some((code, reason)) => (Some(code), Some(reason)),
+            None => (None, None),
+        };
+        Self {
+            code,
+            reason,
+            source,
+        }
+    }
+
+    /// Creates a reason for a failed read or write.
+    fn failed(source: DisconnectSource, error: impl std::fmt::Display) -> Self {
+        Self {
+            code: None,
+            reason: Some(error.to_string()),
+            source,
+        }
+    }
+
+    /// Returns `true` if the connection ended with a close handshake and a
+    /// normal close code (1000 Normal Closure or 1001 Going Away), or none.
+    pub fn is_clean(&self) -> bool {
+        matches!(
+            self.source,
+            DisconnectSource::ClientClose | DisconnectSource::ServerClose
+        ) && matches!(self.code, None | Some(1000) | Some(1001))
+    }
+}
+
+/// Message and byte counts of a connection, returned by
+/// [`Connection::stats()`].
+///
+/// [`ConnectionManager::stats()`] sums them over all active connections.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ConnStats {
+    /// Text and binary messages written to the socket
+    pub messages_sent: u64,
+    /// Text and binary messages read from the socket
+    pub messages_received: u64,
+    /// Payload bytes of the messages sent
+    pub bytes_sent: u64,
+    /// Payload bytes of the messages received
+    pub bytes_received: u64,
+}
+
+impl std::ops::AddAssign for ConnStats {
+    fn add_assign(&mut self, other: Self) {
+        self.messages_sent += other.messages_sent;
+        self.messages_received += other.messages_received;
+        self.bytes_sent += other.bytes_sent;
+        self.bytes_received += other.bytes_received;
+    }
+}
+
+/// Counters behind [`ConnStats`], updated by the read and write tasks.
+///
+/// Relaxed ordering is enough: each counter is independent, and a snapshot
+/// only needs to be roughly current.
+#[derive(Debug, Default)]
+struct Traffic {
+    messages_sent: AtomicU64,
+    messages_received: AtomicU64,
+    bytes_sent: AtomicU64,
+    bytes_received: AtomicU64,
+}
+
+impl Traffic {
+    /// Records `messages` messages written in one frame of `bytes` bytes.
+    fn sent(&self, messages: u64, bytes: usize) {
+        self.messages_sent.fetch_add(messages, Ordering::Relaxed);
+        self.bytes_sent.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    /// Records a message of `bytes` bytes read from the socket.
+    fn received(&self, bytes: usize) {
+        self.messages_received.fetch_add(1, Ordering::Relaxed);
+        self.bytes_received
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    fn snapshot(&self) -> ConnStats {
+        ConnStats {
+            messages_sent: self.messages_sent.load(Ordering::Relaxed),
+            messages_received: self.messages_received.load(Ordering::Relaxed),
+            bytes_sent: self.bytes_sent.load(Ordering::Relaxed),
+            bytes_received: self.bytes_received.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Manages a collection of active WebSocket connections.
+///
+/// `ConnectionManager` provides thread-safe operations for managing connections,
+/// including adding, removing, and broadcasting messages. It uses [`DashMap`]
+/// internally for lock-free concurrent access.
+///
+/// # Thread Safety
+///
+/// All operations are thread-safe and can be called from multiple threads
+/// concurrently without additional synchronization.
+///
+/// # Examples
+///
+/// ## Basic Usage
+///
+/// ```
+/// use wsforge::prelude::*;
+/// use std::sync::Arc;
+///
+/// let manager = Arc::new(ConnectionManager::new());
+///
+/// // Get connection count
+/// let count = manager.count();
+/// println!("Active connections: {}", count);
+///
+/// // Get all connection IDs
+/// let ids = manager.all_ids();
+/// for id in ids {
+///     println!("Connection: {}", id);
+/// }
+/// ```
+///
+/// ## Broadcasting
+///
+/// ```
+/// use wsforge::prelude::*;
+/// use std::sync::Arc;
+///
+/// # let manager = Arc::new(ConnectionManager::new());
+/// // Broadcast system announcement
+/// manager.broadcast(Message::text("Server maintenance in 5 minutes"));
+///
+/// // Notify everyone except the sender
+/// let sender_id = "conn_42";
+/// manager.broadcast_except(&sender_id.to_string(),
+///     Message::text("New user joined the chat"));
+/// ```
+pub struct ConnectionManager {
+    /// Thread-safe map of active connections
+    connections: Arc<DashMap<ConnectionId, Connection>>,
+    /// Rooms and their members
+    rooms: Groups,
+    /// Tags and the connections carrying them
+    tags: Groups,
+    /// Resume token issued to each connection
+    resume_tokens: DashMap<ConnectionId, String>,
+    /// Sessions of removed connections awaiting resumption, keyed by token
+    parked: DashMap<String, ParkedSession>,
+    /// How long a removed connection's session can be resumed, if at all
+    resume_window: Mutex<Option<Duration>>,
+    /// Identifies this manager's broadcasts to the backend
+    node_id: String,
+    /// Forwards broadcasts to other instances, if set
+    backend: Mutex<Option<Arc<dyn BroadcastBackend>>>,
+}
+
+/// What a removed connection leaves behind for
+/// [`ConnectionManager::resume()`].
+struct ParkedSession {
+    /// The connection's metadata
+    meta: Extensions,
+    /// Rooms the connection had joined
+    rooms: Vec<String>,
+    /// Tags the connection carried
+    tags: Vec<String>,
+    /// When the session can no longer be resumed
+    expires_at: Instant,
+}
+
+/// Creates an unguessable resume token.
+///
+/// The token is a bearer credential for the parked session, so it is 128
+/// bits from the operating system's CSPRNG, hex encoded.
+fn new_resume_token() -> String {
+    let mut bytes = [0u8; 16];
+    getrandom::getrandom(&mut bytes).expect("operating system random source is unavailable");
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Named groups of connections, such as rooms or tags, indexed both ways.
+#[derive(Default)]
+struct Groups {
+    /// Members of each group, keyed by group name
+    members: DashMap<String, DashSet<ConnectionId>>,
+    /// Groups each connection belongs to, used to evict it on removal
+    memberships: DashMap<ConnectionId, DashSet<String>>,
+}
+
+impl Groups {
+    /// Adds a connection to a group, creating the group if needed.
+    fn join(&self, conn_id: &ConnectionId, group: &str) {
+        self.members
+            .entry(group.to_string())
+            .or_default()
+            .insert(conn_id.clone());
+        self.memberships
+            .entry(conn_id.clone())
+            .or_default()
+            .insert(group.to_string());
+    }
+
+    /// Removes a connection from a group, dropping the group once empty.
+    ///
+    /// Returns `true` if the connection was a member.
+    fn leave(&self, conn_id: &ConnectionId, group: &str) -> bool {
+        if let Some(groups) = self.memberships.get(conn_id) {
+            groups.remove(group);
+        }
+        self.memberships
+            .remove_if(conn_id, |_, groups| groups.is_empty());
+
+        let removed = self
+            .members
+            .get(group)
+            .map(|members| members.remove(conn_id).is_some())
+            .unwrap_or(false);
+        self.members
+            .remove_if(group, |_, members| members.is_empty());
+        removed
+    }
+
+    /// Removes a connection from every group it belongs to.
+    fn leave_all(&self, conn_id: &ConnectionId) {
+        if let Some((_, groups)) = self.memberships.remove(conn_id) {
+            for group in groups {
+                if let Some(members) = self.members.get(&group) {
+                    members.remove(conn_id);
+                }
+                self.members
+                    .remove_if(&group, |_, members| members.is_empty());
+            }
+        }
+    }
+
+    /// Returns the IDs of all connections in a group.
+    fn members(&self, group: &str) -> Vec<ConnectionId> {
+        self.members
+            .get(group)
+            .map(|members| members.iter().map(|id| id.key().clone()).collect())
+            .unwrap_or_default()
+    }
+
+    /// Returns the names of all groups a connection belongs to.
+    fn groups_for(&self, conn_id: &ConnectionId) -> Vec<String> {
+        self.memberships
+            .get(conn_id)
+            .map(|groups| groups.iter().map(|g| g.key().clone()).collect())
+            .unwrap_or_default()
+    }
+
+    /// Returns the IDs of the connections that are in every one of `groups`.
+    fn members_of_all(&self, groups: &[&str]) -> Vec<ConnectionId> {
+        let Some((first, rest)) = groups.split_first() else {
+            return Vec::new();
+        };
+        self.members(first)
+            .into_iter()
+            .filter(|id| {
+                rest.iter().all(|group| {
+                    self.members
+                        .get(*group)
+                        .is_some_and(|members| members.contains(id))
+                })
+            })
+            .collect()
+    }
+}
+
+impl ConnectionManager {
+    /// Creates a new empty `ConnectionManager`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    /// use std::sync::Arc;
+    ///
+    /// let manager = Arc::new(ConnectionManager::new());
+    /// assert_eq!(manager.count(), 0);
+    /// ```
+    pub fn new() -> Self {
+        Self {
+            connections: Arc::new(DashMap::new()),
+            rooms: Groups::default(),
+            tags: Groups::default(),
+            resume_tokens: DashMap::new(),
+            parked: DashMap::new(),
+            resume_window: Mutex::new(None),
+            node_id: new_resume_token(),
+            backend: Mutex::new(None),
+        }
+    }
+
+    /// Adds a connection to the manager.
+    ///
+    /// Returns the total number of connections after adding.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    /// use tokio::sync::mpsc;
+    /// use std::net::SocketAddr;
+    ///
+    /// # fn example() {
+    /// let manager = ConnectionManager::new();
+    /// let (tx, rx) = mpsc::unbounded_channel();
+    /// let addr: SocketAddr = "127.0.0.1:8080".parse().unwrap();
+    /// let conn = Connection::new("conn_0".to_string(), addr, tx);
+    ///
+    /// let count = manager.add(conn);
+    /// assert_eq!(count, 1);
+    /// # }
+    /// ```
+    pub fn add(&self, conn: Connection) -> usize {
+        let id = conn.id.clone();
+        self.connections.insert(id.clone(), conn);
+        let count = self.connections.len();
+        info!("Added connection: {} (Total: {})", id, count);
+        count
+    }
+
+    /// Removes a connection from the manager.
+    ///
+    /// The connection is also evicted from every room it had joined and
+    /// loses all its tags. If it was issued a
+    /// [`resume_token()`](Self::resume_token) and a
+    /// [resume window](Self::set_resume_window) is set, its metadata, rooms
+    /// and tags are kept for [`resume()`](Self::resume) until the window
+    /// ends.
+    /// Returns the removed connection if it existed, or `None` if not found.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    ///
+    /// # fn example(manager: &ConnectionManager) {
+    /// let conn_id = "conn_42".to_string();
+    /// if let Some(conn) = manager.remove(&conn_id) {
+    ///     println!("Removed connection: {}", conn.id());
+    /// }
+    /// # }
+    /// ```
+    pub fn remove(&self, id: &ConnectionId) -> Option<Connection> {
+        let result = self.connections.remove(id).map(|(_, conn)| conn);
+        self.forget(id, result)
+    }
+
+    /// Cleans up after `id` was taken out of the connection map.
+    fn forget(&self, id: &ConnectionId, result: Option<Connection>) -> Option<Connection> {
+        if let Some((_, token)) = self.resume_tokens.remove(id)
+            && let Some(conn) = &result
+        {
+            self.park(token, conn);
+        }
+        self.rooms.leave_all(id);
+        self.tags.leave_all(id);
+        if result.is_some() {
+            let count = self.connections.len();
+            info!("Removed connection: {} (Total: {})", id, count);
+        }
+        result
+    }
+
+    /// Closes a connection from the server side.
+    ///
+    /// A close frame is sent to the client, carrying the given close code and
+    /// reason if provided, and the connection is removed from the manager
+    /// immediately so it no longer receives broadcasts. The connection's
+    /// read and write tasks are then shut down; if the client doesn't accept
+    /// the close frame within a second, the socket is dropped regardless.
+    /// The router's `on_disconnect` callback still runs for the connection.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::ConnectionNotFound`] if no active connection has the given ID.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    /// use std::sync::Arc;
+    ///
+    /// async fn ban_handler(
+    ///     msg: Message,
+    ///     State(manager): State<Arc<ConnectionManager>>,
+    /// ) -> Result<()> {
+    ///     let target = msg.as_text().unwrap_or_default().to_string();
+    ///
+    ///     // 1008 = policy violation
+    ///     manager.disconnect(&target, Some((1008, "Banned".to_string())))?;
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn disconnect(&self, id: &ConnectionId, close: Option<(u16, String)>) -> Result<()> {
+        let conn = self
+            .remove(id)
+            .ok_or_else(|| Error::ConnectionNotFound(id.clone()))?;
+
+        // The connection may already be closing, or its write task may be
+        // gone, in which case there is nothing to flush
+        let _ = conn.close(close);
+        Ok(())
+    }
+
+    /// Retrieves a connection by its ID.
+    ///
+    /// Returns a clone of the connection if found, or `None` if not found.
+    ///
+    /// # Performance
+    ///
+    /// This operation is O(1) and does not block other concurrent operations.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    ///
+    /// # async fn example(manager: &ConnectionManager) -> Result<()> {
+    /// let conn_id = "conn_0".to_string();
+    /// if let Some(conn) = manager.get(&conn_id) {
     ///     conn.send_text("Hello!")?;
     /// }
-    /// # Ok(())
-    /// # }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn get(&self, id: &ConnectionId) -> Option<Connection> {
+        self.connections.get(id).map(|entry| entry.value().clone())
+    }
+
+    /// Broadcasts a message to all active connections.
+    ///
+    /// This method iterates through all connections and sends the message
+    /// to each one. Failed sends are logged but do not stop the broadcast,
+    /// and are listed in the returned [`BroadcastReport`].
+    ///
+    /// Connections whose write task has already ended, e.g. during a mass
+    /// disconnect, are skipped and removed from the manager, so they neither
+    /// log a failed send nor linger in [`count()`](Self::count).
+    ///
+    /// If a [broadcast backend](Self::set_broadcast_backend) is set, the
+    /// message is also published to the other instances, which deliver it to
+    /// their own connections. The report only covers local connections.
+    ///
+    /// # Performance
+    ///
+    /// Broadcasts are performed synchronously but send operations are async,
+    /// so messages are queued immediately and sent in the background.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    ///
+    /// # fn example(manager: &ConnectionManager) {
+    /// manager.broadcast(Message::text("Server announcement!"));
+    /// # }
+    /// ```
+    pub fn broadcast(&self, message: Message) -> BroadcastReport {
+        let backend = self.backend.lock().unwrap().clone();
+        if let Some(backend) = backend {
+            backend.publish(BroadcastEnvelope {
+                origin: self.node_id.clone(),
+                message: message.clone(),
+            });
+        }
+        self.broadcast_local(message)
+    }
+
+    /// Broadcasts a message to the connections of this instance only.
+    ///
+    /// Same as [`broadcast()`](Self::broadcast) without a broadcast backend.
+    /// With one, the message isn't published to other instances, e.g. for a
+    /// notice that only this instance is restarting.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    ///
+    /// # fn example(manager: &ConnectionManager) {
+    /// manager.broadcast_local(Message::text("This server is restarting"));
+    /// # }
+    /// ```
+    pub fn broadcast_local(&self, message: Message) -> BroadcastReport {
+        debug!(
+            "Broadcasting message to {} connections",
+            self.connections.len()
+        );
+
+        let mut report = BroadcastReport::default();
+        self.for_each_open(|conn| report.send(conn, &message));
+
+        info!(
+            "Broadcast complete: {} success, {} failed out of {} total",
+            report.succeeded, report.failed, report.attempted
+        );
+        report
+    }
+
+    /// Broadcasts a message to all connections except one.
+    ///
+    /// This is useful for notifying all users about an action taken by one user,
+    /// without sending the notification back to the actor. Returns a
+    /// [`BroadcastReport`] and prunes closed connections like
+    /// [`broadcast()`](Self::broadcast).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    ///
+    /// # fn example(manager: &ConnectionManager) {
+    /// let sender_id = "conn_42".to_string();
+    /// manager.broadcast_except(&sender_id,
+    ///     Message::text("User 42 sent a message"));
+    /// # }
+    /// ```
+    pub fn broadcast_except(&self, except_id: &ConnectionId, message: Message) -> BroadcastReport {
+        debug!(
+            "Broadcasting message to {} connections (except {})",
+            self.connections.len().saturating_sub(1),
+            except_id
+        );
+
+        let mut report = BroadcastReport::default();
+        self.for_each_open(|conn| {
+            if conn.id() != except_id {
+                report.send(conn, &message);
+            }
+        });
+        report
+    }
+
+    /// Sends `reply` to one connection and `broadcast` to all others.
+    ///
+    /// This is the common "acknowledge the sender, notify everyone else"
+    /// pattern of chat and game servers, done in a single pass over the
+    /// connections. The returned [`BroadcastReport`] covers both messages,
+    /// so the sender counts as one attempted delivery if it is still
+    /// connected.
+    ///
+    /// # Arguments
+    ///
+    /// * `sender_id` - The connection that receives `reply`
+    /// * `reply` - The message for the sender
+    /// * `broadcast` - The message for every other connection
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    ///
+    /// # fn example(manager: &ConnectionManager, conn: &Connection) {
+    /// let report = manager.reply_and_broadcast(
+    ///     conn.id(),
+    ///     Message::text("move accepted"),
+    ///     Message::text("player moved"),
+    /// );
+    /// println!("Delivered to {}/{}", report.succeeded, report.attempted);
+    /// # }
+    /// ```
+    pub fn reply_and_broadcast(
+        &self,
+        sender_id: &ConnectionId,
+        reply: Message,
+        broadcast: Message,
+    ) -> BroadcastReport {
+        debug!(
+            "Replying to {} and broadcasting to {} connections",
+            sender_id,
+            self.connections.len().saturating_sub(1)
+        );
+
+        let mut report = BroadcastReport::default();
+        self.for_each_open(|conn| {
+            let message = if conn.id() == sender_id {
+                &reply
+            } else {
+                &broadcast
+            };
+            report.send(conn, message);
+        });
+        report
+    }
+
+    /// Broadcasts a message to specific connections.
+    ///
+    /// Only connections whose IDs are in the provided list will receive the message.
+    /// Non-existent connection IDs are silently ignored and not counted in the
+    /// returned [`BroadcastReport`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    ///
+    /// # fn example(manager: &ConnectionManager) {
+    /// let vip_users = vec![
+    ///     "conn_1".to_string(),
+    ///     "conn_5".to_string(),
+    ///     "conn_10".to_string(),
+    /// ];
+    /// manager.broadcast_to(&vip_users, Message::text("VIP announcement"));
+    /// # }
+    /// ```
+    pub fn broadcast_to(&self, ids: &[ConnectionId], message: Message) -> BroadcastReport {
+        let mut report = BroadcastReport::default();
+        for id in ids {
+            if let Some(conn) = self.get(id) {
+                report.send(&conn, &message);
+            }
+        }
+        report
+    }
+
+    /// Serializes `data` to JSON once and broadcasts it as a text message to
+    /// all active connections.
+    ///
+    /// Shorthand for serializing with `serde_json` and calling
+    /// [`broadcast()`](Self::broadcast), including publishing through the
+    /// broadcast backend if one is set.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Json`] if `data` can't be serialized, in which case
+    /// nothing is sent.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    /// use serde::Serialize;
+    ///
+    /// #[derive(Serialize)]
+    /// struct PlayerJoined {
+    ///     player_id: String,
+    /// }
+    ///
+    /// # fn example(manager: &ConnectionManager) -> Result<()> {
+    /// manager.broadcast_json(&PlayerJoined {
+    ///     player_id: "conn_42".to_string(),
+    /// })?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn broadcast_json<T: Serialize + ?Sized>(&self, data: &T) -> Result<BroadcastReport> {
+        Ok(self.broadcast(Message::text(serde_json::to_string(data)?)))
+    }
+
+    /// Serializes `data` to JSON once and broadcasts it to all connections
+    /// except one.
+    ///
+    /// See [`broadcast_json()`](Self::broadcast_json) and
+    /// [`broadcast_except()`](Self::broadcast_except).
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Json`] if `data` can't be serialized.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    /// use std::sync::Arc;
+    ///
+    /// async fn move_handler(
+    ///     conn: Connection,
+    ///     State(manager): State<Arc<ConnectionManager>>,
+    /// ) -> Result<()> {
+    ///     let update = serde_json::json!({ "type": "position_update", "x": 1, "y": 2 });
+    ///     manager.broadcast_json_except(conn.id(), &update)?;
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn broadcast_json_except<T: Serialize + ?Sized>(
+        &self,
+        except_id: &ConnectionId,
+        data: &T,
+    ) -> Result<BroadcastReport> {
+        Ok(self.broadcast_except(except_id, Message::text(serde_json::to_string(data)?)))
+    }
+
+    /// Serializes `data` to JSON once and sends it to specific connections.
+    ///
+    /// See [`broadcast_json()`](Self::broadcast_json) and
+    /// [`broadcast_to()`](Self::broadcast_to).
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Json`] if `data` can't be serialized.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    ///
+    /// # fn example(manager: &ConnectionManager) -> Result<()> {
+    /// let team = vec!["conn_1".to_string(), "conn_2".to_string()];
+    /// manager.broadcast_json_to(&team, &serde_json::json!({ "type": "round_start" }))?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn broadcast_json_to<T: Serialize + ?Sized>(
+        &self,
+        ids: &[ConnectionId],
+        data: &T,
+    ) -> Result<BroadcastReport> {
+        Ok(self.broadcast_to(ids, Message::text(serde_json::to_string(data)?)))
+    }
+
+    /// Broadcasts a message to every connection matching a predicate.
+    ///
+    /// The predicate is evaluated against each active connection, typically
+    /// inspecting its metadata (see [`Connection::get_meta()`]). The message is
+    /// only cloned for connections that match. Failed sends are logged and not
+    /// counted.
+    ///
+    /// The predicate runs while the connection map is being iterated, so it
+    /// must not add or remove connections on this manager.
+    ///
+    /// Returns the number of connections the message was sent to.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    ///
+    /// # fn example(manager: &ConnectionManager) {
+    /// let sent = manager.broadcast_where(
+    ///     |conn| conn.get_meta::<String>("role").is_some_and(|role| *role == "admin"),
+    ///     Message::text("Admin notice"),
+    /// );
+    /// println!("Notified {} admins", sent);
+    /// # }
+    /// ```
+    pub fn broadcast_where<F>(&self, predicate: F, message: Message) -> usize
+    where
+        F: Fn(&Connection) -> bool,
+    {
+        let mut sent = 0;
+
+        defer_callbacks(|| {
+            for entry in self.connections.iter() {
+                if !predicate(entry.value()) {
+                    continue;
+                }
+
+                match entry.value().send(message.clone()) {
+                    Ok(()) => sent += 1,
+                    Err(e) => error!("Failed to broadcast to {}: {}", entry.key(), e),
+                }
+            }
+        });
+
+        sent
+    }
+
+    /// Adds a connection to a room.
+    ///
+    /// Rooms are created on first join and removed once their last member
+    /// leaves. Joining a room the connection is already in has no effect.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::ConnectionNotFound`] if no active connection has the given ID.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    /// use std::sync::Arc;
+    ///
+    /// async fn join_handler(
+    ///     conn: Connection,
+    ///     State(manager): State<Arc<ConnectionManager>>,
+    /// ) -> Result<()> {
+    ///     manager.join_room(conn.id(), "lobby")?;
+    ///     manager.broadcast_to_room("lobby", Message::text(format!("{} joined", conn.id())));
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn join_room(&self, conn_id: &ConnectionId, room: impl Into<String>) -> Result<()> {
+        let room = room.into();
+        self.join_group(&self.rooms, conn_id, &room)?;
+        debug!("Connection {} joined room {}", conn_id, room);
+        Ok(())
+    }
+
+    /// Removes a connection from a room.
+    ///
+    /// Returns `true` if the connection was a member of the room.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    ///
+    /// # fn example(manager: &ConnectionManager, conn: &Connection) {
+    /// if manager.leave_room(conn.id(), "lobby") {
+    ///     manager.broadcast_to_room("lobby", Message::text("Someone left"));
+    /// }
+    /// # }
+    /// ```
+    pub fn leave_room(&self, conn_id: &ConnectionId, room: &str) -> bool {
+        let removed = self.rooms.leave(conn_id, room);
+        if removed {
+            debug!("Connection {} left room {}", conn_id, room);
+        }
+        removed
+    }
+
+    /// Broadcasts a message to every connection in a room.
+    ///
+    /// Does nothing if the room doesn't exist. Failed sends are logged
+    /// but do not stop the broadcast, and are listed in the returned
+    /// [`BroadcastReport`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    ///
+    /// # fn example(manager: &ConnectionManager) {
+    /// manager.broadcast_to_room("game_42", Message::text("Round starting"));
+    /// # }
+    /// ```
+    pub fn broadcast_to_room(&self, room: &str, message: Message) -> BroadcastReport {
+        let members = self.room_members(room);
+        debug!(
+            "Broadcasting message to {} connections in room {}",
+            members.len(),
+            room
+        );
+        self.broadcast_to(&members, message)
+    }
+
+    /// Returns the names of all rooms a connection has joined.
+    ///
+    /// The order of rooms is not guaranteed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    ///
+    /// # fn example(manager: &ConnectionManager, conn: &Connection) {
+    /// for room in manager.rooms_for(conn.id()) {
+    ///     println!("{} is in {}", conn.id(), room);
+    /// }
+    /// # }
+    /// ```
+    pub fn rooms_for(&self, conn_id: &ConnectionId) -> Vec<String> {
+        self.rooms.groups_for(conn_id)
+    }
+
+    /// Returns the IDs of all connections in a room.
+    ///
+    /// Returns an empty list if the room doesn't exist. The order of IDs
+    /// is not guaranteed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    ///
+    /// # fn example(manager: &ConnectionManager) {
+    /// let players = manager.room_members("game_42");
+    /// println!("{} players in game", players.len());
+    /// # }
+    /// ```
+    pub fn room_members(&self, room: &str) -> Vec<ConnectionId> {
+        self.rooms.members(room)
+    }
+
+    /// Adds a tag to a connection.
+    ///
+    /// Tags are labels such as `region:us` or `plan:pro`. Unlike rooms, they
+    /// are usually combined: a connection carries any number of tags, and
+    /// [`broadcast_to_all_tags()`](Self::broadcast_to_all_tags) targets the
+    /// connections that have every one of a set of tags. Tags are removed
+    /// automatically when the connection is removed. Adding a tag the
+    /// connection already has no effect.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::ConnectionNotFound`] if no active connection has the given ID.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    /// use std::sync::Arc;
+    ///
+    /// async fn login_handler(
+    ///     conn: Connection,
+    ///     State(manager): State<Arc<ConnectionManager>>,
+    /// ) -> Result<()> {
+    ///     manager.tag(conn.id(), "region:us-east")?;
+    ///     manager.tag(conn.id(), "plan:pro")?;
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn tag(&self, conn_id: &ConnectionId, tag: impl Into<String>) -> Result<()> {
+        let tag = tag.into();
+        self.join_group(&self.tags, conn_id, &tag)?;
+        debug!("Connection {} tagged {}", conn_id, tag);
+        Ok(())
+    }
+
+    /// Removes a tag from a connection.
+    ///
+    /// Returns `true` if the connection had the tag.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    ///
+    /// # fn example(manager: &ConnectionManager, conn: &Connection) {
+    /// manager.untag(conn.id(), "plan:trial");
+    /// # }
+    /// ```
+    pub fn untag(&self, conn_id: &ConnectionId, tag: &str) -> bool {
+        let removed = self.tags.leave(conn_id, tag);
+        if removed {
+            debug!("Connection {} untagged {}", conn_id, tag);
+        }
+        removed
+    }
+
+    /// Returns all tags of a connection.
+    ///
+    /// The order of tags is not guaranteed.
+    pub fn tags_for(&self, conn_id: &ConnectionId) -> Vec<String> {
+        self.tags.groups_for(conn_id)
+    }
+
+    /// Returns the IDs of all connections with a tag.
+    ///
+    /// The order of IDs is not guaranteed.
+    pub fn tagged(&self, tag: &str) -> Vec<ConnectionId> {
+        self.tags.members(tag)
+    }
+
+    /// Broadcasts a message to every connection with a tag.
+    ///
+    /// Does nothing if no connection has the tag.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    ///
+    /// # fn example(manager: &ConnectionManager) {
+    /// manager.broadcast_to_tag("plan:pro", Message::text("New pro feature!"));
+    /// # }
+    /// ```
+    pub fn broadcast_to_tag(&self, tag: &str, message: Message) -> BroadcastReport {
+        let members = self.tags.members(tag);
+        debug!(
+            "Broadcasting message to {} connections tagged {}",
+            members.len(),
+            tag
+        );
+        self.broadcast_to(&members, message)
+    }
+
+    /// Broadcasts a message to every connection that has all of `tags`.
+    ///
+    /// Does nothing if `tags` is empty.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    ///
+    /// # fn example(manager: &ConnectionManager) {
+    /// // Only pro users in us-east
+    /// manager.broadcast_to_all_tags(
+    ///     &["plan:pro", "region:us-east"],
+    ///     Message::text("Maintenance in us-east tonight"),
+    /// );
+    /// # }
+    /// ```
+    pub fn broadcast_to_all_tags(&self, tags: &[&str], message: Message) -> BroadcastReport {
+        let members = self.tags.members_of_all(tags);
+        debug!(
+            "Broadcasting message to {} connections tagged {:?}",
+            members.len(),
+            tags
+        );
+        self.broadcast_to(&members, message)
+    }
+
+    /// Lets removed connections be resumed for `window` after they are removed.
+    ///
+    /// Only connections that were issued a
+    /// [`resume_token()`](Self::resume_token) can be resumed. Usually set
+    /// through [`Router::session_resumption()`](crate::router::Router::session_resumption).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    /// use std::time::Duration;
+    ///
+    /// let manager = ConnectionManager::new();
+    /// manager.set_resume_window(Duration::from_secs(30));
+    /// ```
+    pub fn set_resume_window(&self, window: Duration) {
+        *self.resume_window.lock().unwrap() = Some(window);
+    }
+
+    /// Returns the token with which a client can resume this connection's
+    /// session after reconnecting.
+    ///
+    /// The token is created on first call and stays the same for the life
+    /// of the connection. Send it to the client; when the client reconnects
+    /// within the [resume window](Self::set_resume_window), pass it to
+    /// [`resume()`](Self::resume), or let the router do so by connecting to
+    /// a URL with a `resume=<token>` query parameter.
+    ///
+    /// Tokens are 128 random bits, but whoever presents one takes over the
+    /// session's metadata, so send them only over TLS.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::ConnectionNotFound`] if no active connection has the given ID.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    /// use std::sync::Arc;
+    ///
+    /// async fn login_handler(
+    ///     conn: Connection,
+    ///     State(manager): State<Arc<ConnectionManager>>,
+    /// ) -> Result<String> {
+    ///     conn.set_meta("user", "alice".to_string());
+    ///     manager.join_room(conn.id(), "lobby")?;
+    ///     manager.resume_token(conn.id())
+    /// }
     /// ```
-    pub fn get(&self, id: &ConnectionId) -> Option<Connection> {
-        self.connections.get(id).map(|entry| entry.value().clone())
+    pub fn resume_token(&self, conn_id: &ConnectionId) -> Result<String> {
+        if !self.connections.contains_key(conn_id) {
+            return Err(Error::ConnectionNotFound(conn_id.clone()));
+        }
+        Ok(self
+            .resume_tokens
+            .entry(conn_id.clone())
+            .or_insert_with(new_resume_token)
+            .clone())
     }
 
-    /// Broadcasts a message to all active connections.
+    /// Moves a removed connection's session onto a new connection.
     ///
-    /// This method iterates through all connections and sends the message
-    /// to each one. Failed sends are logged but do not stop the broadcast.
+    /// If `token` belongs to a connection that was removed within the
+    /// [resume window](Self::set_resume_window), its metadata is copied to
+    /// `new_conn`, which also joins its rooms and gets its tags. The token
+    /// then belongs to `new_conn`, so the session can be resumed again after
+    /// the next reconnect.
     ///
-    /// # Performance
+    /// Returns `Ok(false)` if the token is unknown or its window has ended.
     ///
-    /// Broadcasts are performed synchronously but send operations are async,
-    /// so messages are queued immediately and sent in the background.
+    /// # Errors
+    ///
+    /// Returns [`Error::ConnectionNotFound`] if no active connection has the
+    /// ID `new_conn`.
     ///
     /// # Examples
     ///
     /// ```
     /// use wsforge::prelude::*;
+    /// use std::sync::Arc;
     ///
-    /// # fn example(manager: &ConnectionManager) {
-    /// manager.broadcast(Message::text("Server announcement!"));
-    /// # }
+    /// // The client sends its token as its first message after reconnecting
+    /// async fn resume_handler(
+    ///     msg: Message,
+    ///     conn: Connection,
+    ///     State(manager): State<Arc<ConnectionManager>>,
+    /// ) -> Result<String> {
+    ///     let token = msg.as_text().unwrap_or_default();
+    ///     if manager.resume(token, conn.id())? {
+    ///         Ok("resumed".to_string())
+    ///     } else {
+    ///         Ok("new session".to_string())
+    ///     }
+    /// }
     /// ```
-    pub fn broadcast(&self, message: Message) {
-        let count = self.connections.len();
-        debug!("Broadcasting message to {} connections", count);
+    pub fn resume(&self, token: &str, new_conn: &ConnectionId) -> Result<bool> {
+        let conn = self
+            .get(new_conn)
+            .ok_or_else(|| Error::ConnectionNotFound(new_conn.clone()))?;
 
-        let mut success = 0;
-        let mut failed = 0;
+        let Some((_, session)) = self
+            .parked
+            .remove_if(token, |_, session| session.expires_at > Instant::now())
+        else {
+            self.parked
+                .remove_if(token, |_, session| session.expires_at <= Instant::now());
+            return Ok(false);
+        };
 
-        for entry in self.connections.iter() {
-            match entry.value().send(message.clone()) {
-                Ok(_) => {
-                    success += 1;
-                    debug!("✅ Broadcast sent to {}", entry.key());
-                }
-                Err(e) => {
-                    failed += 1;
-                    error!("❌ Failed to broadcast to {}: {}", entry.key(), e);
+        conn.meta.extend_from(&session.meta);
+        for room in &session.rooms {
+            self.join_group(&self.rooms, new_conn, room)?;
+        }
+        for tag in &session.tags {
+            self.join_group(&self.tags, new_conn, tag)?;
+        }
+        self.resume_tokens
+            .insert(new_conn.clone(), token.to_string());
+
+        info!("Connection {} resumed a previous session", new_conn);
+        Ok(true)
+    }
+
+    /// Returns `true` if a resume window is set.
+    pub(crate) fn resumes_sessions(&self) -> bool {
+        self.resume_window.lock().unwrap().is_some()
+    }
+
+    /// Calls `f` with every connection whose write task is still running.
+    ///
+    /// Connections whose write task has ended are removed afterwards, unless
+    /// a new connection took their ID in the meantime.
+    fn for_each_open(&self, mut f: impl FnMut(&Connection)) {
+        let mut closed = Vec::new();
+        defer_callbacks(|| {
+            for entry in self.connections.iter() {
+                if entry.value().sender.is_closed() {
+                    closed.push(entry.key().clone());
+                } else {
+                    f(entry.value());
                 }
             }
+        });
+
+        // Removing while iterating would deadlock on the map's shard locks
+        for id in closed {
+            let pruned = self
+                .connections
+                .remove_if(&id, |_, conn| conn.sender.is_closed())
+                .map(|(_, conn)| conn);
+            if pruned.is_some() {
+                debug!("🧹 Pruned closed connection {}", id);
+                self.forget(&id, pruned);
+            }
         }
+    }
 
-        info!(
-            "Broadcast complete: {} success, {} failed out of {} total",
-            success, failed, count
+    /// Keeps a removed connection's session for [`resume()`](Self::resume).
+    fn park(&self, token: String, conn: &Connection) {
+        let Some(window) = *self.resume_window.lock().unwrap() else {
+            return;
+        };
+
+        let now = Instant::now();
+        self.parked.retain(|_, session| session.expires_at > now);
+        self.parked.insert(
+            token,
+            ParkedSession {
+                meta: conn.meta.clone(),
+                rooms: self.rooms.groups_for(conn.id()),
+                tags: self.tags.groups_for(conn.id()),
+                expires_at: now + window,
+            },
         );
+        debug!("Session of {} kept for {:?}", conn.id(), window);
     }
 
-    /// Broadcasts a message to all connections except one.
+    /// Adds an active connection to a group of `groups`.
+    fn join_group(&self, groups: &Groups, conn_id: &ConnectionId, group: &str) -> Result<()> {
+        if !self.connections.contains_key(conn_id) {
+            return Err(Error::ConnectionNotFound(conn_id.clone()));
+        }
+
+        groups.join(conn_id, group);
+
+        // The connection may have been removed while we were joining
+        if !self.connections.contains_key(conn_id) {
+            groups.leave_all(conn_id);
+            return Err(Error::ConnectionNotFound(conn_id.clone()));
+        }
+        Ok(())
+    }
+
+    /// Forwards broadcasts to other instances through `backend`.
     ///
-    /// This is useful for notifying all users about an action taken by one user,
-    /// without sending the notification back to the actor.
+    /// From now on, [`broadcast()`](Self::broadcast) also publishes through
+    /// the backend, and broadcasts published by other instances are
+    /// delivered to this manager's connections. Replaces any previous
+    /// backend for publishing. See the [`broadcast`](crate::broadcast)
+    /// module.
+    ///
+    /// Usually set through
+    /// [`Router::broadcast_backend()`](crate::router::Router::broadcast_backend).
+    ///
+    /// # Panics
+    ///
+    /// Must be called within a Tokio runtime, as it spawns the task that
+    /// receives broadcasts from the backend.
     ///
     /// # Examples
     ///
     /// ```
     /// use wsforge::prelude::*;
+    /// use wsforge::broadcast::InProcessBackend;
+    /// use std::sync::Arc;
     ///
-    /// # fn example(manager: &ConnectionManager) {
-    /// let sender_id = "conn_42".to_string();
-    /// manager.broadcast_except(&sender_id,
-    ///     Message::text("User 42 sent a message"));
+    /// # async fn example() {
+    /// let backend = Arc::new(InProcessBackend::new());
+    /// let manager = Arc::new(ConnectionManager::new());
+    /// manager.set_broadcast_backend(backend);
     /// # }
     /// ```
-    pub fn broadcast_except(&self, except_id: &ConnectionId, message: Message) {
-        debug!(
-            "Broadcasting message to {} connections (except {})",
-            self.connections.len() - 1,
-            except_id
-        );
-        for entry in self.connections.iter() {
-            if entry.key() != except_id {
-                if let Err(e) = entry.value().send(message.clone()) {
-                    error!("Failed to broadcast to {}: {}", entry.key(), e);
+    pub fn set_broadcast_backend(self: &Arc<Self>, backend: Arc<dyn BroadcastBackend>) {
+        let mut incoming = backend.subscribe();
+        let manager = Arc::downgrade(self);
+        tokio::spawn(async move {
+            while let Some(envelope) = incoming.next().await {
+                let Some(manager) = manager.upgrade() else {
+                    break;
+                };
+                // Our own broadcasts were already delivered locally
+                if envelope.origin != manager.node_id {
+                    manager.broadcast_local(envelope.message);
                 }
             }
-        }
+            debug!("Broadcast backend subscription ended");
+        });
+
+        *self.backend.lock().unwrap() = Some(backend);
     }
 
-    /// Broadcasts a message to specific connections.
+    /// Returns the ID this manager's broadcasts carry through the broadcast
+    /// backend.
     ///
-    /// Only connections whose IDs are in the provided list will receive the message.
-    /// Non-existent connection IDs are silently ignored.
+    /// Random and unique per manager.
+    pub fn node_id(&self) -> &str {
+        &self.node_id
+    }
+
+    /// Returns the number of active connections.
     ///
     /// # Examples
     ///
@@ -574,25 +2221,18 @@ impl ConnectionManager {
     /// use wsforge::prelude::*;
     ///
     /// # fn example(manager: &ConnectionManager) {
-    /// let vip_users = vec![
-    ///     "conn_1".to_string(),
-    ///     "conn_5".to_string(),
-    ///     "conn_10".to_string(),
-    /// ];
-    /// manager.broadcast_to(&vip_users, Message::text("VIP announcement"));
+    /// let count = manager.count();
+    /// println!("Active connections: {}", count);
     /// # }
     /// ```
-    pub fn broadcast_to(&self, ids: &[ConnectionId], message: Message) {
-        for id in ids {
-            if let Some(conn) = self.get(id) {
-                if let Err(e) = conn.send(message.clone()) {
-                    error!("Failed to send to {}: {}", id, e);
-                }
-            }
-        }
+    pub fn count(&self) -> usize {
+        self.connections.len()
     }
 
-    /// Returns the number of active connections.
+    /// Returns the message and byte counts summed over all active
+    /// connections.
+    ///
+    /// Connections that have closed no longer count towards the total.
     ///
     /// # Examples
     ///
@@ -600,12 +2240,16 @@ impl ConnectionManager {
     /// use wsforge::prelude::*;
     ///
     /// # fn example(manager: &ConnectionManager) {
-    /// let count = manager.count();
-    /// println!("Active connections: {}", count);
+    /// let stats = manager.stats();
+    /// println!("{} connections sent {} bytes", manager.count(), stats.bytes_sent);
     /// # }
     /// ```
-    pub fn count(&self) -> usize {
-        self.connections.len()
+    pub fn stats(&self) -> ConnStats {
+        let mut total = ConnStats::default();
+        for entry in self.connections.iter() {
+            total += entry.value().stats();
+        }
+        total
     }
 
     /// Returns a list of all connection IDs.
@@ -651,6 +2295,442 @@ impl ConnectionManager {
     pub fn all_connections(&self) -> Vec<Connection> {
         self.connections.iter().map(|e| e.value().clone()).collect()
     }
+
+    /// Calls a closure for every active connection, without collecting them.
+    ///
+    /// The closure runs while the connection map is being iterated, so it
+    /// must not add or remove connections on this manager. The order is not
+    /// guaranteed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    ///
+    /// # fn example(manager: &ConnectionManager) {
+    /// manager.for_each(|conn| {
+    ///     println!("{} connected at {}", conn.id(), conn.info().connected_at);
+    /// });
+    /// # }
+    /// ```
+    pub fn for_each<F>(&self, mut f: F)
+    where
+        F: FnMut(&Connection),
+    {
+        defer_callbacks(|| {
+            for entry in self.connections.iter() {
+                f(entry.value());
+            }
+        });
+    }
+
+    /// Counts the active connections matching a predicate.
+    ///
+    /// Like [`for_each()`](Self::for_each), the predicate must not add or
+    /// remove connections on this manager.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    ///
+    /// # fn example(manager: &ConnectionManager) {
+    /// let admins = manager.count_where(|conn| {
+    ///     conn.get_meta::<String>("role").is_some_and(|role| *role == "admin")
+    /// });
+    /// # }
+    /// ```
+    pub fn count_where<F>(&self, predicate: F) -> usize
+    where
+        F: Fn(&Connection) -> bool,
+    {
+        self.connections
+            .iter()
+            .filter(|entry| predicate(entry.value()))
+            .count()
+    }
+
+    /// Returns a clone of the first connection matching a predicate.
+    ///
+    /// Stops at the first match. When several connections match, which one
+    /// is returned is not guaranteed. Like [`for_each()`](Self::for_each),
+    /// the predicate must not add or remove connections on this manager.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    ///
+    /// # fn example(manager: &ConnectionManager) {
+    /// let alice = manager.find(|conn| {
+    ///     conn.get_meta::<String>("username").is_some_and(|name| *name == "alice")
+    /// });
+    /// # }
+    /// ```
+    pub fn find<F>(&self, predicate: F) -> Option<Connection>
+    where
+        F: Fn(&Connection) -> bool,
+    {
+        self.connections
+            .iter()
+            .find(|entry| predicate(entry.value()))
+            .map(|entry| entry.value().clone())
+    }
+
+    /// Records that a frame was just received from a connection.
+    pub(crate) fn touch(&self, id: &ConnectionId) {
+        let now = unix_timestamp();
+
+        // Only take the write lock when the timestamp actually changes
+        if self
+            .connections
+            .get(id)
+            .is_some_and(|c| c.info.last_seen != now)
+            && let Some(mut conn) = self.connections.get_mut(id)
+        {
+            conn.info.last_seen = now;
+        }
+    }
+}
+
+/// How long a server-side disconnect waits for the close frame to be sent.
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Callback told why a connection ended, run by [`serve_websocket`].
+pub(crate) type DisconnectHook = Arc<dyn Fn(ConnectionId, &DisconnectReason) + Send + Sync>;
+
+/// Builds the first message sent to a new connection, run by
+/// [`serve_websocket`].
+pub(crate) type HelloHook = Arc<dyn Fn(&Connection) -> Option<Message> + Send + Sync>;
+
+/// Async lifecycle callback awaited by [`serve_websocket`].
+pub(crate) type ConnectionHook =
+    Arc<dyn Fn(Connection) -> BoxFuture<'static, Result<()>> + Send + Sync>;
+
+/// Per-connection settings used by [`serve_websocket`].
+#[derive(Default)]
+pub(crate) struct ConnectionOptions {
+    /// Heartbeat settings, if keepalive is enabled
+    pub(crate) keepalive: Option<KeepAlive>,
+    /// Values attached by the handshake callback
+    pub(crate) extensions: Extensions,
+    /// Metadata set by the handshake callback
+    pub(crate) meta: Extensions,
+    /// Subprotocol selected during the handshake
+    pub(crate) protocol: Option<String>,
+    /// Path of the handshake URL
+    pub(crate) path: Option<String>,
+    /// Client address reported by a trusted proxy
+    pub(crate) real_ip: Option<IpAddr>,
+    /// Builds a message sent before `on_connect` runs
+    pub(crate) hello: Option<HelloHook>,
+    /// Awaited after `on_connect`, before any message is read
+    pub(crate) on_connect_async: Option<ConnectionHook>,
+    /// Called after `on_disconnect` with the reason the connection ended
+    pub(crate) on_disconnect_reason: Option<DisconnectHook>,
+    /// Awaited after `on_disconnect`
+    pub(crate) on_disconnect_async: Option<ConnectionHook>,
+    /// Applied to every outgoing data message in the write task
+    pub(crate) outbound: Vec<Arc<dyn OutboundMiddleware>>,
+    /// Notified of the connection's lifetime and traffic
+    pub(crate) metrics: Option<Arc<dyn Metrics>>,
+    /// Called when the connection's send queue grows too deep
+    pub(crate) slow_client: Option<SlowClient>,
+    /// Caps the connection's send queue, if set
+    pub(crate) send_limit: Option<SendLimit>,
+    /// Token from the handshake URL of a session to resume
+    pub(crate) resume_token: Option<String>,
+    /// Close with 1007 on invalid UTF-8 instead of decoding lossily
+    pub(crate) strict_utf8: bool,
+    /// Batches outgoing text messages, if enabled
+    pub(crate) coalesce: Option<Coalesce>,
+}
+
+/// Outgoing message batching applied to each connection.
+///
+/// See [`Router::coalesce()`](crate::router::Router::coalesce).
+#[derive(Debug, Clone)]
+pub(crate) struct Coalesce {
+    /// How long to collect text messages after the first one
+    pub(crate) window: Duration,
+    /// How collected messages are joined into one frame
+    pub(crate) strategy: CoalesceStrategy,
+}
+
+/// How [`Router::coalesce()`](crate::router::Router::coalesce) joins the text
+/// messages collected within one window into a single frame.
+///
+/// # Examples
+///
+/// ```
+/// use wsforge::prelude::*;
+/// use wsforge::CoalesceStrategy;
+/// use std::time::Duration;
+///
+/// # fn example() {
+/// // Position updates sent within 16ms reach the client as one JSON array
+/// let router = Router::new()
+///     .coalesce(Duration::from_millis(16), CoalesceStrategy::JsonArray);
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CoalesceStrategy {
+    /// Sends the messages as a JSON array, e.g. `[{"x":1},{"x":2}]`.
+    ///
+    /// Only text messages that are valid JSON are collected, and each is
+    /// inserted as-is. Any other text message ends the window and is sent on
+    /// its own, unwrapped. A window with a single message is still sent as a
+    /// one-element array.
+    JsonArray,
+    /// Sends the messages joined by a delimiter, e.g. `"\n"`.
+    ///
+    /// A window with a single message is sent unchanged.
+    Delimited(String),
+}
+
+impl CoalesceStrategy {
+    /// Whether `message` can be collected into a batch.
+    fn accepts(&self, message: &Message) -> bool {
+        match self {
+            CoalesceStrategy::JsonArray => {
+                message.is_text()
+                    && serde_json::from_slice::<serde::de::IgnoredAny>(&message.data).is_ok()
+            }
+            CoalesceStrategy::Delimited(_) => message.is_text(),
+        }
+    }
+
+    /// Joins a batch of text messages into one text message.
+    fn join(&self, batch: &[Message]) -> Message {
+        let texts = batch
+            .iter()
+            .map(|message| message.as_text().unwrap_or_default());
+        match self {
+            CoalesceStrategy::JsonArray => {
+                Message::text(format!("[{}]", texts.collect::<Vec<_>>().join(",")))
+            }
+            CoalesceStrategy::Delimited(delimiter) => {
+                Message::text(texts.collect::<Vec<_>>().join(delimiter))
+            }
+        }
+    }
+}
+
+/// Heartbeat settings applied to each connection.
+///
+/// See [`Router::keepalive()`](crate::router::Router::keepalive).
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct KeepAlive {
+    /// How often a ping is sent to the client
+    pub(crate) interval: Duration,
+    /// How long a connection may go without sending any frame
+    pub(crate) timeout: Duration,
+}
+
+/// Slow client detection applied to each connection.
+///
+/// See [`Router::on_slow_client()`](crate::router::Router::on_slow_client).
+#[derive(Clone)]
+pub(crate) struct SlowClient {
+    /// Queue depth above which a connection counts as slow
+    pub(crate) threshold: usize,
+    /// Called once each time a connection becomes slow
+    pub(crate) callback: Arc<dyn Fn(Connection) + Send + Sync>,
+}
+
+/// Send queue cap applied to each connection.
+///
+/// See [`Router::max_pending_per_connection()`](crate::router::Router::max_pending_per_connection).
+#[derive(Clone)]
+pub(crate) struct SendLimit {
+    /// Queue depth at which further messages are dropped
+    pub(crate) max_pending: usize,
+    /// Called with each dropped message
+    pub(crate) on_drop: Option<Arc<dyn Fn(ConnectionId, Message) + Send + Sync>>,
+}
+
+/// Applies outbound middleware and the strict UTF-8 check to a message about
+/// to be written.
+///
+/// Returns `None` if the message is dropped. Dropped messages still count
+/// towards [`Connection::send_flush()`].
+async fn prepare_outgoing(
+    message: Message,
+    outbound: &[Arc<dyn OutboundMiddleware>],
+    conn: &Connection,
+    strict_utf8: bool,
+    delivery: &Delivery,
+) -> Option<Message> {
+    let message = if outbound.is_empty() {
+        message
+    } else {
+        match run_outbound(outbound, message, conn).await {
+            Some(message) => message,
+            None => {
+                delivery.written();
+                return None;
+            }
+        }
+    };
+
+    if strict_utf8 && message.is_text() && message.as_text().is_none() {
+        error!("Dropping text message with invalid UTF-8 for {}", conn.id());
+        delivery.written();
+        return None;
+    }
+    Some(message)
+}
+
+/// Callbacks waiting for an iteration over the connection map to end.
+type Deferred = Vec<Box<dyn FnOnce()>>;
+
+thread_local! {
+    /// Callbacks postponed until this thread has finished iterating the
+    /// connection map, or `None` outside such an iteration
+    static DEFERRED: RefCell<Option<Deferred>> = const { RefCell::new(None) };
+}
+
+/// Runs a user callback triggered by sending a message.
+///
+/// Sends made while iterating the connection map, as broadcasts do, hold the
+/// map's shard locks. A callback removing a connection from there would
+/// deadlock, so it is postponed until the iteration ends.
+fn run_callback(callback: Box<dyn FnOnce()>) {
+    let callback = DEFERRED.with(|deferred| match deferred.borrow_mut().as_mut() {
+        Some(queue) => {
+            queue.push(callback);
+            None
+        }
+        None => Some(callback),
+    });
+    if let Some(callback) = callback {
+        callback();
+    }
+}
+
+/// Runs `f`, postponing the callbacks of sends made inside it until it
+/// returns. See [`run_callback()`].
+fn defer_callbacks<R>(f: impl FnOnce() -> R) -> R {
+    /// Restores the enclosing queue, even if `f` panics
+    struct Scope(Option<Deferred>);
+
+    impl Drop for Scope {
+        fn drop(&mut self) {
+            let outer = self.0.take();
+            DEFERRED.with(|deferred| *deferred.borrow_mut() = outer);
+        }
+    }
+
+    let scope = Scope(DEFERRED.with(|deferred| deferred.borrow_mut().replace(Vec::new())));
+    let result = f();
+    let callbacks = DEFERRED
+        .with(|deferred| deferred.borrow_mut().take())
+        .unwrap_or_default();
+    drop(scope);
+
+    // Inside an enclosing iteration, these are postponed again
+    for callback in callbacks {
+        run_callback(callback);
+    }
+    result
+}
+
+/// Tracks how many of a connection's messages have been written, so
+/// [`Connection::send_flush()`] can wait for its own.
+#[derive(Default)]
+struct Delivery {
+    /// Messages queued through [`Connection::send()`] and friends
+    queued: AtomicU64,
+    /// Progress of the write task
+    progress: Mutex<Progress>,
+    /// Slow client detection, if enabled
+    slow_client: Option<SlowClient>,
+    /// Set while the connection is reported as slow
+    slow: AtomicBool,
+    /// Send queue cap, if enabled
+    send_limit: Option<SendLimit>,
+}
+
+#[derive(Default)]
+struct Progress {
+    /// Messages written to the socket so far
+    written: u64,
+    /// Set once the write task is gone
+    closed: bool,
+    /// Waiting `send_flush` calls, by the number of writes they need
+    pending: Vec<(u64, oneshot::Sender<()>)>,
+}
+
+impl Delivery {
+    /// Returns the number of queued messages that haven't been written yet.
+    fn pending(&self) -> u64 {
+        let written = self.progress.lock().unwrap().written;
+        // `send_flush` counts its message after queueing it, so the write
+        // task can briefly be ahead
+        self.queued.load(Ordering::SeqCst).saturating_sub(written)
+    }
+
+    /// Reports `conn` to the slow client callback if its queue just grew
+    /// past the threshold.
+    fn check_slow(&self, conn: &Connection) {
+        let Some(slow_client) = &self.slow_client else {
+            return;
+        };
+
+        if self.pending() > slow_client.threshold as u64 && !self.slow.swap(true, Ordering::SeqCst)
+        {
+            warn!(
+                "🐢 [{}] Slow client: more than {} messages queued",
+                conn.id(),
+                slow_client.threshold
+            );
+            let callback = slow_client.callback.clone();
+            let conn = conn.clone();
+            run_callback(Box::new(move || callback(conn)));
+        }
+    }
+
+    /// Records a written message and wakes the `send_flush` calls it completes.
+    fn written(&self) {
+        let mut progress = self.progress.lock().unwrap();
+        progress.written += 1;
+
+        let written = progress.written;
+
+        // Report the connection again only after it has mostly caught up, so
+        // a client hovering around the threshold isn't reported every message
+        if let Some(slow_client) = &self.slow_client
+            && self.queued.load(Ordering::SeqCst).saturating_sub(written)
+                <= slow_client.threshold as u64 / 2
+        {
+            self.slow.store(false, Ordering::SeqCst);
+        }
+
+        let mut i = 0;
+        while i < progress.pending.len() {
+            if progress.pending[i].0 <= written {
+                let (_, ack) = progress.pending.swap_remove(i);
+                let _ = ack.send(());
+            } else {
+                i += 1;
+            }
+        }
+    }
+
+    /// Marks the write task as gone, failing every waiting `send_flush` call.
+    fn close(&self) {
+        let mut progress = self.progress.lock().unwrap();
+        progress.closed = true;
+        progress.pending.clear();
+    }
+}
+
+/// Returns the current time as a Unix timestamp in seconds.
+fn unix_timestamp() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
 }
 
 impl Clone for Connection {
@@ -659,6 +2739,14 @@ impl Clone for Connection {
             id: self.id.clone(),
             info: self.info.clone(),
             sender: self.sender.clone(),
+            extensions: self.extensions.clone(),
+            meta: self.meta.clone(),
+            shutdown: self.shutdown.clone(),
+            closing: self.closing.clone(),
+            path: self.path.clone(),
+            delivery: self.delivery.clone(),
+            connected: self.connected,
+            traffic: self.traffic.clone(),
         }
     }
 }
@@ -672,10 +2760,13 @@ impl Default for ConnectionManager {
 /// Handles the lifecycle of a WebSocket connection.
 ///
 /// This function manages the entire lifecycle of a WebSocket connection from
-/// establishment to termination. It spawns two concurrent tasks:
+/// establishment to termination. It drives two concurrent tasks:
 /// - A read task that receives messages from the client
 /// - A write task that sends messages to the client
 ///
+/// Both tasks run inside the returned future, so dropping or aborting it
+/// tears down the whole connection.
+///
 /// # Architecture
 ///
 /// The function uses a split WebSocket stream and an unbounded channel to
@@ -687,13 +2778,20 @@ impl Default for ConnectionManager {
 /// 1. Connection is added to the manager
 /// 2. `on_connect` callback is invoked
 /// 3. Read and write tasks run concurrently
-/// 4. When either task completes, both are terminated
+/// 4. When either task completes (or a close frame is sent), both are terminated
 /// 5. Connection is removed from the manager
-/// 6. `on_disconnect` callback is invoked
+/// 6. `on_disconnect` callback is invoked, then the router's
+///    [`on_disconnect_with_reason()`](crate::router::Router::on_disconnect_with_reason)
+///    callback with the [`DisconnectReason`]
+///
+/// The router's async callbacks
+/// ([`Router::on_connect_async()`](crate::router::Router::on_connect_async) and
+/// [`Router::on_disconnect_async()`](crate::router::Router::on_disconnect_async))
+/// are awaited right after steps 2 and 6.
 ///
 /// # Arguments
 ///
-/// * `stream` - The WebSocket stream
+/// * `stream` - The WebSocket stream, over plain TCP or any other transport such as TLS
 /// * `conn_id` - Unique identifier for this connection
 /// * `peer_addr` - Socket address of the connected client
 /// * `manager` - Shared connection manager
@@ -740,15 +2838,49 @@ impl Default for ConnectionManager {
 /// # Ok(())
 /// # }
 /// ```
-pub async fn handle_websocket(
-    stream: WebSocketStream<TcpStream>,
+pub async fn handle_websocket<S>(
+    stream: WebSocketStream<S>,
+    conn_id: ConnectionId,
+    peer_addr: SocketAddr,
+    manager: Arc<ConnectionManager>,
+    on_message: Arc<dyn Fn(ConnectionId, Message) + Send + Sync>,
+    on_connect: Arc<dyn Fn(ConnectionId) + Send + Sync>,
+    on_disconnect: Arc<dyn Fn(ConnectionId) + Send + Sync>,
+) where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    serve_websocket(
+        stream,
+        conn_id,
+        peer_addr,
+        manager,
+        on_message,
+        on_connect,
+        on_disconnect,
+        ConnectionOptions::default(),
+    )
+    .await
+}
+
+/// Drives a WebSocket connection like [`handle_websocket`], optionally
+/// pinging the client and dropping it when it stops responding.
+///
+/// Runs inside a `connection` span carrying the connection ID, so every log
+/// line of the connection, including those of its handlers, is tagged.
+#[allow(clippy::too_many_arguments)]
+#[tracing::instrument(name = "connection", skip_all, fields(conn_id = %conn_id))]
+pub(crate) async fn serve_websocket<S>(
+    stream: WebSocketStream<S>,
     conn_id: ConnectionId,
     peer_addr: SocketAddr,
     manager: Arc<ConnectionManager>,
     on_message: Arc<dyn Fn(ConnectionId, Message) + Send + Sync>,
     on_connect: Arc<dyn Fn(ConnectionId) + Send + Sync>,
     on_disconnect: Arc<dyn Fn(ConnectionId) + Send + Sync>,
-) {
+    options: ConnectionOptions,
+) where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
     info!(
         "WebSocket connection established: {} from {}",
         conn_id, peer_addr
@@ -758,7 +2890,30 @@ pub async fn handle_websocket(
     let (tx, mut rx) = mpsc::unbounded_channel::<Message>();
 
     // Create connection with actual peer address
-    let conn = Connection::new(conn_id.clone(), peer_addr, tx);
+    let delivery = Arc::new(Delivery {
+        slow_client: options.slow_client,
+        send_limit: options.send_limit,
+        ..Default::default()
+    });
+    let mut conn = Connection::new(conn_id.clone(), peer_addr, tx);
+    conn.delivery = Some(delivery.clone());
+    let shutdown = conn.shutdown.clone();
+    conn.info.protocol = options.protocol;
+    conn.info.real_ip = options.real_ip;
+    conn.extensions = options.extensions;
+    conn.meta = options.meta;
+    conn.path = options.path;
+    let keepalive = options.keepalive;
+    // Pings go through the connection so they are counted like any message
+    let ping_conn = conn.clone();
+    let outbound_conn = conn.clone();
+    let outbound = options.outbound;
+    let hook_conn = conn.clone();
+    let metrics = options.metrics;
+    let strict_utf8 = options.strict_utf8;
+    let coalesce = options.coalesce;
+    let traffic = conn.traffic.clone();
+    let opened_at = Instant::now();
 
     // Add connection to manager and get the count
     let _count = manager.add(conn);
@@ -770,65 +2925,1191 @@ pub async fn handle_websocket(
         conn_id, verify_count
     );
 
+    if let Some(metrics) = &metrics {
+        metrics.connection_opened();
+    }
+
+    // Restore the previous session before anyone sees the new connection
+    if let Some(token) = &options.resume_token {
+        match manager.resume(token, &conn_id) {
+            Ok(true) => {}
+            Ok(false) => debug!("No session to resume for {}", conn_id),
+            Err(e) => warn!("Failed to resume session for {}: {}", conn_id, e),
+        }
+    }
+
+    // Queued before on_connect and the read task run, so it's the first
+    // message the client receives
+    if let Some(hello) = &options.hello
+        && let Some(message) = hello(&hook_conn)
+        && let Err(e) = hook_conn.send(message)
+    {
+        warn!("Failed to send hello message to {}: {}", conn_id, e);
+    }
+
     // NOW call on_connect AFTER we've verified the connection is added
     on_connect(conn_id.clone());
 
+    // The async hook finishes before the read task starts, so no message is
+    // dispatched until it's done. If it fails, the client is closed with
+    // 1011 Internal Error.
+    if let Some(hook) = &options.on_connect_async
+        && let Err(e) = hook(hook_conn.clone()).await
+    {
+        error!("Connect callback failed for {}: {}", conn_id, e);
+        let _ = manager.disconnect(&conn_id, Some((1011, "Internal Error".to_string())));
+    }
+
     // Write task - sends messages to WebSocket
     let conn_id_write = conn_id.clone();
-    let write_task = tokio::spawn(async move {
+    let delivery_write = delivery.clone();
+    let metrics_write = metrics.clone();
+    let traffic_write = traffic.clone();
+    let write_task = async move {
         debug!("Write task started for {}", conn_id_write);
 
-        while let Some(message) = rx.recv().await {
+        // A message received while collecting a batch that can't join it
+        let mut held: Option<Message> = None;
+
+        let reason = loop {
+            let message = match held.take() {
+                Some(message) => message,
+                None => {
+                    let Some(message) = rx.recv().await else {
+                        break DisconnectReason::closed(DisconnectSource::ServerClose, None);
+                    };
+                    match prepare_outgoing(
+                        message,
+                        &outbound,
+                        &outbound_conn,
+                        strict_utf8,
+                        &delivery_write,
+                    )
+                    .await
+                    {
+                        Some(message) => message,
+                        None => continue,
+                    }
+                }
+            };
             debug!("📤 Sending message to {}", conn_id_write);
 
+            // Collect further text messages for one window and join them
+            let (message, count) = match &coalesce {
+                Some(coalesce) if coalesce.strategy.accepts(&message) => {
+                    let mut batch = vec![message];
+                    let deadline = tokio::time::Instant::now() + coalesce.window;
+                    while let Ok(Some(next)) = tokio::time::timeout_at(deadline, rx.recv()).await {
+                        let Some(next) = prepare_outgoing(
+                            next,
+                            &outbound,
+                            &outbound_conn,
+                            strict_utf8,
+                            &delivery_write,
+                        )
+                        .await
+                        else {
+                            continue;
+                        };
+                        if !coalesce.strategy.accepts(&next) {
+                            held = Some(next);
+                            break;
+                        }
+                        batch.push(next);
+                    }
+
+                    let count = batch.len();
+                    if count == 1 && matches!(coalesce.strategy, CoalesceStrategy::Delimited(_)) {
+                        (batch.pop().unwrap(), 1)
+                    } else {
+                        (coalesce.strategy.join(&batch), count)
+                    }
+                }
+                _ => (message, 1),
+            };
+
+            let is_close = message.is_close();
+            let close_frame = message.close_frame();
+            let is_data = message.is_text() || message.is_binary();
+            let len = message.data.len();
             let msg = message.into_tungstenite();
             if let Err(e) = ws_sender.send(msg).await {
                 error!("Failed to send message to {}: {}", conn_id_write, e);
-                break;
+                break DisconnectReason::failed(DisconnectSource::WriteError, e);
             }
 
             debug!("✅ Message sent to {}", conn_id_write);
-        }
+            for _ in 0..count {
+                delivery_write.written();
+            }
+            if is_data {
+                traffic_write.sent(count as u64, len);
+            }
+            if is_data && let Some(metrics) = &metrics_write {
+                metrics.message_sent();
+            }
+
+            // Nothing may be sent after a close frame
+            if is_close {
+                break DisconnectReason::closed(DisconnectSource::ServerClose, close_frame);
+            }
+        };
 
         info!("Write task ended for {}", conn_id_write);
-    });
+        reason
+    };
+
+    // Time of the last frame received, shared by the read and keepalive tasks
+    let last_frame = Mutex::new(Instant::now());
 
     // Read task - receives messages from WebSocket
     let conn_id_read = conn_id.clone();
-    let read_task = tokio::spawn(async move {
+    let manager_read = manager.clone();
+    let last_frame_read = &last_frame;
+    let metrics_read = metrics.clone();
+    let read_task = async move {
         debug!("Read task started for {}", conn_id_read);
 
+        let mut reason = DisconnectReason::closed(DisconnectSource::ReadError, None);
         while let Some(result) = ws_receiver.next().await {
             match result {
                 Ok(msg) => {
-                    if msg.is_close() {
-                        info!("Close message received from {}", conn_id_read);
+                    *last_frame_read.lock().unwrap() = Instant::now();
+                    manager_read.touch(&conn_id_read);
+
+                    // Raw frames aren't complete messages, so handlers never
+                    // see them
+                    if let TungsteniteMessage::Frame(_) = msg {
+                        debug!("Skipping raw frame from {}", conn_id_read);
+                        continue;
+                    }
+
+                    let message = Message::from_tungstenite(msg);
+                    if message.is_close() {
+                        let close_frame = message.close_frame();
+                        match &close_frame {
+                            Some((code, reason)) => info!(
+                                "Close message received from {} (code: {}, reason: {:?})",
+                                conn_id_read, code, reason
+                            ),
+                            None => info!("Close message received from {}", conn_id_read),
+                        }
+                        reason =
+                            DisconnectReason::closed(DisconnectSource::ClientClose, close_frame);
                         break;
                     }
+                    // With keepalive on, pongs only answer our own pings
+                    if message.is_pong() && keepalive.is_some() {
+                        continue;
+                    }
                     debug!("📨 Received message from {}", conn_id_read);
-                    let message = Message::from_tungstenite(msg);
+                    if message.is_text() || message.is_binary() {
+                        traffic.received(message.data.len());
+                    }
+                    if let Some(metrics) = &metrics_read {
+                        metrics.message_received();
+                    }
                     on_message(conn_id_read.clone(), message);
                 }
+                Err(WsError::Capacity(e)) => {
+                    warn!("Message from {} too big: {}", conn_id_read, e);
+                    close_and_wait(&manager_read, &conn_id_read, 1009, "Message Too Big").await;
+                }
+                Err(WsError::Utf8) if strict_utf8 => {
+                    warn!("Invalid UTF-8 in text message from {}", conn_id_read);
+                    close_and_wait(&manager_read, &conn_id_read, 1007, "Invalid UTF-8").await;
+                }
                 Err(e) => {
                     warn!("WebSocket error for {}: {}", conn_id_read, e);
+                    reason = DisconnectReason::failed(DisconnectSource::ReadError, e);
                     break;
                 }
             }
         }
         debug!("Read task ended for {}", conn_id_read);
-    });
+        reason
+    };
+
+    // Keepalive task - pings the client and gives up once it goes quiet
+    let conn_id_keepalive = conn_id.clone();
+    let keepalive_task = async {
+        let Some(keepalive) = keepalive else {
+            return std::future::pending().await;
+        };
+
+        let mut ticker = tokio::time::interval(keepalive.interval);
+        ticker.tick().await;
+
+        loop {
+            let deadline = *last_frame.lock().unwrap() + keepalive.timeout;
+
+            tokio::select! {
+                _ = ticker.tick() => {
+                    // Pings continue while a close frame is being flushed
+                    if ping_conn.enqueue(Message::ping(vec![])).is_err() {
+                        break DisconnectReason::closed(DisconnectSource::ServerClose, None);
+                    }
+                }
+                _ = tokio::time::sleep_until(deadline.into()) => {
+                    // A frame may have arrived while we were sleeping
+                    if last_frame.lock().unwrap().elapsed() >= keepalive.timeout {
+                        warn!(
+                            "Connection {} timed out after {:?} without a frame",
+                            conn_id_keepalive, keepalive.timeout
+                        );
+                        break DisconnectReason::closed(DisconnectSource::Timeout, None);
+                    }
+                }
+            }
+        }
+    };
+
+    // Shutdown task - ends the connection after a server-side disconnect. The
+    // write task normally finishes first once it has sent the close frame;
+    // this only fires if that takes too long.
+    let shutdown_task = async {
+        shutdown.notified().await;
+        tokio::time::sleep(CLOSE_TIMEOUT).await;
+        DisconnectReason::closed(DisconnectSource::ServerClose, None)
+    };
 
-    // Wait for either task to complete
-    tokio::select! {
-        _ = write_task => {
+    // Wait for any task to complete. All run inside this future, so the
+    // others are dropped along with the socket when this returns. The task
+    // that finished first tells why the connection ended.
+    let reason = tokio::select! {
+        reason = write_task => {
             debug!("Write task finished first for {}", conn_id);
+            reason
         },
-        _ = read_task => {
+        reason = read_task => {
             debug!("Read task finished first for {}", conn_id);
+            reason
         },
-    }
+        reason = keepalive_task => {
+            debug!("Keepalive timed out for {}", conn_id);
+            reason
+        },
+        reason = shutdown_task => {
+            debug!("Close frame not flushed in time for {}", conn_id);
+            reason
+        },
+    };
+
+    // The write task and its receiver are gone, so nothing else gets written
+    delivery.close();
 
     // Remove connection and call disconnect
     manager.remove(&conn_id);
-    on_disconnect(conn_id);
+    if let Some(metrics) = &metrics {
+        metrics.connection_closed(opened_at.elapsed());
+    }
+    on_disconnect(conn_id.clone());
+    if let Some(hook) = &options.on_disconnect_reason {
+        hook(conn_id.clone(), &reason);
+    }
+
+    if let Some(hook) = &options.on_disconnect_async
+        && let Err(e) = hook(hook_conn).await
+    {
+        error!("Disconnect callback failed for {}: {}", conn_id, e);
+    }
+}
+
+/// Closes a connection from its read task. The returned future never
+/// completes; the connection ends once the close frame has been sent.
+async fn close_and_wait(manager: &ConnectionManager, id: &ConnectionId, code: u16, reason: &str) {
+    let _ = manager.disconnect(id, Some((code, reason.to_string())));
+    // Keep the connection up until the write task has sent the close frame
+    std::future::pending::<()>().await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn add_connection(manager: &ConnectionManager, id: &str) -> mpsc::UnboundedReceiver<Message> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let addr: SocketAddr = "127.0.0.1:9000".parse().unwrap();
+        manager.add(Connection::new(id.to_string(), addr, tx));
+        rx
+    }
+
+    #[test]
+    fn test_join_and_broadcast_to_room() {
+        let manager = ConnectionManager::new();
+        let mut rx_a = add_connection(&manager, "a");
+        let mut rx_b = add_connection(&manager, "b");
+
+        manager.join_room(&"a".to_string(), "lobby").unwrap();
+        manager.broadcast_to_room("lobby", Message::text("hi"));
+
+        assert_eq!(rx_a.try_recv().unwrap().as_text(), Some("hi"));
+        assert!(rx_b.try_recv().is_err());
+        assert_eq!(manager.room_members("lobby"), vec!["a".to_string()]);
+        assert_eq!(
+            manager.rooms_for(&"a".to_string()),
+            vec!["lobby".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_broadcast_by_tags() {
+        let manager = ConnectionManager::new();
+        let mut rx_a = add_connection(&manager, "a");
+        let mut rx_b = add_connection(&manager, "b");
+        let mut rx_c = add_connection(&manager, "c");
+        let (a, b, c) = ("a".to_string(), "b".to_string(), "c".to_string());
+
+        manager.tag(&a, "region:us").unwrap();
+        manager.tag(&a, "plan:pro").unwrap();
+        manager.tag(&b, "region:us").unwrap();
+        manager.tag(&c, "plan:pro").unwrap();
+
+        let report = manager.broadcast_to_tag("region:us", Message::text("us"));
+        assert_eq!(report.succeeded, 2);
+        let report =
+            manager.broadcast_to_all_tags(&["plan:pro", "region:us"], Message::text("pro us"));
+        assert_eq!(report.succeeded, 1);
+        assert_eq!(
+            manager
+                .broadcast_to_all_tags(&[], Message::text("none"))
+                .attempted,
+            0
+        );
+
+        assert_eq!(rx_a.try_recv().unwrap().as_text(), Some("us"));
+        assert_eq!(rx_a.try_recv().unwrap().as_text(), Some("pro us"));
+        assert_eq!(rx_b.try_recv().unwrap().as_text(), Some("us"));
+        assert!(rx_b.try_recv().is_err());
+        assert!(rx_c.try_recv().is_err());
+
+        assert!(manager.untag(&a, "plan:pro"));
+        assert!(!manager.untag(&a, "plan:pro"));
+        assert_eq!(manager.tagged("plan:pro"), vec![c.clone()]);
+
+        // Tags go away with the connection
+        manager.remove(&b);
+        assert_eq!(manager.tagged("region:us"), vec![a.clone()]);
+        assert!(manager.tags_for(&b).is_empty());
+        assert!(matches!(
+            manager.tag(&b, "region:us"),
+            Err(Error::ConnectionNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_broadcast_json() {
+        let manager = ConnectionManager::new();
+        let mut rx_a = add_connection(&manager, "a");
+        let mut rx_b = add_connection(&manager, "b");
+
+        let report = manager
+            .broadcast_json_except(&"a".to_string(), &serde_json::json!({ "n": 1 }))
+            .unwrap();
+        assert_eq!(report.attempted, 1);
+        assert_eq!(rx_b.try_recv().unwrap().as_text(), Some(r#"{"n":1}"#));
+        assert!(rx_a.try_recv().is_err());
+
+        // Maps with non-string keys can't be serialized to JSON
+        let invalid: std::collections::HashMap<(u8, u8), u8> = [((1, 2), 3)].into();
+        assert!(matches!(
+            manager.broadcast_json(&invalid),
+            Err(Error::Json(_))
+        ));
+        assert!(rx_a.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_uptime_shared_by_clones() {
+        let (tx, _rx) = mpsc::unbounded_channel();
+        let addr: SocketAddr = "127.0.0.1:8080".parse().unwrap();
+        let conn = Connection::new("conn_0".to_string(), addr, tx);
+
+        std::thread::sleep(Duration::from_millis(10));
+        let clone = conn.clone();
+        let uptime = clone.uptime();
+        assert!(uptime >= Duration::from_millis(10));
+        assert!(conn.uptime() >= uptime);
+    }
+
+    #[test]
+    fn test_disconnect_reason_is_clean() {
+        let normal =
+            DisconnectReason::closed(DisconnectSource::ClientClose, Some((1000, String::new())));
+        assert_eq!(normal.reason, None);
+        assert!(normal.is_clean());
+        assert!(DisconnectReason::closed(DisconnectSource::ServerClose, None).is_clean());
+
+        let kicked = DisconnectReason::closed(
+            DisconnectSource::ServerClose,
+            Some((1008, "policy violation".to_string())),
+        );
+        assert_eq!(kicked.reason.as_deref(), Some("policy violation"));
+        assert!(!kicked.is_clean());
+        assert!(!DisconnectReason::closed(DisconnectSource::Timeout, None).is_clean());
+        assert!(!DisconnectReason::failed(DisconnectSource::ReadError, "reset").is_clean());
+    }
+
+    #[test]
+    fn test_is_open() {
+        let addr: SocketAddr = "127.0.0.1:8080".parse().unwrap();
+
+        let (tx, _rx) = mpsc::unbounded_channel();
+        let conn = Connection::new("closing".to_string(), addr, tx);
+        let clone = conn.clone();
+        assert!(clone.is_open());
+        conn.close(None).unwrap();
+        assert!(!clone.is_open());
+
+        let (tx, rx) = mpsc::unbounded_channel();
+        let conn = Connection::new("ended".to_string(), addr, tx);
+        drop(rx);
+        assert!(!conn.is_open());
+    }
+
+    #[test]
+    fn test_send_all_keeps_order() {
+        let addr: SocketAddr = "127.0.0.1:8080".parse().unwrap();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let conn = Connection::new("multi".to_string(), addr, tx);
+
+        conn.send_all([Message::text("ack"), Message::text("snapshot")])
+            .unwrap();
+        conn.send_all_json([1, 2]).unwrap();
+        for expected in ["ack", "snapshot", "1", "2"] {
+            assert_eq!(rx.try_recv().unwrap().as_text(), Some(expected));
+        }
+
+        conn.close(None).unwrap();
+        rx.try_recv().unwrap();
+        assert!(conn.send_all([Message::text("late")]).is_err());
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_broadcast_through_backend() {
+        use crate::broadcast::InProcessBackend;
+
+        let backend = Arc::new(InProcessBackend::new());
+        let node_a = Arc::new(ConnectionManager::new());
+        let node_b = Arc::new(ConnectionManager::new());
+        node_a.set_broadcast_backend(backend.clone());
+        node_b.set_broadcast_backend(backend);
+        let mut rx_a = add_connection(&node_a, "a");
+        let mut rx_b = add_connection(&node_b, "b");
+
+        let report = node_a.broadcast(Message::text("all"));
+        assert_eq!(report.attempted, 1);
+        node_a.broadcast_local(Message::text("local"));
+
+        let received = tokio::time::timeout(Duration::from_secs(5), rx_b.recv())
+            .await
+            .expect("broadcast did not reach the other node");
+        assert_eq!(received.unwrap().as_text(), Some("all"));
+
+        node_b.broadcast(Message::text("from b"));
+        assert_eq!(rx_b.recv().await.unwrap().as_text(), Some("from b"));
+
+        // Node A gets its own broadcasts once, then B's
+        assert_eq!(rx_a.recv().await.unwrap().as_text(), Some("all"));
+        assert_eq!(rx_a.recv().await.unwrap().as_text(), Some("local"));
+        assert_eq!(rx_a.recv().await.unwrap().as_text(), Some("from b"));
+        tokio::task::yield_now().await;
+        assert!(rx_a.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_resume_session_after_reconnect() {
+        let manager = ConnectionManager::new();
+        manager.set_resume_window(Duration::from_secs(60));
+        let _rx_a = add_connection(&manager, "a");
+        let (a, b) = ("a".to_string(), "b".to_string());
+
+        let conn = manager.get(&a).unwrap();
+        conn.set_meta("user", "alice".to_string());
+        manager.join_room(&a, "lobby").unwrap();
+        manager.tag(&a, "plan:pro").unwrap();
+        let token = manager.resume_token(&a).unwrap();
+        assert_eq!(manager.resume_token(&a).unwrap(), token);
+
+        manager.remove(&a);
+        let _rx_b = add_connection(&manager, "b");
+        assert!(manager.resume(&token, &b).unwrap());
+
+        let conn = manager.get(&b).unwrap();
+        assert_eq!(conn.get_meta::<String>("user").as_deref().unwrap(), "alice");
+        assert_eq!(manager.room_members("lobby"), vec![b.clone()]);
+        assert_eq!(manager.tags_for(&b), vec!["plan:pro".to_string()]);
+
+        // A session is resumed once, and the token moves to the new connection
+        assert!(!manager.resume(&token, &b).unwrap());
+        assert_eq!(manager.resume_token(&b).unwrap(), token);
+        assert!(!manager.resume("unknown", &b).unwrap());
+    }
+
+    #[test]
+    fn test_resume_needs_window() {
+        let manager = ConnectionManager::new();
+        let _rx_a = add_connection(&manager, "a");
+        let _rx_b = add_connection(&manager, "b");
+        let token = manager.resume_token(&"a".to_string()).unwrap();
+
+        manager.remove(&"a".to_string());
+        assert!(!manager.resume(&token, &"b".to_string()).unwrap());
+        assert_ne!(manager.resume_token(&"b".to_string()).unwrap(), token);
+    }
+
+    #[test]
+    fn test_resume_tokens_are_random_hex() {
+        let a = new_resume_token();
+        let b = new_resume_token();
+
+        assert_eq!(a.len(), 32);
+        assert!(a.chars().all(|c| c.is_ascii_hexdigit()));
+        assert_ne!(a, b);
+    }
+
+    #[test]
+    fn test_broadcast_report_lists_failed_sends() {
+        let manager = ConnectionManager::new();
+        let _rx_a = add_connection(&manager, "a");
+        drop(add_connection(&manager, "b"));
+        let _rx_c = add_connection(&manager, "c");
+
+        let ids = ["a".to_string(), "b".to_string(), "missing".to_string()];
+        let report = manager.broadcast_to(&ids, Message::text("hi"));
+        assert_eq!((report.attempted, report.succeeded), (2, 1));
+        assert_eq!(report.failed_ids, vec!["b".to_string()]);
+        assert!(!report.is_complete());
+
+        let report = manager.broadcast_except(&"a".to_string(), Message::text("hi"));
+        assert!(report.is_complete());
+        assert_eq!(report.succeeded, 1);
+    }
+
+    #[test]
+    fn test_broadcast_prunes_closed_connections() {
+        let manager = ConnectionManager::new();
+        let mut rx_a = add_connection(&manager, "a");
+        drop(add_connection(&manager, "b"));
+        manager.join_room(&"b".to_string(), "lobby").unwrap();
+        assert_eq!(manager.count(), 2);
+
+        let report = manager.broadcast(Message::text("hi"));
+        assert!(report.is_complete());
+        assert_eq!(report.attempted, 1);
+        assert_eq!(rx_a.try_recv().unwrap().as_text(), Some("hi"));
+
+        assert_eq!(manager.count(), 1);
+        assert!(manager.get(&"b".to_string()).is_none());
+        assert!(manager.room_members("lobby").is_empty());
+    }
+
+    #[test]
+    fn test_reply_and_broadcast() {
+        let manager = ConnectionManager::new();
+        let mut rx_a = add_connection(&manager, "a");
+        let mut rx_b = add_connection(&manager, "b");
+        let mut rx_c = add_connection(&manager, "c");
+
+        let report = manager.reply_and_broadcast(
+            &"a".to_string(),
+            Message::text("ack"),
+            Message::text("news"),
+        );
+
+        assert_eq!((report.attempted, report.succeeded), (3, 3));
+        assert_eq!(rx_a.try_recv().unwrap().as_text(), Some("ack"));
+        assert_eq!(rx_b.try_recv().unwrap().as_text(), Some("news"));
+        assert_eq!(rx_c.try_recv().unwrap().as_text(), Some("news"));
+        assert!(rx_a.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_iterate_without_collecting() {
+        let manager = ConnectionManager::new();
+        let _rx_a = add_connection(&manager, "a");
+        let _rx_b = add_connection(&manager, "b");
+        let _rx_c = add_connection(&manager, "c");
+        manager
+            .get(&"b".to_string())
+            .unwrap()
+            .set_meta("role", "admin".to_string());
+
+        let mut seen = 0;
+        manager.for_each(|_| seen += 1);
+        assert_eq!(seen, 3);
+
+        let is_admin = |conn: &Connection| conn.get_meta::<String>("role").is_some();
+        assert_eq!(manager.count_where(is_admin), 1);
+        assert_eq!(manager.find(is_admin).unwrap().id(), "b");
+        assert!(manager.find(|conn| conn.id() == "missing").is_none());
+    }
+
+    #[test]
+    fn test_join_unknown_connection() {
+        let manager = ConnectionManager::new();
+        let result = manager.join_room(&"missing".to_string(), "lobby");
+
+        assert!(matches!(result, Err(Error::ConnectionNotFound(_))));
+        assert!(manager.room_members("lobby").is_empty());
+    }
+
+    #[test]
+    fn test_leave_room() {
+        let manager = ConnectionManager::new();
+        let _rx = add_connection(&manager, "a");
+        let id = "a".to_string();
+
+        manager.join_room(&id, "lobby").unwrap();
+        assert!(manager.leave_room(&id, "lobby"));
+        assert!(!manager.leave_room(&id, "lobby"));
+        assert!(manager.room_members("lobby").is_empty());
+        assert!(manager.rooms_for(&id).is_empty());
+    }
+
+    #[test]
+    fn test_remove_evicts_from_rooms() {
+        let manager = ConnectionManager::new();
+        let _rx_a = add_connection(&manager, "a");
+        let _rx_b = add_connection(&manager, "b");
+        let id = "a".to_string();
+
+        manager.join_room(&id, "lobby").unwrap();
+        manager.join_room(&id, "game").unwrap();
+        manager.join_room(&"b".to_string(), "lobby").unwrap();
+
+        manager.remove(&id);
+
+        assert!(manager.rooms_for(&id).is_empty());
+        assert_eq!(manager.room_members("lobby"), vec!["b".to_string()]);
+        assert!(manager.room_members("game").is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_keepalive_drops_silent_connection() {
+        use tokio_tungstenite::tungstenite::protocol::Role;
+
+        let (server_io, _client_io) = tokio::io::duplex(1024);
+        let stream = WebSocketStream::from_raw_socket(server_io, Role::Server, None).await;
+        let manager = Arc::new(ConnectionManager::new());
+        let addr: SocketAddr = "127.0.0.1:9000".parse().unwrap();
+
+        // The client half is never read, so pings go unanswered
+        let serve = serve_websocket(
+            stream,
+            "silent".to_string(),
+            addr,
+            manager.clone(),
+            Arc::new(|_, _| {}),
+            Arc::new(|_| {}),
+            Arc::new(|_| {}),
+            ConnectionOptions {
+                keepalive: Some(KeepAlive {
+                    interval: Duration::from_millis(10),
+                    timeout: Duration::from_millis(50),
+                }),
+                ..Default::default()
+            },
+        );
+
+        tokio::time::timeout(Duration::from_secs(5), serve)
+            .await
+            .expect("silent connection was not dropped");
+        assert_eq!(manager.count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_pongs_reach_handlers_without_keepalive() {
+        use tokio_tungstenite::tungstenite::protocol::Role;
+
+        let (server_io, client_io) = tokio::io::duplex(1024);
+        let stream = WebSocketStream::from_raw_socket(server_io, Role::Server, None).await;
+        let mut client = WebSocketStream::from_raw_socket(client_io, Role::Client, None).await;
+        let manager = Arc::new(ConnectionManager::new());
+        let addr: SocketAddr = "127.0.0.1:9000".parse().unwrap();
+        let (seen_tx, mut seen_rx) = mpsc::unbounded_channel();
+
+        let serve = tokio::spawn(serve_websocket(
+            stream,
+            "pong".to_string(),
+            addr,
+            manager.clone(),
+            Arc::new(move |_, message: Message| {
+                let _ = seen_tx.send(message);
+            }),
+            Arc::new(|_| {}),
+            Arc::new(|_| {}),
+            ConnectionOptions::default(),
+        ));
+
+        client
+            .send(TungsteniteMessage::Pong(b"unsolicited".to_vec()))
+            .await
+            .unwrap();
+        let message = seen_rx.recv().await.unwrap();
+        assert!(message.is_pong());
+
+        drop(client);
+        serve.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_disconnect_sends_close_and_ends_connection() {
+        use tokio_tungstenite::tungstenite::protocol::Role;
+
+        let (server_io, client_io) = tokio::io::duplex(1024);
+        let stream = WebSocketStream::from_raw_socket(server_io, Role::Server, None).await;
+        let mut client = WebSocketStream::from_raw_socket(client_io, Role::Client, None).await;
+        let manager = Arc::new(ConnectionManager::new());
+        let addr: SocketAddr = "127.0.0.1:9000".parse().unwrap();
+
+        let serve = tokio::spawn(serve_websocket(
+            stream,
+            "target".to_string(),
+            addr,
+            manager.clone(),
+            Arc::new(|_, _| {}),
+            Arc::new(|_| {}),
+            Arc::new(|_| {}),
+            ConnectionOptions::default(),
+        ));
+
+        while manager.count() == 0 {
+            tokio::task::yield_now().await;
+        }
+
+        let id = "target".to_string();
+        manager
+            .disconnect(&id, Some((1008, "Banned".to_string())))
+            .unwrap();
+        assert_eq!(manager.count(), 0);
+
+        let frame = client.next().await.unwrap().unwrap();
+        let message = Message::from_tungstenite(frame);
+        assert_eq!(message.close_frame(), Some((1008, "Banned".to_string())));
+
+        tokio::time::timeout(Duration::from_secs(5), serve)
+            .await
+            .expect("connection was not shut down")
+            .unwrap();
+        assert!(manager.disconnect(&id, None).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_failed_connect_hook_closes_connection() {
+        use std::sync::atomic::{AtomicBool, Ordering};
+        use tokio_tungstenite::tungstenite::protocol::Role;
+
+        let (server_io, client_io) = tokio::io::duplex(1024);
+        let stream = WebSocketStream::from_raw_socket(server_io, Role::Server, None).await;
+        let mut client = WebSocketStream::from_raw_socket(client_io, Role::Client, None).await;
+        let manager = Arc::new(ConnectionManager::new());
+        let addr: SocketAddr = "127.0.0.1:9000".parse().unwrap();
+
+        let cleaned_up = Arc::new(AtomicBool::new(false));
+        let cleaned_up_hook = cleaned_up.clone();
+
+        let serve = tokio::spawn(serve_websocket(
+            stream,
+            "hooked".to_string(),
+            addr,
+            manager.clone(),
+            Arc::new(|_, _| {}),
+            Arc::new(|_| {}),
+            Arc::new(|_| {}),
+            ConnectionOptions {
+                on_connect_async: Some(Arc::new(|_| {
+                    Box::pin(async { Err(Error::custom("presence store down")) })
+                })),
+                on_disconnect_async: Some(Arc::new(move |_| {
+                    let cleaned_up = cleaned_up_hook.clone();
+                    Box::pin(async move {
+                        cleaned_up.store(true, Ordering::SeqCst);
+                        Ok(())
+                    })
+                })),
+                ..Default::default()
+            },
+        ));
+
+        let frame = client.next().await.unwrap().unwrap();
+        let message = Message::from_tungstenite(frame);
+        assert_eq!(message.close_frame().map(|(code, _)| code), Some(1011));
+
+        tokio::time::timeout(Duration::from_secs(5), serve)
+            .await
+            .expect("connection was not shut down")
+            .unwrap();
+        assert_eq!(manager.count(), 0);
+        assert!(cleaned_up.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn test_send_flush_waits_for_write() {
+        use tokio_tungstenite::tungstenite::protocol::Role;
+
+        let (server_io, client_io) = tokio::io::duplex(1024);
+        let stream = WebSocketStream::from_raw_socket(server_io, Role::Server, None).await;
+        let mut client = WebSocketStream::from_raw_socket(client_io, Role::Client, None).await;
+        let manager = Arc::new(ConnectionManager::new());
+        let addr: SocketAddr = "127.0.0.1:9000".parse().unwrap();
+
+        let serve = tokio::spawn(serve_websocket(
+            stream,
+            "flushed".to_string(),
+            addr,
+            manager.clone(),
+            Arc::new(|_, _| {}),
+            Arc::new(|_| {}),
+            Arc::new(|_| {}),
+            ConnectionOptions::default(),
+        ));
+
+        while manager.count() == 0 {
+            tokio::task::yield_now().await;
+        }
+        let conn = manager.get(&"flushed".to_string()).unwrap();
+
+        conn.send_text("first").unwrap();
+        tokio::time::timeout(
+            Duration::from_secs(5),
+            conn.send_flush(Message::text("last")),
+        )
+        .await
+        .expect("send_flush did not resolve")
+        .unwrap();
+
+        // Both messages are already written, so the write task has seen them
+        assert_eq!(delivered(&conn), 2);
+
+        let first = Message::from_tungstenite(client.next().await.unwrap().unwrap());
+        let last = Message::from_tungstenite(client.next().await.unwrap().unwrap());
+        assert_eq!(first.as_text(), Some("first"));
+        assert_eq!(last.as_text(), Some("last"));
+
+        manager.disconnect(conn.id(), None).unwrap();
+        drop(client);
+        serve.await.unwrap();
+
+        let result = conn.send_flush(Message::text("too late")).await;
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_slow_client_reported_once_per_backlog() {
+        let reports = Arc::new(AtomicU64::new(0));
+        let counter = reports.clone();
+        let (tx, _rx) = mpsc::unbounded_channel();
+        let mut conn = Connection::new("slow".to_string(), "127.0.0.1:9000".parse().unwrap(), tx);
+        assert_eq!(conn.pending_count(), 0);
+
+        let delivery = Arc::new(Delivery {
+            slow_client: Some(SlowClient {
+                threshold: 2,
+                callback: Arc::new(move |_| {
+                    counter.fetch_add(1, Ordering::SeqCst);
+                }),
+            }),
+            ..Default::default()
+        });
+        conn.delivery = Some(delivery.clone());
+
+        // Nothing drains the queue, so every send adds to the backlog
+        for _ in 0..4 {
+            conn.send_text("update").unwrap();
+        }
+        assert_eq!(conn.pending_count(), 4);
+        assert_eq!(reports.load(Ordering::SeqCst), 1);
+
+        // Catching up re-arms the callback
+        for _ in 0..4 {
+            delivery.written();
+        }
+        assert_eq!(conn.pending_count(), 0);
+        for _ in 0..3 {
+            conn.send_text("update").unwrap();
+        }
+        assert_eq!(reports.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_slow_client_callback_can_disconnect_during_broadcast() {
+        let manager = Arc::new(ConnectionManager::new());
+        let mut receivers = Vec::new();
+
+        for id in ["a", "b"] {
+            let (tx, rx) = mpsc::unbounded_channel();
+            receivers.push(rx);
+            let mut conn = Connection::new(id.to_string(), "127.0.0.1:9000".parse().unwrap(), tx);
+            let handle = manager.clone();
+            conn.delivery = Some(Arc::new(Delivery {
+                slow_client: Some(SlowClient {
+                    threshold: 1,
+                    callback: Arc::new(move |conn| {
+                        handle.disconnect(conn.id(), None).unwrap();
+                    }),
+                }),
+                ..Default::default()
+            }));
+            manager.add(conn);
+        }
+
+        // The second broadcast pushes both queues past the threshold
+        manager.broadcast(Message::text("one"));
+        assert_eq!(manager.count(), 2);
+        let report = manager.broadcast(Message::text("two"));
+        assert_eq!(report.succeeded, 2);
+        assert_eq!(manager.count(), 0);
+    }
+
+    #[test]
+    fn test_drop_callback_can_disconnect_during_broadcast() {
+        let manager = Arc::new(ConnectionManager::new());
+        let mut receivers = Vec::new();
+
+        for id in ["a", "b"] {
+            let (tx, rx) = mpsc::unbounded_channel();
+            receivers.push(rx);
+            let mut conn = Connection::new(id.to_string(), "127.0.0.1:9000".parse().unwrap(), tx);
+            let handle = manager.clone();
+            conn.delivery = Some(Arc::new(Delivery {
+                send_limit: Some(SendLimit {
+                    max_pending: 1,
+                    on_drop: Some(Arc::new(move |id, _| {
+                        handle.disconnect(&id, None).unwrap();
+                    })),
+                }),
+                ..Default::default()
+            }));
+            manager.add(conn);
+        }
+
+        manager.broadcast(Message::text("one"));
+        let report = manager.broadcast(Message::text("dropped"));
+        assert_eq!(report.attempted, 2);
+        assert_eq!(manager.count(), 0);
+    }
+
+    #[test]
+    fn test_send_limit_drops_and_notifies() {
+        let dropped = Arc::new(Mutex::new(Vec::new()));
+        let log = dropped.clone();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let mut conn =
+            Connection::new("lagging".to_string(), "127.0.0.1:9000".parse().unwrap(), tx);
+
+        let delivery = Arc::new(Delivery {
+            send_limit: Some(SendLimit {
+                max_pending: 2,
+                on_drop: Some(Arc::new(move |id, message: Message| {
+                    let text = message.as_text().unwrap_or_default().to_string();
+                    log.lock().unwrap().push((id, text));
+                })),
+            }),
+            ..Default::default()
+        });
+        conn.delivery = Some(delivery.clone());
+
+        for text in ["a", "b", "c"] {
+            assert!(conn.send_text(text).is_ok());
+        }
+        assert_eq!(conn.pending_count(), 2);
+        assert_eq!(
+            *dropped.lock().unwrap(),
+            vec![("lagging".to_string(), "c".to_string())]
+        );
+
+        // Close frames always get through
+        conn.close(None).unwrap();
+        assert_eq!(conn.pending_count(), 3);
+
+        // Draining the queue makes room again
+        for _ in 0..2 {
+            rx.try_recv().unwrap();
+            delivery.written();
+        }
+        assert!(conn.enqueue(Message::text("d")).is_ok());
+        assert_eq!(conn.pending_count(), 2);
+        assert_eq!(dropped.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_keepalive_pings_bypass_send_limit() {
+        use tokio_tungstenite::tungstenite::protocol::Role;
+
+        // The client half is never read, so the write task stalls on the
+        // first big message and the queue stays full
+        let (server_io, _client_io) = tokio::io::duplex(64);
+        let stream = WebSocketStream::from_raw_socket(server_io, Role::Server, None).await;
+        let manager = Arc::new(ConnectionManager::new());
+        let addr: SocketAddr = "127.0.0.1:9000".parse().unwrap();
+        let dropped = Arc::new(Mutex::new(Vec::new()));
+        let log = dropped.clone();
+
+        let serve = tokio::spawn(serve_websocket(
+            stream,
+            "full".to_string(),
+            addr,
+            manager.clone(),
+            Arc::new(|_, _| {}),
+            Arc::new(|_| {}),
+            Arc::new(|_| {}),
+            ConnectionOptions {
+                keepalive: Some(KeepAlive {
+                    interval: Duration::from_millis(10),
+                    timeout: Duration::from_secs(5),
+                }),
+                send_limit: Some(SendLimit {
+                    max_pending: 1,
+                    on_drop: Some(Arc::new(move |_, message: Message| {
+                        log.lock().unwrap().push(message);
+                    })),
+                }),
+                ..Default::default()
+            },
+        ));
+
+        let id = "full".to_string();
+        let conn = loop {
+            if let Some(conn) = manager.get(&id) {
+                break conn;
+            }
+            tokio::task::yield_now().await;
+        };
+        conn.send_text("x".repeat(4096)).unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        // Pings were queued past the cap without being reported as dropped
+        assert!(conn.pending_count() > 1);
+        assert!(dropped.lock().unwrap().is_empty());
+
+        conn.send_text("late").unwrap();
+        let dropped = dropped.lock().unwrap();
+        assert_eq!(dropped.len(), 1);
+        assert_eq!(dropped[0].as_text(), Some("late"));
+
+        serve.abort();
+    }
+
+    #[tokio::test]
+    async fn test_outbound_middleware_sees_broadcasts() {
+        use tokio_tungstenite::tungstenite::protocol::Role;
+
+        struct Stamp;
+
+        #[async_trait::async_trait]
+        impl OutboundMiddleware for Stamp {
+            async fn on_send(&self, message: Message, conn: &Connection) -> Result<Message> {
+                let text = message.as_text().unwrap_or_default();
+                Ok(Message::text(format!("{}:{}", conn.id(), text)))
+            }
+        }
+
+        let (server_io, client_io) = tokio::io::duplex(1024);
+        let stream = WebSocketStream::from_raw_socket(server_io, Role::Server, None).await;
+        let mut client = WebSocketStream::from_raw_socket(client_io, Role::Client, None).await;
+        let manager = Arc::new(ConnectionManager::new());
+        let addr: SocketAddr = "127.0.0.1:9000".parse().unwrap();
+
+        tokio::spawn(serve_websocket(
+            stream,
+            "stamped".to_string(),
+            addr,
+            manager.clone(),
+            Arc::new(|_, _| {}),
+            Arc::new(|_| {}),
+            Arc::new(|_| {}),
+            ConnectionOptions {
+                outbound: vec![Arc::new(Stamp)],
+                ..Default::default()
+            },
+        ));
+
+        while manager.count() == 0 {
+            tokio::task::yield_now().await;
+        }
+        manager.broadcast(Message::text("news"));
+
+        let frame = Message::from_tungstenite(client.next().await.unwrap().unwrap());
+        assert_eq!(frame.as_text(), Some("stamped:news"));
+    }
+
+    #[tokio::test]
+    async fn test_close_sends_frame_and_tears_down() {
+        use tokio_tungstenite::tungstenite::protocol::Role;
+
+        let (server_io, client_io) = tokio::io::duplex(1024);
+        let stream = WebSocketStream::from_raw_socket(server_io, Role::Server, None).await;
+        let mut client = WebSocketStream::from_raw_socket(client_io, Role::Client, None).await;
+        let manager = Arc::new(ConnectionManager::new());
+        let addr: SocketAddr = "127.0.0.1:9000".parse().unwrap();
+
+        let serve = tokio::spawn(serve_websocket(
+            stream,
+            "closing".to_string(),
+            addr,
+            manager.clone(),
+            Arc::new(|_, _| {}),
+            Arc::new(|_| {}),
+            Arc::new(|_| {}),
+            ConnectionOptions::default(),
+        ));
+
+        while manager.count() == 0 {
+            tokio::task::yield_now().await;
+        }
+        let conn = manager.get(&"closing".to_string()).unwrap();
+
+        conn.send_text("bye").unwrap();
+        conn.close(Some((1008, "policy violation".to_string())))
+            .unwrap();
+        assert!(conn.send_text("too late").is_err());
+        assert!(conn.close(None).is_err());
+
+        let last = Message::from_tungstenite(client.next().await.unwrap().unwrap());
+        assert_eq!(last.as_text(), Some("bye"));
+        let close = Message::from_tungstenite(client.next().await.unwrap().unwrap());
+        assert_eq!(close.close_frame().map(|(code, _)| code), Some(1008));
+
+        tokio::time::timeout(Duration::from_secs(5), serve)
+            .await
+            .expect("connection was not shut down")
+            .unwrap();
+        assert_eq!(manager.count(), 0);
+    }
+
+    fn delivered(conn: &Connection) -> u64 {
+        conn.delivery
+            .as_ref()
+            .unwrap()
+            .progress
+            .lock()
+            .unwrap()
+            .written
+    }
+
+    #[test]
+    fn test_broadcast_where_sends_to_matches() {
+        let manager = ConnectionManager::new();
+        let mut rx_a = add_connection(&manager, "a");
+        let mut rx_b = add_connection(&manager, "b");
+
+        manager
+            .get(&"a".to_string())
+            .unwrap()
+            .set_meta("role", "admin".to_string());
+
+        let sent = manager.broadcast_where(
+            |conn| {
+                conn.get_meta::<String>("role")
+                    .is_some_and(|r| *r == "admin")
+            },
+            Message::text("hi admins"),
+        );
+
+        assert_eq!(sent, 1);
+        assert_eq!(rx_a.try_recv().unwrap().as_text(), Some("hi admins"));
+        assert!(rx_b.try_recv().is_err());
+    }
 }
@@ -0,0 +1,280 @@
+//! Cross-origin resource sharing (CORS) for HTTP responses.
+//!
+//! Browsers only let a page read responses from another origin if the
+//! response allows it with `Access-Control-Allow-*` headers. When the
+//! router serves static files or other HTTP responses to a frontend hosted
+//! elsewhere, configure a [`CorsConfig`] with
+//! [`Router::cors()`](crate::router::Router::cors).
+//!
+//! # Overview
+//!
+//! - Responses to requests from an allowed origin carry
+//!   `Access-Control-Allow-Origin`
+//! - `OPTIONS` preflight requests are answered with `204 No Content` and the
+//!   allowed methods and headers
+//! - Requests from other origins get no CORS headers, so the browser blocks
+//!   them
+//!
+//! CORS only applies to the HTTP side of the router. WebSocket upgrades
+//! aren't subject to it; restrict them with
+//! [`Router::allowed_origins()`](crate::router::Router::allowed_origins).
+//!
+//! # Examples
+//!
+//! ```
+//! use wsforge::prelude::*;
+//! use wsforge::cors::CorsConfig;
+//! use std::time::Duration;
+//!
+//! # fn example() {
+//! let router = Router::new()
+//!     .serve_static("public")
+//!     .cors(
+//!         CorsConfig::new()
+//!             .allow_origin("https://app.example.com")
+//!             .allow_headers(["authorization"])
+//!             .max_age(Duration::from_secs(3600)),
+//!     );
+//! # }
+//! ```
+
+use crate::handshake::HeaderMap;
+use std::time::Duration;
+
+/// CORS settings for the router's HTTP responses.
+///
+/// A new config allows no origins, the `GET`, `HEAD` and `OPTIONS` methods,
+/// and no extra request headers.
+///
+/// # Examples
+///
+/// ## Any Origin
+///
+/// ```
+/// use wsforge::cors::CorsConfig;
+///
+/// // Public assets that any site may load
+/// let cors = CorsConfig::new().allow_any_origin();
+/// ```
+///
+/// ## Specific Origins
+///
+/// ```
+/// use wsforge::cors::CorsConfig;
+///
+/// let cors = CorsConfig::new()
+///     .allow_origins(["https://app.example.com", "http://localhost:5173"])
+///     .allow_methods(["GET", "POST"])
+///     .allow_headers(["authorization", "content-type"]);
+/// ```
+#[derive(Debug, Clone)]
+pub struct CorsConfig {
+    /// Whether every origin is allowed
+    any_origin: bool,
+    /// Allowed origins, if not every origin is
+    origins: Vec<String>,
+    /// Methods announced in preflight responses
+    methods: Vec<String>,
+    /// Request headers announced in preflight responses
+    headers: Vec<String>,
+    /// How long browsers may cache preflight responses
+    max_age: Option<Duration>,
+}
+
+impl CorsConfig {
+    /// Creates a config that allows no origins yet.
+    pub fn new() -> Self {
+        Self {
+            any_origin: false,
+            origins: Vec::new(),
+            methods: vec!["GET".to_string(), "HEAD".to_string(), "OPTIONS".to_string()],
+            headers: Vec::new(),
+            max_age: None,
+        }
+    }
+
+    /// Allows requests from every origin.
+    ///
+    /// Responses carry `Access-Control-Allow-Origin: *`.
+    pub fn allow_any_origin(mut self) -> Self {
+        self.any_origin = true;
+        self
+    }
+
+    /// Allows requests from `origin`, e.g. `https://app.example.com`.
+    ///
+    /// Origins are compared case-insensitively and must match exactly,
+    /// including scheme and port.
+    pub fn allow_origin(mut self, origin: impl Into<String>) -> Self {
+        self.origins.push(origin.into());
+        self
+    }
+
+    /// Allows requests from each of `origins`.
+    ///
+    /// See [`allow_origin()`](Self::allow_origin).
+    pub fn allow_origins<I, S>(mut self, origins: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.origins.extend(origins.into_iter().map(Into::into));
+        self
+    }
+
+    /// Sets the methods announced in preflight responses, replacing the
+    /// default `GET`, `HEAD` and `OPTIONS`.
+    pub fn allow_methods<I, S>(mut self, methods: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.methods = methods.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Sets the request headers announced in preflight responses, such as
+    /// `authorization`.
+    pub fn allow_headers<I, S>(mut self, headers: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.headers = headers.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Lets browsers cache preflight responses for `max_age`.
+    pub fn max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    /// Returns the headers to add to a response to a request with the given
+    /// headers. Empty if the request's origin isn't allowed.
+    pub(crate) fn response_headers(&self, request: &HeaderMap) -> Vec<(String, String)> {
+        let Some(origin) = request.get("origin").and_then(|v| v.to_str().ok()) else {
+            return Vec::new();
+        };
+
+        if self.any_origin {
+            vec![("Access-Control-Allow-Origin".to_string(), "*".to_string())]
+        } else if self
+            .origins
+            .iter()
+            .any(|allowed| allowed.eq_ignore_ascii_case(origin))
+        {
+            vec![
+                (
+                    "Access-Control-Allow-Origin".to_string(),
+                    origin.to_string(),
+                ),
+                // The response differs by origin, so caches must not share it
+                ("Vary".to_string(), "Origin".to_string()),
+            ]
+        } else {
+            Vec::new()
+        }
+    }
+
+    /// Returns the headers of a `204` response to a preflight request.
+    pub(crate) fn preflight_headers(&self, request: &HeaderMap) -> Vec<(String, String)> {
+        let mut headers = self.response_headers(request);
+        if headers.is_empty() {
+            return headers;
+        }
+
+        headers.push((
+            "Access-Control-Allow-Methods".to_string(),
+            self.methods.join(", "),
+        ));
+        if !self.headers.is_empty() {
+            headers.push((
+                "Access-Control-Allow-Headers".to_string(),
+                self.headers.join(", "),
+            ));
+        }
+        if let Some(max_age) = self.max_age {
+            headers.push((
+                "Access-Control-Max-Age".to_string(),
+                max_age.as_secs().to_string(),
+            ));
+        }
+        headers
+    }
+}
+
+impl Default for CorsConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Returns `true` if the request head is a CORS preflight request.
+pub(crate) fn is_preflight(method: &str, request: &HeaderMap) -> bool {
+    method == "OPTIONS"
+        && request.contains_key("origin")
+        && request.contains_key("access-control-request-method")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(origin: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert("origin", origin.parse().unwrap());
+        headers
+    }
+
+    fn header<'a>(headers: &'a [(String, String)], name: &str) -> Option<&'a str> {
+        headers
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    #[test]
+    fn test_listed_origin_is_echoed() {
+        let cors = CorsConfig::new().allow_origin("https://app.example.com");
+
+        let headers = cors.response_headers(&request("https://app.example.com"));
+        assert_eq!(
+            header(&headers, "Access-Control-Allow-Origin"),
+            Some("https://app.example.com")
+        );
+        assert_eq!(header(&headers, "Vary"), Some("Origin"));
+
+        assert!(
+            cors.response_headers(&request("https://evil.example"))
+                .is_empty()
+        );
+        assert!(cors.response_headers(&HeaderMap::new()).is_empty());
+    }
+
+    #[test]
+    fn test_preflight_headers() {
+        let cors = CorsConfig::new()
+            .allow_any_origin()
+            .allow_methods(["GET", "POST"])
+            .allow_headers(["authorization"])
+            .max_age(Duration::from_secs(600));
+
+        let mut request = request("https://app.example.com");
+        request.insert("access-control-request-method", "POST".parse().unwrap());
+        assert!(is_preflight("OPTIONS", &request));
+        assert!(!is_preflight("GET", &request));
+
+        let headers = cors.preflight_headers(&request);
+        assert_eq!(header(&headers, "Access-Control-Allow-Origin"), Some("*"));
+        assert_eq!(
+            header(&headers, "Access-Control-Allow-Methods"),
+            Some("GET, POST")
+        );
+        assert_eq!(
+            header(&headers, "Access-Control-Allow-Headers"),
+            Some("authorization")
+        );
+        assert_eq!(header(&headers, "Access-Control-Max-Age"), Some("600"));
+    }
+}
@@ -103,6 +103,9 @@ use thiserror::Error;
 /// - [`Handler`](Error::Handler): Handler execution errors
 /// - [`Extractor`](Error::Extractor): Type extraction errors
 /// - [`Custom`](Error::Custom): Application-defined errors
+/// - [`Timeout`](Error::Timeout): Handlers that exceeded the router's time limit
+/// - [`HandshakeTimeout`](Error::HandshakeTimeout): Clients too slow to complete the handshake
+/// - [`Coded`](Error::Coded): Machine-readable errors sent to clients as JSON
 ///
 /// # Examples
 ///
@@ -371,6 +374,80 @@ pub enum Error {
     /// ```
     #[error("Custom error: {0}")]
     Custom(String),
+
+    /// Handler did not finish in time.
+    ///
+    /// Returned for a message whose middleware and handler took longer than
+    /// the limit set with
+    /// [`Router::handler_timeout()`](crate::router::Router::handler_timeout).
+    /// The handler is cancelled and the error is reported like any other
+    /// handler error, so a custom
+    /// [`Router::error_handler()`](crate::router::Router::error_handler) can
+    /// choose the response.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    ///
+    /// # fn example() {
+    /// let router = Router::new().error_handler(|err, _conn| match err {
+    ///     Error::Timeout(_) => Some(Message::text("Server busy, try again")),
+    ///     other => Some(Message::text(format!("Error: {}", other))),
+    /// });
+    /// # }
+    /// ```
+    #[error("Handler timed out after {0:?}")]
+    Timeout(std::time::Duration),
+
+    /// Client did not complete the handshake in time.
+    ///
+    /// Returned when a new connection takes longer than
+    /// [`Router::handshake_timeout()`](crate::router::Router::handshake_timeout)
+    /// to send its request head, or to finish the TLS or WebSocket
+    /// handshake. The connection is dropped; if the request head never
+    /// arrived, the client is first sent `408 Request Timeout`. The router
+    /// logs the error, it never reaches handlers.
+    #[error("Handshake timed out after {0:?}")]
+    HandshakeTimeout(std::time::Duration),
+
+    /// Structured error with a status code and machine-readable kind.
+    ///
+    /// Unlike the other variants, which reach clients as plain text, a coded
+    /// error is sent as a JSON envelope by the router's default error
+    /// response:
+    ///
+    /// ```text
+    /// {"code":429,"kind":"rate_limited","message":"slow down"}
+    /// ```
+    ///
+    /// A `data` field is included when extra details are attached with
+    /// [`with_data()`](Error::with_data).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    ///
+    /// async fn send_message(text: String) -> Result<String> {
+    ///     if text.len() > 280 {
+    ///         return Err(Error::coded(413, "too_long", "Message is too long")
+    ///             .with_data(serde_json::json!({ "max": 280 })));
+    ///     }
+    ///     Ok(text)
+    /// }
+    /// ```
+    #[error("{kind} ({code}): {message}")]
+    Coded {
+        /// Status code, typically mirroring HTTP semantics
+        code: u16,
+        /// Stable, machine-readable error identifier
+        kind: String,
+        /// Human-readable description
+        message: String,
+        /// Optional structured details
+        data: Option<serde_json::Value>,
+    },
 }
 
 /// A type alias for `Result<T, Error>`.
@@ -545,6 +622,96 @@ impl Error {
     pub fn extractor<T: fmt::Display>(msg: T) -> Self {
         Error::Extractor(msg.to_string())
     }
+
+    /// Creates a structured error with a code, kind and message.
+    ///
+    /// This is a convenience method for creating [`Error::Coded`] variants.
+    /// Use this when clients need to react to the error programmatically.
+    ///
+    /// # Arguments
+    ///
+    /// * `code` - Status code, such as `429` or `404`
+    /// * `kind` - Machine-readable identifier, such as `"rate_limited"`
+    /// * `message` - Human-readable description
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    ///
+    /// async fn join_room(room: String) -> Result<String> {
+    ///     if room.is_empty() {
+    ///         return Err(Error::coded(400, "invalid_room", "Room name is required"));
+    ///     }
+    ///     Ok(format!("Joined {}", room))
+    /// }
+    /// ```
+    pub fn coded(code: u16, kind: impl Into<String>, message: impl fmt::Display) -> Self {
+        Error::Coded {
+            code,
+            kind: kind.into(),
+            message: message.to_string(),
+            data: None,
+        }
+    }
+
+    /// Attaches structured details to a [`Coded`](Error::Coded) error.
+    ///
+    /// Other variants are returned unchanged.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    ///
+    /// let err = Error::coded(429, "rate_limited", "Slow down")
+    ///     .with_data(serde_json::json!({ "retry_after": 5 }));
+    /// ```
+    pub fn with_data(mut self, value: serde_json::Value) -> Self {
+        if let Error::Coded { ref mut data, .. } = self {
+            *data = Some(value);
+        }
+        self
+    }
+
+    /// Returns the JSON envelope of a [`Coded`](Error::Coded) error.
+    ///
+    /// Returns `None` for other variants. This is what the router sends to
+    /// the client when no custom
+    /// [`error_handler`](crate::router::Router::error_handler) is set.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    ///
+    /// let err = Error::coded(429, "rate_limited", "Slow down");
+    /// assert_eq!(
+    ///     err.to_json().unwrap().to_string(),
+    ///     r#"{"code":429,"kind":"rate_limited","message":"Slow down"}"#
+    /// );
+    /// ```
+    pub fn to_json(&self) -> Option<serde_json::Value> {
+        let Error::Coded {
+            code,
+            kind,
+            message,
+            data,
+        } = self
+        else {
+            return None;
+        };
+
+        let mut envelope = serde_json::json!({
+            "code": code,
+            "kind": kind,
+            "message": message,
+        });
+        if let Some(data) = data {
+            envelope["data"] = data.clone();
+        }
+        Some(envelope)
+    }
 }
 
 #[cfg(test)]
@@ -621,4 +788,19 @@ mod tests {
             assert!(!display.is_empty());
         }
     }
+
+    #[test]
+    fn test_coded_error() {
+        let err = Error::coded(429, "rate_limited", "slow down");
+        assert_eq!(err.to_string(), "rate_limited (429): slow down");
+        assert_eq!(
+            err.to_json().unwrap().to_string(),
+            r#"{"code":429,"kind":"rate_limited","message":"slow down"}"#
+        );
+
+        let err = err.with_data(serde_json::json!({"retry_after": 5}));
+        assert_eq!(err.to_json().unwrap()["data"]["retry_after"], 5);
+
+        assert!(Error::custom("plain").to_json().is_none());
+    }
 }
@@ -27,13 +27,21 @@
 //! |-----------|-------------|---------|
 //! | [`Json<T>`] | Deserialize JSON from message | `Json(user): Json<User>` |
 //! | [`State<T>`] | Extract shared application state | `State(db): State<Arc<Database>>` |
+//! | [`Manager`] | Get the router's connection manager | `Manager(manager): Manager` |
 //! | [`Connection`] | Get the active connection | `conn: Connection` |
 //! | [`ConnectInfo`] | Get connection metadata | `ConnectInfo(info)` |
 //! | [`Message`] | Get raw message | `msg: Message` |
 //! | [`Data`] | Extract binary data | `Data(bytes)` |
+//! | [`Text`] | Extract text content | `Text(text)` |
+//! | `MsgPack<T>` | Deserialize MessagePack (`msgpack` feature) | `MsgPack(cmd): MsgPack<Cmd>` |
+//! | `Valid<T>` | Deserialize and validate JSON (`validator` feature) | `Valid(user): Valid<CreateUser>` |
 //! | [`Path<T>`] | Extract path parameters | `Path(id): Path<UserId>` |
 //! | [`Query<T>`] | Extract query parameters | `Query(params): Query<SearchParams>` |
 //! | [`Extension<T>`] | Extract custom extensions | `Extension(auth): Extension<Auth>` |
+//! | [`Headers`] | Get the handshake headers | `Headers(headers)` |
+//! | [`Header<T>`] | Get a single handshake header | `Header(UserAgent(ua)): Header<UserAgent>` |
+//! | `Option<T>` | Any extractor, `None` if it fails | `body: Option<Json<Cmd>>` |
+//! | `Result<T>` | Any extractor, with its error | `body: Result<Json<Cmd>>` |
 //!
 //! # Examples
 //!
@@ -122,14 +130,19 @@
 //! }
 //! ```
 
-use crate::connection::{Connection, ConnectionInfo};
+use crate::connection::{
+    BroadcastReport, Connection, ConnectionId, ConnectionInfo, ConnectionManager,
+};
 use crate::error::{Error, Result};
+use crate::handshake::HeaderMap;
 use crate::message::Message;
 use crate::state::AppState;
 use async_trait::async_trait;
 use dashmap::DashMap;
 use serde::Serialize;
-use serde::de::DeserializeOwned;
+use serde::de::value::{Error as DeError, SeqDeserializer};
+use serde::de::{DeserializeOwned, Deserializer, Error as _, IntoDeserializer, Visitor};
+use serde::forward_to_deserialize_any;
 use std::sync::Arc;
 
 /// Trait for types that can be extracted from WebSocket messages and context.
@@ -371,6 +384,66 @@ impl Extensions {
             .get(key)
             .and_then(|arc| arc.value().clone().downcast::<T>().ok())
     }
+
+    /// Removes a value from the extensions.
+    ///
+    /// Returns `true` if a value was stored under `key`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    ///
+    /// # fn example() {
+    /// let extensions = Extensions::new();
+    /// extensions.insert("count", 42_u32);
+    ///
+    /// assert!(extensions.remove("count"));
+    /// assert!(extensions.get::<u32>("count").is_none());
+    /// # }
+    /// ```
+    pub fn remove(&self, key: &str) -> bool {
+        self.data.remove(key).is_some()
+    }
+
+    /// Inserts a value keyed by its type.
+    ///
+    /// This is the key the [`Extension<T>`] extractor looks up, so use it
+    /// for values that handlers read with `Extension<T>`. A later value of
+    /// the same type replaces the earlier one.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    ///
+    /// #[derive(Clone)]
+    /// struct UserId(u64);
+    ///
+    /// # fn example() {
+    /// let extensions = Extensions::new();
+    /// extensions.insert_typed(UserId(42));
+    ///
+    /// assert_eq!(extensions.get_typed::<UserId>().unwrap().0, 42);
+    /// # }
+    /// ```
+    pub fn insert_typed<T: Send + Sync + 'static>(&self, value: T) {
+        self.insert(std::any::type_name::<T>(), value);
+    }
+
+    /// Retrieves a value stored with [`insert_typed()`](Self::insert_typed).
+    ///
+    /// Returns `None` if no value of type `T` was inserted.
+    pub fn get_typed<T: Send + Sync + 'static>(&self) -> Option<Arc<T>> {
+        self.get(std::any::type_name::<T>())
+    }
+
+    /// Copies every entry of `other` into this container, sharing the values.
+    pub(crate) fn extend_from(&self, other: &Extensions) {
+        for entry in other.data.iter() {
+            self.data.insert(entry.key().clone(), entry.value().clone());
+        }
+    }
 }
 
 impl Default for Extensions {
@@ -560,6 +633,268 @@ impl<T: Serialize> Json<T> {
     }
 }
 
+/// Extractor for MessagePack-encoded messages.
+///
+/// Deserializes the message payload with [`rmp_serde`]. Both binary and text
+/// messages are accepted, since only the raw bytes are read. Pair it with
+/// [`MsgPackResponse`](crate::handler::MsgPackResponse) to answer in
+/// MessagePack too.
+///
+/// Requires the `msgpack` feature.
+///
+/// # Errors
+///
+/// Returns an extractor error if the payload is not valid MessagePack for `T`.
+///
+/// # Examples
+///
+/// ```
+/// use wsforge::prelude::*;
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Deserialize)]
+/// struct Move {
+///     x: i32,
+///     y: i32,
+/// }
+///
+/// #[derive(Serialize)]
+/// struct Position {
+///     x: i32,
+///     y: i32,
+/// }
+///
+/// async fn move_player(MsgPack(m): MsgPack<Move>) -> Result<MsgPackResponse<Position>> {
+///     Ok(MsgPackResponse(Position { x: m.x, y: m.y }))
+/// }
+/// ```
+#[cfg(feature = "msgpack")]
+#[cfg_attr(docsrs, doc(cfg(feature = "msgpack")))]
+pub struct MsgPack<T>(pub T);
+
+#[cfg(feature = "msgpack")]
+#[async_trait]
+impl<T: DeserializeOwned + Send> FromMessage for MsgPack<T> {
+    async fn from_message(
+        message: &Message,
+        _conn: &Connection,
+        _state: &AppState,
+        _extensions: &Extensions,
+    ) -> Result<Self> {
+        rmp_serde::from_slice(message.as_bytes())
+            .map(MsgPack)
+            .map_err(|e| Error::extractor(format!("Invalid MessagePack: {}", e)))
+    }
+}
+
+/// Extractor for JSON messages that must pass validation.
+///
+/// Deserializes the message like [`Json<T>`], then runs `T`'s
+/// [`validator::Validate`] rules, such as string lengths and numeric ranges.
+/// Invalid messages never reach the handler.
+///
+/// Requires the `validator` feature. Derive `Validate` with the `derive`
+/// feature of the [`validator`] crate.
+///
+/// # Errors
+///
+/// Returns an error if the message isn't valid JSON for `T`, like
+/// [`Json<T>`]. If validation fails, returns a `422 validation_failed`
+/// [`Error::Coded`](crate::error::Error::Coded) error naming the invalid
+/// fields, with the violations of each field as its `data`:
+///
+/// ```text
+/// {"code":422,"kind":"validation_failed","message":"Invalid fields: age",
+///  "data":{"age":[{"code":"range","message":null,"params":{"max":150,"min":0,"value":300}}]}}
+/// ```
+///
+/// # Examples
+///
+/// ```
+/// use wsforge::prelude::*;
+/// use serde::Deserialize;
+/// use validator::Validate;
+///
+/// #[derive(Deserialize, Validate)]
+/// struct CreateUser {
+///     #[validate(length(min = 3, max = 32))]
+///     username: String,
+///     #[validate(range(min = 0, max = 150))]
+///     age: u32,
+/// }
+///
+/// // {"username": "al", "age": 300} is rejected before this runs
+/// async fn create_user(Valid(user): Valid<CreateUser>) -> Result<String> {
+///     Ok(format!("Created {}", user.username))
+/// }
+/// ```
+#[cfg(feature = "validator")]
+#[cfg_attr(docsrs, doc(cfg(feature = "validator")))]
+pub struct Valid<T>(pub T);
+
+#[cfg(feature = "validator")]
+#[async_trait]
+impl<T: DeserializeOwned + validator::Validate + Send> FromMessage for Valid<T> {
+    async fn from_message(
+        message: &Message,
+        _conn: &Connection,
+        _state: &AppState,
+        _extensions: &Extensions,
+    ) -> Result<Self> {
+        let data: T = message.json()?;
+        data.validate().map_err(|errors| {
+            let mut fields: Vec<&str> = errors.errors().keys().map(|f| f.as_ref()).collect();
+            fields.sort_unstable();
+
+            let details = serde_json::to_value(&errors).unwrap_or_default();
+            Error::coded(
+                422,
+                "validation_failed",
+                format!("Invalid fields: {}", fields.join(", ")),
+            )
+            .with_data(details)
+        })?;
+        Ok(Valid(data))
+    }
+}
+
+/// Extractor for the router's [`ConnectionManager`].
+///
+/// Gives handlers access to every connection of the router, for
+/// broadcasting, rooms and lookups. The router registers its manager when it
+/// is created, so this is always available to handlers and middleware run by
+/// a [`Router`](crate::router::Router).
+///
+/// # Errors
+///
+/// Returns an extractor error if the state holds no connection manager,
+/// which only happens when calling handlers outside a router.
+///
+/// # Examples
+///
+/// ```
+/// use wsforge::prelude::*;
+///
+/// async fn chat(msg: Message, conn: Connection, Manager(manager): Manager) -> Result<()> {
+///     manager.broadcast_except(conn.id(), msg);
+///     Ok(())
+/// }
+/// ```
+pub struct Manager(pub Arc<ConnectionManager>);
+
+#[async_trait]
+impl FromMessage for Manager {
+    async fn from_message(
+        _message: &Message,
+        _conn: &Connection,
+        state: &AppState,
+        _extensions: &Extensions,
+    ) -> Result<Self> {
+        state
+            .get::<ConnectionManager>()
+            .map(Manager)
+            .ok_or_else(|| Error::extractor("Connection manager not found"))
+    }
+}
+
+/// Extractor for broadcasting on behalf of the current connection.
+///
+/// Wraps the router's [`ConnectionManager`] together with the ID of the
+/// connection that sent the message, so handlers can broadcast without
+/// passing the sender's ID around. [`broadcast_except()`](Self::broadcast_except)
+/// always skips the right connection.
+///
+/// # Errors
+///
+/// Returns an extractor error if the state holds no connection manager,
+/// like [`Manager`].
+///
+/// # Examples
+///
+/// ```
+/// use wsforge::prelude::*;
+///
+/// async fn chat(msg: Message, bc: Broadcaster) -> Result<()> {
+///     // Everyone but the sender
+///     bc.broadcast_except(msg);
+///     Ok(())
+/// }
+///
+/// async fn shout(Text(text): Text, bc: Broadcaster) -> Result<()> {
+///     bc.to_room("lobby", Message::text(text.to_uppercase()));
+///     Ok(())
+/// }
+/// ```
+pub struct Broadcaster {
+    manager: Arc<ConnectionManager>,
+    sender: ConnectionId,
+}
+
+impl Broadcaster {
+    /// Sends a message to every connection, including the sender.
+    ///
+    /// See [`ConnectionManager::broadcast()`].
+    pub fn broadcast(&self, message: Message) -> BroadcastReport {
+        self.manager.broadcast(message)
+    }
+
+    /// Sends a message to every connection except the sender.
+    ///
+    /// See [`ConnectionManager::broadcast_except()`].
+    pub fn broadcast_except(&self, message: Message) -> BroadcastReport {
+        self.manager.broadcast_except(&self.sender, message)
+    }
+
+    /// Serializes `data` to JSON once and sends it to every connection
+    /// except the sender.
+    ///
+    /// See [`ConnectionManager::broadcast_json_except()`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Json`] if `data` can't be serialized.
+    pub fn broadcast_json_except<T: Serialize + ?Sized>(
+        &self,
+        data: &T,
+    ) -> Result<BroadcastReport> {
+        self.manager.broadcast_json_except(&self.sender, data)
+    }
+
+    /// Sends a message to every member of `room`, including the sender if
+    /// it has joined.
+    ///
+    /// See [`ConnectionManager::broadcast_to_room()`].
+    pub fn to_room(&self, room: &str, message: Message) -> BroadcastReport {
+        self.manager.broadcast_to_room(room, message)
+    }
+
+    /// Returns the ID of the connection that sent the message.
+    pub fn sender(&self) -> &ConnectionId {
+        &self.sender
+    }
+
+    /// Returns the underlying connection manager.
+    pub fn manager(&self) -> &Arc<ConnectionManager> {
+        &self.manager
+    }
+}
+
+#[async_trait]
+impl FromMessage for Broadcaster {
+    async fn from_message(
+        message: &Message,
+        conn: &Connection,
+        state: &AppState,
+        extensions: &Extensions,
+    ) -> Result<Self> {
+        let Manager(manager) = Manager::from_message(message, conn, state, extensions).await?;
+        Ok(Broadcaster {
+            manager,
+            sender: conn.id().clone(),
+        })
+    }
+}
+
 /// Extractor for the active connection.
 ///
 /// Provides access to the connection that sent the message, allowing you to:
@@ -603,6 +938,36 @@ impl FromMessage for Connection {
     }
 }
 
+/// Extractor for the whole application state.
+///
+/// [`State<T>`] is shorter for a single value. Take the [`AppState`] itself
+/// to read values stored under a name with
+/// [`AppState::insert_keyed()`], or to look values up conditionally.
+///
+/// # Examples
+///
+/// ```
+/// use wsforge::prelude::*;
+///
+/// struct DbPool;
+///
+/// async fn handler(state: AppState) -> Result<String> {
+///     let primary = state.get_keyed::<DbPool>("primary");
+///     Ok(format!("Primary configured: {}", primary.is_some()))
+/// }
+/// ```
+#[async_trait]
+impl FromMessage for AppState {
+    async fn from_message(
+        _message: &Message,
+        _conn: &Connection,
+        state: &AppState,
+        _extensions: &Extensions,
+    ) -> Result<Self> {
+        Ok(state.clone())
+    }
+}
+
 /// Extractor for connection metadata.
 ///
 /// Provides detailed information about the connection, including:
@@ -698,17 +1063,81 @@ impl FromMessage for Data {
         _state: &AppState,
         _extensions: &Extensions,
     ) -> Result<Self> {
-        Ok(Data(message.data.clone()))
+        Ok(Data(message.as_bytes().to_vec()))
     }
 }
 
+/// Extractor for text content.
+///
+/// Extracts the payload of a text message as an owned `String`. Binary and
+/// control frames are rejected with an extractor error. Use [`Data`] for
+/// binary payloads.
+///
+/// # Examples
+///
+/// ## Echo Text
+///
+/// ```
+/// use wsforge::prelude::*;
+///
+/// async fn shout(Text(text): Text) -> Result<String> {
+///     Ok(text.to_uppercase())
+/// }
+/// ```
+pub struct Text(pub String);
+
+#[async_trait]
+impl FromMessage for Text {
+    async fn from_message(
+        message: &Message,
+        _conn: &Connection,
+        _state: &AppState,
+        _extensions: &Extensions,
+    ) -> Result<Self> {
+        message
+            .as_text()
+            .map(|text| Text(text.to_string()))
+            .ok_or_else(|| Error::extractor("expected text"))
+    }
+}
+
+/// Extensions key holding the path of the WebSocket handshake URL.
+pub(crate) const REQUEST_PATH_KEY: &str = "request_path";
+
+/// Extensions key holding the raw query string of the WebSocket handshake URL.
+pub(crate) const REQUEST_QUERY_KEY: &str = "request_query";
+
+/// Extensions key holding the headers of the WebSocket handshake request.
+pub(crate) const REQUEST_HEADERS_KEY: &str = "request_headers";
+
 /// Extractor for path parameters.
 ///
-/// Extracts typed parameters from the request path. The type must implement
-/// `serde::Deserialize` and be stored in extensions by routing middleware.
+/// Extracts typed parameters from the request path. If routing middleware has
+/// stored parameters of type `T` in extensions under `"path_params"`, those
+/// are used. Otherwise `T` is deserialized from the path of the WebSocket
+/// handshake URL (for example `/game` for `ws://host/game?room=42`):
+///
+/// - a `String` receives the whole path, `/game`
+/// - a number, `bool` or `char` is parsed from a path with one segment, so
+///   `Path<u64>` accepts `/42`
+/// - a tuple or `Vec` receives each `/`-separated segment, so
+///   `Path<(String, u32)>` accepts `/rooms/7`
+///
+/// Newtypes around any of these work too. Structs with named fields can only
+/// be filled in by routing middleware.
 ///
 /// # Examples
 ///
+/// ## Handshake Path
+///
+/// ```
+/// use wsforge::prelude::*;
+///
+/// async fn where_am_i(Path(path): Path<String>) -> Result<String> {
+///     Ok(format!("Connected to {}", path))
+/// }
+/// ```
+///
 /// ## Single Parameter
 ///
 /// ```
@@ -718,18 +1147,31 @@ impl FromMessage for Data {
 /// #[derive(Deserialize)]
 /// struct UserId(u64);
 ///
+/// // ws://host/42
 /// async fn get_user(Path(UserId(id)): Path<UserId>) -> Result<String> {
 ///     Ok(format!("Getting user {}", id))
 /// }
 /// ```
 ///
-/// ## Multiple Parameters
+/// ## Path Segments
+///
+/// ```
+/// use wsforge::prelude::*;
+///
+/// // ws://host/rooms/7
+/// async fn join_room(Path((kind, id)): Path<(String, u32)>) -> Result<String> {
+///     Ok(format!("Joining {} {}", kind, id))
+/// }
+/// ```
+///
+/// ## Parameters From Middleware
 ///
 /// ```
 /// use wsforge::prelude::*;
 /// use serde::Deserialize;
 ///
-/// #[derive(Deserialize)]
+/// // Stored under "path_params" by routing middleware
+/// #[derive(Clone, Deserialize)]
 /// struct RoomParams {
 ///     room_id: String,
 ///     user_id: u64,
@@ -749,17 +1191,202 @@ impl<T: DeserializeOwned + Send + Sync + Clone + 'static> FromMessage for Path<T
         _state: &AppState,
         extensions: &Extensions,
     ) -> Result<Self> {
-        extensions
-            .get::<T>("path_params")
-            .ok_or_else(|| Error::extractor("Path parameters not found"))
-            .map(|arc| Path((*arc).clone()))
+        if let Some(params) = extensions.get::<T>("path_params") {
+            return Ok(Path((*params).clone()));
+        }
+
+        let path = extensions
+            .get::<String>(REQUEST_PATH_KEY)
+            .ok_or_else(|| Error::extractor("Path parameters not found"))?;
+
+        T::deserialize(PathDeserializer(path.as_str()))
+            .map(Path)
+            .map_err(|e| Error::extractor(format!("Invalid path parameters: {}", e)))
+    }
+}
+
+/// Deserializes a handshake path for [`Path`]: the whole path for strings,
+/// otherwise its `/`-separated segments.
+#[derive(Clone, Copy)]
+struct PathDeserializer<'a>(&'a str);
+
+/// Deserializes a single path segment, parsing it for non-string types.
+#[derive(Clone, Copy)]
+struct SegmentDeserializer<'a>(&'a str);
+
+impl<'a> PathDeserializer<'a> {
+    fn segments(self) -> impl Iterator<Item = SegmentDeserializer<'a>> {
+        self.0
+            .split('/')
+            .filter(|segment| !segment.is_empty())
+            .map(SegmentDeserializer)
+    }
+
+    /// Returns the path's only segment.
+    fn single(self) -> std::result::Result<SegmentDeserializer<'a>, DeError> {
+        let mut segments = self.segments();
+        match (segments.next(), segments.next()) {
+            (Some(segment), None) => Ok(segment),
+            _ => Err(DeError::custom(format!(
+                "expected one path segment in `{}`",
+                self.0
+            ))),
+        }
+    }
+}
+
+/// Implements the listed methods by parsing the path's only segment.
+macro_rules! single_segment {
+    ($($method:ident)*) => {$(
+        fn $method<V: Visitor<'de>>(self, visitor: V) -> std::result::Result<V::Value, DeError> {
+            self.single()?.$method(visitor)
+        }
+    )*};
+}
+
+/// Implements the listed methods by parsing the segment with `FromStr`.
+macro_rules! parse_segment {
+    ($($method:ident => $visit:ident)*) => {$(
+        fn $method<V: Visitor<'de>>(self, visitor: V) -> std::result::Result<V::Value, DeError> {
+            let value = self
+                .0
+                .parse()
+                .map_err(|_| DeError::custom(format!("invalid path segment `{}`", self.0)))?;
+            visitor.$visit(value)
+        }
+    )*};
+}
+
+impl<'de> Deserializer<'de> for PathDeserializer<'_> {
+    type Error = DeError;
+
+    fn deserialize_any<V: Visitor<'de>>(
+        self,
+        visitor: V,
+    ) -> std::result::Result<V::Value, DeError> {
+        visitor.visit_str(self.0)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(
+        self,
+        visitor: V,
+    ) -> std::result::Result<V::Value, DeError> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> std::result::Result<V::Value, DeError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(
+        self,
+        visitor: V,
+    ) -> std::result::Result<V::Value, DeError> {
+        let mut segments = SeqDeserializer::new(self.segments());
+        let value = visitor.visit_seq(&mut segments)?;
+        segments.end()?;
+        Ok(value)
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> std::result::Result<V::Value, DeError> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> std::result::Result<V::Value, DeError> {
+        self.deserialize_seq(visitor)
+    }
+
+    single_segment! {
+        deserialize_bool deserialize_char
+        deserialize_i8 deserialize_i16 deserialize_i32 deserialize_i64 deserialize_i128
+        deserialize_u8 deserialize_u16 deserialize_u32 deserialize_u64 deserialize_u128
+        deserialize_f32 deserialize_f64
+    }
+
+    forward_to_deserialize_any! {
+        str string bytes byte_buf unit unit_struct map struct enum identifier ignored_any
+    }
+}
+
+impl<'de> Deserializer<'de> for SegmentDeserializer<'_> {
+    type Error = DeError;
+
+    fn deserialize_any<V: Visitor<'de>>(
+        self,
+        visitor: V,
+    ) -> std::result::Result<V::Value, DeError> {
+        visitor.visit_str(self.0)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(
+        self,
+        visitor: V,
+    ) -> std::result::Result<V::Value, DeError> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> std::result::Result<V::Value, DeError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    parse_segment! {
+        deserialize_bool => visit_bool
+        deserialize_char => visit_char
+        deserialize_i8 => visit_i8
+        deserialize_i16 => visit_i16
+        deserialize_i32 => visit_i32
+        deserialize_i64 => visit_i64
+        deserialize_i128 => visit_i128
+        deserialize_u8 => visit_u8
+        deserialize_u16 => visit_u16
+        deserialize_u32 => visit_u32
+        deserialize_u64 => visit_u64
+        deserialize_u128 => visit_u128
+        deserialize_f32 => visit_f32
+        deserialize_f64 => visit_f64
+    }
+
+    forward_to_deserialize_any! {
+        str string bytes byte_buf unit unit_struct seq tuple tuple_struct map struct enum
+        identifier ignored_any
+    }
+}
+
+impl<'de> IntoDeserializer<'de, DeError> for SegmentDeserializer<'_> {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
     }
 }
 
 /// Extractor for query parameters.
 ///
-/// Extracts typed parameters from the query string. The type must implement
-/// `serde::Deserialize` and be stored in extensions during connection establishment.
+/// Extracts typed parameters from the query string of the WebSocket handshake
+/// URL, so a client connecting to `ws://host/game?room=42&name=bob` can be
+/// read with `Query<T>` for the whole connection. The type must implement
+/// `serde::Deserialize`. Parameters of type `T` stored in extensions under
+/// `"query_params"` by middleware take precedence.
+///
+/// A missing query string is treated as empty, so all-optional parameter
+/// types still extract successfully.
 ///
 /// # Examples
 ///
@@ -790,10 +1417,16 @@ impl<T: DeserializeOwned + Send + Sync + Clone + 'static> FromMessage for Query<
         _state: &AppState,
         extensions: &Extensions,
     ) -> Result<Self> {
-        extensions
-            .get::<T>("query_params")
-            .ok_or_else(|| Error::extractor("Query parameters not found"))
-            .map(|arc| Query((*arc).clone()))
+        if let Some(params) = extensions.get::<T>("query_params") {
+            return Ok(Query((*params).clone()));
+        }
+
+        let query = extensions.get::<String>(REQUEST_QUERY_KEY);
+        let query = query.as_deref().map_or("", String::as_str);
+
+        serde_urlencoded::from_str(query)
+            .map(Query)
+            .map_err(|e| Error::extractor(format!("Invalid query parameters: {}", e)))
     }
 }
 
@@ -801,8 +1434,46 @@ impl<T: DeserializeOwned + Send + Sync + Clone + 'static> FromMessage for Query<
 ///
 /// Retrieves data that was previously stored in extensions by middleware or other handlers.
 ///
+/// The value is looked up by its type, so it must have been stored with
+/// [`Extensions::insert_typed()`] or
+/// [`HandshakeDecision::extension()`](crate::handshake::HandshakeDecision::extension).
+/// Values stored with [`Extensions::insert()`] under a key of your own are
+/// not found; read those with [`Extensions::get()`] instead. Extraction
+/// fails if no value of type `T` was stored.
+///
 /// # Examples
 ///
+/// ## From Middleware
+///
+/// ```
+/// use wsforge::prelude::*;
+/// use async_trait::async_trait;
+///
+/// #[derive(Clone)]
+/// struct RequestId(String);
+///
+/// struct RequestIdMiddleware;
+///
+/// #[async_trait]
+/// impl Middleware for RequestIdMiddleware {
+///     async fn handle(
+///         &self,
+///         message: Message,
+///         conn: Connection,
+///         state: AppState,
+///         extensions: Extensions,
+///         mut next: Next,
+///     ) -> Result<Vec<Message>> {
+///         extensions.insert_typed(RequestId(format!("{}-{}", conn.id(), message.len())));
+///         next.run(message, conn, state, extensions).await
+///     }
+/// }
+///
+/// async fn handler(Extension(id): Extension<RequestId>) -> Result<String> {
+///     Ok(format!("Handling {}", id.0))
+/// }
+/// ```
+///
 /// ## Authentication Data
 ///
 /// ```
@@ -830,8 +1501,465 @@ impl<T: Send + Sync + Clone + 'static> FromMessage for Extension<T> {
         extensions: &Extensions,
     ) -> Result<Self> {
         extensions
-            .get::<T>(std::any::type_name::<T>())
+            .get_typed::<T>()
             .ok_or_else(|| Error::extractor("Extension not found"))
             .map(Extension)
     }
 }
+
+/// Extractor for the HTTP headers of the WebSocket handshake.
+///
+/// The headers are captured when the connection is upgraded, so every
+/// message of the connection sees the same headers. Use it for values like
+/// `User-Agent` or `Origin`, or for custom headers sent by the client. To
+/// read a single header, [`Header<T>`] is shorter.
+///
+/// Fails for connections that didn't go through the router's handshake.
+///
+/// # Examples
+///
+/// ```
+/// use wsforge::prelude::*;
+///
+/// async fn client_version(Headers(headers): Headers) -> Result<String> {
+///     let version = headers
+///         .get("x-client-version")
+///         .and_then(|v| v.to_str().ok())
+///         .unwrap_or("unknown");
+///
+///     Ok(format!("Client version {}", version))
+/// }
+/// ```
+pub struct Headers(pub HeaderMap);
+
+#[async_trait]
+impl FromMessage for Headers {
+    async fn from_message(
+        _message: &Message,
+        _conn: &Connection,
+        _state: &AppState,
+        extensions: &Extensions,
+    ) -> Result<Self> {
+        extensions
+            .get::<HeaderMap>(REQUEST_HEADERS_KEY)
+            .map(|headers| Headers((*headers).clone()))
+            .ok_or_else(|| Error::extractor("Handshake headers not found"))
+    }
+}
+
+/// A handshake header that can be read with [`Header<T>`].
+///
+/// Implement it for your own types to read custom headers. [`UserAgent`] and
+/// [`Origin`] are provided.
+///
+/// # Examples
+///
+/// ```
+/// use wsforge::prelude::*;
+///
+/// struct ClientVersion(u32);
+///
+/// impl NamedHeader for ClientVersion {
+///     const NAME: &'static str = "x-client-version";
+///
+///     fn decode(value: &str) -> Result<Self> {
+///         value
+///             .parse()
+///             .map(ClientVersion)
+///             .map_err(|_| Error::extractor("Invalid client version"))
+///     }
+/// }
+///
+/// async fn handler(Header(ClientVersion(version)): Header<ClientVersion>) -> Result<String> {
+///     if version < 3 {
+///         return Err(Error::custom("Please update your client"));
+///     }
+///     Ok("Welcome".to_string())
+/// }
+/// ```
+pub trait NamedHeader: Sized {
+    /// Name of the header, case-insensitive.
+    const NAME: &'static str;
+
+    /// Parses the header's value.
+    fn decode(value: &str) -> Result<Self>;
+}
+
+/// Extractor for a single handshake header.
+///
+/// Reads the header named by [`T::NAME`](NamedHeader::NAME) from the
+/// handshake request, like [`Headers`], and decodes it. Fails if the header is
+/// missing or isn't valid; wrap it in `Option` for optional headers.
+///
+/// # Examples
+///
+/// ```
+/// use wsforge::prelude::*;
+///
+/// async fn handler(
+///     Header(UserAgent(agent)): Header<UserAgent>,
+///     origin: Option<Header<Origin>>,
+/// ) -> Result<String> {
+///     let origin = origin.map_or("none".to_string(), |Header(Origin(o))| o);
+///     Ok(format!("{} from {}", agent, origin))
+/// }
+/// ```
+pub struct Header<T>(pub T);
+
+#[async_trait]
+impl<T: NamedHeader + Send> FromMessage for Header<T> {
+    async fn from_message(
+        _message: &Message,
+        _conn: &Connection,
+        _state: &AppState,
+        extensions: &Extensions,
+    ) -> Result<Self> {
+        let headers = extensions
+            .get::<HeaderMap>(REQUEST_HEADERS_KEY)
+            .ok_or_else(|| Error::extractor("Handshake headers not found"))?;
+
+        let value = headers
+            .get(T::NAME)
+            .ok_or_else(|| Error::extractor(format!("Missing header: {}", T::NAME)))?
+            .to_str()
+            .map_err(|_| Error::extractor(format!("Invalid header: {}", T::NAME)))?;
+
+        T::decode(value).map(Header)
+    }
+}
+
+/// The `User-Agent` header, for use with [`Header<T>`].
+#[derive(Debug, Clone)]
+pub struct UserAgent(pub String);
+
+impl NamedHeader for UserAgent {
+    const NAME: &'static str = "user-agent";
+
+    fn decode(value: &str) -> Result<Self> {
+        Ok(UserAgent(value.to_string()))
+    }
+}
+
+/// The `Origin` header, for use with [`Header<T>`].
+#[derive(Debug, Clone)]
+pub struct Origin(pub String);
+
+impl NamedHeader for Origin {
+    const NAME: &'static str = "origin";
+
+    fn decode(value: &str) -> Result<Self> {
+        Ok(Origin(value.to_string()))
+    }
+}
+
+/// Optional extraction.
+///
+/// Wrapping any extractor in `Option` makes it infallible: a failed extraction
+/// yields `None` instead of aborting the handler with an extractor error. Use
+/// this to handle malformed input yourself.
+///
+/// # Examples
+///
+/// ```
+/// use wsforge::prelude::*;
+/// use serde::Deserialize;
+///
+/// #[derive(Deserialize)]
+/// struct Command {
+///     action: String,
+/// }
+///
+/// async fn handler(cmd: Option<Json<Command>>) -> Result<String> {
+///     match cmd {
+///         Some(Json(cmd)) => Ok(format!("Running {}", cmd.action)),
+///         None => Ok("Expected a JSON command".to_string()),
+///     }
+/// }
+/// ```
+#[async_trait]
+impl<T: FromMessage + Send> FromMessage for Option<T> {
+    async fn from_message(
+        message: &Message,
+        conn: &Connection,
+        state: &AppState,
+        extensions: &Extensions,
+    ) -> Result<Self> {
+        Ok(T::from_message(message, conn, state, extensions).await.ok())
+    }
+}
+
+/// Fallible extraction.
+///
+/// Wrapping any extractor in `Result` hands the extraction error to the
+/// handler instead of aborting it, so the handler can inspect the failure.
+///
+/// # Examples
+///
+/// ```
+/// use wsforge::prelude::*;
+///
+/// async fn handler(body: Result<Json<serde_json::Value>>) -> Result<String> {
+///     match body {
+///         Ok(Json(value)) => Ok(format!("Got {}", value)),
+///         Err(e) => Ok(format!("Invalid JSON: {}", e)),
+///     }
+/// }
+/// ```
+#[async_trait]
+impl<T: FromMessage + Send> FromMessage for Result<T> {
+    async fn from_message(
+        message: &Message,
+        conn: &Connection,
+        state: &AppState,
+        extensions: &Extensions,
+    ) -> Result<Self> {
+        Ok(T::from_message(message, conn, state, extensions).await)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::SocketAddr;
+    use tokio::sync::mpsc;
+
+    async fn extract<T: FromMessage>(message: Message) -> Result<T> {
+        let (tx, _rx) = mpsc::unbounded_channel();
+        let addr: SocketAddr = "127.0.0.1:9000".parse().unwrap();
+        let conn = Connection::new("conn_test".to_string(), addr, tx);
+        T::from_message(&message, &conn, &AppState::new(), &Extensions::new()).await
+    }
+
+    #[tokio::test]
+    async fn test_manager_extractor() {
+        let (tx, _rx) = mpsc::unbounded_channel();
+        let addr: SocketAddr = "127.0.0.1:9000".parse().unwrap();
+        let conn = Connection::new("conn_test".to_string(), addr, tx);
+        let manager = Arc::new(ConnectionManager::new());
+        manager.add(conn.clone());
+
+        let state = AppState::new();
+        state.insert(manager);
+        let Manager(extracted) =
+            Manager::from_message(&Message::text("hi"), &conn, &state, &Extensions::new())
+                .await
+                .unwrap();
+        assert_eq!(extracted.count(), 1);
+
+        let err = extract::<Manager>(Message::text("hi")).await;
+        assert!(matches!(err, Err(Error::Extractor(_))));
+    }
+
+    #[tokio::test]
+    async fn test_broadcaster_skips_sender() {
+        let addr: SocketAddr = "127.0.0.1:9000".parse().unwrap();
+        let (tx, mut sender_rx) = mpsc::unbounded_channel();
+        let sender = Connection::new("sender".to_string(), addr, tx);
+        let (tx, mut other_rx) = mpsc::unbounded_channel();
+        let manager = Arc::new(ConnectionManager::new());
+        manager.add(sender.clone());
+        manager.add(Connection::new("other".to_string(), addr, tx));
+
+        let state = AppState::new();
+        state.insert(manager);
+        let bc =
+            Broadcaster::from_message(&Message::text("hi"), &sender, &state, &Extensions::new())
+                .await
+                .unwrap();
+        assert_eq!(bc.sender(), "sender");
+
+        bc.broadcast_except(Message::text("others"));
+        assert_eq!(other_rx.try_recv().unwrap().as_text(), Some("others"));
+        assert!(sender_rx.try_recv().is_err());
+
+        bc.broadcast(Message::text("all"));
+        assert_eq!(other_rx.try_recv().unwrap().as_text(), Some("all"));
+        assert_eq!(sender_rx.try_recv().unwrap().as_text(), Some("all"));
+    }
+
+    #[tokio::test]
+    async fn test_option_extractor_absorbs_failure() {
+        let value = extract::<Option<Json<u32>>>(Message::text("not json"))
+            .await
+            .unwrap();
+        assert!(value.is_none());
+
+        let value = extract::<Option<Json<u32>>>(Message::text("7"))
+            .await
+            .unwrap();
+        assert_eq!(value.map(|Json(n)| n), Some(7));
+    }
+
+    #[tokio::test]
+    async fn test_result_extractor_exposes_error() {
+        let value = extract::<Result<Json<u32>>>(Message::text("not json"))
+            .await
+            .unwrap();
+        assert!(value.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_extension_reads_typed_insert() {
+        let (tx, _rx) = mpsc::unbounded_channel();
+        let addr: SocketAddr = "127.0.0.1:9000".parse().unwrap();
+        let conn = Connection::new("conn_test".to_string(), addr, tx);
+        let extensions = Extensions::new();
+        let message = Message::text("hi");
+
+        let missing =
+            Extension::<u32>::from_message(&message, &conn, &AppState::new(), &extensions).await;
+        assert!(missing.is_err());
+
+        extensions.insert_typed(7u32);
+        let Extension(value) =
+            Extension::<u32>::from_message(&message, &conn, &AppState::new(), &extensions)
+                .await
+                .unwrap();
+        assert_eq!(*value, 7);
+    }
+
+    #[tokio::test]
+    async fn test_header_extractors() {
+        let (tx, _rx) = mpsc::unbounded_channel();
+        let addr: SocketAddr = "127.0.0.1:9000".parse().unwrap();
+        let conn = Connection::new("conn_test".to_string(), addr, tx);
+        let state = AppState::new();
+        let message = Message::text("hi");
+
+        let mut headers = HeaderMap::new();
+        headers.insert("user-agent", "test-client/1.0".parse().unwrap());
+        let extensions = Extensions::new();
+        extensions.insert(REQUEST_HEADERS_KEY, headers);
+
+        let Headers(headers) = Headers::from_message(&message, &conn, &state, &extensions)
+            .await
+            .unwrap();
+        assert_eq!(headers.len(), 1);
+
+        let Header(UserAgent(agent)) =
+            Header::<UserAgent>::from_message(&message, &conn, &state, &extensions)
+                .await
+                .unwrap();
+        assert_eq!(agent, "test-client/1.0");
+
+        let origin = Header::<Origin>::from_message(&message, &conn, &state, &extensions).await;
+        assert!(matches!(origin, Err(Error::Extractor(_))));
+
+        // Connections that didn't go through a handshake have no headers
+        assert!(extract::<Headers>(message).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_path_extractor_parses_segments() {
+        #[derive(Clone, serde::Deserialize)]
+        struct UserId(u64);
+
+        async fn extract_path<T: DeserializeOwned + Send + Sync + Clone + 'static>(
+            path: &str,
+        ) -> Result<T> {
+            let (tx, _rx) = mpsc::unbounded_channel();
+            let addr: SocketAddr = "127.0.0.1:9000".parse().unwrap();
+            let conn = Connection::new("conn_test".to_string(), addr, tx);
+            let extensions = Extensions::new();
+            extensions.insert(REQUEST_PATH_KEY, path.to_string());
+            let Path(value) =
+                Path::<T>::from_message(&Message::text("hi"), &conn, &AppState::new(), &extensions)
+                    .await?;
+            Ok(value)
+        }
+
+        assert_eq!(
+            extract_path::<String>("/rooms/7").await.unwrap(),
+            "/rooms/7"
+        );
+        assert_eq!(extract_path::<u64>("/42").await.unwrap(), 42);
+        assert_eq!(extract_path::<UserId>("/42").await.unwrap().0, 42);
+        assert_eq!(
+            extract_path::<(String, u32)>("/rooms/7").await.unwrap(),
+            ("rooms".to_string(), 7)
+        );
+        assert_eq!(
+            extract_path::<Vec<u8>>("/1/2/3").await.unwrap(),
+            vec![1, 2, 3]
+        );
+
+        assert!(extract_path::<u64>("/abc").await.is_err());
+        assert!(extract_path::<u64>("/rooms/7").await.is_err());
+        assert!(extract_path::<(String, u32)>("/rooms").await.is_err());
+        assert!(extract_path::<(String, u32)>("/rooms/7/8").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_text_extractor() {
+        let Text(text) = extract::<Text>(Message::text("hello")).await.unwrap();
+        assert_eq!(text, "hello");
+
+        let err = extract::<Text>(Message::binary(vec![1, 2, 3])).await;
+        assert!(matches!(err, Err(Error::Extractor(_))));
+
+        let err = extract::<Text>(Message::ping(vec![])).await;
+        assert!(matches!(err, Err(Error::Extractor(_))));
+    }
+
+    #[cfg(feature = "msgpack")]
+    #[tokio::test]
+    async fn test_msgpack_extractor() {
+        let bytes = rmp_serde::to_vec(&("move", 3u32)).unwrap();
+        let MsgPack((action, steps)) = extract::<MsgPack<(String, u32)>>(Message::binary(bytes))
+            .await
+            .unwrap();
+        assert_eq!(action, "move");
+        assert_eq!(steps, 3);
+
+        let err = extract::<MsgPack<u32>>(Message::binary(vec![0xc1])).await;
+        assert!(matches!(err, Err(Error::Extractor(_))));
+    }
+
+    #[cfg(feature = "validator")]
+    #[tokio::test]
+    async fn test_valid_extractor() {
+        use validator::{Validate, ValidationError, ValidationErrors};
+
+        #[derive(serde::Deserialize)]
+        struct CreateUser {
+            name: String,
+            age: u32,
+        }
+
+        impl Validate for CreateUser {
+            fn validate(&self) -> std::result::Result<(), ValidationErrors> {
+                let mut errors = ValidationErrors::new();
+                if self.name.is_empty() {
+                    errors.add("name", ValidationError::new("length"));
+                }
+                if self.age > 150 {
+                    errors.add("age", ValidationError::new("range"));
+                }
+                if errors.is_empty() {
+                    Ok(())
+                } else {
+                    Err(errors)
+                }
+            }
+        }
+
+        let Valid(user) = extract::<Valid<CreateUser>>(Message::text(r#"{"name":"Ada","age":36}"#))
+            .await
+            .unwrap();
+        assert_eq!(user.name, "Ada");
+
+        let Err(err) =
+            extract::<Valid<CreateUser>>(Message::text(r#"{"name":"","age":300}"#)).await
+        else {
+            panic!("invalid user was accepted");
+        };
+        let envelope = err.to_json().unwrap();
+        assert_eq!(envelope["code"], 422);
+        assert_eq!(envelope["message"], "Invalid fields: age, name");
+        assert_eq!(envelope["data"]["age"][0]["code"], "range");
+
+        // Malformed JSON fails like Json<T>
+        let err = extract::<Valid<CreateUser>>(Message::text("{")).await;
+        assert!(matches!(err, Err(Error::Json(_))));
+    }
+}
@@ -51,6 +51,12 @@
 //! | `Message` | Raw message | `async fn handler() -> Result<Message>` |
 //! | `Vec<u8>` | Binary message | `async fn handler() -> Result<Vec<u8>>` |
 //! | `JsonResponse<T>` | JSON response | `async fn handler() -> Result<JsonResponse<T>>` |
+//! | `PrettyJsonResponse<T>` | Indented JSON response | `async fn handler() -> Result<PrettyJsonResponse<T>>` |
+//! | `MsgPackResponse<T>` | MessagePack binary response (`msgpack` feature) | `async fn handler() -> Result<MsgPackResponse<T>>` |
+//! | `Vec<Message>` | Several messages, in order | `async fn handler() -> Result<Vec<Message>>` |
+//! | `Messages` | Several messages, in order | `async fn handler() -> Result<Messages>` |
+//! | `MessageStream<S>` | Messages pushed over time | `async fn handler() -> Result<MessageStream<S>>` |
+//! | `CloseResponse` | Closes the connection | `async fn handler() -> Result<CloseResponse>` |
 //! | `Result<T>` | Automatic error handling | Any of above wrapped in `Result` |
 //!
 //! # Examples
@@ -108,14 +114,16 @@
 //! ```
 
 use crate::connection::Connection;
-use crate::error::Result;
+use crate::error::{Error, Result};
 use crate::extractor::{Extensions, FromMessage};
 use crate::message::Message;
 use crate::state::AppState;
 use async_trait::async_trait;
+use futures_util::{Stream, StreamExt};
 use serde::Serialize;
 use std::marker::PhantomData;
 use std::sync::Arc;
+use tracing::{Instrument, debug, warn};
 
 /// Trait for converting handler return values into WebSocket messages.
 ///
@@ -131,6 +139,9 @@ use std::sync::Arc;
 /// - `&str` - Sent as text message
 /// - `Message` - Sent as-is
 /// - `Vec<u8>` - Sent as binary message
+/// - `Vec<Message>` and [`Messages`] - Each message sent in order
+/// - [`MessageStream`] - Each message sent as the stream yields it
+/// - [`CloseResponse`] - Closes the connection with a close code
 /// - `Result<T>` - Automatically handles errors
 ///
 /// # Examples
@@ -184,6 +195,57 @@ pub trait IntoResponse: Send {
     /// # }
     /// ```
     async fn into_response(self) -> Result<Option<Message>>;
+
+    /// Converts this value into the messages to send back, in order.
+    ///
+    /// Handlers use this method, so a single return value can produce several
+    /// messages. The default implementation returns the message from
+    /// [`into_response()`](Self::into_response), if any. Only types that
+    /// produce several messages, such as [`Messages`], need to override it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    ///
+    /// # async fn example() -> Result<()> {
+    /// let messages = "Hello".into_responses().await?;
+    /// assert_eq!(messages.len(), 1);
+    /// # Ok(())
+    /// # }
+    /// ```
+    async fn into_responses(self) -> Result<Vec<Message>>
+    where
+        Self: Sized,
+    {
+        Ok(self.into_response().await?.into_iter().collect())
+    }
+
+    /// Returns `true` if the messages this value produces are JSON documents.
+    ///
+    /// [`Router::rpc()`](crate::router::Router::rpc) embeds such responses in
+    /// its `result` field as JSON, and wraps any other text as a JSON string,
+    /// so text that merely looks like JSON keeps its type. The default
+    /// implementation returns `false`; [`JsonResponse`] and its variants
+    /// return `true`.
+    fn is_json(&self) -> bool {
+        false
+    }
+
+    /// Delivers this value to the connection that sent the message.
+    ///
+    /// Handlers use this method. The returned messages travel back through
+    /// the middleware chain, and the router sends them to `conn` in order.
+    /// The default implementation returns the messages from
+    /// [`into_responses()`](Self::into_responses). Only types that deliver
+    /// messages some other way, such as [`MessageStream`], need to override
+    /// it.
+    async fn send_to(self, _conn: &Connection) -> Result<Vec<Message>>
+    where
+        Self: Sized,
+    {
+        self.into_responses().await
+    }
 }
 
 /// Response that sends nothing back to the client.
@@ -286,10 +348,231 @@ impl IntoResponse for Vec<u8> {
     }
 }
 
+/// Response that sends several messages, in order.
+///
+/// Use this when a handler needs to reply with more than one message, for
+/// example an acknowledgement followed by a batch of events. Each message is
+/// sent back to the client in order. An empty `Messages` sends nothing.
+///
+/// A plain `Vec<Message>` behaves the same way; `Messages` makes the intent
+/// explicit in handler signatures. All messages go back through the
+/// middleware chain together, so middleware sees and can transform each one.
+///
+/// [`IntoResponse::into_response()`] can only return a single message, so
+/// it fails for more than one message rather than dropping any. Handlers
+/// always use [`IntoResponse::into_responses()`].
+///
+/// # Examples
+///
+/// ```
+/// use wsforge::prelude::*;
+///
+/// async fn subscribe_handler(msg: Message) -> Result<Messages> {
+///     Ok(Messages(vec![
+///         Message::text(r#"{"type":"ack"}"#),
+///         Message::text(r#"{"type":"event","id":1}"#),
+///         Message::text(r#"{"type":"event","id":2}"#),
+///     ]))
+/// }
+/// ```
+pub struct Messages(pub Vec<Message>);
+
+#[async_trait]
+impl IntoResponse for Messages {
+    async fn into_response(self) -> Result<Option<Message>> {
+        self.0.into_response().await
+    }
+
+    async fn into_responses(self) -> Result<Vec<Message>> {
+        Ok(self.0)
+    }
+}
+
+/// Response that sends several messages, in order.
+///
+/// See [`Messages`].
+///
+/// # Examples
+///
+/// ```
+/// use wsforge::prelude::*;
+///
+/// async fn handler(msg: Message) -> Result<Vec<Message>> {
+///     Ok(vec![Message::text("ack"), msg])
+/// }
+/// ```
+#[async_trait]
+impl IntoResponse for Vec<Message> {
+    async fn into_response(mut self) -> Result<Option<Message>> {
+        if self.len() > 1 {
+            return Err(Error::custom(format!(
+                "{} messages can't be converted into a single response",
+                self.len()
+            )));
+        }
+        Ok(self.pop())
+    }
+
+    async fn into_responses(self) -> Result<Vec<Message>> {
+        Ok(self)
+    }
+}
+
+/// Response that pushes messages to the client as a stream yields them.
+///
+/// Use this for subscriptions, such as a live price feed, where a single
+/// request is answered with messages over time. The router forwards each
+/// message to the connection that sent the request until the stream ends
+/// or the connection closes; on disconnect the stream is dropped, so it
+/// stops being polled and any resources it holds are released.
+///
+/// The stream runs in its own task, so the handler returns right away and
+/// later messages from the client are handled while the stream is running.
+/// A stream item that is an error is logged and ends the stream.
+///
+/// When converted with [`IntoResponse::into_response()`], which can only
+/// return a single message, the first message is returned and the rest of
+/// the stream is dropped. Handlers always stream.
+///
+/// # Examples
+///
+/// ```
+/// use wsforge::prelude::*;
+/// use futures_util::stream::{self, Stream};
+/// use std::time::Duration;
+///
+/// async fn subscribe_prices(
+///     Json(symbol): Json<String>,
+/// ) -> Result<MessageStream<impl Stream<Item = Result<Message>>>> {
+///     let ticks = stream::unfold(0u64, move |tick| {
+///         let symbol = symbol.clone();
+///         async move {
+///             tokio::time::sleep(Duration::from_secs(1)).await;
+///             let price = serde_json::json!({ "symbol": symbol, "tick": tick });
+///             Some((Message::builder().json(&price).build(), tick + 1))
+///         }
+///     });
+///     Ok(MessageStream(ticks))
+/// }
+/// ```
+pub struct MessageStream<S>(pub S);
+
+#[async_trait]
+impl<S> IntoResponse for MessageStream<S>
+where
+    S: Stream<Item = Result<Message>> + Send + 'static,
+{
+    async fn into_response(self) -> Result<Option<Message>> {
+        let mut stream = Box::pin(self.0);
+        stream.next().await.transpose()
+    }
+
+    async fn send_to(self, conn: &Connection) -> Result<Vec<Message>> {
+        let conn = conn.clone();
+        let stream = self.0;
+        tokio::spawn(
+            async move {
+                let mut stream = Box::pin(stream);
+                loop {
+                    let item = tokio::select! {
+                        item = stream.next() => item,
+                        _ = conn.closed() => {
+                            debug!("Connection {} closed, dropping message stream", conn.id());
+                            break;
+                        }
+                    };
+                    match item {
+                        Some(Ok(message)) => {
+                            if conn.send(message).is_err() {
+                                break;
+                            }
+                        }
+                        Some(Err(e)) => {
+                            warn!("Message stream for {} failed: {}", conn.id(), e);
+                            break;
+                        }
+                        None => break,
+                    }
+                }
+            }
+            .in_current_span(),
+        );
+        Ok(Vec::new())
+    }
+}
+
+/// Response that closes the connection with a close code and reason.
+///
+/// Use this when a handler detects a protocol violation, such as a message
+/// before authentication, and the client should be disconnected rather
+/// than sent an error. The close frame goes back through the middleware
+/// chain like any response; the router then closes the connection as
+/// [`Connection::close()`] does, after any messages already queued.
+///
+/// Any close message returned from a handler or middleware, e.g. one
+/// built with [`Message::close_with()`], closes the connection the same
+/// way. Handlers that close only sometimes can return [`Message`] and
+/// convert a `CloseResponse` into it.
+///
+/// # Examples
+///
+/// ## Always Closing
+///
+/// ```
+/// use wsforge::prelude::*;
+///
+/// async fn deprecated_handler() -> Result<CloseResponse> {
+///     Ok(CloseResponse::new(1008, "Use /v2 instead"))
+/// }
+/// ```
+///
+/// ## Closing on Violation
+///
+/// ```
+/// use wsforge::prelude::*;
+///
+/// async fn command_handler(conn: Connection) -> Result<Message> {
+///     if conn.get_meta::<String>("user").is_none() {
+///         // 1008 = policy violation
+///         return Ok(CloseResponse::new(1008, "Authenticate first").into());
+///     }
+///     Ok(Message::text("ok"))
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CloseResponse(pub u16, pub String);
+
+impl CloseResponse {
+    /// Creates a close response with the given close code and reason.
+    ///
+    /// The code is one of the status codes defined in
+    /// [RFC 6455 §7.4](https://www.rfc-editor.org/rfc/rfc6455#section-7.4),
+    /// or an application code from 4000 to 4999.
+    pub fn new(code: u16, reason: impl Into<String>) -> Self {
+        Self(code, reason.into())
+    }
+}
+
+impl From<CloseResponse> for Message {
+    fn from(close: CloseResponse) -> Self {
+        Message::close_with(close.0, close.1)
+    }
+}
+
+#[async_trait]
+impl IntoResponse for CloseResponse {
+    async fn into_response(self) -> Result<Option<Message>> {
+        Ok(Some(self.into()))
+    }
+}
+
 /// Automatic error handling for handler results.
 ///
-/// When a handler returns `Result<T>`, errors are automatically converted
-/// to error messages sent back to the client.
+/// When a handler returns `Result<T>`, errors are passed on to the router,
+/// which converts them into a response with its error handler. By default the
+/// client receives a text message of the form `Error: {error}`; see
+/// [`Router::error_handler()`](crate::router::Router::error_handler) to change
+/// this.
 ///
 /// # Examples
 ///
@@ -312,15 +595,40 @@ impl<T: IntoResponse> IntoResponse for Result<T> {
     async fn into_response(self) -> Result<Option<Message>> {
         match self {
             Ok(resp) => resp.into_response().await,
-            Err(e) => Ok(Some(Message::text(format!("Error: {}", e)))),
+            Err(e) => Err(e),
         }
     }
+
+    async fn send_to(self, conn: &Connection) -> Result<Vec<Message>> {
+        match self {
+            Ok(resp) => resp.send_to(conn).await,
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn into_responses(self) -> Result<Vec<Message>> {
+        match self {
+            Ok(resp) => resp.into_responses().await,
+            Err(e) => Err(e),
+        }
+    }
+
+    fn is_json(&self) -> bool {
+        self.as_ref().is_ok_and(IntoResponse::is_json)
+    }
 }
 
 /// JSON response wrapper.
 ///
 /// Automatically serializes data to JSON and sends it as a text message.
-/// The type must implement `serde::Serialize`.
+/// The type must implement `serde::Serialize`. Use
+/// [`pretty()`](JsonResponse::pretty) for indented output while debugging.
+///
+/// WebSocket frames carry no content type, so clients can't tell a JSON
+/// frame from other text on the wire. If your protocol mixes formats,
+/// negotiate one with a subprotocol (see
+/// [`HandshakeDecision::protocol()`](crate::handshake::HandshakeDecision::protocol))
+/// or use a typed envelope such as a `"type"` field.
 ///
 /// # Examples
 ///
@@ -360,12 +668,172 @@ impl<T: IntoResponse> IntoResponse for Result<T> {
 /// ```
 pub struct JsonResponse<T: Serialize>(pub T);
 
+impl<T: Serialize> JsonResponse<T> {
+    /// Serializes the response as indented, human-readable JSON.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    ///
+    /// async fn debug_handler() -> Result<PrettyJsonResponse<serde_json::Value>> {
+    ///     Ok(JsonResponse(serde_json::json!({ "users": 42 })).pretty())
+    /// }
+    /// ```
+    pub fn pretty(self) -> PrettyJsonResponse<T> {
+        PrettyJsonResponse(self.0)
+    }
+
+    /// Sends the JSON in a binary frame instead of a text frame.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    ///
+    /// async fn state_handler() -> Result<BinaryJsonResponse<serde_json::Value>> {
+    ///     Ok(JsonResponse(serde_json::json!({ "score": 10 })).binary())
+    /// }
+    /// ```
+    pub fn binary(self) -> BinaryJsonResponse<T> {
+        BinaryJsonResponse(self.0)
+    }
+}
+
 #[async_trait]
 impl<T: Serialize + Send> IntoResponse for JsonResponse<T> {
     async fn into_response(self) -> Result<Option<Message>> {
         let json = serde_json::to_string(&self.0)?;
         Ok(Some(Message::text(json)))
     }
+
+    fn is_json(&self) -> bool {
+        true
+    }
+}
+
+/// Pretty-printed JSON response wrapper.
+///
+/// Like [`JsonResponse`], but serializes with
+/// [`serde_json::to_string_pretty`]. Useful on development servers where
+/// responses are read by people. Usually created with
+/// [`JsonResponse::pretty()`].
+///
+/// # Examples
+///
+/// ```
+/// use wsforge::prelude::*;
+/// use serde::Serialize;
+///
+/// #[derive(Serialize)]
+/// struct Stats {
+///     users: u32,
+/// }
+///
+/// async fn stats_handler() -> Result<PrettyJsonResponse<Stats>> {
+///     Ok(PrettyJsonResponse(Stats { users: 42 }))
+/// }
+/// ```
+pub struct PrettyJsonResponse<T: Serialize>(pub T);
+
+#[async_trait]
+impl<T: Serialize + Send> IntoResponse for PrettyJsonResponse<T> {
+    async fn into_response(self) -> Result<Option<Message>> {
+        let json = serde_json::to_string_pretty(&self.0)?;
+        Ok(Some(Message::text(json)))
+    }
+
+    fn is_json(&self) -> bool {
+        true
+    }
+}
+
+/// JSON response wrapper that sends a binary frame.
+///
+/// Like [`JsonResponse`], but for clients that expect JSON in binary frames.
+/// Usually created with [`JsonResponse::binary()`].
+///
+/// Responses don't see the connection, so to pick the frame type from the
+/// negotiated subprotocol, check it in the handler and build the message with
+/// [`Message::json_binary()`].
+///
+/// # Examples
+///
+/// ## Always Binary
+///
+/// ```
+/// use wsforge::prelude::*;
+///
+/// async fn state_handler() -> Result<BinaryJsonResponse<serde_json::Value>> {
+///     Ok(BinaryJsonResponse(serde_json::json!({ "score": 10 })))
+/// }
+/// ```
+///
+/// ## By Subprotocol
+///
+/// ```
+/// use wsforge::prelude::*;
+///
+/// async fn state_handler(conn: Connection) -> Result<Message> {
+///     let state = serde_json::json!({ "score": 10 });
+///     match conn.info().protocol.as_deref() {
+///         Some("game.binary") => Message::json_binary(&state),
+///         _ => Ok(Message::text(state.to_string())),
+///     }
+/// }
+/// ```
+pub struct BinaryJsonResponse<T: Serialize>(pub T);
+
+#[async_trait]
+impl<T: Serialize + Send> IntoResponse for BinaryJsonResponse<T> {
+    async fn into_response(self) -> Result<Option<Message>> {
+        Ok(Some(Message::json_binary(&self.0)?))
+    }
+
+    fn is_json(&self) -> bool {
+        true
+    }
+}
+
+/// MessagePack response wrapper.
+///
+/// Serializes data with [`rmp_serde`] and sends it as a binary message.
+/// Structs are encoded as maps with field names, so clients can decode them
+/// without knowing the field order.
+///
+/// Requires the `msgpack` feature.
+///
+/// # Examples
+///
+/// ```
+/// use wsforge::prelude::*;
+/// use serde::Serialize;
+///
+/// #[derive(Serialize)]
+/// struct Snapshot {
+///     tick: u64,
+///     players: Vec<(f32, f32)>,
+/// }
+///
+/// async fn snapshot_handler() -> Result<MsgPackResponse<Snapshot>> {
+///     Ok(MsgPackResponse(Snapshot {
+///         tick: 42,
+///         players: vec![(1.0, 2.0)],
+///     }))
+/// }
+/// ```
+#[cfg(feature = "msgpack")]
+#[cfg_attr(docsrs, doc(cfg(feature = "msgpack")))]
+pub struct MsgPackResponse<T: Serialize>(pub T);
+
+#[cfg(feature = "msgpack")]
+#[async_trait]
+impl<T: Serialize + Send> IntoResponse for MsgPackResponse<T> {
+    async fn into_response(self) -> Result<Option<Message>> {
+        let bytes = rmp_serde::to_vec_named(&self.0)
+            .map_err(|e| Error::custom(format!("MessagePack serialization failed: {}", e)))?;
+        Ok(Some(Message::binary(bytes)))
+    }
 }
 
 /// Core trait for message handlers.
@@ -396,7 +864,7 @@ impl<T: Serialize + Send> IntoResponse for JsonResponse<T> {
 /// ```
 #[async_trait]
 pub trait Handler: Send + Sync + 'static {
-    /// Processes a message and returns an optional response.
+    /// Processes a message and returns the responses to send back.
     ///
     /// This method is called by the framework when a message is received.
     /// It extracts the required data and executes the handler logic.
@@ -410,8 +878,8 @@ pub trait Handler: Send + Sync + 'static {
     ///
     /// # Returns
     ///
-    /// - `Ok(Some(message))` - Send this message back to the client
-    /// - `Ok(None)` - Don't send any response
+    /// - `Ok(messages)` - Send these messages back to the client, in order;
+    ///   an empty `Vec` sends nothing
     /// - `Err(error)` - An error occurred during processing
     async fn call(
         &self,
@@ -419,7 +887,7 @@ pub trait Handler: Send + Sync + 'static {
         conn: Connection,
         state: AppState,
         extensions: Extensions,
-    ) -> Result<Option<Message>>;
+    ) -> Result<Vec<Message>>;
 }
 
 /// Service wrapper for handler functions.
@@ -460,6 +928,14 @@ impl<F, T> HandlerService<F, T> {
     }
 }
 
+/// Extensions key set when a handler function itself returned an error, as
+/// opposed to one of its extractors or a middleware.
+pub(crate) const HANDLER_ERROR_KEY: &str = "handler_error";
+
+/// Extensions key set when a handler function returned a JSON response, see
+/// [`IntoResponse::is_json()`].
+pub(crate) const JSON_RESPONSE_KEY: &str = "json_response";
+
 // Macro to implement Handler trait for various argument counts
 macro_rules! impl_handler {
     (
@@ -480,13 +956,20 @@ macro_rules! impl_handler {
                 _conn: Connection,
                 _state: AppState,
                 _extensions: Extensions,
-            ) -> Result<Option<Message>> {
+            ) -> Result<Vec<Message>> {
                 $(
                     let $ty = $ty::from_message(&_message, &_conn, &_state, &_extensions).await?;
                 )*
 
                 let response = (self.handler)($($ty,)*).await;
-                response.into_response().await
+                if response.is_json() {
+                    _extensions.insert(JSON_RESPONSE_KEY, ());
+                }
+                let result = response.send_to(&_conn).await;
+                if result.is_err() {
+                    _extensions.insert(HANDLER_ERROR_KEY, ());
+                }
+                result
             }
         }
 
@@ -673,6 +1156,106 @@ mod tests {
         assert!(result.is_some());
     }
 
+    #[tokio::test]
+    async fn test_pretty_json_response() {
+        use serde_json::json;
+
+        let response = JsonResponse(json!({"key": "value"})).pretty();
+        let message = response.into_response().await.unwrap().unwrap();
+        assert_eq!(message.as_text(), Some("{\n  \"key\": \"value\"\n}"));
+    }
+
+    #[tokio::test]
+    async fn test_binary_json_response() {
+        use serde_json::json;
+
+        let response = JsonResponse(json!({"key": "value"})).binary();
+        let message = response.into_response().await.unwrap().unwrap();
+        assert!(message.is_binary());
+        assert_eq!(message.as_bytes(), br#"{"key":"value"}"#);
+    }
+
+    #[tokio::test]
+    async fn test_messages_into_responses() {
+        let response = Messages(vec![Message::text("a"), Message::text("b")]);
+        let result = response.into_responses().await.unwrap();
+        assert_eq!(result.len(), 2);
+
+        let response: Result<Vec<Message>> = Ok(vec![]);
+        assert!(response.into_responses().await.unwrap().is_empty());
+
+        // A single response can't hold several messages, so none are dropped
+        let response = vec![Message::text("a"), Message::text("b")];
+        assert!(response.into_response().await.is_err());
+        let response = vec![Message::text("a")];
+        assert!(response.into_response().await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_message_stream_forwards_until_closed() {
+        use futures_util::stream;
+        use tokio::sync::mpsc;
+
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let addr = "127.0.0.1:8080".parse().unwrap();
+        let conn = Connection::new("conn_0".to_string(), addr, tx);
+
+        let response = MessageStream(stream::iter([
+            Ok(Message::text("a")),
+            Ok(Message::text("b")),
+        ]));
+        assert!(response.send_to(&conn).await.unwrap().is_empty());
+        assert_eq!(rx.recv().await.unwrap().as_text(), Some("a"));
+        assert_eq!(rx.recv().await.unwrap().as_text(), Some("b"));
+
+        // An endless stream is dropped once the connection goes away
+        let guard = Arc::new(());
+        let held = guard.clone();
+        let endless = stream::repeat_with(move || {
+            let _ = &held;
+            Ok(Message::text("tick"))
+        })
+        .then(|item| async {
+            tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+            item
+        });
+        MessageStream(endless).send_to(&conn).await.unwrap();
+        assert_eq!(rx.recv().await.unwrap().as_text(), Some("tick"));
+        drop(rx);
+
+        tokio::time::timeout(std::time::Duration::from_secs(5), async {
+            while Arc::strong_count(&guard) > 1 {
+                tokio::task::yield_now().await;
+            }
+        })
+        .await
+        .expect("stream was not dropped after the connection closed");
+    }
+
+    #[tokio::test]
+    async fn test_close_response_is_close_frame() {
+        let message = CloseResponse::new(4001, "unauthenticated")
+            .into_response()
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            message.close_frame(),
+            Some((4001, "unauthenticated".to_string()))
+        );
+    }
+
+    #[cfg(feature = "msgpack")]
+    #[tokio::test]
+    async fn test_msgpack_response_is_binary() {
+        let response = MsgPackResponse(vec![1u8, 2, 3]);
+        let message = response.into_response().await.unwrap().unwrap();
+        assert!(message.is_binary());
+
+        let decoded: Vec<u8> = rmp_serde::from_slice(message.as_bytes()).unwrap();
+        assert_eq!(decoded, vec![1, 2, 3]);
+    }
+
     #[tokio::test]
     async fn test_handler_creation() {
         async fn test_handler() -> Result<String> {
@@ -0,0 +1,198 @@
+//! Inspecting and rejecting WebSocket handshakes.
+//!
+//! A handshake callback registered with
+//! [`Router::on_handshake()`](crate::router::Router::on_handshake) sees the
+//! HTTP headers of every upgrade request before the WebSocket connection is
+//! established. It can reject the request with an HTTP error status, or accept
+//! it and attach values that handlers later read with the
+//! [`Extension`](crate::extractor::Extension) extractor.
+//!
+//! # Examples
+//!
+//! ## Token Authentication
+//!
+//! ```
+//! use wsforge::prelude::*;
+//!
+//! #[derive(Clone)]
+//! struct User {
+//!     id: u64,
+//! }
+//!
+//! async fn whoami(Extension(user): Extension<User>) -> Result<String> {
+//!     Ok(format!("You are user {}", user.id))
+//! }
+//!
+//! # fn example() {
+//! let router = Router::new()
+//!     .on_handshake(|headers| {
+//!         let token = headers
+//!             .get("authorization")
+//!             .and_then(|v| v.to_str().ok())
+//!             .and_then(|v| v.strip_prefix("Bearer "))
+//!             .ok_or_else(|| Error::custom("Missing token"))?;
+//!
+//!         if token != "secret" {
+//!             // Responds with 403 Forbidden
+//!             return Ok(HandshakeDecision::reject(403));
+//!         }
+//!
+//!         Ok(HandshakeDecision::accept().extension(User { id: 42 }))
+//!     })
+//!     .default_handler(handler(whoami));
+//! # }
+//! ```
+
+use crate::error::Result;
+use crate::extractor::Extensions;
+use std::sync::Arc;
+
+/// HTTP headers of a WebSocket upgrade request.
+pub use tokio_tungstenite::tungstenite::http::HeaderMap;
+
+pub(crate) type HandshakeCallback =
+    Arc<dyn Fn(&HeaderMap) -> Result<HandshakeDecision> + Send + Sync>;
+
+/// The outcome of a handshake callback.
+///
+/// Returning `Err` from the callback is equivalent to
+/// [`reject(401)`](Self::reject).
+///
+/// # Examples
+///
+/// ```
+/// use wsforge::prelude::*;
+///
+/// // Accept and choose a subprotocol
+/// let decision = HandshakeDecision::accept().protocol("chat.v1");
+///
+/// // Reject with 403 Forbidden
+/// let decision = HandshakeDecision::reject(403);
+/// ```
+pub struct HandshakeDecision {
+    pub(crate) outcome: Outcome,
+}
+
+pub(crate) enum Outcome {
+    Accept {
+        extensions: Extensions,
+        meta: Extensions,
+        protocol: Option<String>,
+    },
+    Reject(u16),
+}
+
+impl HandshakeDecision {
+    /// Accepts the connection.
+    pub fn accept() -> Self {
+        Self {
+            outcome: Outcome::Accept {
+                extensions: Extensions::new(),
+                meta: Extensions::new(),
+                protocol: None,
+            },
+        }
+    }
+
+    /// Rejects the connection with the given HTTP status code.
+    ///
+    /// Only client and server error codes (400-599) reject a handshake; any
+    /// other status is replaced with 403 Forbidden.
+    ///
+    /// # Panics
+    ///
+    /// Panics in debug builds if `status` is not in 400-599.
+    pub fn reject(status: u16) -> Self {
+        debug_assert!(
+            (400..600).contains(&status),
+            "handshake rejection status must be 4xx or 5xx"
+        );
+        let status = if (400..600).contains(&status) {
+            status
+        } else {
+            403
+        };
+        Self {
+            outcome: Outcome::Reject(status),
+        }
+    }
+
+    /// Attaches a value to the connection.
+    ///
+    /// The value is available to every handler on this connection through the
+    /// [`Extension<T>`](crate::extractor::Extension) extractor. Has no effect
+    /// on a rejected handshake.
+    pub fn extension<T: Send + Sync + 'static>(self, value: T) -> Self {
+        if let Outcome::Accept { extensions, .. } = &self.outcome {
+            extensions.insert_typed(value);
+        }
+        self
+    }
+
+    /// Stores connection metadata, as with
+    /// [`Connection::set_meta()`](crate::connection::Connection::set_meta).
+    ///
+    /// Has no effect on a rejected handshake.
+    pub fn meta<T: Send + Sync + 'static>(self, key: impl Into<String>, value: T) -> Self {
+        if let Outcome::Accept { meta, .. } = &self.outcome {
+            meta.insert(key, value);
+        }
+        self
+    }
+
+    /// Selects the subprotocol returned in the `Sec-WebSocket-Protocol` header.
+    ///
+    /// The protocol is also recorded in the connection's
+    /// [`ConnectionInfo`](crate::connection::ConnectionInfo). Has no effect on a
+    /// rejected handshake.
+    pub fn protocol(mut self, protocol: impl Into<String>) -> Self {
+        if let Outcome::Accept {
+            protocol: selected, ..
+        } = &mut self.outcome
+        {
+            *selected = Some(protocol.into());
+        }
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extension_is_stored_by_type() {
+        let decision = HandshakeDecision::accept().extension(7u32);
+
+        match decision.outcome {
+            Outcome::Accept { extensions, .. } => {
+                assert_eq!(extensions.get_typed::<u32>().as_deref(), Some(&7));
+            }
+            Outcome::Reject(_) => panic!("expected accept"),
+        }
+    }
+
+    #[test]
+    fn test_reject_ignores_extensions() {
+        let decision = HandshakeDecision::reject(403)
+            .protocol("chat")
+            .extension(1u8);
+        assert!(matches!(decision.outcome, Outcome::Reject(403)));
+    }
+
+    #[test]
+    fn test_reject_keeps_error_status() {
+        let decision = HandshakeDecision::reject(503);
+        assert!(matches!(decision.outcome, Outcome::Reject(503)));
+    }
+
+    #[test]
+    #[cfg_attr(
+        debug_assertions,
+        should_panic(expected = "handshake rejection status must be 4xx or 5xx")
+    )]
+    fn test_reject_replaces_non_error_status() {
+        let decision = HandshakeDecision::reject(200);
+        assert!(matches!(decision.outcome, Outcome::Reject(403)));
+    }
+}
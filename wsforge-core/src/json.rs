@@ -0,0 +1,73 @@
+//! The JSON parser behind [`Message::json()`](crate::message::Message::json).
+//!
+//! Every place that decodes JSON from an incoming message, such as the
+//! [`Json`](crate::extractor::Json) extractor, typed routes and RPC calls,
+//! goes through [`from_text()`]. `serde_json` is the default parser; the
+//! `simd-json` feature swaps in [`simd_json`], which is faster on large
+//! payloads. The public API is the same with either.
+
+use crate::error::Result;
+use serde::de::DeserializeOwned;
+
+/// Parses `text` as JSON into a `T`.
+#[cfg(not(feature = "simd-json"))]
+pub(crate) fn from_text<T: DeserializeOwned>(text: &str) -> Result<T> {
+    Ok(serde_json::from_str(text)?)
+}
+
+/// Parses `text` as JSON into a `T`.
+///
+/// simd-json parses in place, so the text is copied into a scratch buffer.
+/// Parse errors are reported as [`Error::Json`](crate::error::Error::Json)
+/// like with `serde_json`.
+#[cfg(feature = "simd-json")]
+pub(crate) fn from_text<T: DeserializeOwned>(text: &str) -> Result<T> {
+    use serde::de::Error as _;
+
+    let mut buffer = text.as_bytes().to_vec();
+    simd_json::serde::from_slice(&mut buffer).map_err(|e| serde_json::Error::custom(e).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Error;
+
+    #[test]
+    fn test_from_text() {
+        let value: Vec<u32> = from_text("[1, 2, 3]").unwrap();
+        assert_eq!(value, vec![1, 2, 3]);
+
+        assert!(matches!(from_text::<Vec<u32>>("[1,"), Err(Error::Json(_))));
+    }
+
+    #[cfg(feature = "simd-json")]
+    #[tokio::test]
+    async fn test_simd_json_backend() {
+        use crate::extractor::Json;
+        use crate::handler::handler;
+        use crate::message::Message;
+        use crate::router::Router;
+        use serde::Deserialize;
+
+        #[derive(Deserialize)]
+        struct Move {
+            x: i32,
+        }
+
+        let message = Message::text(r#"{"type":"move","x":3}"#);
+        assert_eq!(message.json::<Move>().unwrap().x, 3);
+        assert!(matches!(
+            Message::text("{").json::<Move>(),
+            Err(Error::Json(_))
+        ));
+
+        let router = Router::new().route_typed(
+            "move",
+            handler(|Json(m): Json<Move>| async move { Ok(format!("moved {}", m.x)) }),
+        );
+        let mut client = router.test_client();
+        client.send(message).await.unwrap();
+        assert_eq!(client.recv().await.unwrap().as_text(), Some("moved 3"));
+    }
+}
@@ -375,18 +375,21 @@
 //!
 //! ```
 //! use wsforge_core::prelude::*;
+//! use std::time::Duration;
 //! use tokio::signal;
 //!
 //! #[tokio::main]
 //! async fn main() -> Result<()> {
-//!     let router = Router::new();
+//!     let router = Router::new()
+//!         .drain_timeout(Duration::from_secs(5));
 //!
-//!     tokio::select! {
-//!         _ = router.listen("127.0.0.1:8080") => {},
-//!         _ = signal::ctrl_c() => {
+//!     // Stops accepting, closes every connection, then waits for them to drain
+//!     router
+//!         .listen_with_shutdown("127.0.0.1:8080", async {
+//!             let _ = signal::ctrl_c().await;
 //!             println!("Shutting down gracefully...");
-//!         }
-//!     }
+//!         })
+//!         .await?;
 //!
 //!     Ok(())
 //! }
@@ -408,25 +411,60 @@
 // Enable additional documentation lint rules
 #![warn(rustdoc::missing_crate_level_docs)]
 
+pub mod broadcast;
 pub mod connection;
+pub mod cors;
 pub mod error;
 pub mod extractor;
 pub mod handler;
+pub mod handshake;
 pub mod message;
+pub mod metrics;
 pub mod middleware;
 pub mod router;
 pub mod state;
 pub mod static_files;
+pub mod testing;
+#[cfg(feature = "tls")]
+#[cfg_attr(docsrs, doc(cfg(feature = "tls")))]
+pub mod tls;
+
+mod json;
+mod proxy;
+mod rewind;
+mod rpc;
 
-pub use connection::{Connection, ConnectionId};
+pub use connection::{
+    BroadcastReport, CoalesceStrategy, ConnStats, Connection, ConnectionId, DisconnectReason,
+    DisconnectSource,
+};
+pub use cors::CorsConfig;
 pub use error::{Error, Result};
-pub use extractor::{ConnectInfo, Data, Extension, Extensions, Json, Path, Query, State};
-pub use handler::{Handler, HandlerService, IntoResponse, JsonResponse, handler};
-pub use message::{Message, MessageType};
-pub use middleware::{LoggerMiddleware, Middleware, MiddlewareChain, Next};
-pub use router::{Route, Router};
+#[cfg(feature = "msgpack")]
+pub use extractor::MsgPack;
+#[cfg(feature = "validator")]
+pub use extractor::Valid;
+pub use extractor::{
+    Broadcaster, ConnectInfo, Data, Extension, Extensions, Header, Headers, Json, Manager,
+    NamedHeader, Origin, Path, Query, State, Text, UserAgent,
+};
+#[cfg(feature = "msgpack")]
+pub use handler::MsgPackResponse;
+pub use handler::{
+    BinaryJsonResponse, CloseResponse, Handler, HandlerService, IntoResponse, JsonResponse,
+    MessageStream, Messages, PrettyJsonResponse, handler,
+};
+pub use handshake::HandshakeDecision;
+pub use message::{Message, MessageBuilder, MessageType};
+pub use middleware::{
+    GlobalRateLimitMiddleware, LoggerMiddleware, Middleware, MiddlewareChain, Next,
+    OutboundMiddleware, RateLimitAction, RateLimitMiddleware,
+};
+pub use router::{HttpRequest, Route, Router, RouterConfig, WebSocketConfig};
 pub use state::AppState;
 pub use static_files::StaticFileHandler;
+#[cfg(feature = "tls")]
+pub use tls::TlsConfig;
 
 /// Commonly used types and traits for WsForge applications.
 ///
@@ -470,6 +508,7 @@ pub use static_files::StaticFileHandler;
 /// - [`ConnectInfo`]: Connection metadata
 /// - [`Data`]: Raw byte extraction
 /// - [`Extension<T>`]: Custom extensions
+/// - [`Headers`], [`Header<T>`]: Handshake headers
 ///
 /// ## Handlers
 /// - [`handler()`]: Convert functions to handlers
@@ -484,15 +523,33 @@ pub use static_files::StaticFileHandler;
 /// - [`MessageType`]: Message type enum
 /// - [`StaticFileHandler`]: Static file serving
 pub mod prelude {
-    pub use crate::connection::{Connection, ConnectionId, ConnectionManager};
+    pub use crate::connection::{
+        BroadcastReport, ConnStats, Connection, ConnectionId, ConnectionManager,
+    };
     pub use crate::error::{Error, Result};
+    #[cfg(feature = "msgpack")]
+    pub use crate::extractor::MsgPack;
+    #[cfg(feature = "validator")]
+    pub use crate::extractor::Valid;
     pub use crate::extractor::{
-        ConnectInfo, Data, Extension, Extensions, Json, Path, Query, State,
+        Broadcaster, ConnectInfo, Data, Extension, Extensions, Header, Headers, Json, Manager,
+        NamedHeader, Origin, Path, Query, State, Text, UserAgent,
+    };
+    #[cfg(feature = "msgpack")]
+    pub use crate::handler::MsgPackResponse;
+    pub use crate::handler::{
+        BinaryJsonResponse, CloseResponse, Handler, HandlerService, IntoResponse, JsonResponse,
+        MessageStream, Messages, PrettyJsonResponse, handler,
+    };
+    pub use crate::handshake::HandshakeDecision;
+    pub use crate::message::{Message, MessageBuilder, MessageType};
+    pub use crate::middleware::{
+        GlobalRateLimitMiddleware, LoggerMiddleware, Middleware, MiddlewareChain, Next,
+        OutboundMiddleware, RateLimitAction, RateLimitMiddleware,
     };
-    pub use crate::handler::{Handler, HandlerService, IntoResponse, JsonResponse, handler};
-    pub use crate::message::{Message, MessageType};
-    pub use crate::middleware::{LoggerMiddleware, Middleware, MiddlewareChain, Next};
-    pub use crate::router::{Route, Router};
+    pub use crate::router::{HttpRequest, Route, Router};
     pub use crate::state::AppState;
     pub use crate::static_files::StaticFileHandler;
+    #[cfg(feature = "tls")]
+    pub use crate::tls::TlsConfig;
 }
@@ -84,9 +84,13 @@
 //! # }
 //! ```
 
-use crate::error::Result;
+use crate::error::{Error, Result};
+use bytes::Bytes;
+use serde::Serialize;
 use serde::de::DeserializeOwned;
+use std::borrow::Cow;
 use tokio_tungstenite::tungstenite::Message as TungsteniteMessage;
+use tokio_tungstenite::tungstenite::protocol::CloseFrame;
 
 /// Represents the type of a WebSocket message.
 ///
@@ -155,7 +159,8 @@ pub enum MessageType {
     /// Close frame indicating connection termination.
     ///
     /// Sent when either side wants to close the connection gracefully.
-    /// Contains optional close code and reason.
+    /// Contains optional close code and reason, readable with
+    /// [`Message::close_frame`].
     Close,
 }
 
@@ -208,13 +213,47 @@ pub struct Message {
     ///
     /// For text messages, this contains UTF-8 encoded text.
     /// For binary messages, this contains raw bytes.
-    pub data: Vec<u8>,
+    /// For close messages, this contains the close body as sent on the wire:
+    /// a big-endian `u16` close code followed by a UTF-8 reason, or nothing.
+    ///
+    /// The bytes are reference-counted, so cloning a message, as
+    /// broadcasting does for every recipient, doesn't copy the payload. It
+    /// is still copied once per connection when the frame is written, see
+    /// [`into_tungstenite()`](Self::into_tungstenite).
+    ///
+    /// This field was a `Vec<u8>` before it became [`Bytes`]; use
+    /// `Bytes::from(vec)` to build it and `data.to_vec()` for an owned copy.
+    pub data: Bytes,
 
     /// The type of this message.
     pub msg_type: MessageType,
 }
 
 impl Message {
+    /// Starts building a message with a [`MessageBuilder`].
+    ///
+    /// Useful when the frame type is chosen at runtime, or to handle JSON
+    /// serialization errors in the same place as other invalid messages.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    ///
+    /// # fn example(scores: &[u32], wants_json: bool) -> Result<Message> {
+    /// let builder = Message::builder();
+    /// let builder = if wants_json {
+    ///     builder.json(scores)
+    /// } else {
+    ///     builder.text(format!("{:?}", scores))
+    /// };
+    /// builder.build()
+    /// # }
+    /// ```
+    pub fn builder() -> MessageBuilder {
+        MessageBuilder::default()
+    }
+
     /// Creates a new text message.
     ///
     /// The string is converted to UTF-8 bytes and stored as a text message.
@@ -240,11 +279,66 @@ impl Message {
     pub fn text(text: impl Into<String>) -> Self {
         let string = text.into();
         Self {
-            data: string.into_bytes(),
+            data: Bytes::from(string),
             msg_type: MessageType::Text,
         }
     }
 
+    /// Creates a text message from bytes, checking that they are valid UTF-8.
+    ///
+    /// Unlike building a [`Message`] from its fields, this can't produce a
+    /// text message that would have to be decoded lossily when sent.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - The UTF-8 encoded text
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidMessage`] if `data` isn't valid UTF-8.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    ///
+    /// let msg = Message::try_text(b"hello".to_vec()).unwrap();
+    /// assert_eq!(msg.as_text(), Some("hello"));
+    ///
+    /// assert!(Message::try_text(vec![0xff, 0xfe]).is_err());
+    /// ```
+    pub fn try_text(data: Vec<u8>) -> Result<Self> {
+        String::from_utf8(data)
+            .map(Self::text)
+            .map_err(|_| Error::InvalidMessage)
+    }
+
+    /// Serializes a value as JSON into a binary message.
+    ///
+    /// Some protocols expect JSON in binary frames rather than text frames.
+    /// Use [`Message::text()`] with [`serde_json::to_string()`] for the usual
+    /// text frame.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Json`] if `value` can't be serialized.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    ///
+    /// # fn example() -> Result<()> {
+    /// let msg = Message::json_binary(&serde_json::json!({ "score": 10 }))?;
+    /// assert!(msg.is_binary());
+    /// assert_eq!(msg.as_bytes(), br#"{"score":10}"#);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn json_binary<T: Serialize + ?Sized>(value: &T) -> Result<Self> {
+        Ok(Self::binary(serde_json::to_vec(value)?))
+    }
+
     /// Creates a new binary message.
     ///
     /// The bytes are stored as-is without any encoding or processing.
@@ -270,7 +364,7 @@ impl Message {
     /// ```
     pub fn binary(data: Vec<u8>) -> Self {
         Self {
-            data,
+            data: Bytes::from(data),
             msg_type: MessageType::Binary,
         }
     }
@@ -298,7 +392,7 @@ impl Message {
     /// ```
     pub fn ping(data: Vec<u8>) -> Self {
         Self {
-            data,
+            data: Bytes::from(data),
             msg_type: MessageType::Ping,
         }
     }
@@ -320,14 +414,14 @@ impl Message {
     /// # fn example(ping_msg: Message) {
     /// // Respond to a ping
     /// if ping_msg.is_ping() {
-    ///     let pong = Message::pong(ping_msg.data.clone());
+    ///     let pong = Message::pong(ping_msg.as_bytes().to_vec());
     ///     // Send pong back...
     /// }
     /// # }
     /// ```
     pub fn pong(data: Vec<u8>) -> Self {
         Self {
-            data,
+            data: Bytes::from(data),
             msg_type: MessageType::Pong,
         }
     }
@@ -347,11 +441,85 @@ impl Message {
     /// ```
     pub fn close() -> Self {
         Self {
-            data: Vec::new(),
+            data: Bytes::new(),
             msg_type: MessageType::Close,
         }
     }
 
+    /// Creates a close message with a status code and reason.
+    ///
+    /// The code is one of the status codes defined in
+    /// [RFC 6455 §7.4](https://www.rfc-editor.org/rfc/rfc6455#section-7.4),
+    /// such as `1000` (normal closure) or `1008` (policy violation).
+    ///
+    /// Codes that may not appear in a close frame (below 1000, the reserved
+    /// 1004, 1005, 1006 and 1015, and the unassigned 1016-2999) produce a
+    /// close message without a code or reason, like [`close()`](Self::close).
+    /// The reason is cut to the 123 bytes that fit in a control frame, on a
+    /// character boundary.
+    ///
+    /// # Arguments
+    ///
+    /// * `code` - The close status code
+    /// * `reason` - A short human-readable reason
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    ///
+    /// let close = Message::close_with(1008, "Authentication required");
+    /// assert_eq!(close.close_frame(), Some((1008, "Authentication required".to_string())));
+    /// ```
+    pub fn close_with(code: u16, reason: impl Into<String>) -> Self {
+        if !is_sendable_close_code(code) {
+            return Self::close();
+        }
+
+        let mut reason = reason.into();
+        if reason.len() > MAX_CLOSE_REASON {
+            let mut end = MAX_CLOSE_REASON;
+            while !reason.is_char_boundary(end) {
+                end -= 1;
+            }
+            reason.truncate(end);
+        }
+        let mut data = Vec::with_capacity(2 + reason.len());
+        data.extend_from_slice(&code.to_be_bytes());
+        data.extend_from_slice(reason.as_bytes());
+
+        Self {
+            data: Bytes::from(data),
+            msg_type: MessageType::Close,
+        }
+    }
+
+    /// Returns the close code and reason, if this is a close message that has one.
+    ///
+    /// Returns `None` for non-close messages and for close messages sent
+    /// without a status code.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    ///
+    /// # fn example(msg: Message) {
+    /// if let Some((code, reason)) = msg.close_frame() {
+    ///     println!("Client closed with {}: {}", code, reason);
+    /// }
+    /// # }
+    /// ```
+    pub fn close_frame(&self) -> Option<(u16, String)> {
+        if !self.is_close() || self.data.len() < 2 {
+            return None;
+        }
+
+        let code = u16::from_be_bytes([self.data[0], self.data[1]]);
+        let reason = String::from_utf8_lossy(&self.data[2..]).into_owned();
+        Some((code, reason))
+    }
+
     /// Converts this message to a `tungstenite` message.
     ///
     /// This is used internally by the framework to convert between
@@ -368,21 +536,32 @@ impl Message {
     /// # }
     /// ```
     pub fn into_tungstenite(self) -> TungsteniteMessage {
+        // tungstenite owns its payloads, so a payload that is still shared,
+        // e.g. by other recipients of a broadcast, is copied here
         match self.msg_type {
-            MessageType::Text => {
-                TungsteniteMessage::Text(String::from_utf8_lossy(&self.data).to_string())
+            MessageType::Text => TungsteniteMessage::Text(
+                String::from_utf8(self.data.into())
+                    .unwrap_or_else(|e| String::from_utf8_lossy(e.as_bytes()).into_owned()),
+            ),
+            MessageType::Binary => TungsteniteMessage::Binary(self.data.into()),
+            MessageType::Ping => TungsteniteMessage::Ping(self.data.into()),
+            MessageType::Pong => TungsteniteMessage::Pong(self.data.into()),
+            MessageType::Close => {
+                let frame = self.close_frame().map(|(code, reason)| CloseFrame {
+                    code: code.into(),
+                    reason: Cow::Owned(reason),
+                });
+                TungsteniteMessage::Close(frame)
             }
-            MessageType::Binary => TungsteniteMessage::Binary(self.data),
-            MessageType::Ping => TungsteniteMessage::Ping(self.data),
-            MessageType::Pong => TungsteniteMessage::Pong(self.data),
-            MessageType::Close => TungsteniteMessage::Close(None),
         }
     }
 
     /// Creates a message from a `tungstenite` message.
     ///
     /// This is used internally by the framework to convert incoming
-    /// WebSocket messages to WsForge's message type.
+    /// WebSocket messages to WsForge's message type. A raw frame becomes a
+    /// binary message with the frame's payload; the framework never
+    /// dispatches raw frames to handlers.
     ///
     /// # Arguments
     ///
@@ -406,8 +585,13 @@ impl Message {
             TungsteniteMessage::Binary(data) => Self::binary(data),
             TungsteniteMessage::Ping(data) => Self::ping(data),
             TungsteniteMessage::Pong(data) => Self::pong(data),
-            TungsteniteMessage::Close(_) => Self::close(),
-            TungsteniteMessage::Frame(_) => Self::binary(vec![]),
+            TungsteniteMessage::Close(Some(frame)) => {
+                Self::close_with(frame.code.into(), frame.reason.into_owned())
+            }
+            TungsteniteMessage::Close(None) => Self::close(),
+            // Raw frames never come out of a read stream; keep the payload
+            // rather than inventing an empty message
+            TungsteniteMessage::Frame(frame) => Self::binary(frame.into_data()),
         }
     }
 
@@ -562,6 +746,53 @@ impl Message {
         &self.data
     }
 
+    /// Returns the size of the message payload in bytes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    ///
+    /// let msg = Message::text("Hello");
+    /// assert_eq!(msg.len(), 5);
+    /// ```
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Returns `true` if the message payload is empty.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    ///
+    /// assert!(Message::ping(vec![]).is_empty());
+    /// assert!(!Message::text("Hello").is_empty());
+    /// ```
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// Returns the message content as text, replacing invalid UTF-8 with
+    /// `U+FFFD`.
+    ///
+    /// Unlike [`as_text()`](Self::as_text), this works for every message type
+    /// and never fails, which makes it handy for logging. Borrows the data if
+    /// it is valid UTF-8.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    ///
+    /// let msg = Message::binary(vec![b'h', b'i', 0xFF]);
+    /// assert_eq!(msg.as_text_lossy(), "hi\u{FFFD}");
+    /// ```
+    pub fn as_text_lossy(&self) -> Cow<'_, str> {
+        String::from_utf8_lossy(&self.data)
+    }
+
     /// Deserializes the message content as JSON.
     ///
     /// This is a convenience method for parsing JSON from text messages.
@@ -613,7 +844,130 @@ impl Message {
         let text = self
             .as_text()
             .ok_or_else(|| crate::error::Error::InvalidMessage)?;
-        Ok(serde_json::from_str(text)?)
+        crate::json::from_text(text)
+    }
+}
+
+/// The longest close reason that fits in a control frame after the code.
+const MAX_CLOSE_REASON: usize = 123;
+
+/// Whether `code` may be sent in a close frame (RFC 6455 §7.4).
+fn is_sendable_close_code(code: u16) -> bool {
+    matches!(code, 1000..=1003 | 1007..=1014 | 3000..=4999)
+}
+
+/// Builds a [`Message`], created with [`Message::builder()`].
+///
+/// Each frame method replaces the previous one, so the last call decides
+/// the message. Errors, such as a value that can't be serialized, are
+/// reported by [`build()`](Self::build).
+///
+/// # Examples
+///
+/// ```
+/// use wsforge::prelude::*;
+///
+/// # fn example() -> Result<()> {
+/// let msg = Message::builder()
+///     .json(&serde_json::json!({ "status": "ok" }))
+///     .build()?;
+/// assert_eq!(msg.as_text(), Some(r#"{"status":"ok"}"#));
+///
+/// let ping = Message::builder().ping(b"1700000000".to_vec()).build()?;
+/// assert!(ping.is_ping());
+///
+/// let close = Message::builder().close(1001, "Server restarting").build()?;
+/// assert_eq!(close.close_frame(), Some((1001, "Server restarting".to_string())));
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Default)]
+#[must_use]
+pub struct MessageBuilder {
+    /// The message built so far, or the error building it
+    message: Option<Result<Message>>,
+}
+
+impl MessageBuilder {
+    /// Control frame payloads are limited to 125 bytes by RFC 6455.
+    const MAX_CONTROL_PAYLOAD: usize = 125;
+
+    /// Builds a text message.
+    pub fn text(mut self, text: impl Into<String>) -> Self {
+        self.message = Some(Ok(Message::text(text)));
+        self
+    }
+
+    /// Builds a binary message.
+    pub fn binary(mut self, data: Vec<u8>) -> Self {
+        self.message = Some(Ok(Message::binary(data)));
+        self
+    }
+
+    /// Builds a text message holding `value` serialized as JSON.
+    ///
+    /// A serialization error is returned by [`build()`](Self::build).
+    pub fn json<T: Serialize + ?Sized>(mut self, value: &T) -> Self {
+        self.message = Some(
+            serde_json::to_string(value)
+                .map(Message::text)
+                .map_err(Error::from),
+        );
+        self
+    }
+
+    /// Builds a ping message carrying `data`, such as a timestamp.
+    pub fn ping(mut self, data: Vec<u8>) -> Self {
+        self.message = Some(Ok(Message::ping(data)));
+        self
+    }
+
+    /// Builds a close message with a status code and reason.
+    ///
+    /// Unlike [`Message::close_with()`], a code that can't be sent or a
+    /// reason longer than 123 bytes makes [`build()`](Self::build) fail.
+    pub fn close(mut self, code: u16, reason: impl Into<String>) -> Self {
+        let reason = reason.into();
+        self.message = Some(if !is_sendable_close_code(code) {
+            Err(Error::custom(format!(
+                "Close code {} can't be sent in a close frame",
+                code
+            )))
+        } else if reason.len() > MAX_CLOSE_REASON {
+            Err(Error::custom(format!(
+                "Close reason of {} bytes exceeds {} bytes",
+                reason.len(),
+                MAX_CLOSE_REASON
+            )))
+        } else {
+            Ok(Message::close_with(code, reason))
+        });
+        self
+    }
+
+    /// Returns the built message.
+    ///
+    /// # Errors
+    ///
+    /// - [`Error::Json`] if the value passed to [`json()`](Self::json)
+    ///   couldn't be serialized
+    /// - [`Error::Custom`] if no frame type was chosen, a ping payload is
+    ///   longer than the 125 bytes WebSocket allows, or a close code can't be
+    ///   sent or its reason is longer than 123 bytes
+    pub fn build(self) -> Result<Message> {
+        let message = self
+            .message
+            .unwrap_or_else(|| Err(Error::custom("No message type set on the builder")))?;
+
+        let is_control = message.is_ping() || message.is_pong() || message.is_close();
+        if is_control && message.len() > Self::MAX_CONTROL_PAYLOAD {
+            return Err(Error::custom(format!(
+                "Control frame payload of {} bytes exceeds {} bytes",
+                message.len(),
+                Self::MAX_CONTROL_PAYLOAD
+            )));
+        }
+        Ok(message)
     }
 }
 
@@ -638,6 +992,80 @@ mod tests {
         assert_eq!(msg.message_type(), MessageType::Binary);
     }
 
+    #[test]
+    fn test_builder() {
+        let msg = Message::builder().json(&[1, 2]).build().unwrap();
+        assert_eq!(msg.as_text(), Some("[1,2]"));
+
+        // The last frame method wins
+        let msg = Message::builder()
+            .text("draft")
+            .binary(vec![1])
+            .build()
+            .unwrap();
+        assert!(msg.is_binary());
+
+        let close = Message::builder().close(1000, "bye").build().unwrap();
+        assert_eq!(close.close_frame(), Some((1000, "bye".to_string())));
+
+        assert!(Message::builder().build().is_err());
+        assert!(Message::builder().ping(vec![0; 126]).build().is_err());
+        assert!(
+            Message::builder()
+                .close(1000, "x".repeat(124))
+                .build()
+                .is_err()
+        );
+        assert!(Message::builder().close(1006, "gone").build().is_err());
+
+        let mut map = std::collections::HashMap::new();
+        map.insert(vec![1u8], 1);
+        assert!(matches!(
+            Message::builder().json(&map).build(),
+            Err(Error::Json(_))
+        ));
+    }
+
+    #[test]
+    fn test_try_text_validates_utf8() {
+        let msg = Message::try_text("héllo".as_bytes().to_vec()).unwrap();
+        assert!(msg.is_text());
+        assert_eq!(msg.as_text(), Some("héllo"));
+
+        assert!(matches!(
+            Message::try_text(vec![b'o', b'k', 0xC3, 0x28]),
+            Err(Error::InvalidMessage)
+        ));
+    }
+
+    #[test]
+    fn test_len_and_lossy_text() {
+        let msg = Message::text("héllo");
+        assert_eq!(msg.len(), 6);
+        assert!(!msg.is_empty());
+        assert!(matches!(msg.as_text_lossy(), Cow::Borrowed("héllo")));
+
+        // Invalid UTF-8 has no strict text view, but decodes lossily
+        let msg = Message::binary(vec![b'o', b'k', 0xC3, 0x28]);
+        assert_eq!(msg.len(), 4);
+        assert_eq!(msg.as_text(), None);
+        assert_eq!(msg.as_text_lossy(), "ok\u{FFFD}(");
+
+        assert!(Message::close().is_empty());
+    }
+
+    #[test]
+    fn test_clone_shares_payload() {
+        let msg = Message::text("x".repeat(10_000));
+        let copy = msg.clone();
+        assert_eq!(msg.as_bytes().as_ptr(), copy.as_bytes().as_ptr());
+
+        // Converting one clone leaves the other intact
+        let sent = copy.into_tungstenite();
+        assert_eq!(sent.into_text().unwrap().len(), 10_000);
+        assert_eq!(msg.len(), 10_000);
+    }
+
     #[test]
     fn test_ping_message() {
         let msg = Message::ping(vec![]);
@@ -659,6 +1087,68 @@ mod tests {
         assert_eq!(msg.message_type(), MessageType::Close);
     }
 
+    #[test]
+    fn test_close_with_code_and_reason() {
+        let msg = Message::close_with(1008, "policy violation");
+        assert!(msg.is_close());
+        assert_eq!(
+            msg.close_frame(),
+            Some((1008, "policy violation".to_string()))
+        );
+
+        assert_eq!(Message::close().close_frame(), None);
+        assert_eq!(Message::text("hi").close_frame(), None);
+    }
+
+    #[test]
+    fn test_close_with_drops_reserved_codes() {
+        for code in [0, 999, 1004, 1005, 1006, 1015, 2000, 5000] {
+            let msg = Message::close_with(code, "bye");
+            assert!(msg.is_close());
+            assert_eq!(msg.close_frame(), None, "code {}", code);
+        }
+        assert_eq!(
+            Message::close_with(4000, "app").close_frame(),
+            Some((4000, "app".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_close_with_truncates_reason_on_char_boundary() {
+        let (_, reason) = Message::close_with(1000, "a".repeat(200))
+            .close_frame()
+            .unwrap();
+        assert_eq!(reason.len(), 123);
+
+        // 'é' is two bytes, so 62 of them only fit as 61
+        let (_, reason) = Message::close_with(1000, "é".repeat(62))
+            .close_frame()
+            .unwrap();
+        assert_eq!(reason, "é".repeat(61));
+    }
+
+    #[test]
+    fn test_raw_frame_keeps_payload() {
+        use tokio_tungstenite::tungstenite::protocol::frame::Frame;
+        use tokio_tungstenite::tungstenite::protocol::frame::coding::{Data, OpCode};
+
+        let frame = Frame::message(vec![1, 2, 3], OpCode::Data(Data::Binary), true);
+        let msg = Message::from_tungstenite(TungsteniteMessage::Frame(frame));
+        assert!(msg.is_binary());
+        assert_eq!(msg.as_bytes(), &[1, 2, 3]);
+    }
+
+    #[test]
+    fn test_close_frame_tungstenite_conversion() {
+        let msg = Message::close_with(1011, "server error");
+        let back = Message::from_tungstenite(msg.into_tungstenite());
+        assert_eq!(back.close_frame(), Some((1011, "server error".to_string())));
+
+        let back = Message::from_tungstenite(Message::close().into_tungstenite());
+        assert!(back.is_close());
+        assert_eq!(back.close_frame(), None);
+    }
+
     #[test]
     fn test_json_parsing() {
         let msg = Message::text(r#"{"key":"value","number":42}"#);
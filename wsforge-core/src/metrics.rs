@@ -0,0 +1,289 @@
+//! Metrics hooks for observing a running server.
+//!
+//! Implement [`Metrics`] and register it with
+//! [`Router::with_metrics()`](crate::router::Router::with_metrics) to be
+//! notified of connections, messages and handler runs. Every method has an
+//! empty default implementation, so an implementation only overrides what it
+//! records.
+//!
+//! With the `prometheus` feature, [`PrometheusMetrics`] provides a ready-made
+//! implementation backed by the [`prometheus`](::prometheus) crate.
+//!
+//! # Overview
+//!
+//! | Method | Called when |
+//! |--------|-------------|
+//! | [`connection_opened`](Metrics::connection_opened) | A WebSocket connection was accepted |
+//! | [`connection_closed`](Metrics::connection_closed) | A connection ended, with its duration |
+//! | [`message_received`](Metrics::message_received) | A text or binary message arrived |
+//! | [`message_sent`](Metrics::message_sent) | A text or binary message was written |
+//! | [`handler_duration`](Metrics::handler_duration) | A message finished its middleware and handler |
+//! | [`handler_error`](Metrics::handler_error) | A handler or middleware returned an error |
+//!
+//! Methods are called inline on connection tasks, so they should be cheap:
+//! increment a counter, record into a histogram, and return.
+//!
+//! # Examples
+//!
+//! ```
+//! use wsforge::prelude::*;
+//! use wsforge::metrics::Metrics;
+//! use std::sync::Arc;
+//! use std::sync::atomic::{AtomicI64, Ordering};
+//!
+//! #[derive(Default)]
+//! struct ActiveConnections(AtomicI64);
+//!
+//! impl Metrics for ActiveConnections {
+//!     fn connection_opened(&self) {
+//!         self.0.fetch_add(1, Ordering::Relaxed);
+//!     }
+//!
+//!     fn connection_closed(&self, _duration: std::time::Duration) {
+//!         self.0.fetch_sub(1, Ordering::Relaxed);
+//!     }
+//! }
+//!
+//! # fn example() {
+//! let router = Router::new().with_metrics(Arc::new(ActiveConnections::default()));
+//! # }
+//! ```
+
+use std::time::Duration;
+
+#[cfg(feature = "prometheus")]
+use crate::error::{Error, Result};
+#[cfg(feature = "prometheus")]
+use prometheus::{Encoder, Histogram, HistogramOpts, IntCounter, IntGauge, Registry, TextEncoder};
+
+/// Receives events from the router and its connections.
+///
+/// See the [module documentation](self) for when each method is called.
+pub trait Metrics: Send + Sync + 'static {
+    /// A WebSocket connection was accepted.
+    fn connection_opened(&self) {}
+
+    /// A WebSocket connection ended after being open for `duration`.
+    fn connection_closed(&self, duration: Duration) {
+        let _ = duration;
+    }
+
+    /// A text or binary message was received from a client.
+    fn message_received(&self) {}
+
+    /// A text or binary message was written to a client.
+    fn message_sent(&self) {}
+
+    /// A message's middleware and handler finished after `duration`,
+    /// whether they succeeded or not.
+    fn handler_duration(&self, duration: Duration) {
+        let _ = duration;
+    }
+
+    /// A message's middleware or handler returned an error, including
+    /// [`Error::Timeout`](crate::error::Error::Timeout).
+    fn handler_error(&self) {}
+}
+
+/// [`Metrics`] implementation that records into a Prometheus registry.
+///
+/// Records the following metrics:
+///
+/// | Name | Type |
+/// |------|------|
+/// | `wsforge_connections_active` | gauge |
+/// | `wsforge_connections_total` | counter |
+/// | `wsforge_connection_duration_seconds` | histogram |
+/// | `wsforge_messages_received_total` | counter |
+/// | `wsforge_messages_sent_total` | counter |
+/// | `wsforge_handler_duration_seconds` | histogram |
+/// | `wsforge_handler_errors_total` | counter |
+///
+/// Serve [`render()`](Self::render) from an HTTP endpoint for Prometheus to
+/// scrape, or register the metrics in your own registry with
+/// [`with_registry()`](Self::with_registry).
+///
+/// # Examples
+///
+/// ```
+/// use wsforge::prelude::*;
+/// use wsforge::metrics::PrometheusMetrics;
+/// use std::sync::Arc;
+///
+/// # fn example() {
+/// let metrics = Arc::new(PrometheusMetrics::new());
+/// let router = Router::new().with_metrics(metrics.clone());
+///
+/// // Later, from a scrape endpoint
+/// let exposition = metrics.render();
+/// # }
+/// ```
+#[cfg(feature = "prometheus")]
+#[cfg_attr(docsrs, doc(cfg(feature = "prometheus")))]
+pub struct PrometheusMetrics {
+    registry: Registry,
+    connections_active: IntGauge,
+    connections_total: IntCounter,
+    connection_duration: Histogram,
+    messages_received: IntCounter,
+    messages_sent: IntCounter,
+    handler_duration: Histogram,
+    handler_errors: IntCounter,
+}
+
+#[cfg(feature = "prometheus")]
+impl PrometheusMetrics {
+    /// Creates the metrics in a new, private registry.
+    pub fn new() -> Self {
+        Self::with_registry(Registry::new()).expect("metrics are registered in a fresh registry")
+    }
+
+    /// Creates the metrics and registers them in `registry`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `registry` already contains metrics with the same
+    /// names.
+    pub fn with_registry(registry: Registry) -> Result<Self> {
+        let metrics = Self {
+            connections_active: IntGauge::new(
+                "wsforge_connections_active",
+                "Open WebSocket connections",
+            )
+            .map_err(prometheus_error)?,
+            connections_total: IntCounter::new(
+                "wsforge_connections_total",
+                "WebSocket connections accepted",
+            )
+            .map_err(prometheus_error)?,
+            connection_duration: Histogram::with_opts(
+                HistogramOpts::new(
+                    "wsforge_connection_duration_seconds",
+                    "How long WebSocket connections stayed open",
+                )
+                .buckets(vec![1.0, 10.0, 60.0, 300.0, 1800.0, 3600.0, 14400.0]),
+            )
+            .map_err(prometheus_error)?,
+            messages_received: IntCounter::new(
+                "wsforge_messages_received_total",
+                "Text and binary messages received from clients",
+            )
+            .map_err(prometheus_error)?,
+            messages_sent: IntCounter::new(
+                "wsforge_messages_sent_total",
+                "Text and binary messages written to clients",
+            )
+            .map_err(prometheus_error)?,
+            handler_duration: Histogram::with_opts(HistogramOpts::new(
+                "wsforge_handler_duration_seconds",
+                "Time spent in middleware and handlers per message",
+            ))
+            .map_err(prometheus_error)?,
+            handler_errors: IntCounter::new(
+                "wsforge_handler_errors_total",
+                "Messages whose middleware or handler returned an error",
+            )
+            .map_err(prometheus_error)?,
+            registry,
+        };
+
+        metrics.register()?;
+        Ok(metrics)
+    }
+
+    fn register(&self) -> Result<()> {
+        let collectors: [Box<dyn prometheus::core::Collector>; 7] = [
+            Box::new(self.connections_active.clone()),
+            Box::new(self.connections_total.clone()),
+            Box::new(self.connection_duration.clone()),
+            Box::new(self.messages_received.clone()),
+            Box::new(self.messages_sent.clone()),
+            Box::new(self.handler_duration.clone()),
+            Box::new(self.handler_errors.clone()),
+        ];
+        for collector in collectors {
+            self.registry
+                .register(collector)
+                .map_err(prometheus_error)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the registry the metrics are recorded in.
+    pub fn registry(&self) -> &Registry {
+        &self.registry
+    }
+
+    /// Renders all metrics of the registry in the Prometheus text format.
+    pub fn render(&self) -> String {
+        let mut buffer = Vec::new();
+        // Encoding into memory can't fail for well-formed metrics
+        let _ = TextEncoder::new().encode(&self.registry.gather(), &mut buffer);
+        String::from_utf8(buffer).unwrap_or_default()
+    }
+}
+
+#[cfg(feature = "prometheus")]
+impl Default for PrometheusMetrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "prometheus")]
+impl Metrics for PrometheusMetrics {
+    fn connection_opened(&self) {
+        self.connections_active.inc();
+        self.connections_total.inc();
+    }
+
+    fn connection_closed(&self, duration: Duration) {
+        self.connections_active.dec();
+        self.connection_duration.observe(duration.as_secs_f64());
+    }
+
+    fn message_received(&self) {
+        self.messages_received.inc();
+    }
+
+    fn message_sent(&self) {
+        self.messages_sent.inc();
+    }
+
+    fn handler_duration(&self, duration: Duration) {
+        self.handler_duration.observe(duration.as_secs_f64());
+    }
+
+    fn handler_error(&self) {
+        self.handler_errors.inc();
+    }
+}
+
+#[cfg(feature = "prometheus")]
+fn prometheus_error(e: prometheus::Error) -> Error {
+    Error::custom(format!("Prometheus error: {}", e))
+}
+
+#[cfg(all(test, feature = "prometheus"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prometheus_render() {
+        let metrics = PrometheusMetrics::new();
+        metrics.connection_opened();
+        metrics.message_received();
+        metrics.handler_duration(Duration::from_millis(3));
+
+        let text = metrics.render();
+        assert!(text.contains("wsforge_connections_active 1"));
+        assert!(text.contains("wsforge_messages_received_total 1"));
+        assert!(text.contains("wsforge_handler_duration_seconds_count 1"));
+    }
+
+    #[test]
+    fn test_shared_registry_rejects_duplicates() {
+        let metrics = PrometheusMetrics::new();
+        assert!(PrometheusMetrics::with_registry(metrics.registry().clone()).is_err());
+    }
+}
@@ -307,7 +307,7 @@ impl Middleware for LoggerMiddleware {
         state: AppState,
         extensions: Extensions,
         next: Next,
-    ) -> Result<Option<Message>> {
+    ) -> Result<Vec<Message>> {
         let start = Instant::now();
         let msg_type = message.message_type();
         let conn_id = conn.id().clone();
@@ -322,14 +322,14 @@ impl Middleware for LoggerMiddleware {
         let duration = start.elapsed();
 
         match &result {
-            Ok(Some(_)) => match self.log_level {
+            Ok(responses) if !responses.is_empty() => match self.log_level {
                 LogLevel::Debug => debug!("📤 [{}] Sent response in {:?}", conn_id, duration),
                 LogLevel::Info => info!("📤 [{}] Sent response in {:?}", conn_id, duration),
                 LogLevel::Warn => {
                     tracing::warn!("📤 [{}] Sent response in {:?}", conn_id, duration)
                 }
             },
-            Ok(None) => match self.log_level {
+            Ok(_) => match self.log_level {
                 LogLevel::Debug => debug!("✓ [{}] Processed in {:?}", conn_id, duration),
                 LogLevel::Info => info!("✓ [{}] Processed in {:?}", conn_id, duration),
                 LogLevel::Warn => {
@@ -22,7 +22,8 @@
 //! Each middleware can:
 //! - Inspect the incoming message
 //! - Modify the message before passing it forward
-//! - Short-circuit the chain by not calling `next.run()`
+//! - Short-circuit the chain by not calling `next.run()`, answering with its
+//!   own response, no response, or an error
 //! - Modify the response after calling `next.run()`
 //! - Handle errors and transform responses
 //!
@@ -66,7 +67,7 @@
 //!         state: AppState,
 //!         extensions: Extensions,
 //!         mut next: Next,
-//!     ) -> Result<Option<Message>> {
+//!     ) -> Result<Vec<Message>> {
 //!         // Check for auth token in message
 //!         if let Some(text) = message.as_text() {
 //!             if !text.contains(&self.secret) {
@@ -102,23 +103,27 @@
 //!
 //! ```
 //! use wsforge::prelude::*;
+//! use std::time::Duration;
 //!
 //! # async fn example() -> Result<()> {
 //! let router = Router::new()
 //!     .layer(LoggerMiddleware::new())
 //!     .layer(auth_middleware())
-//!     .layer(rate_limit_middleware())
+//!     .layer(RateLimitMiddleware::new(10, Duration::from_secs(1)))
 //!     .default_handler(handler(my_handler));
 //! # Ok(())
 //! # }
 //! # async fn my_handler() -> Result<String> { Ok("".to_string()) }
 //! # fn auth_middleware() -> Arc<dyn Middleware> { unimplemented!() }
-//! # fn rate_limit_middleware() -> Arc<dyn Middleware> { unimplemented!() }
 //! ```
 
 pub mod logger;
+pub mod outbound;
+pub mod rate_limit;
 
 pub use logger::LoggerMiddleware;
+pub use outbound::OutboundMiddleware;
+pub use rate_limit::{GlobalRateLimitMiddleware, RateLimitAction, RateLimitMiddleware};
 
 use crate::connection::Connection;
 use crate::error::Result;
@@ -151,7 +156,7 @@ use std::sync::Arc;
 ///         state: AppState,
 ///         extensions: Extensions,
 ///         mut next: Next,
-///     ) -> Result<Option<Message>> {
+///     ) -> Result<Vec<Message>> {
 ///         println!("Before next");
 ///
 ///         // Call the next middleware/handler
@@ -192,8 +197,8 @@ impl Next {
     ///
     /// # Returns
     ///
-    /// Returns the response from the next middleware or handler, or `None` if
-    /// no response should be sent.
+    /// Returns the responses from the next middleware or handler, in the
+    /// order they will be sent; empty if no response should be sent.
     ///
     /// # Examples
     ///
@@ -212,7 +217,7 @@ impl Next {
     ///         state: AppState,
     ///         extensions: Extensions,
     ///         mut next: Next,
-    ///     ) -> Result<Option<Message>> {
+    ///     ) -> Result<Vec<Message>> {
     ///         let start = std::time::Instant::now();
     ///
     ///         let response = next.run(message, conn, state, extensions).await?;
@@ -230,7 +235,7 @@ impl Next {
         conn: Connection,
         state: AppState,
         extensions: Extensions,
-    ) -> Result<Option<Message>> {
+    ) -> Result<Vec<Message>> {
         if self.index < self.chain.middlewares.len() {
             let middleware = self.chain.middlewares[self.index].clone();
             self.index += 1;
@@ -240,7 +245,7 @@ impl Next {
         } else if let Some(ref handler) = self.chain.handler {
             handler.call(message, conn, state, extensions).await
         } else {
-            Ok(None)
+            Ok(Vec::new())
         }
     }
 }
@@ -253,6 +258,7 @@ impl Next {
 /// # Implementation Guidelines
 ///
 /// - **Always call `next.run()`** unless you want to short-circuit
+///   (see below)
 /// - **Use extensions** to pass data to handlers or other middleware
 /// - **Handle errors gracefully** and provide clear error messages
 /// - **Be mindful of performance** - middleware runs on every message
@@ -278,7 +284,7 @@ impl Next {
 ///         state: AppState,
 ///         extensions: Extensions,
 ///         mut next: Next,
-///     ) -> Result<Option<Message>> {
+///     ) -> Result<Vec<Message>> {
 ///         if let Some(text) = message.as_text() {
 ///             if let Some(token) = text.strip_prefix("TOKEN:") {
 ///                 if token == self.required_token {
@@ -293,22 +299,32 @@ impl Next {
 /// }
 /// ```
 ///
-/// ## Rate Limiting Middleware
+/// ## Short-Circuiting with a Response
+///
+/// A middleware that returns without calling `next.run()` ends the chain:
+/// no later middleware or handler runs. What it returns is handled exactly
+/// like a handler's result:
+///
+/// - `Ok(messages)` sends `messages` to the client, in order
+/// - `Ok(Vec::new())` sends nothing
+/// - `Err(error)` is reported through the router's error handler
+///
+/// For example, a cache that answers repeated queries without reaching the
+/// handler:
 ///
 /// ```
 /// use wsforge::prelude::*;
 /// use async_trait::async_trait;
-/// use std::sync::Arc;
-/// use tokio::sync::RwLock;
 /// use std::collections::HashMap;
+/// use std::sync::Mutex;
 ///
-/// struct RateLimitMiddleware {
-///     limits: Arc<RwLock<HashMap<String, u32>>>,
-///     max_requests: u32,
+/// #[derive(Default)]
+/// struct CacheMiddleware {
+///     responses: Mutex<HashMap<String, Vec<Message>>>,
 /// }
 ///
 /// #[async_trait]
-/// impl Middleware for RateLimitMiddleware {
+/// impl Middleware for CacheMiddleware {
 ///     async fn handle(
 ///         &self,
 ///         message: Message,
@@ -316,23 +332,35 @@ impl Next {
 ///         state: AppState,
 ///         extensions: Extensions,
 ///         mut next: Next,
-///     ) -> Result<Option<Message>> {
-///         let conn_id = conn.id();
-///         let mut limits = self.limits.write().await;
-///         let count = limits.entry(conn_id.clone()).or_insert(0);
+///     ) -> Result<Vec<Message>> {
+///         let key = message.as_text().unwrap_or_default().to_string();
 ///
-///         if *count >= self.max_requests {
-///             return Err(Error::custom("Rate limit exceeded"));
+///         // Cache hit: answer directly, the handler never runs
+///         if let Some(cached) = self.responses.lock().unwrap().get(&key) {
+///             return Ok(cached.clone());
 ///         }
 ///
-///         *count += 1;
-///         drop(limits);
-///
-///         next.run(message, conn, state, extensions).await
+///         let response = next.run(message, conn, state, extensions).await?;
+///         self.responses.lock().unwrap().insert(key, response.clone());
+///         Ok(response)
 ///     }
 /// }
 /// ```
 ///
+/// ## Rate Limiting Middleware
+///
+/// Rate limiting is built in, see [`RateLimitMiddleware`]:
+///
+/// ```
+/// use wsforge::prelude::*;
+/// use std::time::Duration;
+///
+/// # fn example() {
+/// let router = Router::new()
+///     .layer(RateLimitMiddleware::new(10, Duration::from_secs(1)));
+/// # }
+/// ```
+///
 /// ## Request ID Middleware
 ///
 /// ```
@@ -350,7 +378,7 @@ impl Next {
 ///         state: AppState,
 ///         extensions: Extensions,
 ///         mut next: Next,
-///     ) -> Result<Option<Message>> {
+///     ) -> Result<Vec<Message>> {
 ///         use std::sync::atomic::{AtomicU64, Ordering};
 ///         static COUNTER: AtomicU64 = AtomicU64::new(0);
 ///
@@ -375,7 +403,11 @@ pub trait Middleware: Send + Sync + 'static {
     ///
     /// # Returns
     ///
-    /// Returns an optional message to send back to the client, or an error.
+    /// Returns the messages to send back to the client, in order, or an
+    /// error. Usually this is the result of `next.run()`, possibly modified;
+    /// a middleware that returns without calling it short-circuits the chain.
+    /// A handler that returns several messages, e.g. with
+    /// [`Messages`](crate::handler::Messages), passes all of them through.
     async fn handle(
         &self,
         message: Message,
@@ -383,7 +415,67 @@ pub trait Middleware: Send + Sync + 'static {
         state: AppState,
         extensions: Extensions,
         next: Next,
-    ) -> Result<Option<Message>>;
+    ) -> Result<Vec<Message>>;
+
+    /// Called when a WebSocket connection opens.
+    ///
+    /// Runs for middleware registered globally with
+    /// [`Router::layer()`](crate::router::Router::layer), in registration
+    /// order, before the connection's first message is handled. Use it for
+    /// cross-cutting concerns such as metrics, presence or auditing.
+    ///
+    /// Returning an error closes the connection with 1011 Internal Error,
+    /// like a failing
+    /// [`Router::on_connect_async()`](crate::router::Router::on_connect_async)
+    /// callback. The default implementation does nothing.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    /// use async_trait::async_trait;
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    ///
+    /// #[derive(Default)]
+    /// struct ActiveConnections(AtomicUsize);
+    ///
+    /// #[async_trait]
+    /// impl Middleware for ActiveConnections {
+    ///     async fn handle(
+    ///         &self,
+    ///         message: Message,
+    ///         conn: Connection,
+    ///         state: AppState,
+    ///         extensions: Extensions,
+    ///         next: Next,
+    ///     ) -> Result<Vec<Message>> {
+    ///         next.run(message, conn, state, extensions).await
+    ///     }
+    ///
+    ///     async fn on_connect(&self, _conn: &Connection, _state: &AppState) -> Result<()> {
+    ///         self.0.fetch_add(1, Ordering::Relaxed);
+    ///         Ok(())
+    ///     }
+    ///
+    ///     async fn on_disconnect(&self, _conn: &Connection, _state: &AppState) -> Result<()> {
+    ///         self.0.fetch_sub(1, Ordering::Relaxed);
+    ///         Ok(())
+    ///     }
+    /// }
+    /// ```
+    async fn on_connect(&self, _conn: &Connection, _state: &AppState) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called when a WebSocket connection closes.
+    ///
+    /// Runs for global middleware in registration order, after the
+    /// connection was removed from the
+    /// [`ConnectionManager`](crate::connection::ConnectionManager). Errors
+    /// are logged. The default implementation does nothing.
+    async fn on_disconnect(&self, _conn: &Connection, _state: &AppState) -> Result<()> {
+        Ok(())
+    }
 }
 
 /// Middleware chain holds all middlewares and the final handler.
@@ -513,7 +605,7 @@ impl MiddlewareChain {
         conn: Connection,
         state: AppState,
         extensions: Extensions,
-    ) -> Result<Option<Message>> {
+    ) -> Result<Vec<Message>> {
         let next = Next::new(Arc::new(self.clone()), 0);
         next.run(message, conn, state, extensions).await
     }
@@ -559,7 +651,7 @@ impl<F> FnMiddleware<F> {
 impl<F, Fut> Middleware for FnMiddleware<F>
 where
     F: Fn(Message, Connection, AppState, Extensions, Next) -> Fut + Send + Sync + 'static,
-    Fut: std::future::Future<Output = Result<Option<Message>>> + Send + 'static,
+    Fut: std::future::Future<Output = Result<Vec<Message>>> + Send + 'static,
 {
     async fn handle(
         &self,
@@ -568,7 +660,7 @@ where
         state: AppState,
         extensions: Extensions,
         next: Next,
-    ) -> Result<Option<Message>> {
+    ) -> Result<Vec<Message>> {
         (self.func)(message, conn, state, extensions, next).await
     }
 }
@@ -615,7 +707,7 @@ where
 pub fn from_fn<F, Fut>(f: F) -> Arc<FnMiddleware<F>>
 where
     F: Fn(Message, Connection, AppState, Extensions, Next) -> Fut + Send + Sync + 'static,
-    Fut: std::future::Future<Output = Result<Option<Message>>> + Send + 'static,
+    Fut: std::future::Future<Output = Result<Vec<Message>>> + Send + 'static,
 {
     FnMiddleware::new(f)
 }
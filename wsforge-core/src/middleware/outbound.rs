@@ -0,0 +1,187 @@
+//! Middleware for outgoing messages.
+//!
+//! [`Middleware`](super::Middleware) only sees the request-to-response path of
+//! a handler. [`OutboundMiddleware`] instead runs in each connection's write
+//! task, so it sees every data message sent to the client: handler responses,
+//! [`Connection::send()`] calls and broadcasts from the
+//! [`ConnectionManager`](crate::connection::ConnectionManager) alike.
+//!
+//! Register outbound middleware with
+//! [`Router::outbound_layer()`](crate::router::Router::outbound_layer). Layers
+//! run in the order they were added.
+//!
+//! # Overview
+//!
+//! - Only text and binary messages pass through outbound middleware. Ping,
+//!   pong and close frames are sent as they are.
+//! - A message for which a layer returns an error is dropped and the error is
+//!   logged. The connection stays open.
+//! - Because every message of a connection goes through its write task,
+//!   outbound middleware sees them in the order they reach the client.
+//!
+//! # Examples
+//!
+//! ## Stamping Sequence Numbers
+//!
+//! ```
+//! use wsforge::prelude::*;
+//! use async_trait::async_trait;
+//! use std::sync::atomic::{AtomicU64, Ordering};
+//!
+//! struct Sequence(AtomicU64);
+//!
+//! #[async_trait]
+//! impl OutboundMiddleware for Sequence {
+//!     async fn on_send(&self, message: Message, _conn: &Connection) -> Result<Message> {
+//!         let Some(text) = message.as_text() else {
+//!             return Ok(message);
+//!         };
+//!
+//!         let seq = self.0.fetch_add(1, Ordering::Relaxed);
+//!         Ok(Message::text(format!("{}:{}", seq, text)))
+//!     }
+//! }
+//!
+//! # fn example() {
+//! let router = Router::new().outbound_layer(std::sync::Arc::new(Sequence(AtomicU64::new(0))));
+//! # }
+//! ```
+
+use crate::connection::Connection;
+use crate::error::Result;
+use crate::message::Message;
+use async_trait::async_trait;
+use std::sync::Arc;
+use tracing::warn;
+
+/// Middleware that transforms or inspects outgoing messages.
+///
+/// Implementations receive each outgoing text or binary message together
+/// with the connection it is sent on, and return the message to write. Use
+/// the connection's [metadata](Connection::get_meta) for per-connection
+/// settings such as encryption keys.
+///
+/// # Implementation Guidelines
+///
+/// - **Return the message unchanged** if it doesn't concern you
+/// - **Keep it fast** - outbound middleware runs on every message, including
+///   every copy of a broadcast
+/// - **Return an error** to drop a message that must not be sent
+///
+/// # Examples
+///
+/// ## Payload Encryption
+///
+/// ```
+/// use wsforge::prelude::*;
+/// use async_trait::async_trait;
+///
+/// struct XorCipher {
+///     key: u8,
+/// }
+///
+/// #[async_trait]
+/// impl OutboundMiddleware for XorCipher {
+///     async fn on_send(&self, message: Message, _conn: &Connection) -> Result<Message> {
+///         let encrypted = message.as_bytes().iter().map(|b| b ^ self.key).collect();
+///         Ok(Message::binary(encrypted))
+///     }
+/// }
+/// ```
+#[async_trait]
+pub trait OutboundMiddleware: Send + Sync + 'static {
+    /// Processes an outgoing message.
+    ///
+    /// # Arguments
+    ///
+    /// * `message` - The message about to be written
+    /// * `conn` - The connection the message is written to
+    ///
+    /// # Returns
+    ///
+    /// The message to write, or an error to drop it.
+    async fn on_send(&self, message: Message, conn: &Connection) -> Result<Message>;
+}
+
+/// Runs `message` through `layers` in order.
+///
+/// Returns `None` if a layer failed, in which case the message must not be
+/// sent. Control frames are returned unchanged.
+pub(crate) async fn run_outbound(
+    layers: &[Arc<dyn OutboundMiddleware>],
+    mut message: Message,
+    conn: &Connection,
+) -> Option<Message> {
+    if !(message.is_text() || message.is_binary()) {
+        return Some(message);
+    }
+
+    for layer in layers {
+        match layer.on_send(message, conn).await {
+            Ok(next) => message = next,
+            Err(e) => {
+                warn!("📤 [{}] Outgoing message dropped: {}", conn.id(), e);
+                return None;
+            }
+        }
+    }
+
+    Some(message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Error;
+    use tokio::sync::mpsc;
+
+    struct Suffix(&'static str);
+
+    #[async_trait]
+    impl OutboundMiddleware for Suffix {
+        async fn on_send(&self, message: Message, _conn: &Connection) -> Result<Message> {
+            let text = message.as_text().unwrap_or_default();
+            Ok(Message::text(format!("{}{}", text, self.0)))
+        }
+    }
+
+    struct Reject;
+
+    #[async_trait]
+    impl OutboundMiddleware for Reject {
+        async fn on_send(&self, _message: Message, _conn: &Connection) -> Result<Message> {
+            Err(Error::custom("blocked"))
+        }
+    }
+
+    fn connection() -> Connection {
+        let (tx, _rx) = mpsc::unbounded_channel();
+        Connection::new("out".to_string(), "127.0.0.1:9000".parse().unwrap(), tx)
+    }
+
+    #[tokio::test]
+    async fn test_layers_run_in_order() {
+        let layers: Vec<Arc<dyn OutboundMiddleware>> =
+            vec![Arc::new(Suffix("-a")), Arc::new(Suffix("-b"))];
+
+        let message = run_outbound(&layers, Message::text("msg"), &connection()).await;
+        assert_eq!(message.unwrap().as_text(), Some("msg-a-b"));
+    }
+
+    #[tokio::test]
+    async fn test_error_drops_data_but_not_control_frames() {
+        let layers: Vec<Arc<dyn OutboundMiddleware>> = vec![Arc::new(Reject)];
+        let conn = connection();
+
+        assert!(
+            run_outbound(&layers, Message::text("msg"), &conn)
+                .await
+                .is_none()
+        );
+        assert!(
+            run_outbound(&layers, Message::close(), &conn)
+                .await
+                .is_some()
+        );
+    }
+}
@@ -0,0 +1,659 @@
+//! Rate limiting middleware.
+//!
+//! This module provides [`RateLimitMiddleware`], which limits how many messages
+//! each connection may send within a time window. Limits are enforced with a
+//! token bucket keyed by connection ID, so short bursts up to the limit are
+//! allowed while the sustained rate stays below `max_requests / window`.
+//!
+//! [`GlobalRateLimitMiddleware`] applies the same kind of limit to all
+//! connections together, to protect a shared downstream service.
+//!
+//! # Overview
+//!
+//! Every connection starts with a full bucket of `max_requests` tokens. Each
+//! message takes one token, and tokens refill continuously over `window`.
+//! When the bucket is empty, the message is rejected with an error, silently
+//! dropped, or held until a token frees up, depending on the configured
+//! [`RateLimitAction`].
+//!
+//! Buckets that have been idle for a whole window are full again, so they are
+//! removed automatically. Memory stays bounded by the number of recently
+//! active connections.
+//!
+//! # Examples
+//!
+//! ## Basic Usage
+//!
+//! ```
+//! use wsforge::prelude::*;
+//! use std::time::Duration;
+//!
+//! async fn echo(msg: Message) -> Result<Message> {
+//!     Ok(msg)
+//! }
+//!
+//! # async fn example() -> Result<()> {
+//! let router = Router::new()
+//!     // At most 20 messages per second per connection
+//!     .layer(RateLimitMiddleware::new(20, Duration::from_secs(1)))
+//!     .default_handler(handler(echo));
+//!
+//! router.listen("127.0.0.1:8080").await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! ## Dropping Excess Messages
+//!
+//! ```
+//! use wsforge::prelude::*;
+//! use std::time::Duration;
+//!
+//! # fn example() {
+//! // Silently ignore position updates beyond 60 per second
+//! let limiter = RateLimitMiddleware::with_action(
+//!     60,
+//!     Duration::from_secs(1),
+//!     RateLimitAction::Drop,
+//! );
+//! # }
+//! ```
+//!
+//! ## Server-Wide Limit
+//!
+//! ```
+//! use wsforge::prelude::*;
+//! use std::time::Duration;
+//!
+//! # async fn lookup(msg: Message) -> Result<String> { Ok(String::new()) }
+//! # fn example() {
+//! let router = Router::new()
+//!     // Each client may send 10 messages per second...
+//!     .layer(RateLimitMiddleware::new(10, Duration::from_secs(1)))
+//!     // ...but the upstream API takes at most 500 per second in total
+//!     .layer(GlobalRateLimitMiddleware::with_action(
+//!         500,
+//!         Duration::from_secs(1),
+//!         RateLimitAction::Wait(Duration::from_millis(200)),
+//!     ))
+//!     .default_handler(handler(lookup));
+//! # }
+//! ```
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use dashmap::DashMap;
+use tracing::{debug, warn};
+
+use crate::connection::{Connection, ConnectionId};
+use crate::error::{Error, Result};
+use crate::extractor::Extensions;
+use crate::message::Message;
+use crate::middleware::{Middleware, Next};
+use crate::state::AppState;
+
+/// What [`RateLimitMiddleware`] and [`GlobalRateLimitMiddleware`] do with a
+/// message over the limit.
+///
+/// # Examples
+///
+/// ```
+/// use wsforge::prelude::*;
+/// use std::time::Duration;
+///
+/// # fn example() {
+/// // The error response is sent to the client
+/// let reject = RateLimitAction::Reject;
+///
+/// // The message is ignored without a response
+/// let drop = RateLimitAction::Drop;
+///
+/// // The message waits up to 100ms for a token, then is rejected
+/// let wait = RateLimitAction::Wait(Duration::from_millis(100));
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum RateLimitAction {
+    /// Returns an error, which is reported to the client like any handler
+    /// error.
+    ///
+    /// **Use for**: Request/response protocols where clients should back off
+    Reject,
+
+    /// Drops the message without a response.
+    ///
+    /// **Use for**: High-frequency updates where stale messages don't matter
+    Drop,
+
+    /// Holds the message until a token is available, for at most the given
+    /// time, then rejects it like [`Reject`](Self::Reject).
+    ///
+    /// Held messages still occupy their handler task, so keep the wait short.
+    ///
+    /// **Use for**: Smoothing bursts in front of a downstream with a hard
+    /// rate ceiling
+    Wait(Duration),
+}
+
+/// Token bucket state of a single connection, or of the whole server.
+struct Bucket {
+    /// Tokens currently available
+    tokens: f64,
+    /// When `tokens` was last refilled
+    last_refill: Instant,
+}
+
+impl Bucket {
+    /// Creates a full bucket.
+    fn full(capacity: u32) -> Self {
+        Self {
+            tokens: f64::from(capacity),
+            last_refill: Instant::now(),
+        }
+    }
+
+    /// Refills the bucket and takes a token. If it is empty, returns how
+    /// long until the next token is available.
+    fn take(&mut self, capacity: u32, window: Duration) -> std::result::Result<(), Duration> {
+        let now = Instant::now();
+        let capacity = f64::from(capacity);
+        let per_second = capacity / window.as_secs_f64();
+
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * per_second).min(capacity);
+        self.last_refill = now;
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - self.tokens) / per_second))
+        }
+    }
+}
+
+/// Applies `action` to a message, retrying `acquire` while it may wait.
+///
+/// Returns `Ok(true)` if the message may proceed and `Ok(false)` if it
+/// should be dropped. `reject` builds the error for rejected messages.
+async fn admit(
+    action: RateLimitAction,
+    mut acquire: impl FnMut() -> std::result::Result<(), Duration>,
+    reject: impl Fn() -> Error,
+) -> Result<bool> {
+    let mut retry_in = match acquire() {
+        Ok(()) => return Ok(true),
+        Err(retry_in) => retry_in,
+    };
+
+    match action {
+        RateLimitAction::Reject => Err(reject()),
+        RateLimitAction::Drop => Ok(false),
+        RateLimitAction::Wait(max_wait) => {
+            let deadline = Instant::now() + max_wait;
+            loop {
+                if Instant::now() + retry_in > deadline {
+                    return Err(reject());
+                }
+                tokio::time::sleep(retry_in).await;
+                // Other waiters may have taken the token first
+                match acquire() {
+                    Ok(()) => return Ok(true),
+                    Err(next) => retry_in = next,
+                }
+            }
+        }
+    }
+}
+
+/// Built-in middleware that limits the message rate of each connection.
+///
+/// Uses a token bucket per connection: up to `max_requests` messages may
+/// arrive in a burst, after which messages are accepted at a rate of
+/// `max_requests` per `window`. Over-limit messages never reach the handler.
+///
+/// # Features
+///
+/// - **Per connection**: Each connection has its own budget, keyed by
+///   [`Connection::id()`]
+/// - **Burst friendly**: Token bucket instead of fixed windows
+/// - **Bounded memory**: Idle buckets expire automatically
+/// - **Configurable action**: Reject with an error or drop silently
+///
+/// # Examples
+///
+/// ## In Router
+///
+/// ```
+/// use wsforge::prelude::*;
+/// use std::time::Duration;
+///
+/// async fn handler(msg: Message) -> Result<String> {
+///     Ok("processed".to_string())
+/// }
+///
+/// # fn example() {
+/// let router = Router::new()
+///     .layer(LoggerMiddleware::new())
+///     .layer(RateLimitMiddleware::new(100, Duration::from_secs(60)))
+///     .default_handler(handler(handler));
+/// # }
+/// ```
+///
+/// ## Per Route
+///
+/// ```
+/// use wsforge::prelude::*;
+/// use std::time::Duration;
+///
+/// async fn search(msg: Message) -> Result<String> {
+///     Ok("results".to_string())
+/// }
+///
+/// # fn example() {
+/// let router = Router::new().route_with_layers(
+///     "/search",
+///     vec![RateLimitMiddleware::new(5, Duration::from_secs(1))],
+///     handler(search),
+/// );
+/// # }
+/// ```
+pub struct RateLimitMiddleware {
+    /// Bucket capacity, and number of tokens refilled per window
+    max_requests: u32,
+    /// Time it takes to refill an empty bucket
+    window: Duration,
+    /// What to do with messages over the limit
+    action: RateLimitAction,
+    /// Token buckets keyed by connection ID
+    buckets: DashMap<ConnectionId, Bucket>,
+    /// When idle buckets were last removed
+    last_sweep: Mutex<Instant>,
+}
+
+impl RateLimitMiddleware {
+    /// Creates a rate limiter that rejects messages over the limit.
+    ///
+    /// Each connection may send `max_requests` messages per `window`.
+    /// Over-limit messages fail with a `429 rate_limited`
+    /// [`Error::Coded`](crate::error::Error::Coded) error, which the router
+    /// sends to the client as JSON unless a custom
+    /// [`Router::error_handler()`](crate::router::Router::error_handler) is set.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_requests` - Messages allowed per window, also the burst size
+    /// * `window` - Length of the window
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_requests` is zero or `window` is zero.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    /// use std::time::Duration;
+    ///
+    /// # fn example() {
+    /// let limiter = RateLimitMiddleware::new(10, Duration::from_secs(1));
+    /// # }
+    /// ```
+    pub fn new(max_requests: u32, window: Duration) -> Arc<Self> {
+        Self::with_action(max_requests, window, RateLimitAction::Reject)
+    }
+
+    /// Creates a rate limiter with a custom over-limit action.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_requests` - Messages allowed per window, also the burst size
+    /// * `window` - Length of the window
+    /// * `action` - What to do with messages over the limit
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_requests` is zero or `window` is zero.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    /// use std::time::Duration;
+    ///
+    /// # fn example() {
+    /// let limiter = RateLimitMiddleware::with_action(
+    ///     30,
+    ///     Duration::from_secs(1),
+    ///     RateLimitAction::Drop,
+    /// );
+    /// # }
+    /// ```
+    pub fn with_action(max_requests: u32, window: Duration, action: RateLimitAction) -> Arc<Self> {
+        assert!(max_requests > 0, "max_requests must be greater than zero");
+        assert!(!window.is_zero(), "window must be greater than zero");
+
+        Arc::new(Self {
+            max_requests,
+            window,
+            action,
+            buckets: DashMap::new(),
+            last_sweep: Mutex::new(Instant::now()),
+        })
+    }
+
+    /// Takes a token from the connection's bucket. If it is empty, returns
+    /// how long until the next token is available.
+    fn try_acquire(&self, conn_id: &ConnectionId) -> std::result::Result<(), Duration> {
+        self.sweep();
+
+        self.buckets
+            .entry(conn_id.clone())
+            .or_insert_with(|| Bucket::full(self.max_requests))
+            .take(self.max_requests, self.window)
+    }
+
+    /// Removes buckets that have been idle for a whole window, at most once
+    /// per window.
+    fn sweep(&self) {
+        {
+            let mut last_sweep = self.last_sweep.lock().unwrap();
+            if last_sweep.elapsed() < self.window {
+                return;
+            }
+            *last_sweep = Instant::now();
+        }
+
+        // An idle bucket has refilled completely, so forgetting it is lossless
+        self.buckets
+            .retain(|_, bucket| bucket.last_refill.elapsed() < self.window);
+        debug!(
+            "🧹 Rate limiter tracking {} connections",
+            self.buckets.len()
+        );
+    }
+}
+
+#[async_trait]
+impl Middleware for RateLimitMiddleware {
+    async fn handle(
+        &self,
+        message: Message,
+        conn: Connection,
+        state: AppState,
+        extensions: Extensions,
+        next: Next,
+    ) -> Result<Vec<Message>> {
+        let admitted = admit(
+            self.action,
+            || self.try_acquire(conn.id()),
+            || Error::coded(429, "rate_limited", "Rate limit exceeded"),
+        )
+        .await
+        .inspect_err(|_| warn!("🚦 [{}] Rate limit exceeded", conn.id()))?;
+
+        if admitted {
+            next.run(message, conn, state, extensions).await
+        } else {
+            warn!("🚦 [{}] Rate limit exceeded", conn.id());
+            Ok(Vec::new())
+        }
+    }
+
+    async fn on_disconnect(&self, conn: &Connection, _state: &AppState) -> Result<()> {
+        self.buckets.remove(conn.id());
+        Ok(())
+    }
+}
+
+/// Built-in middleware that limits the message rate of the whole server.
+///
+/// All connections share one token bucket: up to `max_requests` messages may
+/// arrive in a burst, after which messages are accepted at a rate of
+/// `max_requests` per `window`, no matter which client sent them. Use it to
+/// shed load before it reaches a downstream service with a hard rate
+/// ceiling.
+///
+/// The limit is independent of [`RateLimitMiddleware`]; layer both to cap
+/// each client and the server as a whole. Messages rejected by an earlier
+/// layer don't use up the global budget, so add the per-connection limit
+/// first.
+///
+/// # Examples
+///
+/// ```
+/// use wsforge::prelude::*;
+/// use std::time::Duration;
+///
+/// async fn quote(msg: Message) -> Result<String> {
+///     Ok("price".to_string())
+/// }
+///
+/// # fn example() {
+/// // The quote API allows 100 requests per second in total
+/// let router = Router::new().route_with_layers(
+///     "/quote",
+///     vec![GlobalRateLimitMiddleware::new(100, Duration::from_secs(1))],
+///     handler(quote),
+/// );
+/// # }
+/// ```
+pub struct GlobalRateLimitMiddleware {
+    /// Bucket capacity, and number of tokens refilled per window
+    max_requests: u32,
+    /// Time it takes to refill an empty bucket
+    window: Duration,
+    /// What to do with messages over the limit
+    action: RateLimitAction,
+    /// The server-wide token bucket
+    bucket: Mutex<Bucket>,
+}
+
+impl GlobalRateLimitMiddleware {
+    /// Creates a server-wide rate limiter that rejects messages over the
+    /// limit.
+    ///
+    /// At most `max_requests` messages per `window` are let through across
+    /// all connections. Over-limit messages fail with a `503 server_busy`
+    /// [`Error::Coded`](crate::error::Error::Coded) error.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_requests` - Messages allowed per window, also the burst size
+    /// * `window` - Length of the window
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_requests` is zero or `window` is zero.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    /// use std::time::Duration;
+    ///
+    /// # fn example() {
+    /// let limiter = GlobalRateLimitMiddleware::new(1000, Duration::from_secs(1));
+    /// # }
+    /// ```
+    pub fn new(max_requests: u32, window: Duration) -> Arc<Self> {
+        Self::with_action(max_requests, window, RateLimitAction::Reject)
+    }
+
+    /// Creates a server-wide rate limiter with a custom over-limit action.
+    ///
+    /// With [`RateLimitAction::Wait`], bursts are queued and let through as
+    /// tokens refill, and only messages that would wait too long are
+    /// rejected.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_requests` - Messages allowed per window, also the burst size
+    /// * `window` - Length of the window
+    /// * `action` - What to do with messages over the limit
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_requests` is zero or `window` is zero.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    /// use std::time::Duration;
+    ///
+    /// # fn example() {
+    /// let limiter = GlobalRateLimitMiddleware::with_action(
+    ///     50,
+    ///     Duration::from_secs(1),
+    ///     RateLimitAction::Wait(Duration::from_millis(500)),
+    /// );
+    /// # }
+    /// ```
+    pub fn with_action(max_requests: u32, window: Duration, action: RateLimitAction) -> Arc<Self> {
+        assert!(max_requests > 0, "max_requests must be greater than zero");
+        assert!(!window.is_zero(), "window must be greater than zero");
+
+        Arc::new(Self {
+            max_requests,
+            window,
+            action,
+            bucket: Mutex::new(Bucket::full(max_requests)),
+        })
+    }
+
+    /// Takes a token from the server-wide bucket. If it is empty, returns
+    /// how long until the next token is available.
+    fn try_acquire(&self) -> std::result::Result<(), Duration> {
+        self.bucket
+            .lock()
+            .unwrap()
+            .take(self.max_requests, self.window)
+    }
+}
+
+#[async_trait]
+impl Middleware for GlobalRateLimitMiddleware {
+    async fn handle(
+        &self,
+        message: Message,
+        conn: Connection,
+        state: AppState,
+        extensions: Extensions,
+        next: Next,
+    ) -> Result<Vec<Message>> {
+        let admitted = admit(
+            self.action,
+            || self.try_acquire(),
+            || Error::coded(503, "server_busy", "Server message rate exceeded"),
+        )
+        .await
+        .inspect_err(|_| warn!("🚦 [{}] Global rate limit exceeded", conn.id()))?;
+
+        if admitted {
+            next.run(message, conn, state, extensions).await
+        } else {
+            warn!("🚦 [{}] Global rate limit exceeded", conn.id());
+            Ok(Vec::new())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bucket_allows_burst_then_refills() {
+        let limiter = RateLimitMiddleware::new(2, Duration::from_millis(100));
+        let id = "conn_a".to_string();
+
+        assert!(limiter.try_acquire(&id).is_ok());
+        assert!(limiter.try_acquire(&id).is_ok());
+        assert!(limiter.try_acquire(&id).is_err());
+
+        // Other connections have their own budget
+        assert!(limiter.try_acquire(&"conn_b".to_string()).is_ok());
+
+        std::thread::sleep(Duration::from_millis(60));
+        assert!(limiter.try_acquire(&id).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_bucket_removed_on_disconnect() {
+        let limiter = RateLimitMiddleware::new(1, Duration::from_secs(60));
+        let (tx, _rx) = tokio::sync::mpsc::unbounded_channel();
+        let conn = Connection::new("gone".to_string(), "127.0.0.1:9000".parse().unwrap(), tx);
+
+        assert!(limiter.try_acquire(conn.id()).is_ok());
+        limiter
+            .on_disconnect(&conn, &AppState::new())
+            .await
+            .unwrap();
+        assert!(limiter.buckets.is_empty());
+    }
+
+    #[test]
+    fn test_idle_buckets_expire() {
+        let limiter = RateLimitMiddleware::new(1, Duration::from_millis(20));
+
+        assert!(limiter.try_acquire(&"idle".to_string()).is_ok());
+        assert_eq!(limiter.buckets.len(), 1);
+
+        std::thread::sleep(Duration::from_millis(30));
+        assert!(limiter.try_acquire(&"active".to_string()).is_ok());
+        assert_eq!(limiter.buckets.len(), 1);
+        assert!(limiter.buckets.contains_key("active"));
+    }
+
+    #[tokio::test]
+    async fn test_global_limit_shared_by_connections() {
+        let limiter = GlobalRateLimitMiddleware::new(2, Duration::from_secs(60));
+
+        assert!(limiter.try_acquire().is_ok());
+        assert!(limiter.try_acquire().is_ok());
+        let retry_in = limiter.try_acquire().unwrap_err();
+        assert!(retry_in > Duration::from_secs(25));
+
+        let rejected = admit(
+            limiter.action,
+            || limiter.try_acquire(),
+            || Error::custom("busy"),
+        )
+        .await;
+        assert!(rejected.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_wait_action_holds_until_refill() {
+        let limiter = GlobalRateLimitMiddleware::with_action(
+            1,
+            Duration::from_millis(40),
+            RateLimitAction::Wait(Duration::from_secs(1)),
+        );
+        assert!(limiter.try_acquire().is_ok());
+
+        let started = Instant::now();
+        let admitted = admit(
+            limiter.action,
+            || limiter.try_acquire(),
+            || Error::custom("busy"),
+        )
+        .await
+        .unwrap();
+        assert!(admitted);
+        assert!(started.elapsed() >= Duration::from_millis(30));
+
+        // A wait shorter than the refill time gives up immediately
+        let short = admit(
+            RateLimitAction::Wait(Duration::from_millis(5)),
+            || limiter.try_acquire(),
+            || Error::custom("busy"),
+        )
+        .await;
+        assert!(short.is_err());
+    }
+}
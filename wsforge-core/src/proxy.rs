@@ -0,0 +1,169 @@
+//! Client addresses reported by reverse proxies.
+//!
+//! Behind a load balancer, the TCP peer of every connection is the proxy.
+//! Proxies pass the original client address in the `Forwarded` (RFC 7239) or
+//! `X-Forwarded-For` header, appending each hop. Any client can send these
+//! headers too, and a proxy appends to whatever the client sent, so they are
+//! only believed when the peer is a trusted proxy, and only from the nearest
+//! hop back to the first address that isn't a trusted proxy.
+
+use crate::handshake::HeaderMap;
+use std::net::{IpAddr, SocketAddr};
+
+/// Which peers may report client addresses.
+///
+/// See [`Router::trust_proxy_headers()`](crate::router::Router::trust_proxy_headers)
+/// and [`Router::trusted_proxies()`](crate::router::Router::trusted_proxies).
+#[derive(Debug, Clone)]
+pub(crate) enum ProxyTrust {
+    /// Every peer is a single proxy, so only the hop it appended is believed
+    Any,
+    /// Only these peers are proxies; an empty list trusts nobody
+    Only(Vec<IpAddr>),
+}
+
+impl ProxyTrust {
+    /// Returns the client address forwarded by `peer`, or `None` if the peer
+    /// isn't trusted or sent no usable forwarding header.
+    pub(crate) fn client_ip(&self, peer: IpAddr, headers: &HeaderMap) -> Option<IpAddr> {
+        let trusted = match self {
+            // The proxy appends the address it saw; everything before it
+            // came from the client
+            ProxyTrust::Any => return forwarded_chain(headers)?.pop().flatten(),
+            ProxyTrust::Only(trusted) => trusted,
+        };
+        if !trusted.contains(&peer) {
+            return None;
+        }
+
+        // Nearest hop first; the first address that isn't a trusted proxy
+        // is the client, everything before it may be forged
+        let chain = forwarded_chain(headers)?;
+        for hop in chain.iter().rev() {
+            let ip = (*hop)?;
+            if !trusted.contains(&ip) {
+                return Some(ip);
+            }
+        }
+
+        // Every hop was a trusted proxy, so the first one is the client
+        chain.first().copied().flatten()
+    }
+}
+
+/// Returns the forwarded addresses, client first, from `Forwarded` or else
+/// `X-Forwarded-For`. Entries that aren't IP addresses, such as `unknown`,
+/// are `None`.
+fn forwarded_chain(headers: &HeaderMap) -> Option<Vec<Option<IpAddr>>> {
+    let forwarded: Vec<_> = headers
+        .get_all("forwarded")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .filter_map(|element| {
+            element.split(';').find_map(|pair| {
+                let (key, value) = pair.split_once('=')?;
+                key.trim()
+                    .eq_ignore_ascii_case("for")
+                    .then(|| parse_node(value))
+            })
+        })
+        .collect();
+    if !forwarded.is_empty() {
+        return Some(forwarded);
+    }
+
+    let forwarded_for: Vec<_> = headers
+        .get_all("x-forwarded-for")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(parse_node)
+        .collect();
+    (!forwarded_for.is_empty()).then_some(forwarded_for)
+}
+
+/// Parses a node such as `192.0.2.60`, `192.0.2.60:8080` or
+/// `"[2001:db8::1]:4711"`.
+fn parse_node(node: &str) -> Option<IpAddr> {
+    let node = node.trim().trim_matches('"');
+
+    if let Some(rest) = node.strip_prefix('[') {
+        let (ip, _) = rest.split_once(']')?;
+        return ip.parse().ok();
+    }
+
+    node.parse()
+        .ok()
+        .or_else(|| node.parse::<SocketAddr>().ok().map(|addr| addr.ip()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(name: &'static str, values: &[&str]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for value in values {
+            headers.append(name, value.parse().unwrap());
+        }
+        headers
+    }
+
+    fn ip(ip: &str) -> IpAddr {
+        ip.parse().unwrap()
+    }
+
+    #[test]
+    fn test_client_ip_skips_trusted_hops() {
+        let trust = ProxyTrust::Only(vec![ip("10.0.0.1"), ip("10.0.0.2")]);
+        let xff = headers("x-forwarded-for", &["6.6.6.6, 203.0.113.7", "10.0.0.2"]);
+
+        // The spoofed leading entry is ignored
+        assert_eq!(
+            trust.client_ip(ip("10.0.0.1"), &xff),
+            Some(ip("203.0.113.7"))
+        );
+        // Untrusted peers can't forward anything
+        assert_eq!(trust.client_ip(ip("198.51.100.1"), &xff), None);
+        assert_eq!(trust.client_ip(ip("10.0.0.1"), &HeaderMap::new()), None);
+
+        // An unparseable hop stops the walk
+        let xff = headers("x-forwarded-for", &["203.0.113.7, unknown"]);
+        assert_eq!(trust.client_ip(ip("10.0.0.1"), &xff), None);
+
+        // An empty list trusts nobody
+        let xff = headers("x-forwarded-for", &["203.0.113.7"]);
+        assert_eq!(
+            ProxyTrust::Only(Vec::new()).client_ip(ip("10.0.0.1"), &xff),
+            None
+        );
+    }
+
+    #[test]
+    fn test_trust_any_takes_nearest_hop() {
+        // The client sent a forged entry, which the proxy appended to
+        let xff = headers("x-forwarded-for", &["6.6.6.6, 203.0.113.7"]);
+        assert_eq!(
+            ProxyTrust::Any.client_ip(ip("10.0.0.1"), &xff),
+            Some(ip("203.0.113.7"))
+        );
+    }
+
+    #[test]
+    fn test_forwarded_header() {
+        let trust = ProxyTrust::Only(vec![ip("10.0.0.1"), ip("192.0.2.60")]);
+        let forwarded = headers(
+            "forwarded",
+            &[r#"for="[2001:db8::1]:4711";proto=https, For=192.0.2.60:8080;by=10.0.0.1"#],
+        );
+
+        // Every hop is a trusted proxy, so the first one is the client
+        assert_eq!(
+            trust.client_ip(ip("10.0.0.1"), &forwarded),
+            Some(ip("2001:db8::1"))
+        );
+        assert_eq!(parse_node("192.0.2.60:8080"), Some(ip("192.0.2.60")));
+        assert_eq!(parse_node("_hidden"), None);
+    }
+}
@@ -0,0 +1,87 @@
+//! A stream wrapper that replays already-read bytes.
+//!
+//! The router reads the whole request head to decide between HTTP and
+//! WebSocket handling, then wraps the stream in [`Rewind`] so the handshake
+//! sees the full request again. This works for any transport, including TLS
+//! streams that can't be peeked.
+
+use pin_project_lite::pin_project;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+pin_project! {
+    /// A stream that yields `prefix` before reading from `inner`.
+    pub(crate) struct Rewind<S> {
+        prefix: Vec<u8>,
+        position: usize,
+        #[pin]
+        inner: S,
+    }
+}
+
+impl<S> Rewind<S> {
+    /// Creates a stream that replays `prefix` before reading from `inner`.
+    pub(crate) fn new(prefix: Vec<u8>, inner: S) -> Self {
+        Self {
+            prefix,
+            position: 0,
+            inner,
+        }
+    }
+}
+
+impl<S: AsyncRead> AsyncRead for Rewind<S> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.project();
+
+        if *this.position < this.prefix.len() {
+            let remaining = &this.prefix[*this.position..];
+            let n = remaining.len().min(buf.remaining());
+            buf.put_slice(&remaining[..n]);
+            *this.position += n;
+            return Poll::Ready(Ok(()));
+        }
+
+        this.inner.poll_read(cx, buf)
+    }
+}
+
+impl<S: AsyncWrite> AsyncWrite for Rewind<S> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.project().inner.poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.project().inner.poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.project().inner.poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncReadExt;
+
+    #[tokio::test]
+    async fn test_replays_prefix_then_inner() {
+        let inner: &[u8] = b" world";
+        let mut stream = Rewind::new(b"hello".to_vec(), inner);
+
+        let mut out = String::new();
+        stream.read_to_string(&mut out).await.unwrap();
+        assert_eq!(out, "hello world");
+    }
+}
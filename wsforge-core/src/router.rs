@@ -144,20 +144,75 @@
 //! # }
 //! ```
 
-use crate::connection::{ConnectionId, ConnectionManager, handle_websocket};
+use crate::broadcast::BroadcastBackend;
+use crate::connection::{
+    Coalesce, CoalesceStrategy, Connection, ConnectionHook, ConnectionId, ConnectionManager,
+    ConnectionOptions, DisconnectHook, DisconnectReason, HelloHook, KeepAlive, SendLimit,
+    SlowClient, serve_websocket,
+};
+use crate::cors::CorsConfig;
 use crate::error::{Error, Result};
-use crate::extractor::Extensions;
-use crate::handler::Handler;
+use crate::extractor::{Extensions, REQUEST_HEADERS_KEY, REQUEST_PATH_KEY, REQUEST_QUERY_KEY};
+use crate::handler::{HANDLER_ERROR_KEY, Handler, JSON_RESPONSE_KEY};
+use crate::handshake::{HandshakeCallback, HandshakeDecision, HeaderMap, Outcome};
 use crate::message::Message;
-use crate::middleware::{Middleware, MiddlewareChain};
+use crate::metrics::Metrics;
+use crate::middleware::{Middleware, MiddlewareChain, OutboundMiddleware};
+use crate::proxy::ProxyTrust;
+use crate::rewind::Rewind;
+use crate::rpc::{self, RpcCall};
 use crate::state::AppState;
+use crate::testing::TestClient;
+#[cfg(feature = "tls")]
+use crate::tls::TlsConfig;
 use dashmap::DashMap;
-use std::net::SocketAddr;
+use futures_util::future::BoxFuture;
+use std::future::Future;
+use std::net::{IpAddr, SocketAddr};
 use std::path::PathBuf;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite};
+#[cfg(unix)]
+use tokio::net::UnixListener;
 use tokio::net::{TcpListener, TcpStream};
-use tokio_tungstenite::accept_async;
-use tracing::{error, info};
+use tokio::sync::mpsc;
+use tokio::task::JoinSet;
+use tokio_tungstenite::accept_hdr_async_with_config;
+use tokio_tungstenite::tungstenite::handshake::server::{ErrorResponse, Request, Response};
+use tokio_tungstenite::tungstenite::http::{HeaderName, HeaderValue, StatusCode};
+use tracing::{Instrument, error, info, warn};
+
+/// Protocol settings applied to every WebSocket connection.
+///
+/// See [`Router::ws_config()`].
+pub use tokio_tungstenite::tungstenite::protocol::WebSocketConfig;
+
+/// Size limits a [`Router`] enforces on incoming WebSocket traffic.
+///
+/// Returned by [`Router::config()`]. A client that exceeds a limit is
+/// disconnected with close code 1009 (Message Too Big).
+///
+/// # Examples
+///
+/// ```
+/// use wsforge::prelude::*;
+///
+/// # fn example() {
+/// let router = Router::new().max_message_size(1024 * 1024);
+/// assert_eq!(router.config().max_message_size, Some(1024 * 1024));
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RouterConfig {
+    /// Largest message accepted from a client, in bytes. `None` means unlimited.
+    pub max_message_size: Option<usize>,
+    /// Largest single frame accepted from a client, in bytes. `None` means unlimited.
+    pub max_frame_size: Option<usize>,
+    /// Largest HTTP request head accepted from a client, in bytes
+    pub max_header_size: usize,
+}
 
 /// Represents a single route with its path and middleware chain.
 ///
@@ -190,6 +245,124 @@ pub struct Route {
     pub chain: Arc<MiddlewareChain>,
 }
 
+/// A plain HTTP request routed to an [`http_route()`](Router::http_route)
+/// handler.
+///
+/// # Examples
+///
+/// ```
+/// use wsforge::prelude::*;
+///
+/// # fn example() {
+/// let router = Router::new().http_route("/whoami", |req: HttpRequest| async move {
+///     let agent = req
+///         .headers
+///         .get("user-agent")
+///         .and_then(|v| v.to_str().ok())
+///         .unwrap_or("unknown")
+///         .to_string();
+///     (200, "text/plain", agent)
+/// });
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct HttpRequest {
+    /// Request method, `GET` or `HEAD`
+    pub method: String,
+    /// Request path without the query string, e.g. `/health`
+    pub path: String,
+    /// Raw query string without the leading `?`, if any
+    pub query: Option<String>,
+    /// Request headers
+    pub headers: HeaderMap,
+}
+
+/// Async handler for a plain HTTP route, producing status, content type and
+/// body.
+type HttpHandler =
+    Arc<dyn Fn(HttpRequest) -> BoxFuture<'static, (u16, String, Vec<u8>)> + Send + Sync>;
+
+/// Callback that converts a handler error into an optional response.
+type ErrorHandler = Arc<dyn Fn(&Error, &Connection) -> Option<Message> + Send + Sync>;
+
+/// Rewrites an incoming message before it is routed.
+type PreProcessor = Arc<dyn Fn(Message, &Connection) -> Result<Message> + Send + Sync>;
+
+/// Picks the route key for a message.
+type RouteMatcher = Arc<dyn Fn(&Message) -> Option<String> + Send + Sync>;
+
+/// Receives messages dropped because a connection's send queue was full.
+type DroppedMessageHook = Arc<dyn Fn(ConnectionId, Message) + Send + Sync>;
+
+/// Callback told why a connection ended.
+type DisconnectReasonHook =
+    Arc<dyn Fn(&Arc<ConnectionManager>, ConnectionId, &DisconnectReason) + Send + Sync>;
+
+/// A static file handler and the URL prefix it is mounted under.
+#[derive(Clone)]
+struct StaticMount {
+    /// Prefix stripped from request paths, e.g. `/assets`; `None` for `/`
+    prefix: Option<String>,
+    handler: crate::static_files::StaticFileHandler,
+}
+
+impl StaticMount {
+    /// Maps a request path to the path of a file in this mount by stripping
+    /// the prefix, if any.
+    ///
+    /// Returns `None` if the path lies outside the prefix.
+    fn file_path(&self, path: &str) -> Option<String> {
+        let Some(prefix) = &self.prefix else {
+            return Some(path.to_string());
+        };
+
+        let rest = path.strip_prefix(prefix.as_str())?;
+        match rest.chars().next() {
+            None | Some('?') => Some(format!("/{}", rest)),
+            Some('/') => Some(rest.to_string()),
+            // `/assetsfoo` doesn't belong to `/assets`
+            Some(_) => None,
+        }
+    }
+}
+
+/// Produces the ID of a new connection from its peer address.
+type IdGenerator = Arc<dyn Fn(SocketAddr) -> ConnectionId + Send + Sync>;
+
+/// Decides whether an upgrade from the given `Origin` is allowed.
+type OriginCheck = Arc<dyn Fn(&str) -> bool + Send + Sync>;
+
+/// Async callback run when a connection opens or closes.
+type LifecycleHook = Arc<
+    dyn Fn(Arc<ConnectionManager>, ConnectionId) -> BoxFuture<'static, Result<()>> + Send + Sync,
+>;
+
+/// Async callback run during graceful shutdown, before connections are closed.
+type ShutdownHook =
+    Arc<dyn Fn(Arc<ConnectionManager>) -> BoxFuture<'static, Result<()>> + Send + Sync>;
+
+/// A place under the [`Router::max_connections()`] limit, held from the
+/// handshake until the connection ends and released on drop.
+struct ConnectionSlot(Arc<AtomicUsize>);
+
+impl ConnectionSlot {
+    /// Takes a slot unless `max` are already taken.
+    fn reserve(taken: &Arc<AtomicUsize>, max: usize) -> Option<Self> {
+        taken
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |n| {
+                (n < max).then_some(n + 1)
+            })
+            .ok()
+            .map(|_| Self(taken.clone()))
+    }
+}
+
+impl Drop for ConnectionSlot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
 /// The main router for WebSocket servers with middleware support.
 ///
 /// `Router` is the central component that manages routing, middleware, state, connections,
@@ -254,20 +427,65 @@ pub struct Route {
 /// ```
 pub struct Router {
     routes: Arc<DashMap<String, Arc<MiddlewareChain>>>,
+    path_routes: Arc<DashMap<String, Arc<MiddlewareChain>>>,
+    typed_routes: Arc<DashMap<String, Arc<MiddlewareChain>>>,
+    binary_routes: Arc<DashMap<u8, Arc<MiddlewareChain>>>,
+    rpc_methods: Arc<DashMap<String, Arc<MiddlewareChain>>>,
+    type_field: String,
     global_middlewares: Vec<Arc<dyn Middleware>>,
+    outbound_middlewares: Vec<Arc<dyn OutboundMiddleware>>,
     state: AppState,
     connection_manager: Arc<ConnectionManager>,
     on_connect: Option<Arc<dyn Fn(&Arc<ConnectionManager>, ConnectionId) + Send + Sync>>,
+    hello: Option<HelloHook>,
     on_disconnect: Option<Arc<dyn Fn(&Arc<ConnectionManager>, ConnectionId) + Send + Sync>>,
+    on_disconnect_with_reason: Option<DisconnectReasonHook>,
+    on_connect_async: Option<LifecycleHook>,
+    on_disconnect_async: Option<LifecycleHook>,
+    on_shutdown: Option<ShutdownHook>,
+    broadcast_backend: Option<Arc<dyn BroadcastBackend>>,
     default_chain: Option<Arc<MiddlewareChain>>,
-    static_handler: Option<crate::static_files::StaticFileHandler>,
+    default_text_chain: Option<Arc<MiddlewareChain>>,
+    default_binary_chain: Option<Arc<MiddlewareChain>>,
+    static_mounts: Vec<StaticMount>,
+    http_routes: Arc<DashMap<String, HttpHandler>>,
+    cors: Option<CorsConfig>,
+    drain_timeout: Duration,
+    handshake_timeout: Duration,
+    max_header_size: usize,
+    keepalive: Option<KeepAlive>,
+    slow_client: Option<SlowClient>,
+    max_pending: Option<usize>,
+    on_message_dropped: Option<DroppedMessageHook>,
+    coalesce: Option<Coalesce>,
+    ws_config: Option<WebSocketConfig>,
+    strict_utf8: bool,
+    ordered: bool,
+    text_pong: Option<(String, String)>,
+    strict_routing: bool,
+    on_handshake: Option<HandshakeCallback>,
+    error_handler: Option<ErrorHandler>,
+    pre_process: Option<PreProcessor>,
+    route_matcher: Option<RouteMatcher>,
+    max_connections: Option<usize>,
+    origin_check: Option<OriginCheck>,
+    proxy_trust: Option<ProxyTrust>,
+    handler_timeout: Option<Duration>,
+    metrics: Option<Arc<dyn Metrics>>,
+    id_generator: Option<IdGenerator>,
+    /// Number of the next default connection ID, shared by clones
+    next_id: Arc<AtomicU64>,
+    /// Connection slots taken under `max_connections`, shared by clones
+    connection_slots: Arc<AtomicUsize>,
 }
 
 impl Router {
     /// Creates a new empty router.
     ///
-    /// The router starts with no routes, no middleware, no state, and no handlers.
-    /// Use the builder methods to configure it.
+    /// The router starts with no routes, no middleware, and no handlers. Its
+    /// state holds only its [`ConnectionManager`], for the
+    /// [`Manager`](crate::extractor::Manager) extractor. Use the builder
+    /// methods to configure it.
     ///
     /// # Examples
     ///
@@ -277,22 +495,70 @@ impl Router {
     /// let router = Router::new();
     /// ```
     pub fn new() -> Self {
+        // Registered up front so the `Manager` extractor always finds it
+        let connection_manager = Arc::new(ConnectionManager::new());
+        let state = AppState::new();
+        state.insert(connection_manager.clone());
+
         Self {
             routes: Arc::new(DashMap::new()),
+            path_routes: Arc::new(DashMap::new()),
+            typed_routes: Arc::new(DashMap::new()),
+            binary_routes: Arc::new(DashMap::new()),
+            rpc_methods: Arc::new(DashMap::new()),
+            type_field: "type".to_string(),
             global_middlewares: Vec::new(),
-            state: AppState::new(),
-            connection_manager: Arc::new(ConnectionManager::new()),
+            outbound_middlewares: Vec::new(),
+            state,
+            connection_manager,
             on_connect: None,
+            hello: None,
             on_disconnect: None,
+            on_disconnect_with_reason: None,
+            on_connect_async: None,
+            on_disconnect_async: None,
+            on_shutdown: None,
+            broadcast_backend: None,
             default_chain: None,
-            static_handler: None,
+            default_text_chain: None,
+            default_binary_chain: None,
+            static_mounts: Vec::new(),
+            http_routes: Arc::new(DashMap::new()),
+            cors: None,
+            drain_timeout: Duration::from_secs(10),
+            handshake_timeout: Duration::from_secs(5),
+            max_header_size: DEFAULT_MAX_HEADER_SIZE,
+            keepalive: None,
+            slow_client: None,
+            max_pending: None,
+            on_message_dropped: None,
+            coalesce: None,
+            ws_config: None,
+            strict_utf8: false,
+            ordered: false,
+            text_pong: None,
+            strict_routing: false,
+            on_handshake: None,
+            error_handler: None,
+            pre_process: None,
+            route_matcher: None,
+            max_connections: None,
+            origin_check: None,
+            proxy_trust: None,
+            handler_timeout: None,
+            metrics: None,
+            id_generator: None,
+            next_id: Arc::new(AtomicU64::new(0)),
+            connection_slots: Arc::new(AtomicUsize::new(0)),
         }
     }
 
     /// Add a global middleware layer that applies to all routes.
     ///
     /// Global middleware are executed before per-route middleware and handlers.
-    /// They are executed in the order they are added.
+    /// They are executed in the order they are added, and apply to every route
+    /// and the default handler regardless of whether `layer()` is called before
+    /// or after they are registered.
     ///
     /// # Arguments
     ///
@@ -330,6 +596,45 @@ impl Router {
         self
     }
 
+    /// Add a middleware layer for outgoing messages.
+    ///
+    /// Outbound middleware runs in each connection's write task, so it sees
+    /// every text and binary message sent to a client: handler responses,
+    /// direct [`Connection::send()`](crate::connection::Connection::send)
+    /// calls and broadcasts. Layers are executed in the order they are added.
+    ///
+    /// See [`OutboundMiddleware`] for details.
+    ///
+    /// # Arguments
+    ///
+    /// * `middleware` - The outbound middleware to add
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    /// use async_trait::async_trait;
+    ///
+    /// struct Envelope;
+    ///
+    /// #[async_trait]
+    /// impl OutboundMiddleware for Envelope {
+    ///     async fn on_send(&self, message: Message, conn: &Connection) -> Result<Message> {
+    ///         let text = message.as_text().unwrap_or_default();
+    ///         Ok(Message::text(format!("{}|{}", conn.id(), text)))
+    ///     }
+    /// }
+    ///
+    /// # fn example() {
+    /// let router = Router::new()
+    ///     .outbound_layer(Arc::new(Envelope));
+    /// # }
+    /// ```
+    pub fn outbound_layer(mut self, middleware: Arc<dyn OutboundMiddleware>) -> Self {
+        self.outbound_middlewares.push(middleware);
+        self
+    }
+
     /// Registers a handler for a specific route without additional middleware.
     ///
     /// Global middleware will still apply to this route. For route-specific middleware,
@@ -337,6 +642,11 @@ impl Router {
     ///
     /// Routes are matched against the beginning of incoming messages.
     /// For example, a message like `/chat hello` would match route `/chat`.
+    /// To route by the URL the client connected to, use
+    /// [`path_route()`](Self::path_route).
+    ///
+    /// Messages matching no route fall back to [`route_typed()`](Self::route_typed)
+    /// handlers and then the [`default_handler()`](Self::default_handler).
     ///
     /// # Arguments
     ///
@@ -363,15 +673,7 @@ impl Router {
     /// # }
     /// ```
     pub fn route(self, path: impl Into<String>, handler: Arc<dyn Handler>) -> Self {
-        let mut chain = MiddlewareChain::new();
-
-        // Add global middlewares first
-        for middleware in &self.global_middlewares {
-            chain = chain.layer(middleware.clone());
-        }
-
-        // Add handler
-        chain = chain.handler(handler);
+        let chain = MiddlewareChain::new().handler(handler);
 
         self.routes.insert(path.into(), Arc::new(chain));
         self
@@ -443,11 +745,6 @@ impl Router {
     ) -> Self {
         let mut chain = MiddlewareChain::new();
 
-        // Add global middlewares first
-        for middleware in &self.global_middlewares {
-            chain = chain.layer(middleware.clone());
-        }
-
         // Add route-specific middlewares
         for middleware in layers {
             chain = chain.layer(middleware);
@@ -460,6 +757,241 @@ impl Router {
         self
     }
 
+    /// Registers a handler for every message from clients connected to `path`.
+    ///
+    /// A client connecting to `ws://host/game` has all its messages handled
+    /// by the path route `/game`, whatever they contain. Connections to a
+    /// path without a path route use the usual per-message routing. Path
+    /// routes are kept apart from [`route()`](Self::route), so registering
+    /// `/chat` as a message route doesn't capture clients connected to
+    /// `/chat`.
+    ///
+    /// Global middleware applies as for any other route.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The URL path, without the query string (e.g., "/game")
+    /// * `handler` - The handler function wrapped with `handler()`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    ///
+    /// async fn game_handler(msg: Message) -> Result<String> {
+    ///     Ok("game update".to_string())
+    /// }
+    ///
+    /// async fn chat_handler(msg: Message) -> Result<String> {
+    ///     Ok("chat message".to_string())
+    /// }
+    ///
+    /// # fn example() {
+    /// // ws://host/game and ws://host/chat get separate handlers
+    /// let router = Router::new()
+    ///     .path_route("/game", handler(game_handler))
+    ///     .path_route("/chat", handler(chat_handler));
+    /// # }
+    /// ```
+    pub fn path_route(self, path: impl Into<String>, handler: Arc<dyn Handler>) -> Self {
+        let chain = MiddlewareChain::new().handler(handler);
+
+        self.path_routes.insert(path.into(), Arc::new(chain));
+        self
+    }
+
+    /// Registers a handler for JSON messages with a given type tag.
+    ///
+    /// Incoming text messages are parsed as JSON objects and the value of the
+    /// discriminator field (`"type"` by default, see [`type_field()`](Self::type_field))
+    /// selects the handler. For example, `{"type": "move", "x": 1}` is dispatched
+    /// to the handler registered for `"move"`.
+    ///
+    /// Path routes registered with [`route()`](Self::route) are matched first.
+    /// Messages that are not JSON, have no string discriminator, or have a
+    /// type with no registered handler fall through to the
+    /// [`default_handler()`](Self::default_handler). Global middleware applies
+    /// as for any other route.
+    ///
+    /// # Arguments
+    ///
+    /// * `type_name` - The discriminator value to match (e.g., "move", "chat")
+    /// * `handler` - The handler function wrapped with `handler()`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    /// use serde::Deserialize;
+    ///
+    /// #[derive(Deserialize)]
+    /// struct Move {
+    ///     x: f32,
+    ///     y: f32,
+    /// }
+    ///
+    /// async fn move_handler(Json(m): Json<Move>) -> Result<String> {
+    ///     Ok(format!("moved to {}, {}", m.x, m.y))
+    /// }
+    ///
+    /// async fn chat_handler(msg: Message) -> Result<()> {
+    ///     Ok(())
+    /// }
+    ///
+    /// # fn example() {
+    /// let router = Router::new()
+    ///     .route_typed("move", handler(move_handler))
+    ///     .route_typed("chat", handler(chat_handler));
+    /// # }
+    /// ```
+    pub fn route_typed(self, type_name: impl Into<String>, handler: Arc<dyn Handler>) -> Self {
+        let chain = MiddlewareChain::new().handler(handler);
+
+        self.typed_routes.insert(type_name.into(), Arc::new(chain));
+        self
+    }
+
+    /// Registers a handler for binary messages starting with an opcode byte.
+    ///
+    /// For binary protocols whose first byte identifies the message, such as
+    /// compact game or IoT protocols. A binary message whose first byte is
+    /// `opcode` is dispatched to `handler`, which receives the full message,
+    /// opcode included, just like [`route()`](Self::route) handlers receive
+    /// the `/route` prefix. Empty binary messages and opcodes with no
+    /// registered handler fall through to the
+    /// [`default_handler()`](Self::default_handler). Global middleware
+    /// applies as for any other route.
+    ///
+    /// # Arguments
+    ///
+    /// * `opcode` - The leading byte to match
+    /// * `handler` - The handler function wrapped with `handler()`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    ///
+    /// const OP_MOVE: u8 = 0x01;
+    /// const OP_SHOOT: u8 = 0x02;
+    ///
+    /// async fn move_handler(Data(bytes): Data) -> Result<()> {
+    ///     let payload = &bytes[1..];
+    ///     println!("move: {:?}", payload);
+    ///     Ok(())
+    /// }
+    ///
+    /// async fn shoot_handler(Data(bytes): Data) -> Result<Vec<u8>> {
+    ///     Ok(vec![OP_SHOOT, bytes.len() as u8])
+    /// }
+    ///
+    /// # fn example() {
+    /// let router = Router::new()
+    ///     .route_binary(OP_MOVE, handler(move_handler))
+    ///     .route_binary(OP_SHOOT, handler(shoot_handler));
+    /// # }
+    /// ```
+    pub fn route_binary(self, opcode: u8, handler: Arc<dyn Handler>) -> Self {
+        let chain = MiddlewareChain::new().handler(handler);
+
+        self.binary_routes.insert(opcode, Arc::new(chain));
+        self
+    }
+
+    /// Registers a handler for an RPC method.
+    ///
+    /// Once a method is registered, text messages that are JSON objects with
+    /// a string `method` field are treated as RPC calls:
+    ///
+    /// ```text
+    /// → {"id": 7, "method": "getUser", "params": {"user_id": 42}}
+    /// ← {"id": 7, "result": {"name": "Alice"}}
+    /// ```
+    ///
+    /// The handler receives the call's `params` as the message text, so the
+    /// [`Json<T>`](crate::extractor::Json) extractor deserializes them
+    /// directly, and other extractors work as usual. Its response is wrapped
+    /// in a `result` envelope: a [`JsonResponse`](crate::handler::JsonResponse)
+    /// is embedded as JSON, any other text as a string, no response as
+    /// `null`, and several messages, e.g. from
+    /// [`Messages`](crate::handler::Messages), as an array.
+    ///
+    /// Errors are answered with an `error` envelope instead of going through
+    /// the [`error_handler()`](Self::error_handler).
+    /// [`Error::Coded`](crate::error::Error::Coded) keeps its code, kind and
+    /// data; other errors use code -32000. Calls to unknown methods get code
+    /// -32601. Calls without an `id` are notifications and are never
+    /// answered.
+    ///
+    /// RPC calls are recognized before any other routing. Global middleware
+    /// applies as for any other route.
+    ///
+    /// # Arguments
+    ///
+    /// * `method` - The method name to match
+    /// * `handler` - The handler function wrapped with `handler()`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    /// use serde::{Deserialize, Serialize};
+    ///
+    /// #[derive(Deserialize)]
+    /// struct GetUser {
+    ///     user_id: u64,
+    /// }
+    ///
+    /// #[derive(Serialize)]
+    /// struct User {
+    ///     name: String,
+    /// }
+    ///
+    /// async fn get_user(Json(params): Json<GetUser>) -> Result<JsonResponse<User>> {
+    ///     if params.user_id != 42 {
+    ///         return Err(Error::coded(404, "not_found", "No such user"));
+    ///     }
+    ///     Ok(JsonResponse(User { name: "Alice".to_string() }))
+    /// }
+    ///
+    /// # fn example() {
+    /// let router = Router::new().rpc("getUser", handler(get_user));
+    /// # }
+    /// ```
+    pub fn rpc(self, method: impl Into<String>, handler: Arc<dyn Handler>) -> Self {
+        let chain = MiddlewareChain::new().handler(handler);
+
+        self.rpc_methods.insert(method.into(), Arc::new(chain));
+        self
+    }
+
+    /// Sets the JSON field used to dispatch [`route_typed()`](Self::route_typed) handlers.
+    ///
+    /// Defaults to `"type"`, which matches enums serialized with
+    /// `#[serde(tag = "type")]`.
+    ///
+    /// # Arguments
+    ///
+    /// * `field` - The name of the discriminator field
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    ///
+    /// # async fn join_handler() -> Result<()> { Ok(()) }
+    /// # fn example() {
+    /// // Dispatches messages like {"action": "join", "room": "lobby"}
+    /// let router = Router::new()
+    ///     .type_field("action")
+    ///     .route_typed("join", handler(join_handler));
+    /// # }
+    /// ```
+    pub fn type_field(mut self, field: impl Into<String>) -> Self {
+        self.type_field = field.into();
+        self
+    }
+
     /// Adds shared state to the router.
     ///
     /// State is shared across all connections and can be extracted in handlers
@@ -525,6 +1057,46 @@ impl Router {
         self
     }
 
+    /// Adds shared state under a name.
+    ///
+    /// Like [`with_state()`](Self::with_state), but several values of the
+    /// same type can be added under different names. Handlers read them
+    /// from the [`AppState`] with [`get_keyed()`](AppState::get_keyed).
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - Name distinguishing this value from others of its type
+    /// * `data` - The state to add
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    /// use std::sync::Arc;
+    ///
+    /// struct DbPool;
+    ///
+    /// async fn handler(state: AppState) -> Result<String> {
+    ///     let _replica = state.get_keyed::<DbPool>("replica");
+    ///     Ok("ok".to_string())
+    /// }
+    ///
+    /// # fn example() {
+    /// let router = Router::new()
+    ///     .with_keyed_state("primary", Arc::new(DbPool))
+    ///     .with_keyed_state("replica", Arc::new(DbPool))
+    ///     .default_handler(handler(handler));
+    /// # }
+    /// ```
+    pub fn with_keyed_state<T: Send + Sync + 'static>(
+        self,
+        key: impl Into<String>,
+        data: Arc<T>,
+    ) -> Self {
+        self.state.insert_keyed(key, data);
+        self
+    }
+
     /// Sets a callback to be called when a new connection is established.
     ///
     /// The callback receives a reference to the connection manager and the
@@ -586,6 +1158,40 @@ impl Router {
         self
     }
 
+    /// Sets the message sent to each client as soon as it connects.
+    ///
+    /// The function is called right after the connection is registered with
+    /// the [`ConnectionManager`], before [`on_connect()`](Self::on_connect)
+    /// and before any message from the client is processed. The message it
+    /// returns is the first one the client receives, which makes it the
+    /// place for a server hello or the client's assigned ID. Return `None`
+    /// to send nothing.
+    ///
+    /// # Arguments
+    ///
+    /// * `f` - Function with signature `Fn(&Connection) -> Option<Message>`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    ///
+    /// # fn example() {
+    /// let router = Router::new().hello_message(|conn| {
+    ///     Some(Message::text(
+    ///         serde_json::json!({ "type": "hello", "id": conn.id() }).to_string(),
+    ///     ))
+    /// });
+    /// # }
+    /// ```
+    pub fn hello_message<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&Connection) -> Option<Message> + Send + Sync + 'static,
+    {
+        self.hello = Some(Arc::new(f));
+        self
+    }
+
     /// Sets a callback to be called when a connection is closed.
     ///
     /// The callback receives a reference to the connection manager and the
@@ -632,24 +1238,354 @@ impl Router {
         self
     }
 
-    /// Sets the default handler for messages that don't match any route.
+    /// Sets a callback to be invoked when a connection is closed, told why
+    /// it ended.
     ///
-    /// This handler is called when no route matches the incoming message.
-    /// Global middleware will still be applied to the default handler.
+    /// Runs after [`on_disconnect()`](Self::on_disconnect), if both are set.
+    /// The [`DisconnectReason`] tells whether the client closed the
+    /// connection, the server did, a read or write failed, or the client
+    /// timed out, along with the close code and reason, if any.
     ///
     /// # Arguments
     ///
-    /// * `handler` - The default handler wrapped with `handler()`
+    /// * `f` - Callback function with signature
+    ///   `Fn(&Arc<ConnectionManager>, ConnectionId, &DisconnectReason)`
     ///
     /// # Examples
     ///
-    /// ## Echo Server
+    /// ## Counting Abnormal Disconnects
     ///
     /// ```
     /// use wsforge::prelude::*;
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
     ///
-    /// async fn echo(msg: Message) -> Result<Message> {
-    ///     Ok(msg)
+    /// static ABNORMAL: AtomicUsize = AtomicUsize::new(0);
+    ///
+    /// # fn example() {
+    /// let router = Router::new()
+    ///     .on_disconnect_with_reason(|_manager, conn_id, reason| {
+    ///         if !reason.is_clean() {
+    ///             ABNORMAL.fetch_add(1, Ordering::Relaxed);
+    ///             println!("{} dropped ({:?}, code {:?})", conn_id, reason.source, reason.code);
+    ///         }
+    ///     });
+    /// # }
+    /// ```
+    pub fn on_disconnect_with_reason<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&Arc<ConnectionManager>, ConnectionId, &DisconnectReason) + Send + Sync + 'static,
+    {
+        self.on_disconnect_with_reason = Some(Arc::new(f));
+        self
+    }
+
+    /// Sets an async callback to be awaited when a new connection is established.
+    ///
+    /// Runs after [`on_connect()`](Self::on_connect), if both are set. No
+    /// message from the client is dispatched until the returned future
+    /// completes. If it returns an error, the error is logged and the
+    /// connection is closed with code 1011 (Internal Error);
+    /// [`on_disconnect()`](Self::on_disconnect) and
+    /// [`on_disconnect_async()`](Self::on_disconnect_async) still run.
+    ///
+    /// # Arguments
+    ///
+    /// * `f` - Callback returning a future, with signature
+    ///   `Fn(Arc<ConnectionManager>, ConnectionId) -> impl Future<Output = Result<()>>`
+    ///
+    /// # Examples
+    ///
+    /// ## Recording Presence
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    ///
+    /// # struct Presence;
+    /// # impl Presence { async fn mark_online(&self, _: &str) -> Result<()> { Ok(()) } }
+    /// # static PRESENCE: Presence = Presence;
+    /// # fn example() {
+    /// let router = Router::new()
+    ///     .on_connect_async(|_manager, conn_id| async move {
+    ///         PRESENCE.mark_online(&conn_id).await?;
+    ///         Ok(())
+    ///     });
+    /// # }
+    /// ```
+    pub fn on_connect_async<F, Fut>(mut self, f: F) -> Self
+    where
+        F: Fn(Arc<ConnectionManager>, ConnectionId) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<()>> + Send + 'static,
+    {
+        self.on_connect_async = Some(Arc::new(move |manager, conn_id| {
+            Box::pin(f(manager, conn_id))
+        }));
+        self
+    }
+
+    /// Sets an async callback to be awaited when a connection is closed.
+    ///
+    /// Runs after [`on_disconnect()`](Self::on_disconnect), if both are set.
+    /// The connection is already removed from the manager. Errors are logged.
+    ///
+    /// # Arguments
+    ///
+    /// * `f` - Callback returning a future, with signature
+    ///   `Fn(Arc<ConnectionManager>, ConnectionId) -> impl Future<Output = Result<()>>`
+    ///
+    /// # Examples
+    ///
+    /// ## Cleaning Up Presence
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    ///
+    /// # struct Presence;
+    /// # impl Presence { async fn mark_offline(&self, _: &str) -> Result<()> { Ok(()) } }
+    /// # static PRESENCE: Presence = Presence;
+    /// # fn example() {
+    /// let router = Router::new()
+    ///     .on_disconnect_async(|_manager, conn_id| async move {
+    ///         PRESENCE.mark_offline(&conn_id).await
+    ///     });
+    /// # }
+    /// ```
+    pub fn on_disconnect_async<F, Fut>(mut self, f: F) -> Self
+    where
+        F: Fn(Arc<ConnectionManager>, ConnectionId) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<()>> + Send + 'static,
+    {
+        self.on_disconnect_async = Some(Arc::new(move |manager, conn_id| {
+            Box::pin(f(manager, conn_id))
+        }));
+        self
+    }
+
+    /// Sets a callback that inspects each WebSocket handshake before upgrading.
+    ///
+    /// The callback receives the HTTP headers of the upgrade request, such as
+    /// `Authorization`, `Cookie` or `Sec-WebSocket-Protocol`, and decides
+    /// whether the connection is established:
+    ///
+    /// - `Ok(HandshakeDecision::accept())` upgrades the connection. Values
+    ///   attached with [`extension()`](HandshakeDecision::extension) are available
+    ///   to every handler on the connection through the `Extension<T>` extractor.
+    /// - `Ok(HandshakeDecision::reject(status))` responds with that HTTP status
+    /// - `Err(_)` responds with 401 Unauthorized
+    ///
+    /// Rejected requests never reach `on_connect` or any handler.
+    ///
+    /// # Arguments
+    ///
+    /// * `f` - Callback function with signature `Fn(&HeaderMap) -> Result<HandshakeDecision>`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    ///
+    /// #[derive(Clone)]
+    /// struct UserId(String);
+    ///
+    /// # fn validate(token: &str) -> Option<String> { Some(token.to_string()) }
+    /// # fn example() {
+    /// let router = Router::new()
+    ///     .on_handshake(|headers| {
+    ///         let token = headers
+    ///             .get("authorization")
+    ///             .and_then(|v| v.to_str().ok())
+    ///             .ok_or_else(|| Error::custom("Missing Authorization header"))?;
+    ///
+    ///         match validate(token) {
+    ///             Some(user) => Ok(HandshakeDecision::accept().extension(UserId(user))),
+    ///             None => Ok(HandshakeDecision::reject(403)),
+    ///         }
+    ///     });
+    /// # }
+    /// ```
+    pub fn on_handshake<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&HeaderMap) -> Result<HandshakeDecision> + Send + Sync + 'static,
+    {
+        self.on_handshake = Some(Arc::new(f));
+        self
+    }
+
+    /// Sets how errors from handlers and middleware are reported to the client.
+    ///
+    /// The callback runs whenever a handler or middleware returns `Err`,
+    /// including extractor failures. It receives the error and the connection
+    /// that sent the message, and returns the message to send back, or `None`
+    /// to send nothing. Errors are still logged on the server.
+    ///
+    /// Without an error handler, an error returned by the handler function
+    /// itself is sent as a text message of the form `Error: {error}`, while
+    /// extractor and middleware errors are only logged. [`Error::Coded`]
+    /// errors are meant for clients, so they are always sent as their JSON
+    /// envelope (see [`Error::to_json()`]).
+    ///
+    /// # Arguments
+    ///
+    /// * `f` - Callback function with signature `Fn(&Error, &Connection) -> Option<Message>`
+    ///
+    /// # Examples
+    ///
+    /// ## JSON Error Envelopes
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    ///
+    /// # fn example() {
+    /// let router = Router::new()
+    ///     .error_handler(|err, _conn| {
+    ///         let code = match err {
+    ///             Error::Extractor(_) | Error::Json(_) => 400,
+    ///             _ => 500,
+    ///         };
+    ///         let body = serde_json::json!({ "error": err.to_string(), "code": code });
+    ///         Some(Message::text(body.to_string()))
+    ///     });
+    /// # }
+    /// ```
+    ///
+    /// ## Hide Errors From Clients
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    ///
+    /// # fn example() {
+    /// let router = Router::new()
+    ///     .error_handler(|_err, _conn| None);
+    /// # }
+    /// ```
+    pub fn error_handler<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&Error, &Connection) -> Option<Message> + Send + Sync + 'static,
+    {
+        self.error_handler = Some(Arc::new(f));
+        self
+    }
+
+    /// Rewrites every incoming message before it is routed.
+    ///
+    /// The function runs first for each message, before auto pongs, RPC
+    /// dispatch, route matching and middleware, and everything after it sees
+    /// the message it returns. Use it for pure message rewriting such as
+    /// trimming, decompressing or decrypting an application-level envelope,
+    /// or stripping a custom framing header. For anything that needs state
+    /// or async work, write a [`Middleware`] instead.
+    ///
+    /// If the function returns an error, the message is dropped and the
+    /// error is reported to the client like a handler error.
+    ///
+    /// # Arguments
+    ///
+    /// * `f` - Function with signature `Fn(Message, &Connection) -> Result<Message>`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    ///
+    /// # fn example() {
+    /// // Clients prefix every message with a protocol version
+    /// let router = Router::new().pre_process(|msg, _conn| {
+    ///     let text = msg.as_text().ok_or_else(|| Error::custom("expected text"))?;
+    ///     let body = text
+    ///         .strip_prefix("v1|")
+    ///         .ok_or_else(|| Error::custom("unsupported protocol version"))?;
+    ///     Ok(Message::text(body.trim()))
+    /// });
+    /// # }
+    /// ```
+    pub fn pre_process<F>(mut self, f: F) -> Self
+    where
+        F: Fn(Message, &Connection) -> Result<Message> + Send + Sync + 'static,
+    {
+        self.pre_process = Some(Arc::new(f));
+        self
+    }
+
+    /// Sets a function that picks the route for each message.
+    ///
+    /// By default a message reaches a [`route()`](Self::route) only if its
+    /// text starts with the route, e.g. `/chat hello`. The matcher lifts
+    /// that restriction: it returns the key of the route to run, such as a
+    /// JSON field or a binary opcode, or `None` to fall back to the usual
+    /// matching. A key without a registered route falls back too.
+    ///
+    /// [`path_route()`](Self::path_route) handlers still take precedence; the
+    /// matcher is consulted before prefix and
+    /// [`route_typed()`](Self::route_typed) matching.
+    ///
+    /// # Arguments
+    ///
+    /// * `f` - Function with signature `Fn(&Message) -> Option<String>`
+    ///
+    /// # Examples
+    ///
+    /// ## By JSON Field
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    ///
+    /// # async fn join() -> Result<String> { Ok("joined".into()) }
+    /// # async fn leave() -> Result<String> { Ok("left".into()) }
+    /// # fn example() {
+    /// // {"action":"join","room":"lobby"} runs the "join" route
+    /// let router = Router::new()
+    ///     .route("join", handler(join))
+    ///     .route("leave", handler(leave))
+    ///     .route_match(|msg| {
+    ///         let value: serde_json::Value = msg.json().ok()?;
+    ///         Some(value.get("action")?.as_str()?.to_string())
+    ///     });
+    /// # }
+    /// ```
+    ///
+    /// ## By Binary Opcode
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    ///
+    /// # async fn movement() -> Result<()> { Ok(()) }
+    /// # async fn chat() -> Result<()> { Ok(()) }
+    /// # fn example() {
+    /// let router = Router::new()
+    ///     .route("move", handler(movement))
+    ///     .route("chat", handler(chat))
+    ///     .route_match(|msg| match msg.as_bytes().first()? {
+    ///         0x01 if msg.is_binary() => Some("move".to_string()),
+    ///         0x02 if msg.is_binary() => Some("chat".to_string()),
+    ///         _ => None,
+    ///     });
+    /// # }
+    /// ```
+    pub fn route_match<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&Message) -> Option<String> + Send + Sync + 'static,
+    {
+        self.route_matcher = Some(Arc::new(f));
+        self
+    }
+
+    /// Sets the default handler for messages that don't match any route.
+    ///
+    /// This handler is called when no route matches the incoming message.
+    /// Global middleware will still be applied to the default handler.
+    ///
+    /// # Arguments
+    ///
+    /// * `handler` - The default handler wrapped with `handler()`
+    ///
+    /// # Examples
+    ///
+    /// ## Echo Server
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    ///
+    /// async fn echo(msg: Message) -> Result<Message> {
+    ///     Ok(msg)
     /// }
     ///
     /// # fn example() {
@@ -674,15 +1610,64 @@ impl Router {
     /// # }
     /// ```
     pub fn default_handler(mut self, handler: Arc<dyn Handler>) -> Self {
-        let mut chain = MiddlewareChain::new();
+        let chain = MiddlewareChain::new().handler(handler);
+        self.default_chain = Some(Arc::new(chain));
+        self
+    }
 
-        // Add global middlewares to default handler too
-        for middleware in &self.global_middlewares {
-            chain = chain.layer(middleware.clone());
-        }
+    /// Sets the default handler for text messages that don't match any
+    /// route.
+    ///
+    /// Takes precedence over [`default_handler()`](Self::default_handler)
+    /// for text messages, which keeps text and binary processing in separate
+    /// handlers. Binary messages still go to
+    /// [`default_binary_handler()`](Self::default_binary_handler) or the
+    /// generic default handler. Ping, pong and close frames are handled by
+    /// the framework and never reach a handler.
+    ///
+    /// # Arguments
+    ///
+    /// * `handler` - The handler wrapped with `handler()`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    ///
+    /// async fn on_text(Text(text): Text) -> Result<String> {
+    ///     Ok(format!("text: {}", text))
+    /// }
+    ///
+    /// async fn on_binary(Data(bytes): Data) -> Result<String> {
+    ///     Ok(format!("{} bytes", bytes.len()))
+    /// }
+    ///
+    /// # fn example() {
+    /// let router = Router::new()
+    ///     .default_text_handler(handler(on_text))
+    ///     .default_binary_handler(handler(on_binary));
+    /// # }
+    /// ```
+    pub fn default_text_handler(mut self, handler: Arc<dyn Handler>) -> Self {
+        let chain = MiddlewareChain::new().handler(handler);
+        self.default_text_chain = Some(Arc::new(chain));
+        self
+    }
 
-        chain = chain.handler(handler);
-        self.default_chain = Some(Arc::new(chain));
+    /// Sets the default handler for binary messages that don't match any
+    /// route.
+    ///
+    /// The binary counterpart of
+    /// [`default_text_handler()`](Self::default_text_handler). Takes
+    /// precedence over [`default_handler()`](Self::default_handler) for
+    /// binary messages.
+    ///
+    /// # Arguments
+    ///
+    /// * `handler` - The handler wrapped with `handler()`
+    pub fn default_binary_handler(mut self, handler: Arc<dyn Handler>) -> Self {
+        let chain = MiddlewareChain::new().handler(handler);
+        self.default_binary_chain = Some(Arc::new(chain));
         self
     }
 
@@ -698,6 +1683,10 @@ impl Router {
     /// - Other requests map directly to files (e.g., `/style.css` → `directory/style.css`)
     /// - MIME types are automatically detected
     ///
+    /// Can be called several times to layer directories. They are tried in
+    /// the order they were added until one has the requested file; only if
+    /// all miss is `404 Not Found` returned.
+    ///
     /// # Security
     ///
     /// Path traversal attempts (e.g., `../../etc/passwd`) are automatically blocked.
@@ -728,15 +1717,37 @@ impl Router {
     /// // ws://localhost:8080             -> WebSocket handler
     /// # }
     /// ```
-    pub fn serve_static(mut self, path: impl Into<PathBuf>) -> Self {
-        self.static_handler = Some(crate::static_files::StaticFileHandler::new(path.into()));
-        self
+    ///
+    /// ## Theme Overrides
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    ///
+    /// # fn example() {
+    /// // Files in app/ win over the base theme's files of the same name
+    /// let router = Router::new()
+    ///     .serve_static("app")
+    ///     .serve_static("vendor/theme");
+    /// # }
+    /// ```
+    pub fn serve_static(self, path: impl Into<PathBuf>) -> Self {
+        self.serve_static_handler(crate::static_files::StaticFileHandler::new(path.into()))
     }
 
-    /// Returns a reference to the connection manager.
+    /// Enables static file serving under a URL prefix.
     ///
-    /// The connection manager is automatically created with the router.
-    /// Use this to get access to it for storing in state or elsewhere.
+    /// Like [`serve_static()`](Self::serve_static), but only requests whose
+    /// path starts with `prefix` are served from the directory, with the
+    /// prefix stripped: `/assets/app.js` maps to `directory/app.js`, and
+    /// `/assets` or `/assets/` to its index file. Other paths get `404 Not
+    /// Found` unless an [`http_route()`](Self::http_route) matches them, so
+    /// top-level paths stay free for API routes. WebSocket upgrades are
+    /// accepted on any path as usual.
+    ///
+    /// # Arguments
+    ///
+    /// * `prefix` - URL prefix, such as `/assets`
+    /// * `path` - Path to the directory containing static files
     ///
     /// # Examples
     ///
@@ -744,284 +1755,4270 @@ impl Router {
     /// use wsforge::prelude::*;
     ///
     /// # fn example() {
-    /// let router = Router::new();
-    /// let manager = router.connection_manager();
+    /// let router = Router::new().serve_static_at("/assets", "public");
     ///
-    /// // Now you can use the manager
-    /// println!("Active connections: {}", manager.count());
+    /// // http://localhost:8080/assets/app.js -> public/app.js
+    /// // http://localhost:8080/app.js        -> 404 Not Found
+    /// // ws://localhost:8080/                -> WebSocket handler
     /// # }
     /// ```
-    pub fn connection_manager(&self) -> Arc<ConnectionManager> {
-        self.connection_manager.clone()
+    pub fn serve_static_at(mut self, prefix: impl Into<String>, path: impl Into<PathBuf>) -> Self {
+        let prefix = prefix.into();
+        let prefix = format!("/{}", prefix.trim_matches('/'));
+        self.static_mounts.push(StaticMount {
+            prefix: (prefix != "/").then_some(prefix),
+            handler: crate::static_files::StaticFileHandler::new(path.into()),
+        });
+        self
     }
 
-    /// Starts the WebSocket server and listens for connections.
-    ///
-    /// This method consumes the router and starts the server loop. It will
-    /// run indefinitely until the process is terminated or an error occurs.
+    /// Enables static file serving with a configured handler.
     ///
-    /// The connection manager is automatically inserted into the router's state
-    /// before the server starts, making it available to all handlers via the
-    /// `State<Arc<ConnectionManager>>` extractor.
+    /// Like [`serve_static()`](Self::serve_static), but takes a
+    /// [`StaticFileHandler`](crate::static_files::StaticFileHandler) whose
+    /// options, such as the index file or directory listings, have already
+    /// been set. Handlers are tried in the order they were added.
     ///
     /// # Arguments
     ///
-    /// * `addr` - The address to bind to (e.g., "127.0.0.1:8080", "0.0.0.0:3000")
-    ///
-    /// # Errors
-    ///
-    /// Returns an error if:
-    /// - The address format is invalid
-    /// - The port is already in use
-    /// - Permission is denied (e.g., ports < 1024 on Unix)
+    /// * `handler` - The static file handler to serve HTTP requests with
     ///
     /// # Examples
     ///
-    /// ## Basic Usage
-    ///
     /// ```
     /// use wsforge::prelude::*;
+    /// use wsforge::static_files::StaticFileHandler;
     ///
-    /// # async fn example() -> Result<()> {
-    /// let router = Router::new();
-    /// router.listen("127.0.0.1:8080").await?;
-    /// # Ok(())
+    /// # fn example() {
+    /// let router = Router::new().serve_static_handler(
+    ///     StaticFileHandler::new("dist")
+    ///         .with_index("app.html")
+    ///         .autoindex(true),
+    /// );
     /// # }
     /// ```
+    pub fn serve_static_handler(mut self, handler: crate::static_files::StaticFileHandler) -> Self {
+        self.static_mounts.push(StaticMount {
+            prefix: None,
+            handler,
+        });
+        self
+    }
+
+    /// Registers a handler for plain HTTP `GET` requests to `path`.
     ///
-    /// ## All Interfaces
+    /// Lets endpoints such as health checks or metrics share the WebSocket
+    /// port. HTTP routes are matched on the exact path, ignoring the query
+    /// string, before falling back to static files. `HEAD` requests run the
+    /// same handler and get its headers without the body.
+    ///
+    /// The handler returns the status code, content type and body of the
+    /// response.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The request path, e.g. `/health`
+    /// * `handler` - Async function from [`HttpRequest`] to
+    ///   `(status, content_type, body)`
+    ///
+    /// # Examples
     ///
     /// ```
     /// use wsforge::prelude::*;
     ///
-    /// # async fn example() -> Result<()> {
-    /// let router = Router::new();
-    /// router.listen("0.0.0.0:8080").await?;  // Accept connections from anywhere
-    /// # Ok(())
+    /// # fn example() {
+    /// let router = Router::new()
+    ///     .http_route("/health", |_req| async { (200, "text/plain", "ok") })
+    ///     .default_handler(handler(|msg: Message| async move { Ok(msg) }));
     /// # }
     /// ```
-    pub async fn listen(self, addr: impl AsRef<str>) -> Result<()> {
-        let addr: SocketAddr = addr
-            .as_ref()
-            .parse()
-            .map_err(|e| Error::custom(format!("Invalid address: {}", e)))?;
+    pub fn http_route<F, Fut, C, B>(self, path: impl Into<String>, handler: F) -> Self
+    where
+        F: Fn(HttpRequest) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = (u16, C, B)> + Send + 'static,
+        C: Into<String>,
+        B: Into<Vec<u8>>,
+    {
+        let handler: HttpHandler = Arc::new(move |req| {
+            let fut = handler(req);
+            Box::pin(async move {
+                let (status, content_type, body) = fut.await;
+                (status, content_type.into(), body.into())
+            })
+        });
+        self.http_routes.insert(path.into(), handler);
+        self
+    }
 
-        self.state.insert(self.connection_manager.clone());
+    /// Adds CORS headers to the router's HTTP responses.
+    ///
+    /// Static files and 404 responses to requests from an allowed origin
+    /// carry `Access-Control-Allow-Origin`, and `OPTIONS` preflight requests
+    /// are answered with `204 No Content` and the allowed methods and
+    /// headers. WebSocket upgrades are unaffected; see
+    /// [`allowed_origins()`](Self::allowed_origins) for those.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - Which origins, methods and headers to allow
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    /// use wsforge::cors::CorsConfig;
+    ///
+    /// # fn example() {
+    /// let router = Router::new()
+    ///     .serve_static("public")
+    ///     .cors(CorsConfig::new().allow_origin("https://app.example.com"));
+    /// # }
+    /// ```
+    pub fn cors(mut self, config: CorsConfig) -> Self {
+        self.cors = Some(config);
+        self
+    }
+
+    /// Enables static file serving from a directory embedded in the binary.
+    ///
+    /// Works like [`serve_static()`](Self::serve_static), but the files are
+    /// compiled in with the `include_dir!` macro, producing a single
+    /// self-contained binary. See
+    /// [`StaticFileHandler::embedded()`](crate::static_files::StaticFileHandler::embedded).
+    ///
+    /// Requires the `embed` feature.
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - The embedded directory
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// use include_dir::{Dir, include_dir};
+    /// use wsforge::prelude::*;
+    ///
+    /// static PUBLIC: Dir<'static> = include_dir!("$CARGO_MANIFEST_DIR/public");
+    ///
+    /// async fn ws_handler(msg: Message) -> Result<Message> {
+    ///     Ok(msg)
+    /// }
+    ///
+    /// # fn example() {
+    /// let router = Router::new()
+    ///     .serve_embedded(PUBLIC.clone())
+    ///     .default_handler(handler(ws_handler));
+    /// # }
+    /// ```
+    #[cfg(feature = "embed")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "embed")))]
+    pub fn serve_embedded(self, dir: include_dir::Dir<'static>) -> Self {
+        self.serve_static_handler(crate::static_files::StaticFileHandler::embedded(dir))
+    }
+
+    /// Enables heartbeat pings and drops connections that go silent.
+    ///
+    /// Every connection is sent a ping each `interval`. Any frame from the
+    /// client, including the pong replying to a ping, counts as activity. A
+    /// connection that produces no frame for `timeout` is closed, removed from
+    /// the [`ConnectionManager`] and reported to `on_disconnect`, so dead
+    /// peers that never send a FIN don't linger in
+    /// [`count()`](ConnectionManager::count).
+    ///
+    /// Keepalive is disabled by default. `timeout` should be longer than
+    /// `interval` so a healthy client has a chance to answer. While it is
+    /// enabled, pongs are consumed by the router; without it, they reach
+    /// handlers like any other message.
+    ///
+    /// # Arguments
+    ///
+    /// * `interval` - How often to ping each connection
+    /// * `timeout` - How long a connection may stay silent before it is dropped
+    ///
+    /// # Panics
+    ///
+    /// Panics if `interval` is zero.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    /// use std::time::Duration;
+    ///
+    /// # fn example() {
+    /// let router = Router::new()
+    ///     .keepalive(Duration::from_secs(15), Duration::from_secs(45));
+    /// # }
+    /// ```
+    pub fn keepalive(mut self, interval: Duration, timeout: Duration) -> Self {
+        assert!(
+            !interval.is_zero(),
+            "keepalive interval must be greater than zero"
+        );
+        self.keepalive = Some(KeepAlive { interval, timeout });
+        self
+    }
+
+    /// Sets a callback for clients that can't keep up with their messages.
+    ///
+    /// Messages are queued without limit, so a client that reads slower than
+    /// the server sends, for example during a high-volume broadcast, makes
+    /// the server's memory grow. The callback runs when a connection's
+    /// [`pending_count()`](Connection::pending_count) grows past `threshold`,
+    /// typically to drop the client with [`Connection::close()`].
+    ///
+    /// The callback runs once each time a connection falls behind. It runs
+    /// again only after the connection has worked its queue down to half the
+    /// threshold and then falls behind again.
+    ///
+    /// The callback runs inline in whatever code sent the message, so it
+    /// should return quickly. During a broadcast it runs once every
+    /// connection has been visited, so it may disconnect or remove
+    /// connections through the [`ConnectionManager`].
+    ///
+    /// # Arguments
+    ///
+    /// * `threshold` - Queue depth above which a connection counts as slow
+    /// * `f` - Callback receiving the slow connection
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    ///
+    /// # fn example() {
+    /// let router = Router::new().on_slow_client(1000, |conn| {
+    ///     // 1013 = try again later
+    ///     let _ = conn.close(Some((1013, "Too slow".to_string())));
+    /// });
+    /// # }
+    /// ```
+    pub fn on_slow_client<F>(mut self, threshold: usize, f: F) -> Self
+    where
+        F: Fn(Connection) + Send + Sync + 'static,
+    {
+        self.slow_client = Some(SlowClient {
+            threshold,
+            callback: Arc::new(f),
+        });
+        self
+    }
+
+    /// Caps how many unsent messages each connection may have queued.
+    ///
+    /// Once a connection has `max` messages waiting to be written, further
+    /// messages to it are dropped until it catches up. Sending still
+    /// succeeds, so broadcasts and handlers aren't affected by a lagging
+    /// client, but the memory a client can tie up stays bounded. Close
+    /// frames and pings, including keepalive pings, are never dropped, and
+    /// [`send_flush()`](Connection::send_flush) isn't capped since it waits
+    /// for its message to be written.
+    ///
+    /// Pair it with [`on_message_dropped()`](Self::on_message_dropped) to
+    /// find out which clients are lagging. Without a cap (the default), the
+    /// queue grows without limit.
+    ///
+    /// # Arguments
+    ///
+    /// * `max` - Queue depth at which messages start being dropped
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    ///
+    /// # fn example() {
+    /// // Position updates are useless once 256 newer ones are queued
+    /// let router = Router::new().max_pending_per_connection(256);
+    /// # }
+    /// ```
+    pub fn max_pending_per_connection(mut self, max: usize) -> Self {
+        self.max_pending = Some(max);
+        self
+    }
+
+    /// Sets a callback for messages dropped because of
+    /// [`max_pending_per_connection()`](Self::max_pending_per_connection).
+    ///
+    /// The callback receives the ID of the lagging connection and the
+    /// dropped message. It runs inline in whatever code sent the message, so
+    /// it should return quickly. During a broadcast it runs once every
+    /// connection has been visited, so it may disconnect or remove
+    /// connections, as in the example below.
+    ///
+    /// # Arguments
+    ///
+    /// * `f` - Callback with signature `Fn(ConnectionId, Message)`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    /// use std::collections::HashMap;
+    /// use std::sync::{Arc, Mutex};
+    ///
+    /// # fn example(manager: Arc<ConnectionManager>) {
+    /// let drops = Mutex::new(HashMap::<ConnectionId, u32>::new());
+    ///
+    /// let router = Router::new()
+    ///     .max_pending_per_connection(256)
+    ///     .on_message_dropped(move |conn_id, _msg| {
+    ///         let mut drops = drops.lock().unwrap();
+    ///         let count = drops.entry(conn_id.clone()).or_insert(0);
+    ///         *count += 1;
+    ///         // Disconnect chronic offenders
+    ///         if *count == 1000 {
+    ///             drops.remove(&conn_id);
+    ///             let _ = manager.disconnect(&conn_id, Some((1013, "Too slow".to_string())));
+    ///         }
+    ///     });
+    /// # }
+    /// ```
+    pub fn on_message_dropped<F>(mut self, f: F) -> Self
+    where
+        F: Fn(ConnectionId, Message) + Send + Sync + 'static,
+    {
+        self.on_message_dropped = Some(Arc::new(f));
+        self
+    }
+
+    /// Batches outgoing text messages sent in quick succession into one frame.
+    ///
+    /// When a text message is about to be written to a client, the
+    /// connection waits up to `window` for more text messages and sends
+    /// them together, joined according to `strategy`. Tick-based servers
+    /// broadcasting many small updates per second then write far fewer
+    /// frames and syscalls, at the cost of up to `window` extra latency on
+    /// every text message.
+    ///
+    /// Binary, ping and close frames are never batched, nor, with
+    /// [`CoalesceStrategy::JsonArray`], text that isn't valid JSON: they end
+    /// the current window and are sent right after it, so ordering is
+    /// preserved. Clients
+    /// must understand the joined format. Messages go through outbound
+    /// middleware before they are joined.
+    ///
+    /// Coalescing is off by default, so messages are sent immediately.
+    ///
+    /// # Arguments
+    ///
+    /// * `window` - How long to collect messages after the first one
+    /// * `strategy` - How collected messages are joined
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    /// use wsforge::CoalesceStrategy;
+    /// use std::time::Duration;
+    ///
+    /// # fn example() {
+    /// // One frame per 60 Hz tick, as a JSON array of updates
+    /// let router = Router::new()
+    ///     .coalesce(Duration::from_millis(16), CoalesceStrategy::JsonArray);
+    ///
+    /// // Newline-delimited messages
+    /// let router = Router::new().coalesce(
+    ///     Duration::from_millis(10),
+    ///     CoalesceStrategy::Delimited("\n".to_string()),
+    /// );
+    /// # }
+    /// ```
+    pub fn coalesce(mut self, window: Duration, strategy: CoalesceStrategy) -> Self {
+        self.coalesce = Some(Coalesce { window, strategy });
+        self
+    }
+
+    /// Sets the WebSocket protocol settings used for every connection.
+    ///
+    /// The config is passed to the WebSocket handshake as is, so everything
+    /// tungstenite supports can be tuned here, such as write buffer sizes.
+    /// For size limits alone, [`max_message_size()`](Self::max_message_size)
+    /// and [`max_frame_size()`](Self::max_frame_size) are shorter. Without a
+    /// config, tungstenite's defaults apply: 64 MiB messages, 16 MiB frames,
+    /// a 128 KiB write buffer and no limit on buffered outgoing data.
+    ///
+    /// Per-message compression (permessage-deflate) is not supported by the
+    /// underlying WebSocket implementation, so it is never negotiated.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - Protocol settings passed to the WebSocket handshake
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    /// use wsforge::WebSocketConfig;
+    ///
+    /// # fn example() {
+    /// let router = Router::new().ws_config(WebSocketConfig {
+    ///     max_message_size: Some(64 * 1024),
+    ///     max_frame_size: Some(16 * 1024),
+    ///     ..Default::default()
+    /// });
+    /// # }
+    /// ```
+    pub fn ws_config(mut self, config: WebSocketConfig) -> Self {
+        self.ws_config = Some(config);
+        self
+    }
+
+    /// Sets the largest message a client may send, in bytes.
+    ///
+    /// Fragmented messages are limited as a whole. A client that sends a
+    /// bigger message is disconnected with close code 1009 (Message Too Big)
+    /// before the message is buffered. Defaults to 64 MiB.
+    ///
+    /// Fragments are assembled in memory and handlers only see complete
+    /// messages, so this limit bounds the memory a single message can use.
+    /// To accept uploads larger than is reasonable to buffer, have the client
+    /// send them as a series of smaller messages and write each chunk out as
+    /// it arrives.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    ///
+    /// # fn example() {
+    /// let router = Router::new()
+    ///     .max_message_size(256 * 1024)
+    ///     .max_frame_size(64 * 1024);
+    /// # }
+    /// ```
+    pub fn max_message_size(mut self, bytes: usize) -> Self {
+        self.ws_config
+            .get_or_insert_with(WebSocketConfig::default)
+            .max_message_size = Some(bytes);
+        self
+    }
+
+    /// Sets the largest single frame a client may send, in bytes.
+    ///
+    /// Oversized frames are handled like oversized messages, see
+    /// [`max_message_size()`](Self::max_message_size). Defaults to 16 MiB.
+    pub fn max_frame_size(mut self, bytes: usize) -> Self {
+        self.ws_config
+            .get_or_insert_with(WebSocketConfig::default)
+            .max_frame_size = Some(bytes);
+        self
+    }
+
+    /// Lets clients resume their session after a brief disconnect.
+    ///
+    /// A connection that was issued a
+    /// [`resume_token()`](ConnectionManager::resume_token) keeps its
+    /// metadata, rooms and tags for `grace` after it disconnects. A client
+    /// that reconnects to a URL with `?resume=<token>` within that time gets
+    /// them back on its new connection before `on_connect` runs. Handlers
+    /// can also call [`resume()`](ConnectionManager::resume) themselves, e.g.
+    /// with a token sent in the first message.
+    ///
+    /// # Arguments
+    ///
+    /// * `grace` - How long a disconnected session can be resumed
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    /// use std::sync::Arc;
+    /// use std::time::Duration;
+    ///
+    /// async fn hello(
+    ///     conn: Connection,
+    ///     State(manager): State<Arc<ConnectionManager>>,
+    /// ) -> Result<String> {
+    ///     // The client reconnects with ws://host/?resume=<token>
+    ///     manager.resume_token(conn.id())
+    /// }
+    ///
+    /// # fn example() {
+    /// let router = Router::new()
+    ///     .session_resumption(Duration::from_secs(30))
+    ///     .route("hello", handler(hello));
+    /// # }
+    /// ```
+    pub fn session_resumption(self, grace: Duration) -> Self {
+        self.connection_manager.set_resume_window(grace);
+        self
+    }
+
+    /// Reports messages that no handler matches as errors.
+    ///
+    /// By default, a message that matches no route when there is no
+    /// [`default_handler()`](Self::default_handler) is logged and dropped.
+    /// With strict routing, it fails with [`Error::RouteNotFound`] instead,
+    /// which is answered through the [`error_handler()`](Self::error_handler)
+    /// like any handler error, so a misconfigured router is noticed right
+    /// away.
+    ///
+    /// # Arguments
+    ///
+    /// * `strict` - Whether unmatched messages are errors
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    ///
+    /// # async fn chat() -> Result<String> { Ok("".to_string()) }
+    /// # fn example() {
+    /// let router = Router::new()
+    ///     .strict_routing(true)
+    ///     .route("/chat", handler(chat));
+    /// # }
+    /// ```
+    pub fn strict_routing(mut self, strict: bool) -> Self {
+        self.strict_routing = strict;
+        self
+    }
+
+    /// Answers application-level `"ping"` text messages with `"pong"`.
+    ///
+    /// Many browser clients send a text `"ping"` as a heartbeat instead of a
+    /// protocol ping frame. When enabled, a text message that is exactly
+    /// `"ping"` is answered with `"pong"` before routing, so it never reaches
+    /// middleware or handlers. Disabled by default. Use
+    /// [`auto_text_pong_with()`](Self::auto_text_pong_with) for other strings.
+    ///
+    /// # Arguments
+    ///
+    /// * `enabled` - Whether to answer `"ping"` with `"pong"`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    ///
+    /// # fn example() {
+    /// let router = Router::new().auto_text_pong(true);
+    /// # }
+    /// ```
+    pub fn auto_text_pong(self, enabled: bool) -> Self {
+        if enabled {
+            self.auto_text_pong_with("ping", "pong")
+        } else {
+            Self {
+                text_pong: None,
+                ..self
+            }
+        }
+    }
+
+    /// Answers the text message `ping` with `pong` before routing.
+    ///
+    /// Like [`auto_text_pong()`](Self::auto_text_pong), with custom strings.
+    /// Only text messages that equal `ping` exactly are intercepted.
+    ///
+    /// # Arguments
+    ///
+    /// * `ping` - The heartbeat text sent by clients
+    /// * `pong` - The text to answer it with
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    ///
+    /// # fn example() {
+    /// let router = Router::new().auto_text_pong_with(r#"{"type":"ping"}"#, r#"{"type":"pong"}"#);
+    /// # }
+    /// ```
+    pub fn auto_text_pong_with(mut self, ping: impl Into<String>, pong: impl Into<String>) -> Self {
+        self.text_pong = Some((ping.into(), pong.into()));
+        self
+    }
+
+    /// Enforces valid UTF-8 in text messages, as the WebSocket spec requires.
+    ///
+    /// When enabled, a client that sends a text frame with invalid UTF-8 is
+    /// disconnected with close code 1007 (Invalid Frame Payload Data), and
+    /// an outgoing text [`Message`] whose payload isn't valid UTF-8 is
+    /// dropped with an error log instead of being decoded lossily. Build
+    /// text from bytes with [`Message::try_text()`] to catch this early.
+    ///
+    /// When disabled (the default), invalid incoming text still ends the
+    /// connection, but without a close frame, and outgoing text is decoded
+    /// lossily.
+    ///
+    /// # Arguments
+    ///
+    /// * `strict` - Whether to enforce valid UTF-8
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    ///
+    /// # fn example() {
+    /// let router = Router::new().strict_utf8(true);
+    /// # }
+    /// ```
+    pub fn strict_utf8(mut self, strict: bool) -> Self {
+        self.strict_utf8 = strict;
+        self
+    }
+
+    /// Handles each connection's messages one at a time, in arrival order.
+    ///
+    /// By default every incoming message is handled in its own task, so a
+    /// slow handler doesn't hold up the next message, but two messages from
+    /// the same client can finish, and reply, out of order. Protocols where
+    /// order matters, such as incremental edits or game inputs, should
+    /// enable this.
+    ///
+    /// When enabled, each connection gets a single task that handles its
+    /// messages sequentially. Different connections are still handled
+    /// concurrently. The trade-off is throughput per connection: a slow
+    /// handler delays every later message from the same client, which queue
+    /// up in memory until it finishes.
+    ///
+    /// # Arguments
+    ///
+    /// * `ordered` - Whether to handle each connection's messages in order
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    ///
+    /// # async fn apply_edit(msg: Message) -> Result<String> { Ok(String::new()) }
+    /// # fn example() {
+    /// // Edits must be applied in the order the client made them
+    /// let router = Router::new()
+    ///     .ordered(true)
+    ///     .default_handler(handler(apply_edit));
+    /// # }
+    /// ```
+    pub fn ordered(mut self, ordered: bool) -> Self {
+        self.ordered = ordered;
+        self
+    }
+
+    /// Sets how long a graceful shutdown waits for connections to close.
+    ///
+    /// When the shutdown signal passed to
+    /// [`listen_with_shutdown()`](Self::listen_with_shutdown) fires, every
+    /// connection is sent a close frame. Connections that have not finished
+    /// within this duration are dropped. Defaults to 10 seconds.
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` - Maximum time to wait for connections to drain
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    /// use std::time::Duration;
+    ///
+    /// # fn example() {
+    /// let router = Router::new()
+    ///     .drain_timeout(Duration::from_secs(30));
+    /// # }
+    /// ```
+    pub fn drain_timeout(mut self, timeout: Duration) -> Self {
+        self.drain_timeout = timeout;
+        self
+    }
+
+    /// Forwards broadcasts to other server instances through `backend`.
+    ///
+    /// When several instances run behind a load balancer,
+    /// [`ConnectionManager::broadcast()`] then reaches the clients of every
+    /// instance, not only this one's. The backend is attached to the
+    /// connection manager when the server starts. See the
+    /// [`broadcast`](crate::broadcast) module for writing a backend for
+    /// Redis, NATS or similar.
+    ///
+    /// # Arguments
+    ///
+    /// * `backend` - Pub/sub transport shared by all instances
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    /// use wsforge::broadcast::InProcessBackend;
+    /// use std::sync::Arc;
+    ///
+    /// # fn example() {
+    /// let router = Router::new()
+    ///     .broadcast_backend(Arc::new(InProcessBackend::new()));
+    /// # }
+    /// ```
+    pub fn broadcast_backend(mut self, backend: Arc<dyn BroadcastBackend>) -> Self {
+        self.broadcast_backend = Some(backend);
+        self
+    }
+
+    /// Sets how long a new connection may take to complete its handshake.
+    ///
+    /// Applies separately to each phase of accepting a connection: the TLS
+    /// handshake, receiving the HTTP request head, and the WebSocket
+    /// upgrade. A client that misses the limit is dropped with
+    /// [`Error::HandshakeTimeout`], which is logged. If the request head
+    /// never arrived, the client is first sent `408 Request Timeout`.
+    ///
+    /// Defaults to 5 seconds. Raise it behind load balancers that delay the
+    /// first bytes; lower it to shed idle sockets sooner.
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` - Maximum time per handshake phase
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    /// use std::time::Duration;
+    ///
+    /// # fn example() {
+    /// let router = Router::new()
+    ///     .handshake_timeout(Duration::from_secs(15));
+    /// # }
+    /// ```
+    pub fn handshake_timeout(mut self, timeout: Duration) -> Self {
+        self.handshake_timeout = timeout;
+        self
+    }
+
+    /// Sets the largest HTTP request head a client may send, in bytes.
+    ///
+    /// The head is the request line and headers of both WebSocket upgrades
+    /// and plain HTTP requests. A client whose head grows past the limit is
+    /// sent `431 Request Header Fields Too Large` and disconnected, so it
+    /// can't tie up memory by sending endless headers. Together with
+    /// [`handshake_timeout()`](Self::handshake_timeout), which bounds how
+    /// long the head may take to arrive, this protects against slowloris
+    /// clients.
+    ///
+    /// Defaults to 16 KiB. Raise it if clients send large cookies.
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes` - Maximum size of the request head
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    ///
+    /// # fn example() {
+    /// let router = Router::new()
+    ///     .serve_static("public")
+    ///     .max_header_size(8 * 1024);
+    /// assert_eq!(router.config().max_header_size, 8 * 1024);
+    /// # }
+    /// ```
+    pub fn max_header_size(mut self, bytes: usize) -> Self {
+        self.max_header_size = bytes;
+        self
+    }
+
+    /// Sets an async callback to be awaited when a graceful shutdown starts.
+    ///
+    /// Runs after the listener stops accepting connections but before any
+    /// connection is sent a close frame, so it can still message every
+    /// client, e.g. to announce a restart. Messages sent from the callback
+    /// are queued ahead of the close frames and delivered first. Only once
+    /// the returned future completes are connections closed and the
+    /// [`drain_timeout()`](Self::drain_timeout) started. If it returns an
+    /// error, the error is logged and the shutdown continues.
+    ///
+    /// The callback isn't bounded by a timeout; keep it short.
+    ///
+    /// # Arguments
+    ///
+    /// * `f` - Callback returning a future, with signature
+    ///   `Fn(Arc<ConnectionManager>) -> impl Future<Output = Result<()>>`
+    ///
+    /// # Examples
+    ///
+    /// ## Announcing a Restart
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    ///
+    /// # async fn example() -> Result<()> {
+    /// let router = Router::new()
+    ///     .on_shutdown(|manager| async move {
+    ///         manager.broadcast(Message::text(r#"{"type":"server_restarting"}"#));
+    ///         Ok(())
+    ///     });
+    ///
+    /// router
+    ///     .listen_with_shutdown("127.0.0.1:8080", async {
+    ///         let _ = tokio::signal::ctrl_c().await;
+    ///     })
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn on_shutdown<F, Fut>(mut self, f: F) -> Self
+    where
+        F: Fn(Arc<ConnectionManager>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<()>> + Send + 'static,
+    {
+        self.on_shutdown = Some(Arc::new(move |manager| Box::pin(f(manager))));
+        self
+    }
+
+    /// Limits the number of concurrent WebSocket connections.
+    ///
+    /// Each upgrade request takes a slot before the WebSocket handshake
+    /// completes and keeps it until the connection ends. Once `max` slots are
+    /// taken, further upgrades are refused with `503 Service Unavailable`, so
+    /// they never reach the [`ConnectionManager`], `on_connect` or any
+    /// handler. Static file requests are not affected.
+    ///
+    /// The slot is taken before the [`on_handshake()`](Self::on_handshake)
+    /// callback, atomically, so concurrent handshakes can't push the number
+    /// of connections past the limit. A rejected or failed handshake gives
+    /// its slot back.
+    ///
+    /// # Arguments
+    ///
+    /// * `max` - Maximum number of open connections
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    ///
+    /// # fn example() {
+    /// let router = Router::new().max_connections(10_000);
+    ///
+    /// assert_eq!(router.connection_limit(), Some(10_000));
+    /// # }
+    /// ```
+    pub fn max_connections(mut self, max: usize) -> Self {
+        self.max_connections = Some(max);
+        self
+    }
+
+    /// Only accepts WebSocket upgrades from the given origins.
+    ///
+    /// Browsers let any web page open a WebSocket to any server, sending the
+    /// user's cookies along. Without an origin check, a malicious site can
+    /// act on the user's behalf (cross-site WebSocket hijacking). With an
+    /// allow-list, upgrades whose `Origin` header isn't listed are refused
+    /// with `403 Forbidden` before the handshake completes.
+    ///
+    /// Origins are compared case-insensitively and must match exactly,
+    /// including scheme and port, e.g. `https://app.example.com` or
+    /// `http://localhost:3000`. `"*"` allows every origin.
+    ///
+    /// Requests without an `Origin` header are accepted, since browsers
+    /// always send one and other clients can't be hijacked this way. Use
+    /// [`on_handshake()`](Self::on_handshake) to require other credentials
+    /// from them.
+    ///
+    /// The check runs before [`max_connections()`](Self::max_connections)
+    /// and [`on_handshake()`](Self::on_handshake). For rules an allow-list
+    /// can't express, use [`allow_origin()`](Self::allow_origin) instead;
+    /// the last of the two called wins.
+    ///
+    /// # Arguments
+    ///
+    /// * `origins` - The origins allowed to connect
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    ///
+    /// # fn example() {
+    /// let router = Router::new()
+    ///     .allowed_origins(["https://app.example.com", "https://admin.example.com"]);
+    /// # }
+    /// ```
+    pub fn allowed_origins<I, S>(self, origins: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let origins: Vec<String> = origins.into_iter().map(Into::into).collect();
+        if origins.iter().any(|origin| origin == "*") {
+            return self.allow_origin(|_| true);
+        }
+
+        self.allow_origin(move |origin| {
+            origins
+                .iter()
+                .any(|allowed| allowed.eq_ignore_ascii_case(origin))
+        })
+    }
+
+    /// Only accepts WebSocket upgrades whose origin passes `predicate`.
+    ///
+    /// Like [`allowed_origins()`](Self::allowed_origins), but decides with a
+    /// function of the `Origin` header value. Upgrades it returns `false` for
+    /// are refused with `403 Forbidden`. Requests without an `Origin` header
+    /// are accepted without calling it.
+    ///
+    /// # Arguments
+    ///
+    /// * `predicate` - Returns `true` for origins allowed to connect
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    ///
+    /// # fn example() {
+    /// // Any subdomain of example.com over HTTPS
+    /// let router = Router::new().allow_origin(|origin| {
+    ///     origin
+    ///         .strip_prefix("https://")
+    ///         .is_some_and(|host| host == "example.com" || host.ends_with(".example.com"))
+    /// });
+    /// # }
+    /// ```
+    pub fn allow_origin<F>(mut self, predicate: F) -> Self
+    where
+        F: Fn(&str) -> bool + Send + Sync + 'static,
+    {
+        self.origin_check = Some(Arc::new(predicate));
+        self
+    }
+
+    /// Reads the client's address from headers set by a reverse proxy.
+    ///
+    /// Behind nginx or a cloud load balancer, the peer address of every
+    /// connection is the proxy's. When enabled, the client address from the
+    /// `Forwarded` header, or else `X-Forwarded-For`, is stored in
+    /// [`ConnectionInfo::real_ip`](crate::connection::ConnectionInfo::real_ip).
+    ///
+    /// Every peer is treated as the single proxy in front of the server, so
+    /// only the last forwarded address, the one that proxy appended, is
+    /// believed; entries before it come from the client and are ignored.
+    /// Clients can still send these headers directly, so only enable this
+    /// when the server can't be reached except through the proxy. For a
+    /// chain of proxies, or to restrict which peers are believed, use
+    /// [`trusted_proxies()`](Self::trusted_proxies). Disabled by default.
+    ///
+    /// # Arguments
+    ///
+    /// * `enabled` - Whether to trust forwarding headers from any peer
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    ///
+    /// async fn whoami(ConnectInfo(info): ConnectInfo) -> Result<String> {
+    ///     Ok(info.client_ip().to_string())
+    /// }
+    ///
+    /// # fn example() {
+    /// // Only reachable through the proxy on the same host
+    /// let router = Router::new()
+    ///     .trust_proxy_headers(true)
+    ///     .default_handler(handler(whoami));
+    /// # }
+    /// ```
+    pub fn trust_proxy_headers(mut self, enabled: bool) -> Self {
+        self.proxy_trust = enabled.then_some(ProxyTrust::Any);
+        self
+    }
+
+    /// Reads the client's address from headers set by the given proxies.
+    ///
+    /// Like [`trust_proxy_headers()`](Self::trust_proxy_headers), but only
+    /// believes forwarding headers from connections whose peer is one of
+    /// `proxies`. The forwarded chain is walked from the nearest hop, and the
+    /// first address that isn't a listed proxy is taken as the client, so
+    /// addresses a client prepends itself are ignored. An empty list trusts
+    /// no peer.
+    ///
+    /// # Arguments
+    ///
+    /// * `proxies` - Addresses of the trusted proxies
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    /// use std::net::IpAddr;
+    ///
+    /// # fn example() {
+    /// let router = Router::new().trusted_proxies([
+    ///     "10.0.0.10".parse::<IpAddr>().unwrap(),
+    ///     "10.0.0.11".parse::<IpAddr>().unwrap(),
+    /// ]);
+    /// # }
+    /// ```
+    pub fn trusted_proxies(mut self, proxies: impl IntoIterator<Item = IpAddr>) -> Self {
+        self.proxy_trust = Some(ProxyTrust::Only(proxies.into_iter().collect()));
+        self
+    }
+
+    /// Limits how long a message may take to be handled.
+    ///
+    /// Applies to the whole middleware chain and handler of each message. A
+    /// message that takes longer is cancelled and fails with
+    /// [`Error::Timeout`], which is answered through the
+    /// [`error_handler()`](Self::error_handler) like any other handler error.
+    /// This keeps a hung downstream call from pinning a task forever.
+    ///
+    /// There is no limit by default.
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` - Maximum time per message
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    /// use std::time::Duration;
+    ///
+    /// # fn example() {
+    /// let router = Router::new().handler_timeout(Duration::from_secs(5));
+    /// # }
+    /// ```
+    pub fn handler_timeout(mut self, timeout: Duration) -> Self {
+        self.handler_timeout = Some(timeout);
+        self
+    }
+
+    /// Registers a [`Metrics`] implementation.
+    ///
+    /// The router reports accepted and closed connections, received and
+    /// sent messages, and the duration and failures of every handled
+    /// message. See [`crate::metrics`] for details.
+    ///
+    /// # Arguments
+    ///
+    /// * `metrics` - The implementation to notify
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// use wsforge::prelude::*;
+    /// use wsforge::metrics::PrometheusMetrics;
+    ///
+    /// // Requires the `prometheus` feature
+    /// let metrics = Arc::new(PrometheusMetrics::new());
+    /// let router = Router::new().with_metrics(metrics.clone());
+    ///
+    /// // Expose `metrics.render()` on your scrape endpoint
+    /// ```
+    pub fn with_metrics(mut self, metrics: Arc<dyn Metrics>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Sets the function that assigns IDs to new connections.
+    ///
+    /// By default, connections are numbered `conn_0`, `conn_1`, ... from a
+    /// counter owned by the router, so each router (but not its clones)
+    /// starts at `conn_0` and tests get reproducible IDs. The numbering
+    /// restarts with the process. Plug in UUIDs, ULIDs or a shard prefix
+    /// when IDs must be unique across restarts or server instances.
+    ///
+    /// The generator is called once per accepted WebSocket connection, after
+    /// the handshake. It must return unique IDs: a connection whose ID is
+    /// already in use replaces the existing entry in the
+    /// [`ConnectionManager`].
+    ///
+    /// # Arguments
+    ///
+    /// * `f` - Function with signature `Fn(SocketAddr) -> ConnectionId`
+    ///
+    /// # Examples
+    ///
+    /// ## Shard Prefix
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    /// use std::sync::atomic::{AtomicU64, Ordering};
+    ///
+    /// # fn example() {
+    /// static NEXT: AtomicU64 = AtomicU64::new(0);
+    ///
+    /// let router = Router::new().connection_id_generator(|_peer| {
+    ///     format!("eu-1-{}", NEXT.fetch_add(1, Ordering::Relaxed))
+    /// });
+    /// # }
+    /// ```
+    ///
+    /// ## UUID v4
+    ///
+    /// ```ignore
+    /// use wsforge::prelude::*;
+    ///
+    /// let router = Router::new()
+    ///     .connection_id_generator(|_peer| uuid::Uuid::new_v4().to_string());
+    /// ```
+    pub fn connection_id_generator<F>(mut self, f: F) -> Self
+    where
+        F: Fn(SocketAddr) -> ConnectionId + Send + Sync + 'static,
+    {
+        self.id_generator = Some(Arc::new(f));
+        self
+    }
+
+    /// Connects an in-memory client for integration tests.
+    ///
+    /// Messages sent with the [`TestClient`] go through the same routing,
+    /// middleware and handlers as messages from a WebSocket client, and
+    /// everything sent to its connection can be read back. The connection
+    /// is registered with the router's [`ConnectionManager`], so broadcasts
+    /// reach it too.
+    ///
+    /// No socket or handshake is involved, so handshake checks, connection
+    /// hooks, outbound middleware, the hello message and keepalive don't
+    /// run. See the [`testing`](crate::testing) module.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    ///
+    /// async fn ping() -> Result<String> {
+    ///     Ok("pong".to_string())
+    /// }
+    ///
+    /// # async fn example() -> Result<()> {
+    /// let router = Router::new().route("/ping", handler(ping));
+    ///
+    /// let mut client = router.test_client();
+    /// client.send_text("/ping").await?;
+    /// assert_eq!(client.recv().await.unwrap().as_text(), Some("pong"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn test_client(&self) -> TestClient {
+        TestClient::new(self.clone())
+    }
+
+    /// Returns a reference to the connection manager.
+    ///
+    /// The connection manager is automatically created with the router.
+    /// Use this to get access to it for storing in state or elsewhere.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    ///
+    /// # fn example() {
+    /// let router = Router::new();
+    /// let manager = router.connection_manager();
+    ///
+    /// // Now you can use the manager
+    /// println!("Active connections: {}", manager.count());
+    /// # }
+    /// ```
+    pub fn connection_manager(&self) -> Arc<ConnectionManager> {
+        self.connection_manager.clone()
+    }
+
+    /// Returns the connection limit set with
+    /// [`max_connections()`](Self::max_connections), if any.
+    pub fn connection_limit(&self) -> Option<usize> {
+        self.max_connections
+    }
+
+    /// Returns the size limits applied to incoming traffic.
+    ///
+    /// WebSocket limits that haven't been configured report tungstenite's
+    /// defaults.
+    pub fn config(&self) -> RouterConfig {
+        let ws_config = self.ws_config.unwrap_or_default();
+        RouterConfig {
+            max_message_size: ws_config.max_message_size,
+            max_frame_size: ws_config.max_frame_size,
+            max_header_size: self.max_header_size,
+        }
+    }
+
+    /// Returns the number of open WebSocket connections.
+    ///
+    /// Shorthand for `router.connection_manager().count()`, useful next to
+    /// [`connection_limit()`](Self::connection_limit) for monitoring.
+    pub fn connection_count(&self) -> usize {
+        self.connection_manager.count()
+    }
+
+    /// Starts the WebSocket server and listens for connections.
+    ///
+    /// This method consumes the router and starts the server loop. It will
+    /// run indefinitely until the process is terminated or an error occurs.
+    ///
+    /// The connection manager is automatically inserted into the router's state
+    /// before the server starts, making it available to all handlers via the
+    /// `State<Arc<ConnectionManager>>` extractor.
+    ///
+    /// # Arguments
+    ///
+    /// * `addr` - The address to bind to (e.g., "127.0.0.1:8080", "0.0.0.0:3000")
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if:
+    /// - The address format is invalid
+    /// - The port is already in use
+    /// - Permission is denied (e.g., ports < 1024 on Unix)
+    ///
+    /// # Examples
+    ///
+    /// ## Basic Usage
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    ///
+    /// # async fn example() -> Result<()> {
+    /// let router = Router::new();
+    /// router.listen("127.0.0.1:8080").await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// ## All Interfaces
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    ///
+    /// # async fn example() -> Result<()> {
+    /// let router = Router::new();
+    /// router.listen("0.0.0.0:8080").await?;  // Accept connections from anywhere
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn listen(self, addr: impl AsRef<str>) -> Result<()> {
+        self.listen_with_shutdown(addr, std::future::pending())
+            .await
+    }
+
+    /// Starts the server and runs until the `shutdown` future completes.
+    ///
+    /// Once `shutdown` resolves, the server performs a graceful shutdown:
+    ///
+    /// 1. The listener is closed so no new TCP connections are accepted
+    /// 2. The [`on_shutdown()`](Self::on_shutdown) callback, if set, is awaited
+    /// 3. A close frame is sent to every active connection
+    /// 4. The server waits for connections to flush and close, for at most
+    ///    the configured [`drain_timeout()`](Self::drain_timeout)
+    /// 5. Any connections still open after the timeout are dropped
+    ///
+    /// # Arguments
+    ///
+    /// * `addr` - The address to bind to (e.g., "127.0.0.1:8080")
+    /// * `shutdown` - A future that resolves when the server should stop
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as [`listen()`](Self::listen).
+    ///
+    /// # Examples
+    ///
+    /// ## Stop on Ctrl+C
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    /// use std::time::Duration;
+    ///
+    /// # async fn example() -> Result<()> {
+    /// let router = Router::new()
+    ///     .drain_timeout(Duration::from_secs(5));
+    ///
+    /// router
+    ///     .listen_with_shutdown("127.0.0.1:8080", async {
+    ///         let _ = tokio::signal::ctrl_c().await;
+    ///     })
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// ## Stop from Another Task
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    /// use tokio::sync::oneshot;
+    ///
+    /// # async fn example() -> Result<()> {
+    /// let (tx, rx) = oneshot::channel::<()>();
+    ///
+    /// let server = tokio::spawn(
+    ///     Router::new().listen_with_shutdown("127.0.0.1:8080", async {
+    ///         let _ = rx.await;
+    ///     }),
+    /// );
+    ///
+    /// // Later...
+    /// let _ = tx.send(());
+    /// server.await.unwrap()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn listen_with_shutdown<F>(self, addr: impl AsRef<str>, shutdown: F) -> Result<()>
+    where
+        F: Future<Output = ()> + Send,
+    {
+        let listener = bind(addr).await?;
+        self.serve(listener, shutdown, |router, stream, peer_addr| async move {
+            router.handle_connection(stream, peer_addr).await
+        })
+        .await
+    }
+
+    /// Starts the WebSocket server on an already-bound listener.
+    ///
+    /// Behaves like [`listen()`](Self::listen), but accepts connections from
+    /// `listener` instead of binding its own socket. Use it when the socket
+    /// comes from elsewhere:
+    ///
+    /// - **Tests**: bind to port 0 and read the assigned port before serving
+    /// - **Socket activation**: serve a listener inherited from systemd
+    /// - **Custom socket options**: e.g. `SO_REUSEPORT` set up with `socket2`
+    ///
+    /// # Arguments
+    ///
+    /// * `listener` - The listener to accept connections from
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if accepting a connection fails.
+    ///
+    /// # Examples
+    ///
+    /// ## Ephemeral Port
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    /// use tokio::net::TcpListener;
+    ///
+    /// # async fn example() -> Result<()> {
+    /// let listener = TcpListener::bind("127.0.0.1:0").await?;
+    /// let addr = listener.local_addr()?;
+    ///
+    /// tokio::spawn(Router::new().serve_on(listener));
+    ///
+    /// // Connect test clients to `ws://{addr}`
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn serve_on(self, listener: TcpListener) -> Result<()> {
+        self.serve(
+            listener,
+            std::future::pending(),
+            |router, stream, peer_addr| async move {
+                router.handle_connection(stream, peer_addr).await
+            },
+        )
+        .await
+    }
+
+    /// Starts the server on a Unix domain socket.
+    ///
+    /// Useful for sidecars and for fronting the server with a reverse proxy
+    /// on the same host, such as nginx with
+    /// `proxy_pass http://unix:/run/app.sock`. WebSocket upgrades, HTTP
+    /// routes and static files behave exactly as with
+    /// [`listen()`](Self::listen).
+    ///
+    /// A socket file already at `path`, e.g. left behind by a previous run,
+    /// is replaced. The file isn't removed when the server stops. Unix peers
+    /// have no IP address, so [`ConnectionInfo::addr`] is `0.0.0.0:0`; pass
+    /// the client address from the proxy in a header if you need it.
+    ///
+    /// Available on Unix platforms.
+    ///
+    /// [`ConnectionInfo::addr`]: crate::connection::ConnectionInfo::addr
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Filesystem path of the socket (e.g., "/run/app.sock")
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the socket can't be bound, e.g. because `path`
+    /// exists and isn't a socket, or if accepting a connection fails.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use wsforge::prelude::*;
+    ///
+    /// async fn echo(msg: Message) -> Result<Message> {
+    ///     Ok(msg)
+    /// }
+    ///
+    /// # async fn example() -> Result<()> {
+    /// let router = Router::new()
+    ///     .default_handler(handler(echo));
+    ///
+    /// router.listen_unix("/run/wsforge.sock").await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(unix)]
+    #[cfg_attr(docsrs, doc(cfg(unix)))]
+    pub async fn listen_unix(self, path: impl AsRef<std::path::Path>) -> Result<()> {
+        self.listen_unix_with_shutdown(path, std::future::pending())
+            .await
+    }
+
+    /// Starts the server on a Unix domain socket and runs until the
+    /// `shutdown` future completes.
+    ///
+    /// Combines [`listen_unix()`](Self::listen_unix) with the graceful
+    /// shutdown of [`listen_with_shutdown()`](Self::listen_with_shutdown).
+    ///
+    /// Available on Unix platforms.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Filesystem path of the socket
+    /// * `shutdown` - A future that resolves when the server should stop
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as [`listen_unix()`](Self::listen_unix).
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use wsforge::prelude::*;
+    ///
+    /// # async fn example() -> Result<()> {
+    /// Router::new()
+    ///     .listen_unix_with_shutdown("/run/wsforge.sock", async {
+    ///         let _ = tokio::signal::ctrl_c().await;
+    ///     })
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(unix)]
+    #[cfg_attr(docsrs, doc(cfg(unix)))]
+    pub async fn listen_unix_with_shutdown<F>(
+        self,
+        path: impl AsRef<std::path::Path>,
+        shutdown: F,
+    ) -> Result<()>
+    where
+        F: Future<Output = ()> + Send,
+    {
+        let listener = bind_unix(path.as_ref())?;
+        self.serve(listener, shutdown, |router, stream, peer_addr| async move {
+            router.dispatch_connection(stream, peer_addr).await
+        })
+        .await
+    }
+
+    /// Starts a TLS-secured server and listens for connections.
+    ///
+    /// Each accepted TCP connection completes a TLS handshake before being
+    /// handled, so clients connect with `wss://` and static files (if
+    /// configured with [`serve_static()`](Self::serve_static)) are served over
+    /// `https://` on the same port. Apart from the transport, connections
+    /// behave exactly as with [`listen()`](Self::listen).
+    ///
+    /// Available with the `tls` feature.
+    ///
+    /// # Arguments
+    ///
+    /// * `addr` - The address to bind to (e.g., "0.0.0.0:8443")
+    /// * `tls` - The certificate and key configuration
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as [`listen()`](Self::listen). Failed TLS
+    /// handshakes are logged and only close the affected connection.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use wsforge::prelude::*;
+    ///
+    /// async fn echo(msg: Message) -> Result<Message> {
+    ///     Ok(msg)
+    /// }
+    ///
+    /// # async fn example() -> Result<()> {
+    /// let tls = TlsConfig::from_pem_files("cert.pem", "key.pem")?;
+    ///
+    /// let router = Router::new()
+    ///     .default_handler(handler(echo));
+    ///
+    /// router.listen_tls("0.0.0.0:8443", tls).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "tls")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "tls")))]
+    pub async fn listen_tls(self, addr: impl AsRef<str>, tls: TlsConfig) -> Result<()> {
+        let acceptor = tls.acceptor();
+        let listener = bind(addr).await?;
+
+        self.serve(
+            listener,
+            std::future::pending(),
+            move |router, stream, peer_addr| {
+                let acceptor = acceptor.clone();
+                async move {
+                    router
+                        .handle_tls_connection(stream, peer_addr, acceptor)
+                        .await
+                }
+            },
+        )
+        .await
+    }
+
+    /// Accepts connections until `shutdown` completes, passing each one to `handle`.
+    async fn serve<L, F, H, Fut>(self, listener: L, shutdown: F, handle: H) -> Result<()>
+    where
+        L: Listener,
+        F: Future<Output = ()> + Send,
+        H: Fn(Arc<Router>, L::Stream, SocketAddr) -> Fut,
+        Fut: Future<Output = Result<()>> + Send + 'static,
+    {
+        self.state.insert(self.connection_manager.clone());
+        if let Some(backend) = self.broadcast_backend.clone() {
+            self.connection_manager.set_broadcast_backend(backend);
+        }
+
+        info!("WebSocket server listening on {}", listener.describe()?);
+        if !self.has_message_handlers() {
+            warn!(
+                "⚠️ Router has no routes, RPC methods or default handler; WebSocket messages will not be handled"
+            );
+        }
+
+        let router = Arc::new(self);
+        let mut connections = JoinSet::new();
+        tokio::pin!(shutdown);
+
+        loop {
+            tokio::select! {
+                _ = &mut shutdown => break,
+                accepted = listener.accept() => {
+                    let (stream, peer_addr) = accepted?;
+                    let connection = handle(router.clone(), stream, peer_addr);
+
+                    connections.spawn(async move {
+                        if let Err(e) = connection.await {
+                            error!("Connection error: {}", e);
+                        }
+                    });
+                }
+                // Reap finished connection tasks so the set doesn't grow unbounded
+                Some(_) = connections.join_next(), if !connections.is_empty() => {}
+            }
+        }
+
+        drop(listener);
+        router.shutdown(connections).await;
+        Ok(())
+    }
+
+    /// Runs the shutdown hook, then closes all connections and waits up to
+    /// the drain timeout for them to finish.
+    async fn shutdown(&self, mut connections: JoinSet<()>) {
+        if let Some(ref cb) = self.on_shutdown
+            && let Err(e) = cb(self.connection_manager.clone()).await
+        {
+            error!("Shutdown hook failed: {}", e);
+        }
+
+        info!(
+            "Shutting down, closing {} connections",
+            self.connection_manager.count()
+        );
+
+        // Other instances keep running, so only close our own connections
+        self.connection_manager.broadcast_local(Message::close());
+
+        let drained = tokio::time::timeout(self.drain_timeout, async {
+            while connections.join_next().await.is_some() {}
+        })
+        .await;
+
+        if drained.is_err() {
+            warn!(
+                "Drain timeout elapsed, dropping {} connections",
+                connections.len()
+            );
+            connections.abort_all();
+            while connections.join_next().await.is_some() {}
+        }
+
+        info!("Server shut down");
+    }
+
+    async fn handle_connection(&self, stream: TcpStream, peer_addr: SocketAddr) -> Result<()> {
+        self.dispatch_connection(stream, peer_addr).await
+    }
+
+    #[cfg(feature = "tls")]
+    async fn handle_tls_connection(
+        &self,
+        stream: TcpStream,
+        peer_addr: SocketAddr,
+        acceptor: tokio_rustls::TlsAcceptor,
+    ) -> Result<()> {
+        let stream = tokio::time::timeout(self.handshake_timeout, acceptor.accept(stream))
+            .await
+            .map_err(|_| Error::HandshakeTimeout(self.handshake_timeout))?
+            .map_err(|e| Error::custom(format!("TLS handshake failed: {}", e)))?;
+
+        self.dispatch_connection(stream, peer_addr).await
+    }
+
+    /// Reads the request head and routes the connection to the WebSocket or
+    /// static file handler.
+    async fn dispatch_connection<S>(&self, mut stream: S, peer_addr: SocketAddr) -> Result<()>
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send,
+    {
+        use tokio::io::AsyncWriteExt;
+
+        let head = match tokio::time::timeout(
+            self.handshake_timeout,
+            read_request_head(&mut stream, self.max_header_size),
+        )
+        .await
+        {
+            Ok(head) => head.map_err(|e| Error::custom(format!("Failed to read: {}", e)))?,
+            Err(_) => {
+                warn!(
+                    "⏱️ No request from {} within {:?}",
+                    peer_addr, self.handshake_timeout
+                );
+                let response = crate::static_files::http_response(408, "text/plain", Vec::new());
+                // Best effort, the client may not be reading either
+                let _ = stream.write_all(&response).await;
+                let _ = stream.flush().await;
+                return Err(Error::HandshakeTimeout(self.handshake_timeout));
+            }
+        };
+
+        let Some(head) = head else {
+            warn!(
+                "Request head from {} exceeds {} bytes",
+                peer_addr, self.max_header_size
+            );
+            let response = crate::static_files::http_response(431, "text/plain", Vec::new());
+            stream.write_all(&response).await?;
+            stream.flush().await?;
+            return Err(Error::custom("Request headers too large"));
+        };
+
+        // The head has been consumed, so replay it for the handshake
+        let header = String::from_utf8_lossy(&head).into_owned();
+        let mut stream = Rewind::new(head, stream);
+
+        if is_websocket_upgrade(&header) {
+            self.handle_websocket_connection(stream, peer_addr).await
+        } else if !self.static_mounts.is_empty()
+            || !self.http_routes.is_empty()
+            || self.cors.is_some()
+        {
+            self.handle_http_request(stream, &header).await
+        } else {
+            // Tell misdirected HTTP clients what this endpoint expects
+            // instead of resetting the connection
+            warn!(
+                "Plain HTTP request from {} without an HTTP handler",
+                peer_addr
+            );
+            let response = crate::static_files::StaticResponse {
+                status: 426,
+                content_type: "text/plain".to_string(),
+                headers: vec![("Upgrade".to_string(), "websocket".to_string())],
+                body: b"This endpoint only accepts WebSocket connections".to_vec(),
+            };
+            stream.write_all(&response.into_bytes()).await?;
+            stream.flush().await?;
+            Ok(())
+        }
+    }
+
+    async fn handle_http_request<S>(&self, mut stream: S, header: &str) -> Result<()>
+    where
+        S: AsyncWrite + Unpin,
+    {
+        use crate::static_files::StaticResponse;
+        use tokio::io::AsyncWriteExt;
+
+        let mut parts = header.lines().next().unwrap_or("").split_whitespace();
+        let method = parts.next().unwrap_or("");
+        let path = match parts.next() {
+            Some(path) if method == "GET" || method == "HEAD" => path,
+            _ => "/",
+        };
+        let headers = parse_headers(header);
+
+        if let Some(ref cors) = self.cors
+            && crate::cors::is_preflight(method, &headers)
+        {
+            tracing::debug!("CORS preflight for {}", path);
+            let response = StaticResponse {
+                status: 204,
+                content_type: String::new(),
+                headers: cors.preflight_headers(&headers),
+                body: Vec::new(),
+            };
+            stream.write_all(&response.into_bytes()).await?;
+            stream.flush().await?;
+            return Ok(());
+        }
+
+        let cors_headers = self
+            .cors
+            .as_ref()
+            .map(|cors| cors.response_headers(&headers))
+            .unwrap_or_default();
+
+        let (route_path, query) = match path.split_once('?') {
+            Some((path, query)) => (path, Some(query.to_string())),
+            None => (path, None),
+        };
+        let http_handler = self
+            .http_routes
+            .get(route_path)
+            .map(|entry| entry.value().clone());
+        if let Some(http_handler) = http_handler {
+            let request = HttpRequest {
+                method: method.to_string(),
+                path: route_path.to_string(),
+                query,
+                headers,
+            };
+            let (status, content_type, body) = http_handler(request).await;
+            info!("{} {} -> {}", method, route_path, status);
+
+            let response = StaticResponse {
+                status,
+                content_type,
+                headers: cors_headers,
+                body,
+            };
+            let bytes = if method == "HEAD" {
+                response.head(response.body.len() as u64).into_bytes()
+            } else {
+                response.into_bytes()
+            };
+            stream.write_all(&bytes).await?;
+            stream.flush().await?;
+            return Ok(());
+        }
+
+        // Try each mount in order until one has the file
+        let mut result = Err(Error::custom("No static file handler"));
+        for mount in &self.static_mounts {
+            let Some(file_path) = mount.file_path(path) else {
+                continue;
+            };
+            result = mount
+                .handler
+                .serve_streaming_with(&file_path, &headers, cors_headers.clone(), &mut stream)
+                .await;
+            if matches!(result, Ok(_) | Err(Error::Io(_))) {
+                break;
+            }
+        }
+
+        match result {
+            Ok(sent) => {
+                info!("Served: {} ({} bytes)", path, sent);
+                Ok(())
+            }
+            // The response was already started, so it can't become a 404
+            Err(Error::Io(e)) => Err(Error::Io(e)),
+            Err(e) => {
+                tracing::warn!("File not found: {} - {}", path, e);
+                let response = StaticResponse {
+                    status: 404,
+                    content_type: "text/html".to_string(),
+                    headers: cors_headers,
+                    body: b"<html><body><h1>404 Not Found</h1></body></html>".to_vec(),
+                };
+                stream.write_all(&response.into_bytes()).await?;
+                stream.flush().await?;
+                Ok(())
+            }
+        }
+    }
+
+    async fn handle_websocket_connection<S>(&self, stream: S, peer_addr: SocketAddr) -> Result<()>
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send,
+    {
+        let mut options = ConnectionOptions {
+            keepalive: self.keepalive,
+            slow_client: self.slow_client.clone(),
+            send_limit: self.send_limit(),
+            strict_utf8: self.strict_utf8,
+            coalesce: self.coalesce.clone(),
+            hello: self.hello.clone(),
+            on_connect_async: self.connect_hook(),
+            on_disconnect_reason: self.disconnect_reason_hook(),
+            on_disconnect_async: self.disconnect_hook(),
+            outbound: self.outbound_middlewares.clone(),
+            metrics: self.metrics.clone(),
+            ..Default::default()
+        };
+
+        // Held until the connection has been served
+        let mut slot = None;
+        let callback = |request: &Request, response: Response| {
+            if let Some(trust) = &self.proxy_trust {
+                options.real_ip = trust.client_ip(peer_addr.ip(), request.headers());
+            }
+            self.check_handshake(request, response, &mut options, &mut slot)
+        };
+        let ws_stream = tokio::time::timeout(
+            self.handshake_timeout,
+            accept_hdr_async_with_config(stream, callback, self.ws_config),
+        )
+        .await
+        .map_err(|_| Error::HandshakeTimeout(self.handshake_timeout))??;
+        let conn_id = self.next_connection_id(peer_addr);
+
+        // Cloned once per connection; each message task only bumps the Arc
+        // instead of cloning every field of the router
+        let router = Arc::new(self.clone());
+        let manager = self.connection_manager.clone();
+
+        let on_message: Arc<dyn Fn(ConnectionId, Message) + Send + Sync> = if self.ordered {
+            // One task per connection handles its messages in order. It ends
+            // once the queue is drained after the connection closes.
+            let (tx, mut rx) = mpsc::unbounded_channel::<(ConnectionId, Message)>();
+            tokio::spawn(
+                async move {
+                    while let Some((conn_id, message)) = rx.recv().await {
+                        if let Err(e) = router.handle_message(conn_id, message).await {
+                            error!("Message handling error: {}", e);
+                        }
+                    }
+                }
+                .in_current_span(),
+            );
+            Arc::new(move |conn_id: ConnectionId, message: Message| {
+                let _ = tx.send((conn_id, message));
+            })
+        } else {
+            Arc::new(move |conn_id: ConnectionId, message: Message| {
+                let router = router.clone();
+                // Carry the connection's span over to the message task
+                tokio::spawn(
+                    async move {
+                        if let Err(e) = router.handle_message(conn_id, message).await {
+                            error!("Message handling error: {}", e);
+                        }
+                    }
+                    .in_current_span(),
+                );
+            })
+        };
+
+        let manager_ref = manager.clone();
+        let on_connect = self
+            .on_connect
+            .clone()
+            .map(move |cb| {
+                let manager = manager_ref.clone();
+                Arc::new(move |conn_id: ConnectionId| {
+                    cb(&manager, conn_id);
+                }) as Arc<dyn Fn(ConnectionId) + Send + Sync>
+            })
+            .unwrap_or_else(|| {
+                Arc::new(|conn_id: ConnectionId| {
+                    info!("Client connected: {}", conn_id);
+                })
+            });
+
+        let manager_ref = manager.clone();
+        let on_disconnect = self
+            .on_disconnect
+            .clone()
+            .map(move |cb| {
+                let manager = manager_ref.clone();
+                Arc::new(move |conn_id: ConnectionId| {
+                    cb(&manager, conn_id);
+                }) as Arc<dyn Fn(ConnectionId) + Send + Sync>
+            })
+            .unwrap_or_else(|| {
+                Arc::new(|conn_id: ConnectionId| {
+                    info!("Client disconnected: {}", conn_id);
+                })
+            });
+
+        serve_websocket(
+            ws_stream,
+            conn_id,
+            peer_addr,
+            manager,
+            on_message,
+            on_connect,
+            on_disconnect,
+            options,
+        )
+        .await;
+        drop(slot);
+
+        Ok(())
+    }
+
+    /// Builds the send queue cap for new connections, if one is set.
+    fn send_limit(&self) -> Option<SendLimit> {
+        self.max_pending.map(|max_pending| SendLimit {
+            max_pending,
+            on_drop: self.on_message_dropped.clone(),
+        })
+    }
+
+    /// Binds the [`on_disconnect_with_reason()`](Self::on_disconnect_with_reason)
+    /// callback to this router's connection manager.
+    fn disconnect_reason_hook(&self) -> Option<DisconnectHook> {
+        let manager = self.connection_manager.clone();
+        self.on_disconnect_with_reason.clone().map(|hook| {
+            Arc::new(move |conn_id: ConnectionId, reason: &DisconnectReason| {
+                hook(&manager, conn_id, reason)
+            }) as DisconnectHook
+        })
+    }
+
+    /// Binds a lifecycle hook to this router's connection manager.
+    fn bind_hook(&self, hook: Option<LifecycleHook>) -> Option<ConnectionHook> {
+        let manager = self.connection_manager.clone();
+        hook.map(|hook| {
+            Arc::new(move |conn: Connection| hook(manager.clone(), conn.id().clone()))
+                as ConnectionHook
+        })
+    }
+
+    /// Builds the hook awaited when a connection opens: the global
+    /// middleware's [`on_connect()`](Middleware::on_connect) in order, then
+    /// the [`on_connect_async()`](Self::on_connect_async) callback.
+    fn connect_hook(&self) -> Option<ConnectionHook> {
+        let callback = self.bind_hook(self.on_connect_async.clone());
+        if self.global_middlewares.is_empty() {
+            return callback;
+        }
+
+        let middlewares = self.global_middlewares.clone();
+        let state = self.state.clone();
+        Some(Arc::new(move |conn: Connection| {
+            let middlewares = middlewares.clone();
+            let state = state.clone();
+            let callback = callback.clone();
+            Box::pin(async move {
+                for middleware in &middlewares {
+                    middleware.on_connect(&conn, &state).await?;
+                }
+                match callback {
+                    Some(callback) => callback(conn).await,
+                    None => Ok(()),
+                }
+            })
+        }))
+    }
+
+    /// Builds the hook awaited when a connection closes: the global
+    /// middleware's [`on_disconnect()`](Middleware::on_disconnect) in order,
+    /// then the [`on_disconnect_async()`](Self::on_disconnect_async) callback.
+    ///
+    /// A failing middleware is logged and doesn't keep the others from running.
+    fn disconnect_hook(&self) -> Option<ConnectionHook> {
+        let callback = self.bind_hook(self.on_disconnect_async.clone());
+        if self.global_middlewares.is_empty() {
+            return callback;
+        }
+
+        let middlewares = self.global_middlewares.clone();
+        let state = self.state.clone();
+        Some(Arc::new(move |conn: Connection| {
+            let middlewares = middlewares.clone();
+            let state = state.clone();
+            let callback = callback.clone();
+            Box::pin(async move {
+                for middleware in &middlewares {
+                    if let Err(e) = middleware.on_disconnect(&conn, &state).await {
+                        error!("Middleware disconnect hook failed for {}: {}", conn.id(), e);
+                    }
+                }
+                match callback {
+                    Some(callback) => callback(conn).await,
+                    None => Ok(()),
+                }
+            })
+        }))
+    }
+
+    /// Records the request URL, takes a connection slot into `slot` and runs
+    /// the handshake callback, if any, against an upgrade request.
+    fn check_handshake(
+        &self,
+        request: &Request,
+        mut response: Response,
+        options: &mut ConnectionOptions,
+        slot: &mut Option<ConnectionSlot>,
+    ) -> std::result::Result<Response, ErrorResponse> {
+        // Make the URL and headers available to the Path, Query and Headers
+        // extractors
+        let uri = request.uri();
+        let path = percent_encoding::percent_decode_str(uri.path())
+            .decode_utf8_lossy()
+            .into_owned();
+        options.extensions.insert(REQUEST_PATH_KEY, path.clone());
+        options.path = Some(path);
+        if let Some(query) = uri.query() {
+            options
+                .extensions
+                .insert(REQUEST_QUERY_KEY, query.to_string());
+            if self.connection_manager.resumes_sessions() {
+                options.resume_token = serde_urlencoded::from_str::<Vec<(String, String)>>(query)
+                    .ok()
+                    .and_then(|params| {
+                        params
+                            .into_iter()
+                            .find(|(key, _)| key == "resume")
+                            .map(|(_, token)| token)
+                    });
+            }
+        }
+        options
+            .extensions
+            .insert(REQUEST_HEADERS_KEY, request.headers().clone());
+
+        if let Some(ref origin_check) = self.origin_check
+            && let Some(origin) = request.headers().get("origin")
+            && !origin.to_str().is_ok_and(|origin| origin_check(origin))
+        {
+            warn!("Rejecting upgrade from disallowed origin {:?}", origin);
+            let mut rejection = ErrorResponse::new(None);
+            *rejection.status_mut() = StatusCode::FORBIDDEN;
+            return Err(rejection);
+        }
+
+        if let Some(max) = self.max_connections {
+            *slot = ConnectionSlot::reserve(&self.connection_slots, max);
+            if slot.is_none() {
+                warn!("Connection limit of {} reached, rejecting upgrade", max);
+                let mut rejection = ErrorResponse::new(None);
+                *rejection.status_mut() = StatusCode::SERVICE_UNAVAILABLE;
+                return Err(rejection);
+            }
+        }
+
+        let Some(ref on_handshake) = self.on_handshake else {
+            return Ok(response);
+        };
+
+        let status = match on_handshake(request.headers()) {
+            Ok(HandshakeDecision {
+                outcome:
+                    Outcome::Accept {
+                        extensions,
+                        meta,
+                        protocol,
+                    },
+            }) => {
+                if let Some(ref protocol) = protocol {
+                    match HeaderValue::from_str(protocol) {
+                        Ok(value) => {
+                            response
+                                .headers_mut()
+                                .insert("Sec-WebSocket-Protocol", value);
+                        }
+                        Err(_) => warn!("Ignoring invalid subprotocol: {}", protocol),
+                    }
+                }
+                options.extensions.extend_from(&extensions);
+                options.meta = meta;
+                options.protocol = protocol;
+                return Ok(response);
+            }
+            Ok(HandshakeDecision {
+                outcome: Outcome::Reject(status),
+            }) => StatusCode::from_u16(status).unwrap_or(StatusCode::FORBIDDEN),
+            Err(e) => {
+                warn!("Handshake rejected: {}", e);
+                StatusCode::UNAUTHORIZED
+            }
+        };
+
+        let mut rejection = ErrorResponse::new(None);
+        *rejection.status_mut() = status;
+        Err(rejection)
+    }
+
+    /// Dispatches a message to its handler and sends the response.
+    ///
+    /// Runs in a `message` span, which is a child of the connection's span
+    /// because the message task inherits it.
+    #[tracing::instrument(name = "message", skip_all)]
+    pub(crate) async fn handle_message(
+        &self,
+        conn_id: ConnectionId,
+        message: Message,
+    ) -> Result<()> {
+        let conn = self
+            .connection_manager
+            .get(&conn_id)
+            .ok_or_else(|| Error::ConnectionNotFound(conn_id.clone()))?;
+
+        let message = match &self.pre_process {
+            Some(pre_process) => match pre_process(message, &conn) {
+                Ok(message) => message,
+                Err(e) => {
+                    warn!("Pre-processing failed for {}: {}", conn_id, e);
+                    self.send_error(&e, &conn, true);
+                    return Ok(());
+                }
+            },
+            None => message,
+        };
+
+        if let Some((ping, pong)) = &self.text_pong
+            && message.as_text() == Some(ping.as_str())
+        {
+            conn.send_text(pong.clone())?;
+            return Ok(());
+        }
+
+        let extensions = Extensions::new();
+        extensions.extend_from(&conn.extensions);
+
+        if !self.rpc_methods.is_empty()
+            && let Some(call) = RpcCall::parse(&message)
+        {
+            self.handle_rpc(call, conn, extensions).await;
+            return Ok(());
+        }
+
+        // The handshake path selects a handler for the whole connection,
+        // then the route matcher, the message prefix, the binary opcode and
+        // the JSON type tag
+        let chain = conn
+            .path()
+            .and_then(|path| self.path_routes.get(path).map(|c| c.value().clone()))
+            .or_else(|| self.matched_chain(&message))
+            .or_else(|| self.prefix_chain(&message))
+            .or_else(|| self.binary_chain(&message))
+            .or_else(|| self.typed_chain(&message))
+            .or_else(|| self.fallback_chain(&message));
+
+        if let Some(route_chain) = chain {
+            let result = self
+                .run_chain(&route_chain, message, &conn, extensions.clone())
+                .await;
+
+            match result {
+                Ok(responses) if responses.is_empty() => {
+                    tracing::debug!("Handler processed message without response");
+                }
+                Ok(responses) => {
+                    for response in responses {
+                        // A close response, e.g. a `CloseResponse`, ends the
+                        // connection after the messages before it
+                        if response.is_close() {
+                            info!("Handler closed connection {}", conn_id);
+                            if let Err(e) = conn.close(response.close_frame()) {
+                                error!("Failed to close {}: {}", conn_id, e);
+                            }
+                            break;
+                        }
+                        if let Err(e) = conn.send(response) {
+                            error!("Failed to send response to {}: {}", conn_id, e);
+                            break;
+                        }
+                    }
+                }
+                Err(e) => {
+                    error!("Handler error for {}: {}", conn_id, e);
+                    let from_handler = matches!(e, Error::Timeout(_))
+                        || extensions.get::<()>(HANDLER_ERROR_KEY).is_some();
+                    self.send_error(&e, &conn, from_handler);
+                }
+            }
+        } else if self.strict_routing {
+            let e = Error::RouteNotFound(conn.path().unwrap_or("/").to_string());
+            warn!("No handler found for message from {}", conn_id);
+            self.send_error(&e, &conn, true);
+        } else {
+            tracing::warn!("No handler found for message from {}", conn_id);
+        }
+
+        Ok(())
+    }
+
+    /// Answers a failed message through the error handler.
+    ///
+    /// Without one, [`Error::Coded`] errors are sent as JSON, and other errors
+    /// as `Error: {error}` text only if `from_handler` is set. Extractor and
+    /// middleware errors may describe server internals, so they are only
+    /// logged.
+    fn send_error(&self, e: &Error, conn: &Connection, from_handler: bool) {
+        let response = match self.error_handler {
+            Some(ref error_handler) => error_handler(e, conn),
+            None => match e.to_json() {
+                Some(envelope) => Some(Message::text(envelope.to_string())),
+                None => from_handler.then(|| Message::text(format!("Error: {}", e))),
+            },
+        };
+
+        if let Some(response) = response
+            && let Err(e) = conn.send(response)
+        {
+            error!("Failed to send error response to {}: {}", conn.id(), e);
+        }
+    }
+
+    /// Returns the default handler for a message no route matched, preferring
+    /// the one for its type.
+    fn fallback_chain(&self, message: &Message) -> Option<Arc<MiddlewareChain>> {
+        let typed = if message.is_text() {
+            &self.default_text_chain
+        } else if message.is_binary() {
+            &self.default_binary_chain
+        } else {
+            &None
+        };
+
+        typed.as_ref().or(self.default_chain.as_ref()).cloned()
+    }
+
+    /// Returns `true` if any message could reach a handler.
+    fn has_message_handlers(&self) -> bool {
+        self.default_chain.is_some()
+            || self.default_text_chain.is_some()
+            || self.default_binary_chain.is_some()
+            || !self.routes.is_empty()
+            || !self.path_routes.is_empty()
+            || !self.typed_routes.is_empty()
+            || !self.binary_routes.is_empty()
+            || !self.rpc_methods.is_empty()
+    }
+
+    /// Runs a route's full chain for a message, applying the handler timeout
+    /// and recording metrics.
+    async fn run_chain(
+        &self,
+        route_chain: &MiddlewareChain,
+        message: Message,
+        conn: &Connection,
+        extensions: Extensions,
+    ) -> Result<Vec<Message>> {
+        let chain = self.build_chain(route_chain);
+        let started = std::time::Instant::now();
+        let result = chain.execute(message, conn.clone(), self.state.clone(), extensions);
+        let result = match self.handler_timeout {
+            Some(limit) => tokio::time::timeout(limit, result)
+                .await
+                .unwrap_or(Err(Error::Timeout(limit))),
+            None => result.await,
+        };
+
+        if let Some(metrics) = &self.metrics {
+            metrics.handler_duration(started.elapsed());
+            if result.is_err() {
+                metrics.handler_error();
+            }
+        }
+
+        result
+    }
+
+    /// Dispatches an RPC call and answers it, unless it is a notification.
+    async fn handle_rpc(&self, call: RpcCall, conn: Connection, extensions: Extensions) {
+        let chain = self
+            .rpc_methods
+            .get(&call.method)
+            .map(|c| c.value().clone());
+        let Some(route_chain) = chain else {
+            warn!("Unknown RPC method from {}: {}", conn.id(), call.method);
+            if let Some(id) = call.id
+                && let Err(e) = conn.send(rpc::method_not_found(id, &call.method))
+            {
+                error!("Failed to send RPC error to {}: {}", conn.id(), e);
+            }
+            return;
+        };
+
+        let result = self
+            .run_chain(
+                &route_chain,
+                call.params_message(),
+                &conn,
+                extensions.clone(),
+            )
+            .await;
+        if let Err(e) = &result {
+            error!("RPC method {} failed for {}: {}", call.method, conn.id(), e);
+        }
+
+        let Some(id) = call.id else {
+            return;
+        };
+        let response = match result {
+            Ok(responses) => {
+                let json = extensions.get::<()>(JSON_RESPONSE_KEY).is_some();
+                rpc::result_response(id, responses, json)
+            }
+            Err(e) => rpc::error_response(id, &e),
+        };
+        if let Err(e) = conn.send(response) {
+            error!("Failed to send RPC response to {}: {}", conn.id(), e);
+        }
+    }
+
+    /// Looks up the route picked by the [`route_match()`](Self::route_match)
+    /// function, if any.
+    fn matched_chain(&self, message: &Message) -> Option<Arc<MiddlewareChain>> {
+        let key = (self.route_matcher.as_ref()?)(message)?;
+        self.routes.get(&key).map(|c| c.value().clone())
+    }
+
+    /// Looks up the route named by a message's leading `/path` token.
+    fn prefix_chain(&self, message: &Message) -> Option<Arc<MiddlewareChain>> {
+        let text = message.as_text()?;
+        if !text.starts_with('/') {
+            return None;
+        }
+
+        let route = text.split_once(' ').map_or(text, |(route, _)| route);
+        self.routes.get(route).map(|c| c.value().clone())
+    }
+
+    /// Looks up the [`route_binary()`](Self::route_binary) handler for a
+    /// binary message's leading opcode.
+    fn binary_chain(&self, message: &Message) -> Option<Arc<MiddlewareChain>> {
+        if !message.is_binary() {
+            return None;
+        }
+
+        let opcode = message.as_bytes().first()?;
+        self.binary_routes.get(opcode).map(|c| c.value().clone())
+    }
+
+    /// Looks up the [`route_typed()`](Self::route_typed) handler for a JSON message.
+    fn typed_chain(&self, message: &Message) -> Option<Arc<MiddlewareChain>> {
+        if self.typed_routes.is_empty() {
+            return None;
+        }
+
+        let value: serde_json::Value = crate::json::from_text(message.as_text()?).ok()?;
+        let type_name = value.get(&self.type_field)?.as_str()?;
+
+        self.typed_routes.get(type_name).map(|c| c.value().clone())
+    }
+
+    /// Assembles the full chain for a route: global middleware first, then the
+    /// route's own layers, then its handler.
+    ///
+    /// Global middleware is resolved here rather than at registration time so
+    /// that `layer()` applies to every route regardless of call order.
+    fn build_chain(&self, route_chain: &MiddlewareChain) -> MiddlewareChain {
+        let mut chain = MiddlewareChain::new();
+
+        for middleware in self
+            .global_middlewares
+            .iter()
+            .chain(&route_chain.middlewares)
+        {
+            chain = chain.layer(middleware.clone());
+        }
+
+        if let Some(ref handler) = route_chain.handler {
+            chain = chain.handler(handler.clone());
+        }
+
+        chain
+    }
+
+    pub(crate) fn next_connection_id(&self, peer_addr: SocketAddr) -> ConnectionId {
+        match self.id_generator {
+            Some(ref generate) => generate(peer_addr),
+            None => format!("conn_{}", self.next_id.fetch_add(1, Ordering::SeqCst)),
+        }
+    }
+}
+
+/// Parses `addr` and binds a listener to it.
+async fn bind(addr: impl AsRef<str>) -> Result<TcpListener> {
+    let addr: SocketAddr = addr
+        .as_ref()
+        .parse()
+        .map_err(|e| Error::custom(format!("Invalid address: {}", e)))?;
+
+    Ok(TcpListener::bind(addr).await?)
+}
+
+/// Binds a Unix domain socket at `path`, replacing a stale socket file.
+#[cfg(unix)]
+fn bind_unix(path: &std::path::Path) -> Result<UnixListener> {
+    use std::os::unix::fs::FileTypeExt;
+
+    // A socket file left behind by a previous run would make bind fail
+    if let Ok(metadata) = std::fs::symlink_metadata(path)
+        && metadata.file_type().is_socket()
+    {
+        std::fs::remove_file(path)?;
+    }
+
+    Ok(UnixListener::bind(path)?)
+}
+
+/// A listener the server accepts connections from.
+trait Listener: Send {
+    /// The stream of an accepted connection.
+    type Stream: AsyncRead + AsyncWrite + Unpin + Send + 'static;
+
+    /// Accepts the next connection, with the peer's address.
+    fn accept(&self) -> impl Future<Output = std::io::Result<(Self::Stream, SocketAddr)>> + Send;
+
+    /// Describes the local address for logging.
+    fn describe(&self) -> std::io::Result<String>;
+}
+
+impl Listener for TcpListener {
+    type Stream = TcpStream;
+
+    fn accept(&self) -> impl Future<Output = std::io::Result<(TcpStream, SocketAddr)>> + Send {
+        TcpListener::accept(self)
+    }
+
+    fn describe(&self) -> std::io::Result<String> {
+        Ok(self.local_addr()?.to_string())
+    }
+}
+
+#[cfg(unix)]
+impl Listener for UnixListener {
+    type Stream = tokio::net::UnixStream;
+
+    async fn accept(&self) -> std::io::Result<(tokio::net::UnixStream, SocketAddr)> {
+        let (stream, _) = UnixListener::accept(self).await?;
+        // Unix peers have no IP address
+        Ok((stream, SocketAddr::from(([0, 0, 0, 0], 0))))
+    }
+
+    fn describe(&self) -> std::io::Result<String> {
+        let addr = self.local_addr()?;
+        Ok(match addr.as_pathname() {
+            Some(path) => format!("unix:{}", path.display()),
+            None => "unix:(unnamed)".to_string(),
+        })
+    }
+}
+
+/// Default maximum size of an HTTP request head before the request is
+/// rejected.
+const DEFAULT_MAX_HEADER_SIZE: usize = 16 * 1024;
+
+/// Reads from `stream` until the end of the HTTP headers (`\r\n\r\n`).
+///
+/// Returns everything read so far, which may include bytes past the headers,
+/// or `None` if the headers exceed `max_size` bytes.
+async fn read_request_head<S>(stream: &mut S, max_size: usize) -> std::io::Result<Option<Vec<u8>>>
+where
+    S: AsyncRead + Unpin,
+{
+    use tokio::io::AsyncReadExt;
+
+    let mut head = Vec::with_capacity(1024);
+    let mut chunk = [0u8; 1024];
+
+    loop {
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            return Ok(Some(head));
+        }
+
+        // The terminator may straddle two reads
+        let search_from = head.len().saturating_sub(3);
+        head.extend_from_slice(&chunk[..n]);
+
+        if let Some(pos) = head[search_from..]
+            .windows(4)
+            .position(|w| w == b"\r\n\r\n")
+        {
+            // Bytes after the terminator don't count towards the limit
+            let head_len = search_from + pos + 4;
+            return Ok((head_len <= max_size).then_some(head));
+        }
+
+        if head.len() > max_size {
+            return Ok(None);
+        }
+    }
+}
+
+/// Returns true if the request head carries an `Upgrade: websocket` header.
+fn is_websocket_upgrade(header: &str) -> bool {
+    header.lines().skip(1).any(|line| {
+        line.split_once(':').is_some_and(|(name, value)| {
+            name.trim().eq_ignore_ascii_case("upgrade")
+                && value.to_ascii_lowercase().contains("websocket")
+        })
+    })
+}
+
+/// Parses the header lines of an HTTP request head, skipping malformed ones.
+fn parse_headers(header: &str) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    for line in header.lines().skip(1) {
+        if let Some((name, value)) = line.split_once(':')
+            && let (Ok(name), Ok(value)) = (
+                HeaderName::from_bytes(name.trim().as_bytes()),
+                HeaderValue::from_str(value.trim()),
+            )
+        {
+            headers.append(name, value);
+        }
+    }
+    headers
+}
+
+impl Clone for Router {
+    fn clone(&self) -> Self {
+        Self {
+            routes: self.routes.clone(),
+            path_routes: self.path_routes.clone(),
+            typed_routes: self.typed_routes.clone(),
+            binary_routes: self.binary_routes.clone(),
+            rpc_methods: self.rpc_methods.clone(),
+            type_field: self.type_field.clone(),
+            global_middlewares: self.global_middlewares.clone(),
+            outbound_middlewares: self.outbound_middlewares.clone(),
+            state: self.state.clone(),
+            connection_manager: self.connection_manager.clone(),
+            on_connect: self.on_connect.clone(),
+            hello: self.hello.clone(),
+            on_disconnect: self.on_disconnect.clone(),
+            on_disconnect_with_reason: self.on_disconnect_with_reason.clone(),
+            on_connect_async: self.on_connect_async.clone(),
+            on_disconnect_async: self.on_disconnect_async.clone(),
+            on_shutdown: self.on_shutdown.clone(),
+            broadcast_backend: self.broadcast_backend.clone(),
+            default_chain: self.default_chain.clone(),
+            default_text_chain: self.default_text_chain.clone(),
+            default_binary_chain: self.default_binary_chain.clone(),
+            static_mounts: self.static_mounts.clone(),
+            http_routes: self.http_routes.clone(),
+            cors: self.cors.clone(),
+            drain_timeout: self.drain_timeout,
+            handshake_timeout: self.handshake_timeout,
+            max_header_size: self.max_header_size,
+            keepalive: self.keepalive,
+            slow_client: self.slow_client.clone(),
+            max_pending: self.max_pending,
+            on_message_dropped: self.on_message_dropped.clone(),
+            coalesce: self.coalesce.clone(),
+            ws_config: self.ws_config,
+            strict_utf8: self.strict_utf8,
+            ordered: self.ordered,
+            text_pong: self.text_pong.clone(),
+            strict_routing: self.strict_routing,
+            on_handshake: self.on_handshake.clone(),
+            error_handler: self.error_handler.clone(),
+            pre_process: self.pre_process.clone(),
+            route_matcher: self.route_matcher.clone(),
+            max_connections: self.max_connections,
+            origin_check: self.origin_check.clone(),
+            proxy_trust: self.proxy_trust.clone(),
+            handler_timeout: self.handler_timeout,
+            metrics: self.metrics.clone(),
+            id_generator: self.id_generator.clone(),
+            next_id: self.next_id.clone(),
+            connection_slots: self.connection_slots.clone(),
+        }
+    }
+}
+
+impl Default for Router {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handler::handler;
+    use crate::middleware::from_fn;
+    use std::sync::Mutex;
+
+    fn connect(router: &Router) -> (ConnectionId, mpsc::UnboundedReceiver<Message>) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let addr: SocketAddr = "127.0.0.1:9000".parse().unwrap();
+        let conn = Connection::new("conn_test".to_string(), addr, tx);
+        router.connection_manager.add(conn);
+        ("conn_test".to_string(), rx)
+    }
+
+    #[tokio::test]
+    async fn test_manager_extractor_without_listen() {
+        use crate::extractor::Manager;
+
+        let router =
+            Router::new().default_handler(handler(|Manager(manager): Manager| async move {
+                Ok(manager.count().to_string())
+            }));
+
+        let (conn_id, mut rx) = connect(&router);
+        router
+            .handle_message(conn_id, Message::text("count"))
+            .await
+            .unwrap();
+        assert_eq!(rx.try_recv().unwrap().as_text(), Some("1"));
+    }
+
+    #[test]
+    #[should_panic(expected = "keepalive interval must be greater than zero")]
+    fn test_keepalive_rejects_zero_interval() {
+        let _ = Router::new().keepalive(Duration::ZERO, Duration::from_secs(30));
+    }
+
+    #[tokio::test]
+    async fn test_pre_process_rewrites_before_routing() {
+        let router = Router::new()
+            .route("/echo", handler(|msg: Message| async move { Ok(msg) }))
+            .pre_process(|msg, _conn| {
+                let text = msg.as_text().unwrap_or_default();
+                let body = text
+                    .strip_prefix("v1|")
+                    .ok_or_else(|| Error::custom("missing header"))?;
+                Ok(Message::text(body))
+            });
+
+        let (conn_id, mut rx) = connect(&router);
+        router
+            .handle_message(conn_id.clone(), Message::text("v1|/echo hi"))
+            .await
+            .unwrap();
+        assert_eq!(rx.try_recv().unwrap().as_text(), Some("/echo hi"));
+
+        router
+            .handle_message(conn_id, Message::text("/echo hi"))
+            .await
+            .unwrap();
+        assert_eq!(
+            rx.try_recv().unwrap().as_text(),
+            Some("Error: Custom error: missing header")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_global_middleware_runs_in_order() {
+        let calls = Arc::new(Mutex::new(Vec::new()));
+
+        let first = calls.clone();
+        let second = calls.clone();
+        let route_layer = calls.clone();
+
+        // Global layers are added after the route to ensure order of registration
+        // does not matter.
+        let router = Router::new()
+            .route_with_layers(
+                "/echo",
+                vec![from_fn(move |msg, conn, state, ext, next| {
+                    route_layer.lock().unwrap().push("route");
+                    next.run(msg, conn, state, ext)
+                })],
+                handler(|msg: Message| async move { Ok(msg) }),
+            )
+            .layer(from_fn(move |msg, conn, state, ext, next| {
+                first.lock().unwrap().push("first");
+                next.run(msg, conn, state, ext)
+            }))
+            .layer(from_fn(move |msg, conn, state, ext, next| {
+                second.lock().unwrap().push("second");
+                next.run(msg, conn, state, ext)
+            }));
+
+        let (conn_id, mut rx) = connect(&router);
+        router
+            .handle_message(conn_id, Message::text("/echo hi"))
+            .await
+            .unwrap();
+
+        assert_eq!(*calls.lock().unwrap(), vec!["first", "second", "route"]);
+        assert_eq!(rx.try_recv().unwrap().as_text(), Some("/echo hi"));
+    }
+
+    #[tokio::test]
+    async fn test_middleware_can_short_circuit_with_response() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let handler_calls = calls.clone();
+        let router = Router::new()
+            .default_handler(handler(move |msg: Message| {
+                handler_calls.fetch_add(1, Ordering::SeqCst);
+                async move { Ok(msg) }
+            }))
+            .layer(from_fn(|msg, conn, state, ext, next| async move {
+                if msg.as_text() == Some("cached") {
+                    return Ok(vec![Message::text("from cache")]);
+                }
+                next.run(msg, conn, state, ext).await
+            }));
+
+        let (conn_id, mut rx) = connect(&router);
+        router
+            .handle_message(conn_id.clone(), Message::text("cached"))
+            .await
+            .unwrap();
+        assert_eq!(rx.try_recv().unwrap().as_text(), Some("from cache"));
+        assert_eq!(calls.load(Ordering::SeqCst), 0);
+
+        router
+            .handle_message(conn_id, Message::text("fresh"))
+            .await
+            .unwrap();
+        assert_eq!(rx.try_recv().unwrap().as_text(), Some("fresh"));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_middleware_sees_every_message_of_a_response() {
+        use crate::handler::Messages;
+
+        let router = Router::new()
+            .default_handler(handler(|| async {
+                Ok(Messages(vec![Message::text("ack"), Message::text("event")]))
+            }))
+            .layer(from_fn(|msg, conn, state, ext, next| async move {
+                let responses = next.run(msg, conn, state, ext).await?;
+                Ok(responses
+                    .into_iter()
+                    .map(|msg| Message::text(msg.as_text().unwrap_or("").to_uppercase()))
+                    .collect())
+            }));
+
+        let (conn_id, mut rx) = connect(&router);
+        router
+            .handle_message(conn_id, Message::text("subscribe"))
+            .await
+            .unwrap();
+
+        assert_eq!(rx.try_recv().unwrap().as_text(), Some("ACK"));
+        assert_eq!(rx.try_recv().unwrap().as_text(), Some("EVENT"));
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_middleware_can_suppress_response() {
+        let router = Router::new()
+            .default_handler(handler(|msg: Message| async move { Ok(msg) }))
+            .layer(from_fn(|_msg, _conn, _state, _ext, _next| async move {
+                Ok(Vec::new())
+            }));
+
+        let (conn_id, mut rx) = connect(&router);
+        router
+            .handle_message(conn_id, Message::text("hello"))
+            .await
+            .unwrap();
+
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_listen_with_shutdown_returns() {
+        let (tx, rx) = tokio::sync::oneshot::channel::<()>();
+
+        let server = tokio::spawn(Router::new().listen_with_shutdown("127.0.0.1:0", async {
+            let _ = rx.await;
+        }));
+
+        tx.send(()).unwrap();
+
+        let result = tokio::time::timeout(Duration::from_secs(5), server)
+            .await
+            .expect("server did not shut down");
+        assert!(result.unwrap().is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_on_shutdown_runs_before_close() {
+        use futures_util::StreamExt;
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (tx, rx) = tokio::sync::oneshot::channel::<()>();
+        let router = Router::new().on_shutdown(|manager| async move {
+            manager.broadcast(Message::text("restarting"));
+            Ok(())
+        });
+        let manager = router.connection_manager();
+        let server =
+            tokio::spawn(router.serve(
+                listener,
+                async {
+                    let _ = rx.await;
+                },
+                |router, stream, peer_addr| async move {
+                    router.handle_connection(stream, peer_addr).await
+                },
+            ));
+
+        let stream = TcpStream::connect(addr).await.unwrap();
+        let (mut client, _) = tokio_tungstenite::client_async(format!("ws://{}/", addr), stream)
+            .await
+            .unwrap();
+        while manager.count() == 0 {
+            tokio::task::yield_now().await;
+        }
+        tx.send(()).unwrap();
+
+        let notice = Message::from_tungstenite(client.next().await.unwrap().unwrap());
+        assert_eq!(notice.as_text(), Some("restarting"));
+        let close = Message::from_tungstenite(client.next().await.unwrap().unwrap());
+        assert!(close.is_close());
+        while let Some(Ok(_)) = client.next().await {}
+
+        let result = tokio::time::timeout(Duration::from_secs(5), server)
+            .await
+            .expect("server did not shut down");
+        assert!(result.unwrap().is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_route_typed_dispatches_by_type_field() {
+        let router = Router::new()
+            .route_typed("move", handler(|| async { Ok("moved".to_string()) }))
+            .default_handler(handler(|| async { Ok("default".to_string()) }));
+
+        let (conn_id, mut rx) = connect(&router);
+
+        router
+            .handle_message(conn_id.clone(), Message::text(r#"{"type":"move","x":1}"#))
+            .await
+            .unwrap();
+        assert_eq!(rx.try_recv().unwrap().as_text(), Some("moved"));
+
+        for text in [r#"{"type":"jump"}"#, r#"{"kind":"move"}"#, "move"] {
+            router
+                .handle_message(conn_id.clone(), Message::text(text))
+                .await
+                .unwrap();
+            assert_eq!(rx.try_recv().unwrap().as_text(), Some("default"));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_route_typed_custom_field() {
+        let router = Router::new()
+            .type_field("action")
+            .route_typed("join", handler(|| async { Ok("joined".to_string()) }));
+
+        let (conn_id, mut rx) = connect(&router);
+        router
+            .handle_message(conn_id, Message::text(r#"{"action":"join"}"#))
+            .await
+            .unwrap();
+
+        assert_eq!(rx.try_recv().unwrap().as_text(), Some("joined"));
+    }
+
+    #[test]
+    fn test_handshake_callback_rejects_and_accepts() {
+        let router = Router::new().on_handshake(|headers| {
+            match headers.get("authorization").map(|v| v.as_bytes()) {
+                Some(b"good") => Ok(HandshakeDecision::accept().extension(7u32)),
+                Some(_) => Ok(HandshakeDecision::reject(403)),
+                None => Err(Error::custom("missing token")),
+            }
+        });
+
+        let check = |token: Option<&str>| {
+            let mut request = Request::builder().uri("/");
+            if let Some(token) = token {
+                request = request.header("authorization", token);
+            }
+            let mut options = ConnectionOptions::default();
+            let result = router.check_handshake(
+                &request.body(()).unwrap(),
+                Response::new(()),
+                &mut options,
+                &mut None,
+            );
+            (result.map_err(|e| e.status()), options)
+        };
+
+        assert_eq!(check(None).0.unwrap_err(), StatusCode::UNAUTHORIZED);
+        assert_eq!(check(Some("bad")).0.unwrap_err(), StatusCode::FORBIDDEN);
+
+        let (result, options) = check(Some("good"));
+        assert!(result.is_ok());
+        assert_eq!(options.extensions.get_typed::<u32>().as_deref(), Some(&7));
+    }
+
+    #[test]
+    fn test_max_connections_rejects_upgrade() {
+        let router = Router::new().max_connections(1);
+        assert_eq!(router.connection_limit(), Some(1));
+
+        let check = |slot: &mut Option<ConnectionSlot>| {
+            let request = Request::builder().uri("/").body(()).unwrap();
+            let mut options = ConnectionOptions::default();
+            router
+                .check_handshake(&request, Response::new(()), &mut options, slot)
+                .map(|_| ())
+                .map_err(|e| e.status())
+        };
+
+        let mut first = None;
+        assert!(check(&mut first).is_ok());
+        assert!(first.is_some());
+
+        let mut second = None;
+        assert_eq!(
+            check(&mut second).unwrap_err(),
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+        assert!(second.is_none());
+
+        // The slot is free again once the first connection ends
+        drop(first);
+        assert!(check(&mut second).is_ok());
+    }
+
+    #[test]
+    fn test_allowed_origins_rejects_other_origins() {
+        let check = |router: &Router, origin: Option<&str>| {
+            let mut request = Request::builder().uri("/");
+            if let Some(origin) = origin {
+                request = request.header("origin", origin);
+            }
+            let mut options = ConnectionOptions::default();
+            router
+                .check_handshake(
+                    &request.body(()).unwrap(),
+                    Response::new(()),
+                    &mut options,
+                    &mut None,
+                )
+                .map_err(|e| e.status())
+        };
+
+        let router = Router::new().allowed_origins(["https://app.example.com"]);
+        assert!(check(&router, Some("https://app.example.com")).is_ok());
+        assert!(check(&router, Some("HTTPS://APP.EXAMPLE.COM")).is_ok());
+        assert!(check(&router, None).is_ok());
+        assert_eq!(
+            check(&router, Some("https://evil.example")).unwrap_err(),
+            StatusCode::FORBIDDEN
+        );
+
+        let router = Router::new().allowed_origins(["*"]);
+        assert!(check(&router, Some("https://evil.example")).is_ok());
+
+        let router = Router::new().allow_origin(|origin| origin.ends_with(".example.com"));
+        assert!(check(&router, Some("https://api.example.com")).is_ok());
+        assert_eq!(
+            check(&router, Some("https://example.org")).unwrap_err(),
+            StatusCode::FORBIDDEN
+        );
+    }
+
+    #[tokio::test]
+    async fn test_cors_preflight_and_headers() {
+        let router = Router::new().cors(CorsConfig::new().allow_origin("https://app.example.com"));
+
+        let mut response = Vec::new();
+        router
+            .handle_http_request(
+                &mut response,
+                "OPTIONS /api HTTP/1.1\r\nOrigin: https://app.example.com\r\n\
+                 Access-Control-Request-Method: GET\r\n\r\n",
+            )
+            .await
+            .unwrap();
+        let response = String::from_utf8(response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 204 No Content\r\n"));
+        assert!(response.contains("Access-Control-Allow-Origin: https://app.example.com\r\n"));
+        assert!(response.contains("Access-Control-Allow-Methods: GET, HEAD, OPTIONS\r\n"));
+        assert!(!response.contains("Content-Length"));
+
+        let mut response = Vec::new();
+        router
+            .handle_http_request(
+                &mut response,
+                "GET /missing HTTP/1.1\r\nOrigin: https://evil.example\r\n\r\n",
+            )
+            .await
+            .unwrap();
+        let response = String::from_utf8(response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 404 Not Found\r\n"));
+        assert!(!response.contains("Access-Control-Allow-Origin"));
+    }
+
+    #[tokio::test]
+    async fn test_serve_static_at_strips_prefix() {
+        let dir = std::env::temp_dir().join(format!("wsforge-prefix-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("app.js"), "console.log(1)").unwrap();
+
+        let router = Router::new().serve_static_at("/assets/", &dir);
+        let mount = &router.static_mounts[0];
+        assert_eq!(mount.file_path("/assets?v=1").as_deref(), Some("/?v=1"));
+        assert_eq!(mount.file_path("/assetsfoo"), None);
+
+        let mut response = Vec::new();
+        router
+            .handle_http_request(&mut response, "GET /assets/app.js HTTP/1.1\r\n\r\n")
+            .await
+            .unwrap();
+        let response = String::from_utf8(response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.ends_with("console.log(1)"));
+
+        let mut response = Vec::new();
+        router
+            .handle_http_request(&mut response, "GET /app.js HTTP/1.1\r\n\r\n")
+            .await
+            .unwrap();
+        assert!(response.starts_with(b"HTTP/1.1 404 Not Found\r\n"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_static_dirs_tried_in_order() {
+        let base = std::env::temp_dir().join(format!("wsforge-layers-{}", std::process::id()));
+        let (app, theme) = (base.join("app"), base.join("theme"));
+        std::fs::create_dir_all(&app).unwrap();
+        std::fs::create_dir_all(&theme).unwrap();
+        std::fs::write(app.join("style.css"), "app").unwrap();
+        std::fs::write(theme.join("style.css"), "theme").unwrap();
+        std::fs::write(theme.join("logo.svg"), "<svg/>").unwrap();
+
+        let router = Router::new().serve_static(&app).serve_static(&theme);
+        let get = |path: &'static str| {
+            let router = router.clone();
+            async move {
+                let mut response = Vec::new();
+                let request = format!("GET {} HTTP/1.1\r\n\r\n", path);
+                router
+                    .handle_http_request(&mut response, &request)
+                    .await
+                    .unwrap();
+                String::from_utf8(response).unwrap()
+            }
+        };
+
+        assert!(get("/style.css").await.ends_with("\r\n\r\napp"));
+        assert!(get("/logo.svg").await.ends_with("\r\n\r\n<svg/>"));
+        assert!(get("/missing.js").await.starts_with("HTTP/1.1 404 "));
+
+        std::fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_http_route_before_static_files() {
+        let router = Router::new().serve_static("/nonexistent").http_route(
+            "/health",
+            |req: HttpRequest| async move {
+                (
+                    200,
+                    "text/plain",
+                    format!("ok {}", req.query.unwrap_or_default()),
+                )
+            },
+        );
+
+        let mut response = Vec::new();
+        router
+            .handle_http_request(&mut response, "GET /health?verbose=1 HTTP/1.1\r\n\r\n")
+            .await
+            .unwrap();
+        let response = String::from_utf8(response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.contains("Content-Type: text/plain\r\n"));
+        assert!(response.ends_with("\r\n\r\nok verbose=1"));
+
+        let mut response = Vec::new();
+        router
+            .handle_http_request(&mut response, "HEAD /health HTTP/1.1\r\n\r\n")
+            .await
+            .unwrap();
+        let response = String::from_utf8(response).unwrap();
+        assert!(response.contains("Content-Length: 3\r\n"));
+        assert!(response.ends_with("\r\n\r\n"));
+
+        let mut response = Vec::new();
+        router
+            .handle_http_request(&mut response, "GET /other HTTP/1.1\r\n\r\n")
+            .await
+            .unwrap();
+        assert!(response.starts_with(b"HTTP/1.1 404 Not Found\r\n"));
+    }
+
+    #[tokio::test]
+    async fn test_strict_utf8_closes_with_1007() {
+        use futures_util::{SinkExt, StreamExt};
+        use tokio_tungstenite::tungstenite::protocol::frame::Frame;
+        use tokio_tungstenite::tungstenite::protocol::frame::coding::{Data, OpCode};
+
+        let router = Router::new().strict_utf8(true);
+
+        let (server_io, client_io) = tokio::io::duplex(4096);
+        let peer: SocketAddr = "127.0.0.1:9000".parse().unwrap();
+        let server = tokio::spawn({
+            let router = router.clone();
+            async move { router.handle_websocket_connection(server_io, peer).await }
+        });
+
+        let (mut client, _) = tokio_tungstenite::client_async("ws://localhost/", client_io)
+            .await
+            .unwrap();
+        let frame = Frame::message(vec![b'o', b'k', 0xC3, 0x28], OpCode::Data(Data::Text), true);
+        client
+            .send(tokio_tungstenite::tungstenite::Message::Frame(frame))
+            .await
+            .unwrap();
+
+        let frame = client.next().await.unwrap().unwrap();
+        let close = Message::from_tungstenite(frame);
+        assert_eq!(close.close_frame().map(|(code, _)| code), Some(1007));
+        while let Some(Ok(_)) = client.next().await {}
+        tokio::time::timeout(Duration::from_secs(5), server)
+            .await
+            .expect("invalid text did not end the connection")
+            .unwrap()
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_hello_message_sent_first() {
+        use futures_util::{SinkExt, StreamExt};
+
+        let router = Router::new()
+            .hello_message(|conn| Some(Message::text(format!("hello {}", conn.id()))))
+            .on_connect(|manager, conn_id| {
+                let _ = manager.get(&conn_id).unwrap().send_text("connected");
+            })
+            .default_handler(handler(|msg: Message| async move { Ok(msg) }));
+
+        let (server_io, client_io) = tokio::io::duplex(4096);
+        let peer: SocketAddr = "127.0.0.1:9000".parse().unwrap();
+        tokio::spawn(async move { router.handle_websocket_connection(server_io, peer).await });
+        let (mut client, _) = tokio_tungstenite::client_async("ws://localhost/", client_io)
+            .await
+            .unwrap();
+        client
+            .send(tokio_tungstenite::tungstenite::Message::text("echo"))
+            .await
+            .unwrap();
+
+        for expected in ["hello conn_0", "connected", "echo"] {
+            let reply = Message::from_tungstenite(client.next().await.unwrap().unwrap());
+            assert_eq!(reply.as_text(), Some(expected));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_trusted_proxy_sets_real_ip() {
+        use crate::extractor::ConnectInfo;
+        use futures_util::{SinkExt, StreamExt};
+        use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+
+        async fn whoami(ConnectInfo(info): ConnectInfo) -> Result<String> {
+            Ok(format!("{:?} {}", info.real_ip, info.client_ip()))
+        }
+
+        let router = Router::new()
+            .trusted_proxies(["10.0.0.10".parse().unwrap()])
+            .default_handler(handler(whoami));
+
+        for (peer, expected) in [
+            ("10.0.0.10:9000", "Some(203.0.113.7) 203.0.113.7"),
+            ("198.51.100.1:9000", "None 198.51.100.1"),
+        ] {
+            let (server_io, client_io) = tokio::io::duplex(4096);
+            let peer: SocketAddr = peer.parse().unwrap();
+            let router = router.clone();
+            tokio::spawn(async move { router.handle_websocket_connection(server_io, peer).await });
+
+            let mut request = "ws://localhost/".into_client_request().unwrap();
+            request
+                .headers_mut()
+                .insert("x-forwarded-for", "203.0.113.7".parse().unwrap());
+            let (mut client, _) = tokio_tungstenite::client_async(request, client_io)
+                .await
+                .unwrap();
+            client
+                .send(tokio_tungstenite::tungstenite::Message::text("who"))
+                .await
+                .unwrap();
+
+            let reply = Message::from_tungstenite(client.next().await.unwrap().unwrap());
+            assert_eq!(reply.as_text(), Some(expected));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_ordered_replies_in_arrival_order() {
+        use futures_util::{SinkExt, StreamExt};
+
+        async fn slow_first(msg: Message) -> Result<String> {
+            let text = msg.as_text().unwrap_or_default().to_string();
+            if text == "first" {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+            Ok(text)
+        }
+
+        let router = Router::new()
+            .ordered(true)
+            .default_handler(handler(slow_first));
+
+        let (server_io, client_io) = tokio::io::duplex(4096);
+        let peer: SocketAddr = "127.0.0.1:9000".parse().unwrap();
+        tokio::spawn(async move { router.handle_websocket_connection(server_io, peer).await });
+        let (mut client, _) = tokio_tungstenite::client_async("ws://localhost/", client_io)
+            .await
+            .unwrap();
+
+        for text in ["first", "second"] {
+            client
+                .send(tokio_tungstenite::tungstenite::Message::text(text))
+                .await
+                .unwrap();
+        }
+
+        let reply = Message::from_tungstenite(client.next().await.unwrap().unwrap());
+        assert_eq!(reply.as_text(), Some("first"));
+        let reply = Message::from_tungstenite(client.next().await.unwrap().unwrap());
+        assert_eq!(reply.as_text(), Some("second"));
+    }
+
+    #[tokio::test]
+    async fn test_coalesce_batches_text_messages() {
+        use futures_util::StreamExt;
+
+        let router = Router::new()
+            .coalesce(Duration::from_millis(50), CoalesceStrategy::JsonArray)
+            .connection_id_generator(|_| "player".to_string());
+        let manager = router.connection_manager();
+
+        let (server_io, client_io) = tokio::io::duplex(4096);
+        let peer: SocketAddr = "127.0.0.1:9000".parse().unwrap();
+        tokio::spawn({
+            let router = router.clone();
+            async move { router.handle_websocket_connection(server_io, peer).await }
+        });
+        let (mut client, _) = tokio_tungstenite::client_async("ws://localhost/", client_io)
+            .await
+            .unwrap();
+        while manager.get(&"player".to_string()).is_none() {
+            tokio::task::yield_now().await;
+        }
+
+        manager.broadcast(Message::text(r#"{"x":1}"#));
+        manager.broadcast(Message::text(r#"{"x":2}"#));
+        manager.broadcast(Message::binary(vec![7]));
+        manager.broadcast(Message::text(r#"{"x":3}"#));
+        manager.broadcast(Message::text("not json"));
+
+        let batch = Message::from_tungstenite(client.next().await.unwrap().unwrap());
+        assert_eq!(batch.as_text(), Some(r#"[{"x":1},{"x":2}]"#));
+        let binary = Message::from_tungstenite(client.next().await.unwrap().unwrap());
+        assert_eq!(binary.as_bytes(), &[7]);
+        let batch = Message::from_tungstenite(client.next().await.unwrap().unwrap());
+        assert_eq!(batch.as_text(), Some(r#"[{"x":3}]"#));
+        let plain = Message::from_tungstenite(client.next().await.unwrap().unwrap());
+        assert_eq!(plain.as_text(), Some("not json"));
+    }
+
+    #[tokio::test]
+    async fn test_connection_stats_count_traffic() {
+        use futures_util::{SinkExt, StreamExt};
+
+        async fn echo(msg: Message) -> Result<Message> {
+            Ok(msg)
+        }
+
+        let router = Router::new()
+            .default_handler(handler(echo))
+            .connection_id_generator(|_| "client".to_string());
+        let manager = router.connection_manager();
+
+        let (server_io, client_io) = tokio::io::duplex(4096);
+        let peer: SocketAddr = "127.0.0.1:9000".parse().unwrap();
+        tokio::spawn({
+            let router = router.clone();
+            async move { router.handle_websocket_connection(server_io, peer).await }
+        });
+        let (mut client, _) = tokio_tungstenite::client_async("ws://localhost/", client_io)
+            .await
+            .unwrap();
+
+        client
+            .send(tokio_tungstenite::tungstenite::Message::text("hello"))
+            .await
+            .unwrap();
+        let reply = Message::from_tungstenite(client.next().await.unwrap().unwrap());
+        assert_eq!(reply.as_text(), Some("hello"));
+
+        let conn = manager.get(&"client".to_string()).unwrap();
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while conn.stats().messages_sent == 0 {
+                tokio::task::yield_now().await;
+            }
+        })
+        .await
+        .expect("reply was not counted");
+
+        let expected = crate::connection::ConnStats {
+            messages_sent: 1,
+            messages_received: 1,
+            bytes_sent: 5,
+            bytes_received: 5,
+        };
+        assert_eq!(conn.stats(), expected);
+        assert_eq!(manager.stats(), expected);
+    }
+
+    #[tokio::test]
+    async fn test_session_resumed_from_query() {
+        use futures_util::StreamExt;
+
+        let router = Router::new().session_resumption(Duration::from_secs(60));
+        let manager = router.connection_manager();
+        let connect = |id: &'static str, url: String| {
+            let (server_io, client_io) = tokio::io::duplex(4096);
+            let router = router
+                .clone()
+                .connection_id_generator(move |_| id.to_string());
+            let peer: SocketAddr = "127.0.0.1:9000".parse().unwrap();
+            tokio::spawn(async move { router.handle_websocket_connection(server_io, peer).await });
+            async move {
+                tokio_tungstenite::client_async(url, client_io)
+                    .await
+                    .unwrap()
+                    .0
+            }
+        };
+
+        let mut first = connect("first", "ws://localhost/".to_string()).await;
+        while manager.get(&"first".to_string()).is_none() {
+            tokio::task::yield_now().await;
+        }
+        manager.join_room(&"first".to_string(), "lobby").unwrap();
+        let token = manager.resume_token(&"first".to_string()).unwrap();
+        first.close(None).await.unwrap();
+        while first.next().await.is_some() {}
+        while manager.count() > 0 {
+            tokio::task::yield_now().await;
+        }
+
+        let _second = connect("second", format!("ws://localhost/?resume={}", token)).await;
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while manager.room_members("lobby") != vec!["second".to_string()] {
+                tokio::task::yield_now().await;
+            }
+        })
+        .await
+        .expect("session was not resumed");
+    }
+
+    #[test]
+    fn test_ws_config_sets_limits() {
+        let router = Router::new().ws_config(WebSocketConfig {
+            max_frame_size: Some(8 * 1024),
+            ..Default::default()
+        });
+        assert_eq!(router.config().max_frame_size, Some(8 * 1024));
+        assert_eq!(
+            router.config().max_message_size,
+            WebSocketConfig::default().max_message_size
+        );
+
+        // The shorthands adjust the config instead of replacing it
+        let router = router.max_message_size(1024);
+        assert_eq!(router.config().max_frame_size, Some(8 * 1024));
+    }
+
+    #[tokio::test]
+    async fn test_max_message_size_closes_with_1009() {
+        use futures_util::{SinkExt, StreamExt};
+
+        let router = Router::new().max_message_size(16);
+        assert_eq!(router.config().max_message_size, Some(16));
+
+        let (server_io, client_io) = tokio::io::duplex(4096);
+        let peer: SocketAddr = "127.0.0.1:9000".parse().unwrap();
+        let server = tokio::spawn({
+            let router = router.clone();
+            async move { router.handle_websocket_connection(server_io, peer).await }
+        });
+
+        let (mut client, _) = tokio_tungstenite::client_async("ws://localhost/", client_io)
+            .await
+            .unwrap();
+        client
+            .send(Message::text("x".repeat(64)).into_tungstenite())
+            .await
+            .unwrap();
+
+        // The server closes the connection instead of reading the message
+        let frame = client.next().await.unwrap().unwrap();
+        let close = Message::from_tungstenite(frame);
+        assert_eq!(close.close_frame().map(|(code, _)| code), Some(1009));
+        while let Some(Ok(_)) = client.next().await {}
+        tokio::time::timeout(Duration::from_secs(5), server)
+            .await
+            .expect("oversized message did not end the connection")
+            .unwrap()
+            .unwrap();
+        assert_eq!(router.connection_count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_max_message_size_limits_assembled_fragments() {
+        use futures_util::{SinkExt, StreamExt};
+        use tokio_tungstenite::tungstenite::protocol::frame::Frame;
+        use tokio_tungstenite::tungstenite::protocol::frame::coding::{Data, OpCode};
+
+        let router = Router::new()
+            .max_message_size(16)
+            .default_handler(handler(|| async { Ok("handled") }));
+
+        let (server_io, client_io) = tokio::io::duplex(4096);
+        let peer: SocketAddr = "127.0.0.1:9000".parse().unwrap();
+        let server = tokio::spawn({
+            let router = router.clone();
+            async move { router.handle_websocket_connection(server_io, peer).await }
+        });
+
+        let (mut client, _) = tokio_tungstenite::client_async("ws://localhost/", client_io)
+            .await
+            .unwrap();
+        // Every fragment fits the limit, but the whole message doesn't
+        let fragments = [
+            Frame::message(vec![b'x'; 10], OpCode::Data(Data::Text), false),
+            Frame::message(vec![b'x'; 10], OpCode::Data(Data::Continue), false),
+            Frame::message(vec![b'x'; 10], OpCode::Data(Data::Continue), true),
+        ];
+        for fragment in fragments {
+            client
+                .send(tokio_tungstenite::tungstenite::Message::Frame(fragment))
+                .await
+                .unwrap();
+        }
+
+        let frame = client.next().await.unwrap().unwrap();
+        let close = Message::from_tungstenite(frame);
+        assert_eq!(close.close_frame().map(|(code, _)| code), Some(1009));
+        while let Some(Ok(_)) = client.next().await {}
+        tokio::time::timeout(Duration::from_secs(5), server)
+            .await
+            .expect("oversized fragmented message did not end the connection")
+            .unwrap()
+            .unwrap();
+    }
 
-        let listener = TcpListener::bind(addr).await?;
-        info!("WebSocket server listening on {}", addr);
+    #[tokio::test]
+    async fn test_disconnect_reason_reports_client_close() {
+        use crate::connection::DisconnectSource;
+        use futures_util::StreamExt;
+        use tokio_tungstenite::tungstenite::protocol::CloseFrame;
 
-        let router = Arc::new(self);
+        let reasons = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let router = Router::new().on_disconnect_with_reason({
+            let reasons = reasons.clone();
+            move |_manager, _conn_id, reason| reasons.lock().unwrap().push(reason.clone())
+        });
 
-        loop {
-            let (stream, peer_addr) = listener.accept().await?;
+        let (server_io, client_io) = tokio::io::duplex(4096);
+        let peer: SocketAddr = "127.0.0.1:9000".parse().unwrap();
+        let server = tokio::spawn({
             let router = router.clone();
+            async move { router.handle_websocket_connection(server_io, peer).await }
+        });
+
+        let (mut client, _) = tokio_tungstenite::client_async("ws://localhost/", client_io)
+            .await
+            .unwrap();
+        client
+            .close(Some(CloseFrame {
+                code: 4000.into(),
+                reason: "bye".into(),
+            }))
+            .await
+            .unwrap();
+        while let Some(Ok(_)) = client.next().await {}
+        tokio::time::timeout(Duration::from_secs(5), server)
+            .await
+            .expect("close frame did not end the connection")
+            .unwrap()
+            .unwrap();
+
+        let reasons = reasons.lock().unwrap();
+        assert_eq!(reasons.len(), 1);
+        assert_eq!(reasons[0].source, DisconnectSource::ClientClose);
+        assert_eq!(reasons[0].code, Some(4000));
+        assert_eq!(reasons[0].reason.as_deref(), Some("bye"));
+        assert!(!reasons[0].is_clean());
+    }
+
+    #[tokio::test]
+    async fn test_serve_on_ephemeral_port() {
+        use futures_util::{SinkExt, StreamExt};
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let router = Router::new().default_handler(handler(|msg: Message| async move { Ok(msg) }));
+        let server = tokio::spawn(router.serve_on(listener));
+
+        let stream = TcpStream::connect(addr).await.unwrap();
+        let (mut client, _) = tokio_tungstenite::client_async(format!("ws://{}/", addr), stream)
+            .await
+            .unwrap();
+        client
+            .send(Message::text("ping").into_tungstenite())
+            .await
+            .unwrap();
 
-            tokio::spawn(async move {
-                if let Err(e) = router.handle_connection(stream, peer_addr).await {
-                    error!("Connection error: {}", e);
+        let reply = Message::from_tungstenite(client.next().await.unwrap().unwrap());
+        assert_eq!(reply.as_text(), Some("ping"));
+        server.abort();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_listen_unix() {
+        use futures_util::{SinkExt, StreamExt};
+
+        let path = std::env::temp_dir().join(format!("wsforge-test-{}.sock", std::process::id()));
+        let (tx, rx) = tokio::sync::oneshot::channel::<()>();
+        let router =
+            Router::new().default_handler(handler(|msg: Message, conn: Connection| async move {
+                assert!(conn.info().addr.ip().is_unspecified());
+                Ok(msg)
+            }));
+        let server = tokio::spawn(router.listen_unix_with_shutdown(path.clone(), async {
+            let _ = rx.await;
+        }));
+
+        let stream = tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                match tokio::net::UnixStream::connect(&path).await {
+                    Ok(stream) => break stream,
+                    Err(_) => tokio::task::yield_now().await,
                 }
+            }
+        })
+        .await
+        .expect("socket was not bound");
+        let (mut client, _) = tokio_tungstenite::client_async("ws://localhost/", stream)
+            .await
+            .unwrap();
+        client
+            .send(Message::text("ping").into_tungstenite())
+            .await
+            .unwrap();
+        let reply = Message::from_tungstenite(client.next().await.unwrap().unwrap());
+        assert_eq!(reply.as_text(), Some("ping"));
+
+        tx.send(()).unwrap();
+        while let Some(Ok(_)) = client.next().await {}
+        server.await.unwrap().unwrap();
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_connection_id_generator() {
+        let peer: SocketAddr = "10.0.0.7:4000".parse().unwrap();
+
+        let router = Router::new().connection_id_generator(|peer| format!("shard-a-{}", peer.ip()));
+        assert_eq!(router.next_connection_id(peer), "shard-a-10.0.0.7");
+
+        let router = Router::new();
+        assert!(router.next_connection_id(peer).starts_with("conn_"));
+    }
+
+    #[test]
+    fn test_routers_number_connections_independently() {
+        let peer: SocketAddr = "10.0.0.7:4000".parse().unwrap();
+
+        let first = Router::new();
+        let second = Router::new();
+        assert_eq!(first.next_connection_id(peer), "conn_0");
+        assert_eq!(first.next_connection_id(peer), "conn_1");
+        assert_eq!(second.next_connection_id(peer), "conn_0");
+
+        // Clones share the counter, so IDs stay unique per server
+        assert_eq!(first.clone().next_connection_id(peer), "conn_2");
+        assert_eq!(first.next_connection_id(peer), "conn_3");
+    }
+
+    #[tokio::test]
+    async fn test_plain_http_gets_426() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let router = Router::new();
+        let (server_io, mut client_io) = tokio::io::duplex(4096);
+        let peer: SocketAddr = "127.0.0.1:9000".parse().unwrap();
+
+        client_io
+            .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .await
+            .unwrap();
+        router.dispatch_connection(server_io, peer).await.unwrap();
+
+        let mut response = Vec::new();
+        client_io.read_to_end(&mut response).await.unwrap();
+        let response = String::from_utf8(response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 426 Upgrade Required\r\n"));
+        assert!(response.contains("Upgrade: websocket\r\n"));
+    }
+
+    #[tokio::test]
+    async fn test_handshake_timeout_sends_408() {
+        use tokio::io::AsyncReadExt;
+
+        let router = Router::new()
+            .serve_static("public")
+            .handshake_timeout(Duration::from_millis(50));
+        let (server_io, mut client_io) = tokio::io::duplex(4096);
+        let peer: SocketAddr = "127.0.0.1:9000".parse().unwrap();
+
+        // The client connects but never sends a request
+        let result = router.dispatch_connection(server_io, peer).await;
+        assert!(matches!(
+            result,
+            Err(Error::HandshakeTimeout(t)) if t == Duration::from_millis(50)
+        ));
+
+        let mut response = Vec::new();
+        client_io.read_to_end(&mut response).await.unwrap();
+        assert!(response.starts_with(b"HTTP/1.1 408 Request Timeout\r\n"));
+    }
+
+    #[tokio::test]
+    async fn test_read_request_head_beyond_1kb() {
+        let cookie = "c".repeat(4096);
+        let request = format!(
+            "GET / HTTP/1.1\r\nCookie: {}\r\nUpgrade: websocket\r\n\r\nframe",
+            cookie
+        );
+        let mut stream = request.as_bytes();
+
+        let head = read_request_head(&mut stream, DEFAULT_MAX_HEADER_SIZE)
+            .await
+            .unwrap()
+            .unwrap();
+        let header = String::from_utf8_lossy(&head);
+
+        assert!(is_websocket_upgrade(&header));
+        assert!(header.ends_with("frame"));
+    }
+
+    #[tokio::test]
+    async fn test_read_request_head_too_large() {
+        let request = format!(
+            "GET / HTTP/1.1\r\nCookie: {}",
+            "c".repeat(DEFAULT_MAX_HEADER_SIZE)
+        );
+        let mut stream = request.as_bytes();
+
+        assert!(
+            read_request_head(&mut stream, DEFAULT_MAX_HEADER_SIZE)
+                .await
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_oversized_head_gets_431() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let router = Router::new().serve_static("public").max_header_size(64);
+        let (server_io, mut client_io) = tokio::io::duplex(4096);
+        let peer: SocketAddr = "127.0.0.1:9000".parse().unwrap();
+
+        let request = format!("GET / HTTP/1.1\r\nCookie: {}\r\n\r\n", "c".repeat(128));
+        client_io.write_all(request.as_bytes()).await.unwrap();
+        assert!(router.dispatch_connection(server_io, peer).await.is_err());
+
+        let mut response = Vec::new();
+        client_io.read_to_end(&mut response).await.unwrap();
+        assert!(response.starts_with(b"HTTP/1.1 431 "));
+    }
+
+    #[test]
+    fn test_is_websocket_upgrade() {
+        assert!(is_websocket_upgrade(
+            "GET / HTTP/1.1\r\nHost: a\r\nUPGRADE:  WebSocket\r\n"
+        ));
+        assert!(!is_websocket_upgrade("GET / HTTP/1.1\r\nHost: a\r\n"));
+        assert!(!is_websocket_upgrade(
+            "GET /Upgrade:websocket HTTP/1.1\r\nHost: a\r\n"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_connection_meta_persists_across_messages() {
+        let router = Router::new().default_handler(handler(|conn: Connection| async move {
+            let count = conn.get_meta::<u32>("count").map_or(0, |c| *c) + 1;
+            conn.set_meta("count", count);
+            Ok(count.to_string())
+        }));
+
+        let (conn_id, mut rx) = connect(&router);
+        for expected in ["1", "2"] {
+            router
+                .handle_message(conn_id.clone(), Message::text("hi"))
+                .await
+                .unwrap();
+            assert_eq!(rx.try_recv().unwrap().as_text(), Some(expected));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_handler_returning_messages_sends_in_order() {
+        let router = Router::new().default_handler(handler(|| async {
+            Ok(vec![
+                Message::text("ack"),
+                Message::text("one"),
+                Message::text("two"),
+            ])
+        }));
+
+        let (conn_id, mut rx) = connect(&router);
+        router
+            .handle_message(conn_id, Message::text("hi"))
+            .await
+            .unwrap();
+
+        for expected in ["ack", "one", "two"] {
+            assert_eq!(rx.try_recv().unwrap().as_text(), Some(expected));
+        }
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_default_handler_per_message_type() {
+        let router = Router::new()
+            .route("/known", handler(|| async { Ok("known") }))
+            .default_binary_handler(handler(|| async { Ok("binary") }))
+            .default_handler(handler(|| async { Ok("default") }));
+
+        let (conn_id, mut rx) = connect(&router);
+        for (message, expected) in [
+            (Message::binary(vec![1, 2]), "binary"),
+            (Message::text("hello"), "default"),
+            (Message::text("/known"), "known"),
+        ] {
+            router
+                .handle_message(conn_id.clone(), message)
+                .await
+                .unwrap();
+            assert_eq!(rx.try_recv().unwrap().as_text(), Some(expected));
+        }
+
+        let router = router.default_text_handler(handler(|| async { Ok("text") }));
+        router
+            .handle_message(conn_id.clone(), Message::text("hello"))
+            .await
+            .unwrap();
+        assert_eq!(rx.try_recv().unwrap().as_text(), Some("text"));
+    }
+
+    #[tokio::test]
+    async fn test_route_binary_dispatches_by_opcode() {
+        use crate::extractor::Data;
+
+        let router = Router::new()
+            .route_binary(
+                0x01,
+                handler(|Data(bytes): Data| async move { Ok(format!("move {:?}", &bytes[1..])) }),
+            )
+            .route_binary(
+                0x02,
+                handler(|Data(bytes): Data| async move { Ok(format!("chat {:?}", &bytes[1..])) }),
+            )
+            .default_handler(handler(|| async { Ok("default") }));
+
+        let (conn_id, mut rx) = connect(&router);
+        for (message, expected) in [
+            (Message::binary(vec![0x01, 4, 2]), "move [4, 2]"),
+            (Message::binary(vec![0x02, 7]), "chat [7]"),
+            (Message::binary(vec![0x03, 7]), "default"),
+            (Message::binary(vec![]), "default"),
+            (Message::text("\u{1}"), "default"),
+        ] {
+            router
+                .handle_message(conn_id.clone(), message)
+                .await
+                .unwrap();
+            assert_eq!(rx.try_recv().unwrap().as_text(), Some(expected));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_route_match_picks_route_by_field() {
+        let router = Router::new()
+            .route("join", handler(|| async { Ok("joined") }))
+            .default_handler(handler(|| async { Ok("default") }))
+            .route_match(|msg| {
+                let value: serde_json::Value = msg.json().ok()?;
+                Some(value.get("action")?.as_str()?.to_string())
             });
+
+        let (conn_id, mut rx) = connect(&router);
+        for (text, expected) in [
+            (r#"{"action":"join"}"#, "joined"),
+            (r#"{"action":"dance"}"#, "default"),
+            ("plain text", "default"),
+        ] {
+            router
+                .handle_message(conn_id.clone(), Message::text(text))
+                .await
+                .unwrap();
+            assert_eq!(rx.try_recv().unwrap().as_text(), Some(expected));
         }
     }
 
-    async fn handle_connection(&self, stream: TcpStream, peer_addr: SocketAddr) -> Result<()> {
-        let mut buffer = [0u8; 1024];
+    #[tokio::test]
+    async fn test_close_response_closes_connection() {
+        use crate::handler::CloseResponse;
 
-        let n = tokio::time::timeout(std::time::Duration::from_secs(5), stream.peek(&mut buffer))
+        let router = Router::new().default_handler(handler(|| async {
+            Ok(CloseResponse::new(1008, "Authenticate first"))
+        }));
+
+        let (conn_id, mut rx) = connect(&router);
+        router
+            .handle_message(conn_id.clone(), Message::text("hi"))
             .await
-            .map_err(|_| Error::custom("Connection timeout"))?
-            .map_err(|e| Error::custom(format!("Failed to read: {}", e)))?;
+            .unwrap();
+
+        let close = rx.try_recv().unwrap();
+        assert_eq!(
+            close.close_frame(),
+            Some((1008, "Authenticate first".to_string()))
+        );
+        let conn = router.connection_manager.get(&conn_id).unwrap();
+        assert!(!conn.is_open());
+    }
 
-        let header = String::from_utf8_lossy(&buffer[..n]);
+    #[tokio::test]
+    async fn test_error_handler_formats_errors() {
+        let failing = || async { Err::<String, _>(Error::custom("rate limited")) };
 
-        if header.contains("Upgrade: websocket") || header.contains("upgrade: websocket") {
-            self.handle_websocket_connection(stream, peer_addr).await
-        } else if let Some(ref static_handler) = self.static_handler {
-            self.handle_http_request(stream, static_handler, &header)
+        let router = Router::new().default_handler(handler(failing));
+        let (conn_id, mut rx) = connect(&router);
+        router
+            .handle_message(conn_id, Message::text("hi"))
+            .await
+            .unwrap();
+        assert_eq!(
+            rx.try_recv().unwrap().as_text(),
+            Some("Error: Custom error: rate limited")
+        );
+
+        let router = Router::new()
+            .default_handler(handler(failing))
+            .error_handler(|e, _conn| Some(Message::text(format!("{{\"error\":\"{}\"}}", e))));
+        let (conn_id, mut rx) = connect(&router);
+        router
+            .handle_message(conn_id, Message::text("hi"))
+            .await
+            .unwrap();
+        assert_eq!(
+            rx.try_recv().unwrap().as_text(),
+            Some(r#"{"error":"Custom error: rate limited"}"#)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_default_error_response_depends_on_source() {
+        use crate::extractor::Json;
+
+        let router = Router::new()
+            .route(
+                "/json",
+                handler(|Json(n): Json<u32>| async move { Ok(n.to_string()) }),
+            )
+            .route_with_layers(
+                "/guarded",
+                vec![from_fn(|_msg, _conn, _state, _ext, _next| async move {
+                    Err(Error::custom("database password rejected"))
+                })],
+                handler(|| async { Ok("unreachable") }),
+            )
+            .route(
+                "/fail",
+                handler(|| async { Err::<String, _>(Error::custom("no such user")) }),
+            );
+        let (conn_id, mut rx) = connect(&router);
+
+        // Extractor and middleware errors are only logged
+        for text in ["/json", "/guarded"] {
+            router
+                .handle_message(conn_id.clone(), Message::text(text))
                 .await
-        } else {
-            Err(Error::custom("No handler for HTTP requests"))
+                .unwrap();
+            assert!(rx.try_recv().is_err());
         }
+
+        router
+            .handle_message(conn_id, Message::text("/fail"))
+            .await
+            .unwrap();
+        assert_eq!(
+            rx.try_recv().unwrap().as_text(),
+            Some("Error: Custom error: no such user")
+        );
     }
 
-    async fn handle_http_request(
-        &self,
-        mut stream: TcpStream,
-        static_handler: &crate::static_files::StaticFileHandler,
-        header: &str,
-    ) -> Result<()> {
-        use crate::static_files::http_response;
-        use tokio::io::AsyncWriteExt;
+    #[tokio::test]
+    async fn test_coded_error_is_sent_as_json() {
+        let router = Router::new().default_handler(handler(|| async {
+            Err::<String, _>(Error::coded(429, "rate_limited", "slow down"))
+        }));
 
-        let path = header
-            .lines()
-            .next()
-            .and_then(|line| {
-                let parts: Vec<&str> = line.split_whitespace().collect();
-                if parts.len() >= 2 && (parts[0] == "GET" || parts[0] == "HEAD") {
-                    Some(parts[1])
-                } else {
-                    None
-                }
-            })
-            .unwrap_or("/");
+        let (conn_id, mut rx) = connect(&router);
+        router
+            .handle_message(conn_id, Message::text("hi"))
+            .await
+            .unwrap();
+        assert_eq!(
+            rx.try_recv().unwrap().as_text(),
+            Some(r#"{"code":429,"kind":"rate_limited","message":"slow down"}"#)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_middleware_lifecycle_hooks() {
+        struct Audit(Arc<Mutex<Vec<String>>>);
 
-        let response = match static_handler.serve(path).await {
-            Ok((content, mime_type)) => {
-                info!("Served: {} ({} bytes)", path, content.len());
-                http_response(200, &mime_type, content)
+        #[async_trait::async_trait]
+        impl Middleware for Audit {
+            async fn handle(
+                &self,
+                message: Message,
+                conn: Connection,
+                state: AppState,
+                extensions: Extensions,
+                next: crate::middleware::Next,
+            ) -> Result<Vec<Message>> {
+                next.run(message, conn, state, extensions).await
             }
-            Err(e) => {
-                tracing::warn!("File not found: {} - {}", path, e);
-                let html = b"<html><body><h1>404 Not Found</h1></body></html>".to_vec();
-                http_response(404, "text/html", html)
+
+            async fn on_connect(&self, conn: &Connection, _state: &AppState) -> Result<()> {
+                self.0.lock().unwrap().push(format!("open {}", conn.id()));
+                Ok(())
             }
-        };
 
-        stream.write_all(&response).await?;
-        stream.flush().await?;
-        Ok(())
+            async fn on_disconnect(&self, conn: &Connection, _state: &AppState) -> Result<()> {
+                self.0.lock().unwrap().push(format!("close {}", conn.id()));
+                Ok(())
+            }
+        }
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let callback_events = events.clone();
+        let router = Router::new()
+            .layer(Arc::new(Audit(events.clone())))
+            .on_disconnect_async(move |_manager, conn_id| {
+                let events = callback_events.clone();
+                async move {
+                    events.lock().unwrap().push(format!("callback {}", conn_id));
+                    Ok(())
+                }
+            });
+
+        let (tx, _rx) = mpsc::unbounded_channel();
+        let conn = Connection::new("audited".to_string(), "127.0.0.1:9000".parse().unwrap(), tx);
+
+        router.connect_hook().unwrap()(conn.clone()).await.unwrap();
+        router.disconnect_hook().unwrap()(conn).await.unwrap();
+
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec!["open audited", "close audited", "callback audited"]
+        );
     }
 
-    async fn handle_websocket_connection(
-        &self,
-        stream: TcpStream,
-        peer_addr: SocketAddr,
-    ) -> Result<()> {
-        let ws_stream = accept_async(stream).await?;
-        let conn_id = Self::generate_connection_id();
+    #[tokio::test]
+    async fn test_metrics_record_handler_runs() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
 
-        let router = self.clone();
-        let manager = self.connection_manager.clone();
+        #[derive(Default)]
+        struct Counts {
+            runs: AtomicUsize,
+            errors: AtomicUsize,
+        }
 
-        let on_message = Arc::new(move |conn_id: ConnectionId, message: Message| {
-            let router = router.clone();
-            tokio::spawn(async move {
-                if let Err(e) = router.handle_message(conn_id, message).await {
-                    error!("Message handling error: {}", e);
-                }
-            });
-        });
+        impl Metrics for Counts {
+            fn handler_duration(&self, _duration: Duration) {
+                self.runs.fetch_add(1, Ordering::SeqCst);
+            }
 
-        let manager_ref = manager.clone();
-        let on_connect = self
-            .on_connect
-            .clone()
-            .map(move |cb| {
-                let manager = manager_ref.clone();
-                Arc::new(move |conn_id: ConnectionId| {
-                    cb(&manager, conn_id);
-                }) as Arc<dyn Fn(ConnectionId) + Send + Sync>
-            })
-            .unwrap_or_else(|| {
-                Arc::new(|conn_id: ConnectionId| {
-                    info!("Client connected: {}", conn_id);
-                })
-            });
+            fn handler_error(&self) {
+                self.errors.fetch_add(1, Ordering::SeqCst);
+            }
+        }
 
-        let manager_ref = manager.clone();
-        let on_disconnect = self
-            .on_disconnect
-            .clone()
-            .map(move |cb| {
-                let manager = manager_ref.clone();
-                Arc::new(move |conn_id: ConnectionId| {
-                    cb(&manager, conn_id);
-                }) as Arc<dyn Fn(ConnectionId) + Send + Sync>
-            })
-            .unwrap_or_else(|| {
-                Arc::new(|conn_id: ConnectionId| {
-                    info!("Client disconnected: {}", conn_id);
-                })
-            });
+        let counts = Arc::new(Counts::default());
+        let router = Router::new()
+            .with_metrics(counts.clone())
+            .default_handler(handler(|msg: Message| async move {
+                match msg.as_text() {
+                    Some("fail") => Err(Error::custom("failed")),
+                    _ => Ok("ok".to_string()),
+                }
+            }));
 
-        handle_websocket(
-            ws_stream,
-            conn_id,
-            peer_addr,
-            manager,
-            on_message,
-            on_connect,
-            on_disconnect,
-        )
-        .await;
+        let (conn_id, _rx) = connect(&router);
+        for text in ["a", "fail", "b"] {
+            router
+                .handle_message(conn_id.clone(), Message::text(text))
+                .await
+                .unwrap();
+        }
 
-        Ok(())
+        assert_eq!(counts.runs.load(Ordering::SeqCst), 3);
+        assert_eq!(counts.errors.load(Ordering::SeqCst), 1);
     }
 
-    async fn handle_message(&self, conn_id: ConnectionId, message: Message) -> Result<()> {
-        let conn = self
-            .connection_manager
-            .get(&conn_id)
-            .ok_or_else(|| Error::ConnectionNotFound(conn_id.clone()))?;
+    #[tokio::test]
+    async fn test_handler_timeout_reports_error() {
+        let router = Router::new()
+            .handler_timeout(Duration::from_millis(10))
+            .default_handler(handler(|| async {
+                tokio::time::sleep(Duration::from_secs(60)).await;
+                Ok("too late".to_string())
+            }))
+            .error_handler(|e, _conn| {
+                assert!(matches!(e, Error::Timeout(_)));
+                Some(Message::text("timeout"))
+            });
 
-        let extensions = Extensions::new();
+        let (conn_id, mut rx) = connect(&router);
+        router
+            .handle_message(conn_id, Message::text("hi"))
+            .await
+            .unwrap();
+        assert_eq!(rx.try_recv().unwrap().as_text(), Some("timeout"));
+    }
 
-        let chain = if let Some(text) = message.as_text() {
-            if text.starts_with('/') {
-                if let Some((route, _)) = text.split_once(' ') {
-                    self.routes.get(route).map(|c| c.value().clone())
-                } else {
-                    self.routes.get(text).map(|c| c.value().clone())
-                }
-            } else {
-                None
-            }
-        } else {
-            None
-        };
+    #[tokio::test]
+    async fn test_strict_routing_reports_unmatched_messages() {
+        let router = Router::new();
+        assert!(!router.has_message_handlers());
+        let (conn_id, mut rx) = connect(&router);
+        router
+            .handle_message(conn_id, Message::text("hello"))
+            .await
+            .unwrap();
+        assert!(rx.try_recv().is_err());
+
+        let router = Router::new()
+            .strict_routing(true)
+            .error_handler(|e, _conn| Some(Message::text(e.to_string())));
+        let (conn_id, mut rx) = connect(&router);
+        router
+            .handle_message(conn_id, Message::text("hello"))
+            .await
+            .unwrap();
+        assert_eq!(rx.try_recv().unwrap().as_text(), Some("Route not found: /"));
+    }
 
-        let chain = chain.or_else(|| self.default_chain.clone());
+    #[tokio::test]
+    async fn test_auto_text_pong() {
+        let router = Router::new()
+            .auto_text_pong(true)
+            .default_handler(handler(|| async { Ok("default".to_string()) }));
+        let (conn_id, mut rx) = connect(&router);
 
-        if let Some(chain) = chain {
-            match chain
-                .execute(message, conn.clone(), self.state.clone(), extensions)
+        for (text, reply) in [("ping", "pong"), ("ping ", "default"), ("hello", "default")] {
+            router
+                .handle_message(conn_id.clone(), Message::text(text))
                 .await
-            {
-                Ok(Some(response)) => {
-                    if let Err(e) = conn.send(response) {
-                        error!("Failed to send response to {}: {}", conn_id, e);
-                    }
-                }
-                Ok(None) => {
-                    tracing::debug!("Handler processed message without response");
-                }
-                Err(e) => {
-                    error!("Handler error for {}: {}", conn_id, e);
-                }
-            }
-        } else {
-            tracing::warn!("No handler found for message from {}", conn_id);
+                .unwrap();
+            assert_eq!(rx.try_recv().unwrap().as_text(), Some(reply));
         }
 
-        Ok(())
+        let router = router.auto_text_pong_with("hb", "ack");
+        let (conn_id, mut rx) = connect(&router);
+        router
+            .handle_message(conn_id.clone(), Message::text("hb"))
+            .await
+            .unwrap();
+        assert_eq!(rx.try_recv().unwrap().as_text(), Some("ack"));
+
+        let router = router.auto_text_pong(false);
+        let (conn_id, mut rx) = connect(&router);
+        router
+            .handle_message(conn_id, Message::text("hb"))
+            .await
+            .unwrap();
+        assert_eq!(rx.try_recv().unwrap().as_text(), Some("default"));
     }
 
-    fn generate_connection_id() -> ConnectionId {
-        use std::sync::atomic::{AtomicU64, Ordering};
-        static COUNTER: AtomicU64 = AtomicU64::new(0);
-        format!("conn_{}", COUNTER.fetch_add(1, Ordering::SeqCst))
+    #[tokio::test]
+    async fn test_rpc_dispatches_by_method() {
+        use crate::extractor::Json;
+        use crate::handler::JsonResponse;
+
+        let router = Router::new()
+            .rpc(
+                "add",
+                handler(|Json(params): Json<Vec<i32>>| async move {
+                    Ok(JsonResponse(params.iter().sum::<i32>()))
+                }),
+            )
+            .rpc(
+                "echo",
+                handler(
+                    |msg: Message| async move { Ok(msg.as_text().unwrap_or_default().to_string()) },
+                ),
+            )
+            .default_handler(handler(|| async { Ok("default".to_string()) }));
+
+        let (conn_id, mut rx) = connect(&router);
+        let send = |text: &str| router.handle_message(conn_id.clone(), Message::text(text));
+
+        send(r#"{"id":7,"method":"add","params":[1,2]}"#)
+            .await
+            .unwrap();
+        assert_eq!(
+            rx.try_recv().unwrap().as_text(),
+            Some(r#"{"id":7,"result":3}"#)
+        );
+
+        // Plain text stays a string, even if it parses as JSON
+        send(r#"{"id":9,"method":"echo","params":true}"#)
+            .await
+            .unwrap();
+        assert_eq!(
+            rx.try_recv().unwrap().as_text(),
+            Some(r#"{"id":9,"result":"true"}"#)
+        );
+
+        send(r#"{"id":8,"method":"sub","params":[1,2]}"#)
+            .await
+            .unwrap();
+        assert_eq!(
+            rx.try_recv().unwrap().as_text(),
+            Some(r#"{"error":{"code":-32601,"message":"Method not found: sub"},"id":8}"#)
+        );
+
+        // Notifications get no response, other messages are routed as usual
+        send(r#"{"method":"add","params":[1,2]}"#).await.unwrap();
+        assert!(rx.try_recv().is_err());
+        send("hello").await.unwrap();
+        assert_eq!(rx.try_recv().unwrap().as_text(), Some("default"));
     }
-}
 
-impl Clone for Router {
-    fn clone(&self) -> Self {
-        Self {
-            routes: self.routes.clone(),
-            global_middlewares: self.global_middlewares.clone(),
-            state: self.state.clone(),
-            connection_manager: self.connection_manager.clone(),
-            on_connect: self.on_connect.clone(),
-            on_disconnect: self.on_disconnect.clone(),
-            default_chain: self.default_chain.clone(),
-            static_handler: self.static_handler.clone(),
+    #[tokio::test]
+    async fn test_error_handler_can_suppress_response() {
+        let router = Router::new()
+            .default_handler(handler(|| async { Err::<(), _>(Error::custom("boom")) }))
+            .error_handler(|_e, _conn| None);
+
+        let (conn_id, mut rx) = connect(&router);
+        router
+            .handle_message(conn_id, Message::text("hi"))
+            .await
+            .unwrap();
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_handshake_url_feeds_path_and_query() {
+        use crate::extractor::{Path, Query};
+
+        #[derive(serde::Deserialize, Clone)]
+        struct RoomParams {
+            room: u32,
+            name: String,
         }
+
+        let router = Router::new().default_handler(handler(
+            |Path(path): Path<String>, Query(params): Query<RoomParams>| async move {
+                Ok(format!("{} {} {}", path, params.room, params.name))
+            },
+        ));
+
+        let request = Request::builder()
+            .uri("/game?room=42&name=bob%20smith")
+            .body(())
+            .unwrap();
+        let mut options = ConnectionOptions::default();
+        assert!(
+            router
+                .check_handshake(&request, Response::new(()), &mut options, &mut None)
+                .is_ok()
+        );
+
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let mut conn = Connection::new(
+            "conn_url".to_string(),
+            "127.0.0.1:9000".parse().unwrap(),
+            tx,
+        );
+        conn.extensions = options.extensions;
+        router.connection_manager.add(conn);
+
+        router
+            .handle_message("conn_url".to_string(), Message::text("hi"))
+            .await
+            .unwrap();
+
+        assert_eq!(rx.try_recv().unwrap().as_text(), Some("/game 42 bob smith"));
     }
-}
 
-impl Default for Router {
-    fn default() -> Self {
-        Self::new()
+    #[tokio::test]
+    async fn test_handshake_path_selects_route() {
+        let router = Router::new()
+            .path_route("/game", handler(|| async { Ok("game".to_string()) }))
+            .route("/chat", handler(|| async { Ok("chat".to_string()) }))
+            .default_handler(handler(|| async { Ok("default".to_string()) }));
+
+        let connect_to = |id: &str, path: Option<&str>| {
+            let (tx, rx) = mpsc::unbounded_channel();
+            let mut conn = Connection::new(id.to_string(), "127.0.0.1:9000".parse().unwrap(), tx);
+            conn.path = path.map(str::to_string);
+            router.connection_manager.add(conn);
+            rx
+        };
+
+        let mut game = connect_to("game_conn", Some("/game"));
+        let mut root = connect_to("root_conn", Some("/"));
+        let mut chat = connect_to("chat_conn", Some("/chat"));
+
+        // The connection path wins over the message prefix
+        router
+            .handle_message("game_conn".to_string(), Message::text("/chat hi"))
+            .await
+            .unwrap();
+        assert_eq!(game.try_recv().unwrap().as_text(), Some("game"));
+
+        // Unrouted paths fall back to prefix routing, then the default handler
+        for (text, expected) in [("/chat hi", "chat"), ("hi", "default")] {
+            router
+                .handle_message("root_conn".to_string(), Message::text(text))
+                .await
+                .unwrap();
+            assert_eq!(root.try_recv().unwrap().as_text(), Some(expected));
+        }
+
+        // A message route doesn't capture connections to the same path
+        router
+            .handle_message("chat_conn".to_string(), Message::text("hi"))
+            .await
+            .unwrap();
+        assert_eq!(chat.try_recv().unwrap().as_text(), Some("default"));
     }
 }
@@ -0,0 +1,181 @@
+//! Request and response envelopes for [`Router::rpc()`](crate::router::Router::rpc).
+//!
+//! A call is a JSON object with a string `method`, optional `params` and an
+//! optional `id`:
+//!
+//! ```text
+//! {"id": 7, "method": "getUser", "params": {"user_id": 42}}
+//! ```
+//!
+//! Calls with an `id` are answered with `{"id": 7, "result": ...}` or
+//! `{"id": 7, "error": {"code": ..., "message": ...}}`. Calls without an `id`
+//! are notifications and get no response.
+
+use crate::error::Error;
+use crate::message::Message;
+use serde_json::{Map, Value, json};
+
+/// Error code for a call to an unregistered method.
+const METHOD_NOT_FOUND: i64 = -32601;
+/// Error code for a handler response that can't be represented as JSON.
+const INTERNAL_ERROR: i64 = -32603;
+/// Error code for handler errors without a code of their own.
+const SERVER_ERROR: i64 = -32000;
+
+/// A parsed RPC call.
+pub(crate) struct RpcCall {
+    /// Request ID, echoed in the response. `None` for notifications.
+    pub(crate) id: Option<Value>,
+    /// Name of the method to call
+    pub(crate) method: String,
+    /// Parameters, `null` if absent
+    pub(crate) params: Value,
+}
+
+impl RpcCall {
+    /// Parses a text message as an RPC call.
+    ///
+    /// Returns `None` for anything that isn't a JSON object with a string
+    /// `method` field.
+    pub(crate) fn parse(message: &Message) -> Option<Self> {
+        let Value::Object(mut call) = crate::json::from_text(message.as_text()?).ok()? else {
+            return None;
+        };
+
+        let Value::String(method) = call.remove("method")? else {
+            return None;
+        };
+
+        Some(Self {
+            id: call.remove("id"),
+            method,
+            params: call.remove("params").unwrap_or(Value::Null),
+        })
+    }
+
+    /// Returns the message handed to the method's handler, whose text is the
+    /// call's `params`.
+    pub(crate) fn params_message(&self) -> Message {
+        Message::text(self.params.to_string())
+    }
+}
+
+/// Wraps a handler's responses in a `result` envelope.
+///
+/// If the handler returned a JSON response, as reported by
+/// [`IntoResponse::is_json()`](crate::handler::IntoResponse::is_json), each
+/// message is embedded as JSON; other text is embedded as a string, even if
+/// it happens to parse as JSON. No response becomes `null`, and several
+/// responses become an array.
+pub(crate) fn result_response(id: Value, responses: Vec<Message>, json: bool) -> Message {
+    let mut results = Vec::with_capacity(responses.len());
+    for message in &responses {
+        match result_value(message, json) {
+            Some(value) => results.push(value),
+            None => {
+                return error_envelope(id, INTERNAL_ERROR, "Response is not text".to_string());
+            }
+        }
+    }
+
+    let result = match results.len() {
+        0 => Value::Null,
+        1 => results.remove(0),
+        _ => Value::Array(results),
+    };
+    Message::text(json!({ "id": id, "result": result }).to_string())
+}
+
+/// Converts one response message into a `result` value, or `None` if it
+/// can't be represented.
+fn result_value(message: &Message, json: bool) -> Option<Value> {
+    if json && let Ok(value) = serde_json::from_slice(message.as_bytes()) {
+        return Some(value);
+    }
+    message
+        .as_text()
+        .map(|text| Value::String(text.to_string()))
+}
+
+/// Wraps a handler error in an `error` envelope.
+///
+/// [`Error::Coded`] keeps its code, kind and data. Other errors use code
+/// -32000 and their display text.
+pub(crate) fn error_response(id: Value, error: &Error) -> Message {
+    match error.to_json() {
+        Some(envelope) => Message::text(json!({ "id": id, "error": envelope }).to_string()),
+        None => error_envelope(id, SERVER_ERROR, error.to_string()),
+    }
+}
+
+/// Returns the error response for a call to an unregistered method.
+pub(crate) fn method_not_found(id: Value, method: &str) -> Message {
+    error_envelope(
+        id,
+        METHOD_NOT_FOUND,
+        format!("Method not found: {}", method),
+    )
+}
+
+fn error_envelope(id: Value, code: i64, message: String) -> Message {
+    let mut error = Map::new();
+    error.insert("code".to_string(), code.into());
+    error.insert("message".to_string(), message.into());
+    Message::text(json!({ "id": id, "error": error }).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_call() {
+        let call =
+            RpcCall::parse(&Message::text(r#"{"id":7,"method":"add","params":[1,2]}"#)).unwrap();
+        assert_eq!(call.id, Some(json!(7)));
+        assert_eq!(call.method, "add");
+        assert_eq!(call.params_message().as_text(), Some("[1,2]"));
+
+        let notification = RpcCall::parse(&Message::text(r#"{"method":"ping"}"#)).unwrap();
+        assert!(notification.id.is_none());
+        assert_eq!(notification.params, Value::Null);
+
+        assert!(RpcCall::parse(&Message::text(r#"{"type":"chat"}"#)).is_none());
+        assert!(RpcCall::parse(&Message::text("not json")).is_none());
+    }
+
+    #[test]
+    fn test_result_response() {
+        let response = result_response(json!(1), vec![Message::text(r#"{"name":"alice"}"#)], true);
+        assert_eq!(
+            response.as_text(),
+            Some(r#"{"id":1,"result":{"name":"alice"}}"#)
+        );
+
+        let response = result_response(json!("a"), vec![Message::text("plain")], false);
+        assert_eq!(response.as_text(), Some(r#"{"id":"a","result":"plain"}"#));
+
+        // Text that only looks like JSON keeps its type
+        let response = result_response(json!(2), vec![Message::text("42")], false);
+        assert_eq!(response.as_text(), Some(r#"{"id":2,"result":"42"}"#));
+
+        let response = result_response(json!(3), Vec::new(), false);
+        assert_eq!(response.as_text(), Some(r#"{"id":3,"result":null}"#));
+
+        let response =
+            result_response(json!(4), vec![Message::text("1"), Message::text("2")], true);
+        assert_eq!(response.as_text(), Some(r#"{"id":4,"result":[1,2]}"#));
+
+        let response = result_response(json!(5), vec![Message::binary(vec![0xff])], false);
+        assert!(response.as_text().unwrap().contains("-32603"));
+    }
+
+    #[test]
+    fn test_error_response() {
+        let response = error_response(json!(2), &Error::custom("boom"));
+        assert_eq!(
+            response.as_text(),
+            Some(r#"{"error":{"code":-32000,"message":"Custom error: boom"},"id":2}"#)
+        );
+    }
+}
@@ -252,8 +252,23 @@ use std::sync::Arc;
 /// ```
 #[derive(Clone)]
 pub struct AppState {
-    /// Internal storage mapping TypeId to Arc-wrapped values
-    data: Arc<DashMap<TypeId, Arc<dyn Any + Send + Sync>>>,
+    /// Internal storage mapping keys to Arc-wrapped values
+    data: Arc<DashMap<StateKey, Arc<dyn Any + Send + Sync>>>,
+}
+
+/// Identifies a value in an [`AppState`].
+#[derive(Clone, PartialEq, Eq, Hash)]
+enum StateKey {
+    /// The single value of a type, see [`AppState::insert()`]
+    Type(TypeId),
+    /// A named value of a type, see [`AppState::insert_keyed()`]
+    Named(TypeId, String),
+}
+
+impl StateKey {
+    fn of<T: 'static>() -> Self {
+        StateKey::Type(TypeId::of::<T>())
+    }
 }
 
 impl AppState {
@@ -347,7 +362,73 @@ impl AppState {
     /// # }
     /// ```
     pub fn insert<T: Send + Sync + 'static>(&self, value: Arc<T>) {
-        self.data.insert(TypeId::of::<T>(), value);
+        self.data.insert(StateKey::of::<T>(), value);
+    }
+
+    /// Inserts a value under a name, next to other values of the same type.
+    ///
+    /// [`insert()`](Self::insert) keeps one value per type, so a second
+    /// database pool would replace the first. Keyed values are stored by
+    /// type *and* name, so a primary and a read-replica pool can live side
+    /// by side. They are separate from the value stored with `insert()` and
+    /// aren't visible to the [`State<T>`](crate::extractor::State) extractor;
+    /// read them with [`get_keyed()`](Self::get_keyed).
+    ///
+    /// If a value of the same type already exists under `key`, it is replaced.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - Name distinguishing this value from others of its type
+    /// * `value` - The value to store in state
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    /// use std::sync::Arc;
+    ///
+    /// struct DbPool {
+    ///     url: String,
+    /// }
+    ///
+    /// # fn example() {
+    /// let state = AppState::new();
+    /// state.insert_keyed("primary", Arc::new(DbPool { url: "postgres://primary".into() }));
+    /// state.insert_keyed("replica", Arc::new(DbPool { url: "postgres://replica".into() }));
+    ///
+    /// let replica = state.get_keyed::<DbPool>("replica").unwrap();
+    /// assert_eq!(replica.url, "postgres://replica");
+    /// # }
+    /// ```
+    pub fn insert_keyed<T: Send + Sync + 'static>(&self, key: impl Into<String>, value: Arc<T>) {
+        self.data
+            .insert(StateKey::Named(TypeId::of::<T>(), key.into()), value);
+    }
+
+    /// Retrieves a value stored with [`insert_keyed()`](Self::insert_keyed).
+    ///
+    /// Returns `None` if no value of type `T` was stored under `key`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    /// use std::sync::Arc;
+    ///
+    /// struct DbPool;
+    ///
+    /// // Handlers can take the whole state to read keyed values
+    /// async fn report_handler(state: AppState) -> Result<String> {
+    ///     let replica = state
+    ///         .get_keyed::<DbPool>("replica")
+    ///         .ok_or_else(|| Error::custom("Replica not configured"))?;
+    ///     Ok("report".to_string())
+    /// }
+    /// ```
+    pub fn get_keyed<T: Send + Sync + 'static>(&self, key: &str) -> Option<Arc<T>> {
+        self.data
+            .get(&StateKey::Named(TypeId::of::<T>(), key.to_string()))
+            .and_then(|arc| arc.value().clone().downcast::<T>().ok())
     }
 
     /// Retrieves a value from the state by its type.
@@ -446,10 +527,73 @@ impl AppState {
     /// ```
     pub fn get<T: Send + Sync + 'static>(&self) -> Option<Arc<T>> {
         self.data
-            .get(&TypeId::of::<T>())
+            .get(&StateKey::of::<T>())
             .and_then(|arc| arc.value().clone().downcast::<T>().ok())
     }
 
+    /// Returns the value of type `T`, creating and inserting it first if absent.
+    ///
+    /// The check and the insert happen atomically under the map's entry lock,
+    /// so when several handlers race to initialize the same type, `f` runs
+    /// exactly once and all of them receive the same value.
+    ///
+    /// `f` runs while the entry is locked, so it must not access this
+    /// `AppState`, or it may deadlock.
+    ///
+    /// # Arguments
+    ///
+    /// * `f` - Builds the value if none is stored yet
+    ///
+    /// # Examples
+    ///
+    /// ## Lazy Cache
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    /// use std::collections::HashMap;
+    /// use std::sync::{Arc, Mutex};
+    ///
+    /// #[derive(Default)]
+    /// struct ProfileCache {
+    ///     entries: Mutex<HashMap<u64, String>>,
+    /// }
+    ///
+    /// fn profile_cache(state: &AppState) -> Arc<ProfileCache> {
+    ///     // Safe to call from many handlers at once
+    ///     state.get_or_insert_with(ProfileCache::default)
+    /// }
+    /// ```
+    ///
+    /// ## Only Built Once
+    ///
+    /// ```
+    /// use wsforge::prelude::*;
+    /// use std::sync::Arc;
+    ///
+    /// # fn example() {
+    /// let state = AppState::new();
+    ///
+    /// let first = state.get_or_insert_with(|| 1_u32);
+    /// let second = state.get_or_insert_with(|| 2_u32);
+    ///
+    /// assert_eq!(*second, 1);
+    /// assert!(Arc::ptr_eq(&first, &second));
+    /// # }
+    /// ```
+    pub fn get_or_insert_with<T, F>(&self, f: F) -> Arc<T>
+    where
+        T: Send + Sync + 'static,
+        F: FnOnce() -> T,
+    {
+        self.data
+            .entry(StateKey::of::<T>())
+            .or_insert_with(|| Arc::new(f()))
+            .value()
+            .clone()
+            .downcast::<T>()
+            .expect("state entries are keyed by their TypeId")
+    }
+
     /// Checks if a value of type `T` exists in the state.
     ///
     /// This is equivalent to `state.get::<T>().is_some()` but more explicit.
@@ -469,7 +613,7 @@ impl AppState {
     /// # }
     /// ```
     pub fn contains<T: Send + Sync + 'static>(&self) -> bool {
-        self.data.contains_key(&TypeId::of::<T>())
+        self.data.contains_key(&StateKey::of::<T>())
     }
 
     /// Removes a value of type `T` from the state.
@@ -496,11 +640,14 @@ impl AppState {
     /// ```
     pub fn remove<T: Send + Sync + 'static>(&self) -> Option<Arc<T>> {
         self.data
-            .remove(&TypeId::of::<T>())
+            .remove(&StateKey::of::<T>())
             .and_then(|(_, arc)| arc.downcast::<T>().ok())
     }
 
-    /// Returns the number of different types stored in the state.
+    /// Returns the number of values stored in the state.
+    ///
+    /// Counts one value per type stored with [`insert()`](Self::insert),
+    /// plus every value stored with [`insert_keyed()`](Self::insert_keyed).
     ///
     /// # Examples
     ///
@@ -684,4 +831,58 @@ mod tests {
         state2.insert(Arc::new(100_u32));
         assert_eq!(*state1.get::<u32>().unwrap(), 100);
     }
+
+    #[test]
+    fn test_keyed_values_of_same_type() {
+        let state = AppState::new();
+        state.insert(Arc::new(String::from("default")));
+        state.insert_keyed("primary", Arc::new(String::from("pg://primary")));
+        state.insert_keyed("replica", Arc::new(String::from("pg://replica")));
+
+        assert_eq!(*state.get::<String>().unwrap(), "default");
+        assert_eq!(
+            *state.get_keyed::<String>("primary").unwrap(),
+            "pg://primary"
+        );
+        assert_eq!(
+            *state.get_keyed::<String>("replica").unwrap(),
+            "pg://replica"
+        );
+        assert!(state.get_keyed::<u32>("primary").is_none());
+        assert!(state.get_keyed::<String>("missing").is_none());
+        assert_eq!(state.len(), 3);
+    }
+
+    #[test]
+    fn test_get_or_insert_with_builds_once() {
+        use std::sync::Barrier;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let state = AppState::new();
+        let builds = Arc::new(AtomicUsize::new(0));
+        let barrier = Arc::new(Barrier::new(2));
+
+        let threads: Vec<_> = (0..2)
+            .map(|_| {
+                let state = state.clone();
+                let builds = builds.clone();
+                let barrier = barrier.clone();
+                std::thread::spawn(move || {
+                    barrier.wait();
+                    state.get_or_insert_with(|| {
+                        builds.fetch_add(1, Ordering::SeqCst);
+                        // Widen the race window
+                        std::thread::sleep(std::time::Duration::from_millis(20));
+                        String::from("cache")
+                    })
+                })
+            })
+            .collect();
+
+        let values: Vec<Arc<String>> = threads.into_iter().map(|t| t.join().unwrap()).collect();
+
+        assert_eq!(builds.load(Ordering::SeqCst), 1);
+        assert!(Arc::ptr_eq(&values[0], &values[1]));
+        assert_eq!(*state.get::<String>().unwrap(), "cache");
+    }
 }
@@ -7,12 +7,16 @@
 //! # Overview
 //!
 //! The static file handler:
-//! - Serves files from a specified directory
+//! - Serves files from a specified directory, or embedded in the binary
 //! - Automatically detects MIME types
 //! - Handles index files (e.g., `index.html` for directory requests)
 //! - Prevents path traversal attacks
 //! - Supports percent-encoded URLs
 //! - Returns proper HTTP responses with status codes
+//! - Supports conditional requests with `ETag` and `Last-Modified`
+//! - Streams files to the client in chunks instead of buffering them
+//! - Optionally lists directories that have no index file
+//! - Has a development mode that always serves the current files
 //!
 //! # Security
 //!
@@ -21,6 +25,9 @@
 //! - **Canonical path validation**: All paths are canonicalized and checked against the root
 //! - **Access control**: Only files within the configured root directory can be served
 //!
+//! Embedded files (see [`StaticFileHandler::embedded()`]) are looked up by key
+//! in the embedded tree, so requests can never reach outside of it.
+//!
 //! # MIME Type Detection
 //!
 //! File types are automatically detected based on file extensions:
@@ -51,6 +58,8 @@
 //!          │
 //!          ├──→ Check if directory → serve index.html
 //!          │
+//!          ├──→ Compare ETag / modification time → 304 Not Modified
+//!          │
 //!          ├──→ Read file contents
 //!          │
 //!          └──→ Return HTTP response with MIME type
@@ -141,11 +150,35 @@
 //! # Ok(())
 //! # }
 //! ```
+//!
+//! ## Embedded Files
+//!
+//! With the `embed` feature, the frontend can be compiled into the binary:
+//!
+//! ```ignore
+//! use include_dir::{Dir, include_dir};
+//! use wsforge::prelude::*;
+//!
+//! static PUBLIC: Dir<'static> = include_dir!("$CARGO_MANIFEST_DIR/public");
+//!
+//! # async fn example() -> Result<()> {
+//! let router = Router::new().serve_embedded(PUBLIC.clone());
+//!
+//! router.listen("0.0.0.0:3000").await?;
+//! # Ok(())
+//! # }
+//! ```
 
 use crate::error::{Error, Result};
-use std::path::PathBuf;
+use http::HeaderMap;
+use http::header::{IF_MODIFIED_SINCE, IF_NONE_MATCH};
+#[cfg(feature = "embed")]
+use include_dir::Dir;
+use percent_encoding::{AsciiSet, CONTROLS, utf8_percent_encode};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
 use tokio::fs::File;
-use tokio::io::AsyncReadExt;
+use tokio::io::{AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
 use tracing::{debug, warn};
 
 /// Handler for serving static files from a directory.
@@ -162,7 +195,8 @@ use tracing::{debug, warn};
 /// # Performance
 ///
 /// - Files are read asynchronously using tokio's `AsyncReadExt`
-/// - No buffering overhead for small files
+/// - [`serve_streaming()`](Self::serve_streaming) sends large files in
+///   chunks, so they are never held in memory as a whole
 /// - Efficient path resolution and validation
 ///
 /// # Examples
@@ -212,10 +246,16 @@ use tracing::{debug, warn};
 /// ```
 #[derive(Debug, Clone)]
 pub struct StaticFileHandler {
-    /// The root directory from which files are served
-    root: PathBuf,
+    /// Where files are served from
+    source: Source,
     /// The default file to serve for directory requests (e.g., "index.html")
     index_file: String,
+    /// Whether directories without an index file are listed
+    autoindex: bool,
+    /// Whether caching is disabled so edits show up immediately
+    dev_mode: bool,
+    /// Where files are read from in dev mode instead of `source`
+    dev_source: Option<Source>,
 }
 
 impl StaticFileHandler {
@@ -273,8 +313,50 @@ impl StaticFileHandler {
     /// ```
     pub fn new(root: impl Into<PathBuf>) -> Self {
         Self {
-            root: root.into(),
+            source: Source::Directory(root.into()),
             index_file: "index.html".to_string(),
+            autoindex: false,
+            dev_mode: false,
+            dev_source: None,
+        }
+    }
+
+    /// Creates a handler that serves files embedded in the binary.
+    ///
+    /// The directory is embedded at compile time with the
+    /// [`include_dir!`](https://docs.rs/include_dir) macro, so the files
+    /// don't need to be shipped alongside the binary and the working
+    /// directory doesn't matter. Lookups are plain key matches against the
+    /// embedded tree, so path traversal is impossible.
+    ///
+    /// Embedded files carry a content-based `ETag` but no `Last-Modified`
+    /// header.
+    ///
+    /// Requires the `embed` feature and a direct dependency on `include_dir`.
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - The embedded directory
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// use include_dir::include_dir;
+    /// use wsforge::static_files::StaticFileHandler;
+    ///
+    /// # fn example() {
+    /// let handler = StaticFileHandler::embedded(include_dir!("$CARGO_MANIFEST_DIR/public"));
+    /// # }
+    /// ```
+    #[cfg(feature = "embed")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "embed")))]
+    pub fn embedded(dir: Dir<'static>) -> Self {
+        Self {
+            source: Source::Embedded(dir),
+            index_file: "index.html".to_string(),
+            autoindex: false,
+            dev_mode: false,
+            dev_source: None,
         }
     }
 
@@ -315,6 +397,100 @@ impl StaticFileHandler {
         self
     }
 
+    /// Enables HTML listings for directories without an index file.
+    ///
+    /// When enabled, a request for a directory that has no
+    /// [index file](Self::with_index) is answered with a generated page
+    /// linking to the directory's entries, instead of `404 Not Found`.
+    /// Hidden entries (starting with `.`) are left out, and the usual path
+    /// traversal checks apply, so only directories under the root can be
+    /// listed.
+    ///
+    /// Disabled by default, since listings reveal every file being served.
+    /// Only directories on disk are listed, not
+    /// [embedded](Self::embedded) ones.
+    ///
+    /// # Arguments
+    ///
+    /// * `enabled` - Whether to list directories
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::static_files::StaticFileHandler;
+    ///
+    /// # fn example() {
+    /// // Browse the build output while debugging
+    /// let handler = StaticFileHandler::new("dist").autoindex(true);
+    /// # }
+    /// ```
+    pub fn autoindex(mut self, enabled: bool) -> Self {
+        self.autoindex = enabled;
+        self
+    }
+
+    /// Enables development mode, so edited files show up on the next reload.
+    ///
+    /// Files on disk are already read on every request, but browsers may
+    /// keep serving their cached copy. In development mode, responses carry
+    /// `Cache-Control: no-store` instead of `ETag` and `Last-Modified`, and
+    /// conditional requests are always answered with the full file.
+    ///
+    /// [Embedded](Self::embedded) handlers additionally read from the
+    /// directory set with [`dev_dir()`](Self::dev_dir) instead of the files
+    /// baked into the binary.
+    ///
+    /// Disabled by default. Tie it to a debug build or an environment
+    /// variable, so production keeps caching.
+    ///
+    /// # Arguments
+    ///
+    /// * `enabled` - Whether to disable caching
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::static_files::StaticFileHandler;
+    ///
+    /// # fn example() {
+    /// let handler = StaticFileHandler::new("public").dev_mode(cfg!(debug_assertions));
+    /// # }
+    /// ```
+    pub fn dev_mode(mut self, enabled: bool) -> Self {
+        self.dev_mode = enabled;
+        self
+    }
+
+    /// Sets the directory an [embedded](Self::embedded) handler reads from
+    /// in [development mode](Self::dev_mode).
+    ///
+    /// This is normally the directory passed to `include_dir!`, so edits
+    /// are served without recompiling. Outside development mode, the
+    /// embedded files are served as usual.
+    ///
+    /// # Arguments
+    ///
+    /// * `root` - The directory the files were embedded from
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// use include_dir::include_dir;
+    /// use wsforge::static_files::StaticFileHandler;
+    ///
+    /// # fn example() {
+    /// let handler = StaticFileHandler::embedded(include_dir!("$CARGO_MANIFEST_DIR/public"))
+    ///     .dev_dir(concat!(env!("CARGO_MANIFEST_DIR"), "/public"))
+    ///     .dev_mode(cfg!(debug_assertions));
+    /// # }
+    /// ```
+    #[cfg(feature = "embed")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "embed")))]
+    pub fn dev_dir(mut self, root: impl Into<PathBuf>) -> Self {
+        self.dev_source = Some(Source::Directory(root.into()));
+        self
+    }
+
     /// Serves a file at the given path.
     ///
     /// This method:
@@ -416,22 +592,310 @@ impl StaticFileHandler {
     /// # }
     /// ```
     pub async fn serve(&self, path: &str) -> Result<(Vec<u8>, String)> {
-        let mut file_path = self.root.clone();
+        // Without the `embed` feature there is only one source
+        #[cfg_attr(not(feature = "embed"), allow(clippy::infallible_destructuring_match))]
+        let root = match self.source() {
+            Source::Directory(root) => root,
+            #[cfg(feature = "embed")]
+            Source::Embedded(dir) => {
+                let file = self.lookup(dir, path)?;
+                return Ok((file.contents().to_vec(), mime_type(file.path())));
+            }
+        };
+
+        let file_path = match self.resolve(root, path).await? {
+            Resolved::File(file_path) => file_path,
+            Resolved::Listing(html) => return Ok((html.into_bytes(), "text/html".to_string())),
+        };
+
+        debug!("Serving file: {:?}", file_path);
+
+        let contents = read_file(&file_path).await?;
+        Ok((contents, mime_type(&file_path)))
+    }
+
+    /// Serves a file, honoring the request's conditional headers.
+    ///
+    /// Behaves like [`serve()`](Self::serve), but also emits `ETag` and
+    /// `Last-Modified` headers derived from the file's size and modification
+    /// time. When the request's `If-None-Match` or `If-Modified-Since` header
+    /// shows the client already has the current version, a `304 Not Modified`
+    /// response is returned without reading the file.
+    ///
+    /// `If-None-Match` takes precedence: `If-Modified-Since` is only checked
+    /// when no `If-None-Match` header is present.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The requested path (e.g., "/app.js", "/images/logo.png")
+    /// * `headers` - Headers of the HTTP request
+    ///
+    /// # Errors
+    ///
+    /// Returns an error in the same cases as [`serve()`](Self::serve).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::static_files::StaticFileHandler;
+    /// use wsforge::handshake::HeaderMap;
+    ///
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let handler = StaticFileHandler::new("public");
+    ///
+    /// let first = handler.serve_request("/bundle.js", &HeaderMap::new()).await?;
+    /// assert_eq!(first.status, 200);
+    ///
+    /// // Revalidate with the ETag from the first response
+    /// let mut headers = HeaderMap::new();
+    /// headers.insert("if-none-match", first.header("etag").unwrap().parse()?);
+    ///
+    /// let second = handler.serve_request("/bundle.js", &headers).await?;
+    /// assert_eq!(second.status, 304);
+    /// assert!(second.body.is_empty());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn serve_request(&self, path: &str, headers: &HeaderMap) -> Result<StaticResponse> {
+        #[cfg_attr(not(feature = "embed"), allow(clippy::infallible_destructuring_match))]
+        let root = match self.source() {
+            Source::Directory(root) => root,
+            #[cfg(feature = "embed")]
+            Source::Embedded(dir) => {
+                let file = self.lookup(dir, path)?;
+                let etag = content_tag(file.contents());
+
+                let mut response = StaticResponse {
+                    status: 200,
+                    content_type: mime_type(file.path()),
+                    headers: vec![("ETag".to_string(), etag.clone())],
+                    body: Vec::new(),
+                };
+
+                if self.dev_mode {
+                    response.headers = vec![no_store()];
+                    response.body = file.contents().to_vec();
+                } else if is_fresh(headers, &etag, None) {
+                    response.status = 304;
+                } else {
+                    response.body = file.contents().to_vec();
+                }
+                return Ok(response);
+            }
+        };
+
+        let (mut response, file) = self.file_response(root, path, headers).await?;
+        let Some((file_path, _)) = file else {
+            return Ok(response);
+        };
+        if response.status == 304 {
+            return Ok(response);
+        }
+
+        debug!("Serving file: {:?}", file_path);
 
-        // Remove leading slash and decode percent-encoding
-        let clean_path = path.trim_start_matches('/');
-        let decoded = percent_encoding::percent_decode_str(clean_path)
-            .decode_utf8()
-            .map_err(|e| Error::custom(format!("Invalid path encoding: {}", e)))?;
+        response.body = read_file(&file_path).await?;
+        Ok(response)
+    }
 
-        file_path.push(decoded.as_ref());
+    /// Serves a file by writing the whole HTTP response to `stream`.
+    ///
+    /// Behaves like [`serve_request()`](Self::serve_request), but instead of
+    /// reading the file into memory, writes the response headers and then
+    /// copies the file to `stream` in 64 KiB chunks. Memory use stays
+    /// constant however large the file is, which matters for large
+    /// downloads. The router serves static files this way.
+    ///
+    /// Embedded files are already in memory and are written in one go.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The requested path (e.g., "/app.js", "/images/logo.png")
+    /// * `headers` - Headers of the HTTP request
+    /// * `stream` - Where the response is written, typically the client socket
+    ///
+    /// # Returns
+    ///
+    /// The number of body bytes written, `0` for `304 Not Modified`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error in the same cases as [`serve()`](Self::serve), in
+    /// which case nothing has been written yet. Failures while writing are
+    /// returned as [`Error::Io`], possibly after part of the response was
+    /// sent.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wsforge::static_files::StaticFileHandler;
+    /// use wsforge::handshake::HeaderMap;
+    ///
+    /// # async fn example(mut socket: tokio::net::TcpStream) -> Result<(), Box<dyn std::error::Error>> {
+    /// let handler = StaticFileHandler::new("downloads");
+    ///
+    /// let sent = handler
+    ///     .serve_streaming("/dataset.tar.gz", &HeaderMap::new(), &mut socket)
+    ///     .await?;
+    /// println!("Sent {} bytes", sent);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn serve_streaming<W>(
+        &self,
+        path: &str,
+        headers: &HeaderMap,
+        stream: &mut W,
+    ) -> Result<u64>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        self.serve_streaming_with(path, headers, Vec::new(), stream)
+            .await
+    }
+
+    /// Like [`serve_streaming()`](Self::serve_streaming), but adds
+    /// `extra_headers` to the response, e.g. for CORS.
+    pub(crate) async fn serve_streaming_with<W>(
+        &self,
+        path: &str,
+        headers: &HeaderMap,
+        extra_headers: Vec<(String, String)>,
+        stream: &mut W,
+    ) -> Result<u64>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        #[cfg_attr(not(feature = "embed"), allow(clippy::infallible_destructuring_match))]
+        let root = match self.source() {
+            Source::Directory(root) => root,
+            #[cfg(feature = "embed")]
+            Source::Embedded(_) => {
+                let mut response = self.serve_request(path, headers).await?;
+                response.headers.extend(extra_headers);
+                let len = response.body.len() as u64;
+                stream.write_all(&response.into_bytes()).await?;
+                stream.flush().await?;
+                return Ok(len);
+            }
+        };
+
+        let (mut response, file) = self.file_response(root, path, headers).await?;
+        response.headers.extend(extra_headers);
+        let (file_path, len) = match file {
+            Some(file) if response.status != 304 => file,
+            // Not modified, or a directory listing that is already in memory
+            _ => {
+                let len = response.body.len() as u64;
+                stream.write_all(&response.into_bytes()).await?;
+                stream.flush().await?;
+                return Ok(len);
+            }
+        };
+
+        // Opened before anything is written, so a missing file can still
+        // be answered with a 404
+        let file = File::open(&file_path)
+            .await
+            .map_err(|_| Error::custom("File not found"))?;
+
+        debug!("Streaming file: {:?} ({} bytes)", file_path, len);
+
+        stream.write_all(response.head(len).as_bytes()).await?;
+        // Never send more than the announced Content-Length, even if the
+        // file grew in the meantime
+        let mut body = BufReader::with_capacity(STREAM_CHUNK_SIZE, file.take(len));
+        let written = tokio::io::copy_buf(&mut body, stream).await?;
+        stream.flush().await?;
+
+        if written < len {
+            warn!(
+                "File shrank while streaming: {:?} ({} of {} bytes)",
+                file_path, written, len
+            );
+        }
+
+        Ok(written)
+    }
+
+    /// Returns where files are currently read from.
+    fn source(&self) -> &Source {
+        match &self.dev_source {
+            Some(source) if self.dev_mode => source,
+            _ => &self.source,
+        }
+    }
+
+    /// Resolves a file under the root directory and builds its response,
+    /// without a body.
+    ///
+    /// The response is `304 Not Modified` if the client's copy is current.
+    /// Also returns the file's path and size, or `None` for a directory
+    /// listing, whose response already has its body.
+    async fn file_response(
+        &self,
+        root: &Path,
+        path: &str,
+        headers: &HeaderMap,
+    ) -> Result<(StaticResponse, Option<(PathBuf, u64)>)> {
+        let file_path = match self.resolve(root, path).await? {
+            Resolved::File(file_path) => file_path,
+            Resolved::Listing(html) => {
+                let headers = if self.dev_mode {
+                    vec![no_store()]
+                } else {
+                    Vec::new()
+                };
+                let response = StaticResponse {
+                    status: 200,
+                    content_type: "text/html".to_string(),
+                    headers,
+                    body: html.into_bytes(),
+                };
+                return Ok((response, None));
+            }
+        };
+
+        let metadata = tokio::fs::metadata(&file_path)
+            .await
+            .map_err(|_| Error::custom("File not found"))?;
+        let modified = metadata.modified().unwrap_or(UNIX_EPOCH);
+        let etag = entity_tag(metadata.len(), modified);
+        let last_modified = httpdate::fmt_http_date(modified);
+
+        let mut response = StaticResponse {
+            status: 200,
+            content_type: mime_type(&file_path),
+            headers: vec![
+                ("ETag".to_string(), etag.clone()),
+                ("Last-Modified".to_string(), last_modified),
+            ],
+            body: Vec::new(),
+        };
+
+        if self.dev_mode {
+            response.headers = vec![no_store()];
+        } else if is_fresh(headers, &etag, Some(modified)) {
+            debug!("Not modified: {:?}", file_path);
+            response.status = 304;
+        }
+
+        Ok((response, Some((file_path, metadata.len()))))
+    }
+
+    /// Maps a request path to a file under the root directory, or to the
+    /// listing of a directory without an index file.
+    async fn resolve(&self, root: &Path, path: &str) -> Result<Resolved> {
+        let decoded = decode_path(path)?;
+        let mut file_path = root.to_path_buf();
+        file_path.push(&decoded);
 
         // Security: prevent path traversal
         let canonical = tokio::fs::canonicalize(&file_path)
             .await
             .map_err(|_| Error::custom("File not found"))?;
 
-        let root_canonical = tokio::fs::canonicalize(&self.root)
+        let root_canonical = tokio::fs::canonicalize(root)
             .await
             .map_err(|e| Error::custom(format!("Invalid root directory: {}", e)))?;
 
@@ -443,27 +907,317 @@ impl StaticFileHandler {
         // If it's a directory, serve index.html
         if canonical.is_dir() {
             file_path.push(&self.index_file);
+
+            if self.autoindex && !tokio::fs::try_exists(&file_path).await.unwrap_or(false) {
+                debug!("Listing directory: {:?}", canonical);
+                return list_directory(&canonical, &decoded)
+                    .await
+                    .map(Resolved::Listing);
+            }
         }
 
-        debug!("Serving file: {:?}", file_path);
+        Ok(Resolved::File(file_path))
+    }
 
-        // Read file
-        let mut file = File::open(&file_path)
-            .await
-            .map_err(|_| Error::custom("File not found"))?;
+    /// Maps a request path to a file in the embedded directory.
+    #[cfg(feature = "embed")]
+    fn lookup(
+        &self,
+        dir: &Dir<'static>,
+        path: &str,
+    ) -> Result<&'static include_dir::File<'static>> {
+        let key = decode_path(path)?;
+        let key = key.trim_end_matches('/');
+
+        let key = if key.is_empty() {
+            self.index_file.clone()
+        } else if dir.get_dir(key).is_some() {
+            format!("{}/{}", key, self.index_file)
+        } else {
+            key.to_string()
+        };
+
+        debug!("Serving embedded file: {}", key);
+
+        dir.get_file(&key)
+            .ok_or_else(|| Error::custom("File not found"))
+    }
+}
+
+/// A static file response produced by
+/// [`StaticFileHandler::serve_request()`].
+///
+/// # Examples
+///
+/// ```
+/// use wsforge::static_files::StaticFileHandler;
+/// use wsforge::handshake::HeaderMap;
+///
+/// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let handler = StaticFileHandler::new("public");
+/// let response = handler.serve_request("/", &HeaderMap::new()).await?;
+///
+/// println!("{} {}", response.status, response.content_type);
+/// let bytes = response.into_bytes(); // Ready to write to the socket
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct StaticResponse {
+    /// HTTP status code, `200` or `304`
+    pub status: u16,
+    /// MIME type of the file
+    pub content_type: String,
+    /// Additional response headers, such as `ETag` and `Last-Modified`
+    pub headers: Vec<(String, String)>,
+    /// File contents, empty for `304 Not Modified`
+    pub body: Vec<u8>,
+}
+
+impl StaticResponse {
+    /// Returns the value of the first header with the given name.
+    ///
+    /// Header names are compared case-insensitively.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
 
-        let mut contents = Vec::new();
-        file.read_to_end(&mut contents)
+    /// Serializes the response into raw HTTP/1.1 bytes.
+    ///
+    /// A `204 No Content` or `304 Not Modified` response carries no
+    /// `Content-Type`,
+    /// `Content-Length` or body.
+    pub fn into_bytes(self) -> Vec<u8> {
+        let mut result = self.head(self.body.len() as u64).into_bytes();
+        result.extend_from_slice(&self.body);
+        result
+    }
+
+    /// Serializes the status line and headers, announcing a body of
+    /// `content_length` bytes.
+    pub(crate) fn head(&self, content_length: u64) -> String {
+        let mut response = format!("HTTP/1.1 {} {}\r\n", self.status, status_text(self.status));
+
+        if self.status != 204 && self.status != 304 {
+            response.push_str(&format!(
+                "Content-Type: {}\r\nContent-Length: {}\r\n",
+                self.content_type, content_length
+            ));
+        }
+
+        for (name, value) in &self.headers {
+            response.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        response.push_str("Connection: close\r\n\r\n");
+        response
+    }
+}
+
+/// Where a [`StaticFileHandler`] reads files from.
+#[derive(Debug, Clone)]
+enum Source {
+    Directory(PathBuf),
+    #[cfg(feature = "embed")]
+    Embedded(Dir<'static>),
+}
+
+/// What a request path resolved to.
+enum Resolved {
+    /// A file to serve
+    File(PathBuf),
+    /// The HTML listing of a directory without an index file
+    Listing(String),
+}
+
+/// Characters percent-encoded in the links of a directory listing.
+const PATH_SEGMENT: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'\'')
+    .add(b'/')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'\\')
+    .add(b'`')
+    .add(b'{')
+    .add(b'}');
+
+/// Renders an HTML page linking to the entries of `dir`, which was requested
+/// as `request_path` (decoded, without the leading slash).
+async fn list_directory(dir: &Path, request_path: &str) -> Result<String> {
+    let mut entries = Vec::new();
+    let mut read_dir = tokio::fs::read_dir(dir)
+        .await
+        .map_err(|e| Error::custom(format!("Failed to list directory: {}", e)))?;
+
+    while let Some(entry) = read_dir
+        .next_entry()
+        .await
+        .map_err(|e| Error::custom(format!("Failed to list directory: {}", e)))?
+    {
+        let Ok(name) = entry.file_name().into_string() else {
+            continue;
+        };
+        if name.starts_with('.') {
+            continue;
+        }
+        // Follows symlinks, so linked directories are listed as directories
+        let is_dir = tokio::fs::metadata(entry.path())
             .await
-            .map_err(|e| Error::custom(format!("Failed to read file: {}", e)))?;
+            .is_ok_and(|metadata| metadata.is_dir());
+        entries.push((name, is_dir));
+    }
 
-        // Determine MIME type
-        let mime_type = mime_guess::from_path(&file_path)
-            .first_or_octet_stream()
-            .to_string();
+    // Directories first, then alphabetically
+    entries.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
 
-        Ok((contents, mime_type))
+    let segments: Vec<&str> = request_path.split('/').filter(|s| !s.is_empty()).collect();
+    let mut base = String::from("/");
+    let mut title = String::from("/");
+    for segment in &segments {
+        base.push_str(&format!("{}/", utf8_percent_encode(segment, PATH_SEGMENT)));
+        title.push_str(&format!("{}/", segment));
     }
+    let title = escape_html(&title);
+
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>Index of {title}</title></head>\n\
+         <body><h1>Index of {title}</h1><ul>\n"
+    );
+    if !segments.is_empty() {
+        html.push_str("<li><a href=\"../\">../</a></li>\n");
+    }
+    for (name, is_dir) in entries {
+        let suffix = if is_dir { "/" } else { "" };
+        html.push_str(&format!(
+            "<li><a href=\"{}{}{}\">{}{}</a></li>\n",
+            base,
+            utf8_percent_encode(&name, PATH_SEGMENT),
+            suffix,
+            escape_html(&name),
+            suffix
+        ));
+    }
+    html.push_str("</ul></body></html>\n");
+
+    Ok(html)
+}
+
+/// Escapes text for use in HTML content and attribute values.
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Size of the chunks [`StaticFileHandler::serve_streaming()`] reads files in.
+const STREAM_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Strips the leading slash and decodes percent-encoding.
+fn decode_path(path: &str) -> Result<String> {
+    percent_encoding::percent_decode_str(path.trim_start_matches('/'))
+        .decode_utf8()
+        .map(|decoded| decoded.into_owned())
+        .map_err(|e| Error::custom(format!("Invalid path encoding: {}", e)))
+}
+
+async fn read_file(file_path: &Path) -> Result<Vec<u8>> {
+    let mut file = File::open(file_path)
+        .await
+        .map_err(|_| Error::custom("File not found"))?;
+
+    let mut contents = Vec::new();
+    file.read_to_end(&mut contents)
+        .await
+        .map_err(|e| Error::custom(format!("Failed to read file: {}", e)))?;
+
+    Ok(contents)
+}
+
+fn mime_type(file_path: &Path) -> String {
+    mime_guess::from_path(file_path)
+        .first_or_octet_stream()
+        .to_string()
+}
+
+/// Builds a strong entity tag from the file size and modification time.
+fn entity_tag(len: u64, modified: SystemTime) -> String {
+    let nanos = modified
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or(0);
+    format!("\"{:x}-{:x}\"", len, nanos)
+}
+
+/// Builds a strong entity tag from the file contents.
+#[cfg(feature = "embed")]
+fn content_tag(contents: &[u8]) -> String {
+    use std::hash::{DefaultHasher, Hasher};
+
+    let mut hasher = DefaultHasher::new();
+    hasher.write(contents);
+    format!("\"{:x}-{:x}\"", contents.len(), hasher.finish())
+}
+
+/// Header keeping browsers from caching a response in development mode.
+fn no_store() -> (String, String) {
+    ("Cache-Control".to_string(), "no-store".to_string())
+}
+
+/// Returns `true` if the client's cached copy is still current.
+fn is_fresh(headers: &HeaderMap, etag: &str, modified: Option<SystemTime>) -> bool {
+    if let Some(if_none_match) = headers.get(IF_NONE_MATCH) {
+        let Ok(value) = if_none_match.to_str() else {
+            return false;
+        };
+        return value.split(',').map(str::trim).any(|tag| {
+            // Weak comparison, as required for If-None-Match
+            tag == "*" || tag.trim_start_matches("W/") == etag
+        });
+    }
+
+    let Some(modified) = modified else {
+        return false;
+    };
+
+    headers
+        .get(IF_MODIFIED_SINCE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| httpdate::parse_http_date(v).ok())
+        .is_some_and(|since| {
+            // HTTP dates have one-second resolution
+            let modified = modified
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0);
+            let since = since
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0);
+            modified <= since
+        })
+}
+
+fn status_text(status: u16) -> &'static str {
+    http::StatusCode::from_u16(status)
+        .ok()
+        .and_then(|status| status.canonical_reason())
+        .unwrap_or("Unknown")
 }
 
 /// Constructs an HTTP response with the given status, content type, and body.
@@ -564,13 +1318,6 @@ impl StaticFileHandler {
 /// # }
 /// ```
 pub fn http_response(status: u16, content_type: &str, body: Vec<u8>) -> Vec<u8> {
-    let status_text = match status {
-        200 => "OK",
-        404 => "Not Found",
-        500 => "Internal Server Error",
-        _ => "Unknown",
-    };
-
     let response = format!(
         "HTTP/1.1 {} {}\r\n\
          Content-Type: {}\r\n\
@@ -578,7 +1325,7 @@ pub fn http_response(status: u16, content_type: &str, body: Vec<u8>) -> Vec<u8>
          Connection: close\r\n\
          \r\n",
         status,
-        status_text,
+        status_text(status),
         content_type,
         body.len()
     );
@@ -587,3 +1334,246 @@ pub fn http_response(status: u16, content_type: &str, body: Vec<u8>) -> Vec<u8>
     result.extend_from_slice(&body);
     result
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn temp_root(name: &str) -> PathBuf {
+        let root =
+            std::env::temp_dir().join(format!("wsforge-static-{}-{}", name, std::process::id()));
+        tokio::fs::create_dir_all(&root).await.unwrap();
+        tokio::fs::write(root.join("app.js"), b"console.log(1);")
+            .await
+            .unwrap();
+        root
+    }
+
+    #[tokio::test]
+    async fn test_serve_request_emits_validators() {
+        let root = temp_root("validators").await;
+        let handler = StaticFileHandler::new(&root);
+
+        let response = handler
+            .serve_request("/app.js", &HeaderMap::new())
+            .await
+            .unwrap();
+        assert_eq!(response.status, 200);
+        assert_eq!(response.body, b"console.log(1);");
+        assert!(response.header("etag").is_some());
+        assert!(response.header("last-modified").is_some());
+
+        tokio::fs::remove_dir_all(root).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_serve_request_not_modified() {
+        let root = temp_root("not-modified").await;
+        let handler = StaticFileHandler::new(&root);
+
+        let first = handler
+            .serve_request("/app.js", &HeaderMap::new())
+            .await
+            .unwrap();
+
+        let mut headers = HeaderMap::new();
+        let etag = format!("W/{}, \"other\"", first.header("etag").unwrap());
+        headers.insert(IF_NONE_MATCH, etag.parse().unwrap());
+        let response = handler.serve_request("/app.js", &headers).await.unwrap();
+        assert_eq!(response.status, 304);
+        assert!(response.body.is_empty());
+
+        let mut headers = HeaderMap::new();
+        let since = first.header("last-modified").unwrap();
+        headers.insert(IF_MODIFIED_SINCE, since.parse().unwrap());
+        let response = handler.serve_request("/app.js", &headers).await.unwrap();
+        assert_eq!(response.status, 304);
+
+        // A stale ETag wins over a matching If-Modified-Since
+        headers.insert(IF_NONE_MATCH, "\"stale\"".parse().unwrap());
+        let response = handler.serve_request("/app.js", &headers).await.unwrap();
+        assert_eq!(response.status, 200);
+
+        let bytes = String::from_utf8(response.into_bytes()).unwrap();
+        assert!(bytes.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(bytes.contains("ETag: \""));
+
+        tokio::fs::remove_dir_all(root).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_serve_streaming_writes_large_file() {
+        let root = temp_root("streaming").await;
+        let contents: Vec<u8> = (0..200_000u32).map(|i| (i % 251) as u8).collect();
+        tokio::fs::write(root.join("large.bin"), &contents)
+            .await
+            .unwrap();
+        let handler = StaticFileHandler::new(&root);
+
+        let mut output = Vec::new();
+        let written = handler
+            .serve_streaming("/large.bin", &HeaderMap::new(), &mut output)
+            .await
+            .unwrap();
+        assert_eq!(written, contents.len() as u64);
+
+        let split = output.windows(4).position(|w| w == b"\r\n\r\n").unwrap() + 4;
+        let head = String::from_utf8(output[..split].to_vec()).unwrap();
+        assert!(head.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(head.contains(&format!("Content-Length: {}\r\n", contents.len())));
+        assert_eq!(&output[split..], &contents[..]);
+
+        // Missing files fail before anything is written
+        let mut output = Vec::new();
+        assert!(
+            handler
+                .serve_streaming("/missing.bin", &HeaderMap::new(), &mut output)
+                .await
+                .is_err()
+        );
+        assert!(output.is_empty());
+
+        tokio::fs::remove_dir_all(root).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_autoindex_lists_directory() {
+        let root = temp_root("autoindex").await;
+        tokio::fs::create_dir_all(root.join("assets"))
+            .await
+            .unwrap();
+        tokio::fs::write(root.join("a <b>.txt"), b"x")
+            .await
+            .unwrap();
+        tokio::fs::write(root.join(".secret"), b"x").await.unwrap();
+
+        // Off by default
+        assert!(StaticFileHandler::new(&root).serve("/").await.is_err());
+
+        let handler = StaticFileHandler::new(&root).autoindex(true);
+        let (content, mime_type) = handler.serve("/").await.unwrap();
+        let html = String::from_utf8(content).unwrap();
+        assert_eq!(mime_type, "text/html");
+        assert!(html.contains("<a href=\"/assets/\">assets/</a>"));
+        assert!(html.contains("<a href=\"/app.js\">app.js</a>"));
+        assert!(html.contains("<a href=\"/a%20%3Cb%3E.txt\">a &lt;b&gt;.txt</a>"));
+        assert!(!html.contains(".secret"));
+        assert!(html.find("assets/").unwrap() < html.find("app.js").unwrap());
+
+        let response = handler
+            .serve_request("/assets/", &HeaderMap::new())
+            .await
+            .unwrap();
+        assert_eq!(response.status, 200);
+        assert!(
+            String::from_utf8(response.body)
+                .unwrap()
+                .contains("href=\"../\"")
+        );
+
+        // An existing index file still wins
+        tokio::fs::write(root.join("index.html"), b"<h1>home</h1>")
+            .await
+            .unwrap();
+        let (content, _) = handler.serve("/").await.unwrap();
+        assert_eq!(content, b"<h1>home</h1>");
+
+        assert!(handler.serve("/../").await.is_err());
+
+        tokio::fs::remove_dir_all(root).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_dev_mode_disables_caching() {
+        let root = temp_root("dev-mode").await;
+        let handler = StaticFileHandler::new(&root).dev_mode(true);
+
+        let mut headers = HeaderMap::new();
+        headers.insert(IF_NONE_MATCH, "*".parse().unwrap());
+        let response = handler.serve_request("/app.js", &headers).await.unwrap();
+        assert_eq!(response.status, 200);
+        assert_eq!(response.body, b"console.log(1);");
+        assert_eq!(response.header("cache-control"), Some("no-store"));
+        assert!(response.header("etag").is_none());
+        assert!(response.header("last-modified").is_none());
+
+        // Edits are served right away
+        tokio::fs::write(root.join("app.js"), b"console.log(2);")
+            .await
+            .unwrap();
+        let mut output = Vec::new();
+        handler
+            .serve_streaming("/app.js", &headers, &mut output)
+            .await
+            .unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(output.contains("Cache-Control: no-store\r\n"));
+        assert!(output.ends_with("console.log(2);"));
+
+        tokio::fs::remove_dir_all(root).await.unwrap();
+    }
+
+    #[cfg(feature = "embed")]
+    #[tokio::test]
+    async fn test_embedded_dev_dir() {
+        use include_dir::{DirEntry, File};
+
+        static PUBLIC: Dir<'static> =
+            Dir::new("", &[DirEntry::File(File::new("app.js", b"baked"))]);
+
+        let root = temp_root("embedded-dev").await;
+        let handler = StaticFileHandler::embedded(PUBLIC.clone()).dev_dir(&root);
+
+        let (content, _) = handler.clone().serve("/app.js").await.unwrap();
+        assert_eq!(content, b"baked");
+
+        let (content, _) = handler.dev_mode(true).serve("/app.js").await.unwrap();
+        assert_eq!(content, b"console.log(1);");
+
+        tokio::fs::remove_dir_all(root).await.unwrap();
+    }
+
+    #[cfg(feature = "embed")]
+    #[tokio::test]
+    async fn test_embedded_lookup() {
+        use include_dir::{DirEntry, File};
+
+        static PUBLIC: Dir<'static> = Dir::new(
+            "",
+            &[
+                DirEntry::File(File::new("index.html", b"<h1>home</h1>")),
+                DirEntry::Dir(Dir::new(
+                    "docs",
+                    &[DirEntry::File(File::new(
+                        "docs/index.html",
+                        b"<h1>docs</h1>",
+                    ))],
+                )),
+            ],
+        );
+
+        let handler = StaticFileHandler::embedded(PUBLIC.clone());
+
+        let (content, mime_type) = handler.serve("/").await.unwrap();
+        assert_eq!(content, b"<h1>home</h1>");
+        assert_eq!(mime_type, "text/html");
+
+        let (content, _) = handler.serve("/docs/").await.unwrap();
+        assert_eq!(content, b"<h1>docs</h1>");
+
+        assert!(handler.serve("/../Cargo.toml").await.is_err());
+
+        let first = handler
+            .serve_request("/index.html", &HeaderMap::new())
+            .await
+            .unwrap();
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            IF_NONE_MATCH,
+            first.header("etag").unwrap().parse().unwrap(),
+        );
+        let second = handler.serve_request("/", &headers).await.unwrap();
+        assert_eq!(second.status, 304);
+    }
+}
@@ -0,0 +1,260 @@
+//! In-memory clients for testing routers without a socket.
+//!
+//! Calling handler functions directly skips everything around them: routing,
+//! middleware, extractors and response conversion. A [`TestClient`] sends
+//! messages through the same dispatch path a WebSocket connection uses, so
+//! integration tests catch regressions in any of those layers.
+//!
+//! # Overview
+//!
+//! - [`Router::test_client()`](crate::router::Router::test_client) registers
+//!   a connection with the router's
+//!   [`ConnectionManager`](crate::connection::ConnectionManager)
+//! - [`send()`](TestClient::send) dispatches a message and returns once its
+//!   handler has finished
+//! - [`recv()`](TestClient::recv) returns the next message sent to the
+//!   client, whether a handler response, a broadcast or a direct send
+//!
+//! Only message dispatch is exercised. No socket or handshake is involved,
+//! so none of the connection-level behavior runs:
+//!
+//! - handshake checks, connection limits and connection hooks
+//! - outbound middleware, the hello message, keepalive, coalescing and
+//!   send limits
+//!
+//! The connection also has no
+//! [`path()`](crate::connection::Connection::path). Use a real socket to
+//! test those.
+//!
+//! # Examples
+//!
+//! ```
+//! use wsforge::prelude::*;
+//!
+//! async fn echo(msg: Message) -> Result<Message> {
+//!     Ok(msg)
+//! }
+//!
+//! # async fn example() -> Result<()> {
+//! let router = Router::new().default_handler(handler(echo));
+//!
+//! let mut client = router.test_client();
+//! client.send_text("hello").await?;
+//! assert_eq!(client.recv().await.unwrap().as_text(), Some("hello"));
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::connection::{Connection, ConnectionId};
+use crate::error::{Error, Result};
+use crate::message::Message;
+use crate::router::Router;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+/// How long [`TestClient::recv()`] waits for a message.
+const RECV_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A client connected to a [`Router`] in memory.
+///
+/// Created with [`Router::test_client()`](crate::router::Router::test_client).
+/// The connection is removed from the router's manager when the client is
+/// dropped.
+///
+/// # Examples
+///
+/// ```
+/// use wsforge::prelude::*;
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct Join {
+///     room: String,
+/// }
+///
+/// async fn join(Json(join): Json<Join>) -> Result<JsonResponse<Join>> {
+///     Ok(JsonResponse(join))
+/// }
+///
+/// # async fn example() -> Result<()> {
+/// let router = Router::new().default_handler(handler(join));
+///
+/// let mut client = router.test_client();
+/// client.send_json(&Join { room: "lobby".to_string() }).await?;
+///
+/// let reply: Join = client.recv_json().await?;
+/// assert_eq!(reply.room, "lobby");
+/// # Ok(())
+/// # }
+/// ```
+pub struct TestClient {
+    router: Router,
+    id: ConnectionId,
+    receiver: mpsc::UnboundedReceiver<Message>,
+}
+
+impl TestClient {
+    /// Registers a new connection with `router`'s connection manager.
+    pub(crate) fn new(router: Router) -> Self {
+        let addr = SocketAddr::from(([127, 0, 0, 1], 0));
+        let id = router.next_connection_id(addr);
+        let (sender, receiver) = mpsc::unbounded_channel();
+
+        router
+            .connection_manager()
+            .add(Connection::new(id.clone(), addr, sender));
+
+        Self {
+            router,
+            id,
+            receiver,
+        }
+    }
+
+    /// Returns the ID of the client's connection.
+    pub fn id(&self) -> &ConnectionId {
+        &self.id
+    }
+
+    /// Returns the client's connection, as handlers see it.
+    ///
+    /// Useful to set metadata before sending, e.g. the user an
+    /// authentication middleware would have stored.
+    pub fn connection(&self) -> Connection {
+        self.router
+            .connection_manager()
+            .get(&self.id)
+            .expect("test client connection is registered")
+    }
+
+    /// Dispatches a message as if the client had sent it.
+    ///
+    /// Returns once the middleware and handler have finished, so their
+    /// responses can be read with [`recv()`](Self::recv) or
+    /// [`try_recv()`](Self::try_recv). Handler errors are sent to the client
+    /// like for a real connection, not returned.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::ConnectionNotFound`] if the connection was removed
+    /// from the manager, e.g. by a handler disconnecting it.
+    pub async fn send(&self, message: Message) -> Result<()> {
+        self.router.handle_message(self.id.clone(), message).await
+    }
+
+    /// Dispatches a text message. See [`send()`](Self::send).
+    pub async fn send_text(&self, text: impl Into<String>) -> Result<()> {
+        self.send(Message::text(text.into())).await
+    }
+
+    /// Serializes `data` to JSON and dispatches it as a text message. See
+    /// [`send()`](Self::send).
+    ///
+    /// # Errors
+    ///
+    /// Also returns an error if serialization fails.
+    pub async fn send_json<T: Serialize>(&self, data: &T) -> Result<()> {
+        self.send_text(serde_json::to_string(data)?).await
+    }
+
+    /// Waits up to 5 seconds for the next message sent to the client.
+    ///
+    /// Returns `None` if nothing arrives in time, so a missing response fails
+    /// the test instead of hanging it. Use [`try_recv()`](Self::try_recv) to
+    /// check that nothing was sent.
+    pub async fn recv(&mut self) -> Option<Message> {
+        self.recv_timeout(RECV_TIMEOUT).await
+    }
+
+    /// Waits up to `timeout` for the next message sent to the client.
+    ///
+    /// Returns `None` if nothing arrives in time.
+    pub async fn recv_timeout(&mut self, timeout: Duration) -> Option<Message> {
+        tokio::time::timeout(timeout, self.receiver.recv())
+            .await
+            .ok()
+            .flatten()
+    }
+
+    /// Returns the next message sent to the client, if one is queued.
+    pub fn try_recv(&mut self) -> Option<Message> {
+        self.receiver.try_recv().ok()
+    }
+
+    /// Waits for the next message and deserializes it from JSON.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no message arrives within
+    /// [`recv()`](Self::recv)'s timeout or the message isn't valid JSON for
+    /// `T`.
+    pub async fn recv_json<T: DeserializeOwned>(&mut self) -> Result<T> {
+        self.recv()
+            .await
+            .ok_or_else(|| Error::custom("No message was sent to the test client"))?
+            .json()
+    }
+}
+
+impl Drop for TestClient {
+    fn drop(&mut self) {
+        self.router.connection_manager().remove(&self.id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::extractor::Json;
+    use crate::handler::{JsonResponse, handler};
+    use crate::message::Message;
+    use crate::middleware::from_fn;
+    use crate::router::Router;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_client_runs_routes_and_middleware() {
+        let router = Router::new()
+            .layer(from_fn(|msg, conn, state, ext, next| async move {
+                let response = next.run(msg, conn, state, ext).await?;
+                Ok(response
+                    .into_iter()
+                    .map(|msg| Message::text(msg.as_text().unwrap_or("").to_uppercase()))
+                    .collect())
+            }))
+            .route("/greet", handler(|| async { Ok("hello") }))
+            .default_handler(handler(|Json(n): Json<u32>| async move {
+                Ok(JsonResponse(n + 1))
+            }));
+
+        let mut client = router.test_client();
+        assert_eq!(client.id(), "conn_0");
+        assert_eq!(router.connection_manager().count(), 1);
+
+        client.send_text("/greet").await.unwrap();
+        assert_eq!(client.recv().await.unwrap().as_text(), Some("HELLO"));
+
+        client.send_json(&41).await.unwrap();
+        assert_eq!(client.recv_json::<u32>().await.unwrap(), 42);
+        assert!(client.try_recv().is_none());
+
+        drop(client);
+        assert_eq!(router.connection_manager().count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_recv_gives_up_without_a_message() {
+        let router = Router::new().route("/quiet", handler(|| async { Ok(()) }));
+
+        let mut client = router.test_client();
+        client.send_text("/quiet").await.unwrap();
+        assert!(
+            client
+                .recv_timeout(Duration::from_millis(20))
+                .await
+                .is_none()
+        );
+    }
+}
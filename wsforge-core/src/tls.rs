@@ -0,0 +1,176 @@
+//! TLS configuration for serving `wss://` and `https://`.
+//!
+//! This module is available with the `tls` feature. It provides [`TlsConfig`],
+//! which wraps a rustls [`ServerConfig`] and is passed to
+//! [`Router::listen_tls`](crate::router::Router::listen_tls). Both WebSocket
+//! connections and static files are served over TLS on the same port.
+//!
+//! # Examples
+//!
+//! ## Loading PEM Files
+//!
+//! ```no_run
+//! use wsforge::prelude::*;
+//! use wsforge::tls::TlsConfig;
+//!
+//! async fn echo(msg: Message) -> Result<Message> {
+//!     Ok(msg)
+//! }
+//!
+//! # async fn example() -> Result<()> {
+//! let tls = TlsConfig::from_pem_files("certs/cert.pem", "certs/key.pem")?;
+//!
+//! let router = Router::new()
+//!     .serve_static("public")
+//!     .default_handler(handler(echo));
+//!
+//! // https://localhost:8443 serves static files,
+//! // wss://localhost:8443 connects to the WebSocket handler
+//! router.listen_tls("0.0.0.0:8443", tls).await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! ## Custom rustls Configuration
+//!
+//! ```no_run
+//! use wsforge::tls::TlsConfig;
+//! use std::sync::Arc;
+//! use wsforge::tls::rustls::ServerConfig;
+//!
+//! # fn example(server_config: ServerConfig) {
+//! let tls = TlsConfig::from_server_config(Arc::new(server_config));
+//! # }
+//! ```
+
+use crate::error::{Error, Result};
+use rustls::ServerConfig;
+use rustls::crypto::ring;
+use std::path::Path;
+use std::sync::Arc;
+use tokio_rustls::TlsAcceptor;
+
+/// Re-export of [`rustls`](tokio_rustls::rustls) for building custom configurations.
+pub use tokio_rustls::rustls;
+
+/// TLS settings used by [`Router::listen_tls`](crate::router::Router::listen_tls).
+///
+/// `TlsConfig` is cheaply cloneable (uses `Arc` internally).
+///
+/// # Examples
+///
+/// ```no_run
+/// use wsforge::tls::TlsConfig;
+///
+/// # fn example() -> wsforge::Result<()> {
+/// let cert = std::fs::read("cert.pem")?;
+/// let key = std::fs::read("key.pem")?;
+/// let tls = TlsConfig::from_pem(&cert, &key)?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct TlsConfig {
+    config: Arc<ServerConfig>,
+}
+
+impl TlsConfig {
+    /// Creates a TLS configuration from PEM-encoded data held in memory.
+    ///
+    /// The certificate chain may contain several certificates, leaf first.
+    /// The private key may be PKCS#1, PKCS#8 or SEC1 encoded.
+    ///
+    /// # Arguments
+    ///
+    /// * `cert_chain` - PEM-encoded certificate chain
+    /// * `private_key` - PEM-encoded private key
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the PEM data cannot be parsed, contains no
+    /// certificate or key, or the key does not match the certificate.
+    pub fn from_pem(cert_chain: &[u8], private_key: &[u8]) -> Result<Self> {
+        let certs = rustls_pemfile::certs(&mut &cert_chain[..])
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(|e| Error::custom(format!("Invalid TLS certificate: {}", e)))?;
+
+        if certs.is_empty() {
+            return Err(Error::custom(
+                "Invalid TLS certificate: no certificate found",
+            ));
+        }
+
+        let key = rustls_pemfile::private_key(&mut &private_key[..])
+            .map_err(|e| Error::custom(format!("Invalid TLS private key: {}", e)))?
+            .ok_or_else(|| Error::custom("Invalid TLS private key: no key found"))?;
+
+        let mut config = ServerConfig::builder_with_provider(Arc::new(ring::default_provider()))
+            .with_safe_default_protocol_versions()
+            .map_err(|e| Error::custom(format!("Invalid TLS configuration: {}", e)))?
+            .with_no_client_auth()
+            .with_single_cert(certs, key)
+            .map_err(|e| Error::custom(format!("Invalid TLS configuration: {}", e)))?;
+
+        // WebSocket upgrades and static files are both served over HTTP/1.1
+        config.alpn_protocols = vec![b"http/1.1".to_vec()];
+
+        Ok(Self::from_server_config(Arc::new(config)))
+    }
+
+    /// Creates a TLS configuration from PEM files on disk.
+    ///
+    /// # Arguments
+    ///
+    /// * `cert_path` - Path to the PEM-encoded certificate chain
+    /// * `key_path` - Path to the PEM-encoded private key
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if either file cannot be read, or for any reason
+    /// listed in [`from_pem()`](Self::from_pem).
+    pub fn from_pem_files(cert_path: impl AsRef<Path>, key_path: impl AsRef<Path>) -> Result<Self> {
+        let cert_chain = std::fs::read(cert_path)?;
+        let private_key = std::fs::read(key_path)?;
+        Self::from_pem(&cert_chain, &private_key)
+    }
+
+    /// Creates a TLS configuration from an existing rustls [`ServerConfig`].
+    ///
+    /// Use this for full control over protocol versions, cipher suites,
+    /// client authentication or certificate resolution.
+    pub fn from_server_config(config: Arc<ServerConfig>) -> Self {
+        Self { config }
+    }
+
+    /// Returns the underlying rustls configuration.
+    pub fn server_config(&self) -> &Arc<ServerConfig> {
+        &self.config
+    }
+
+    pub(crate) fn acceptor(&self) -> TlsAcceptor {
+        TlsAcceptor::from(self.config.clone())
+    }
+}
+
+impl From<Arc<ServerConfig>> for TlsConfig {
+    fn from(config: Arc<ServerConfig>) -> Self {
+        Self::from_server_config(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_pem_rejects_missing_certificate() {
+        let result = TlsConfig::from_pem(b"", b"");
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_from_pem_files_missing_file() {
+        let result = TlsConfig::from_pem_files("does/not/exist.pem", "does/not/exist.key");
+        assert!(matches!(result, Err(Error::Io(_))));
+    }
+}
@@ -398,6 +398,9 @@ pub fn derive_websocket_message(input: TokenStream) -> TokenStream {
 /// ) -> wsforge::Result<Option<wsforge::Message>>
 /// ```
 ///
+/// Returning `wsforge::Result<Vec<wsforge::Message>>` instead sends several
+/// messages, in order.
+///
 /// # Examples
 ///
 /// ## Simple Handler
@@ -494,8 +497,10 @@ pub fn derive_websocket_handler(input: TokenStream) -> TokenStream {
                 conn: wsforge::Connection,
                 state: wsforge::AppState,
                 extensions: wsforge::Extensions,
-            ) -> wsforge::Result<Option<wsforge::Message>> {
-                self.handle(message, conn, state, extensions).await
+            ) -> wsforge::Result<Vec<wsforge::Message>> {
+                self.handle(message, conn, state, extensions)
+                    .await
+                    .map(|response| response.into_iter().collect())
             }
         }
     };
@@ -335,22 +335,21 @@
 //!
 //! ```
 //! use wsforge::prelude::*;
+//! use std::time::Duration;
 //! use tokio::signal;
 //!
 //! #[tokio::main]
 //! async fn main() -> Result<()> {
-//!     let router = Router::new();
-//!
-//!     tokio::select! {
-//!         result = router.listen("127.0.0.1:8080") => {
-//!             if let Err(e) = result {
-//!                 eprintln!("Server error: {}", e);
-//!             }
-//!         }
-//!         _ = signal::ctrl_c() => {
+//!     let router = Router::new()
+//!         .drain_timeout(Duration::from_secs(5));
+//!
+//!     // Stops accepting, closes every connection, then waits for them to drain
+//!     router
+//!         .listen_with_shutdown("127.0.0.1:8080", async {
+//!             let _ = signal::ctrl_c().await;
 //!             println!("Shutting down gracefully...");
-//!         }
-//!     }
+//!         })
+//!         .await?;
 //!
 //!     Ok(())
 //! }